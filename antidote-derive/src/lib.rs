@@ -0,0 +1,177 @@
+//! Derive macros for [`antidote`](https://docs.rs/antidote).
+//!
+//! They are meant to be used through `antidote`'s `derive` feature, which
+//! re-exports them, as the code they generate refers to `antidote`'s types.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{ext::IdentExt, parse_macro_input, Data, DeriveInput, Error, Field, Index, Member};
+
+/// Generates accessors locking a `Mutex` or `RwLock` holding the struct and
+/// returning a guard for one of its fields.
+///
+/// For a struct `State`, this generates two traits with the visibility of the
+/// struct:
+///
+/// * `StateMutexExt`, implemented for `Mutex<State>`, with a `lock_<field>`
+///   method for each field, returning a `MappedMutexGuard<'_, State, Field>`.
+/// * `StateRwLockExt`, implemented for `RwLock<State>`, with `read_<field>` and
+///   `write_<field>` methods, returning a `MappedRwLockReadGuard` and a
+///   `MappedRwLockWriteGuard`.
+///
+/// The fields of a tuple struct are named by their index, as in `lock_0`. The
+/// whole lock is held for as long as the returned guard is alive, so this is
+/// only shorthand for mapping a guard, not a way to lock fields separately.
+///
+/// The accessors give access to every field, whatever its visibility. A field
+/// marked `#[lock_project(skip)]` gets none.
+#[proc_macro_derive(LockProject, attributes(lock_project))]
+pub fn derive_lock_project(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "LockProject can only be derived for structs",
+            ))
+        }
+    };
+
+    let vis = &input.vis;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mutex_trait = format_ident!("{}MutexExt", name);
+    let rwlock_trait = format_ident!("{}RwLockExt", name);
+
+    let mut lock_decls = vec![];
+    let mut lock_impls = vec![];
+    let mut rwlock_decls = vec![];
+    let mut rwlock_impls = vec![];
+    for (i, field) in fields.iter().enumerate() {
+        if skipped(field)? {
+            continue;
+        }
+        let (member, suffix) = match &field.ident {
+            Some(ident) => (Member::Named(ident.clone()), ident.unraw().to_string()),
+            None => (Member::Unnamed(Index::from(i)), i.to_string()),
+        };
+        let ty = &field.ty;
+
+        let lock = format_ident!("lock_{}", suffix);
+        let doc = format!(
+            "Locks the mutex and returns a guard for the `{}` field.",
+            suffix
+        );
+        let lock_sig = quote! {
+            fn #lock(&self) -> ::antidote::MappedMutexGuard<'_, #name #ty_generics, #ty>
+        };
+        lock_decls.push(quote! {
+            #[doc = #doc]
+            #lock_sig;
+        });
+        lock_impls.push(quote! {
+            #[inline]
+            #lock_sig {
+                ::antidote::MutexGuard::map(::antidote::Mutex::lock(self), |t| &mut t.#member)
+            }
+        });
+
+        let read = format_ident!("read_{}", suffix);
+        let write = format_ident!("write_{}", suffix);
+        let read_doc = format!(
+            "Locks the lock for reading and returns a guard for the `{}` field.",
+            suffix
+        );
+        let write_doc = format!(
+            "Locks the lock for writing and returns a guard for the `{}` field.",
+            suffix
+        );
+        let read_sig = quote! {
+            fn #read(&self) -> ::antidote::MappedRwLockReadGuard<'_, #name #ty_generics, #ty>
+        };
+        let write_sig = quote! {
+            fn #write(&self) -> ::antidote::MappedRwLockWriteGuard<'_, #name #ty_generics, #ty>
+        };
+        rwlock_decls.push(quote! {
+            #[doc = #read_doc]
+            #read_sig;
+            #[doc = #write_doc]
+            #write_sig;
+        });
+        rwlock_impls.push(quote! {
+            #[inline]
+            #read_sig {
+                ::antidote::RwLockReadGuard::map(::antidote::RwLock::read(self), |t| &t.#member)
+            }
+            #[inline]
+            #write_sig {
+                ::antidote::RwLockWriteGuard::map(
+                    ::antidote::RwLock::write(self),
+                    |t| &mut t.#member,
+                )
+            }
+        });
+    }
+
+    let mutex_doc = format!(
+        "Accessors for the fields of a `{}` in a `Mutex`, generated by `LockProject`.",
+        name
+    );
+    let rwlock_doc = format!(
+        "Accessors for the fields of a `{}` in an `RwLock`, generated by `LockProject`.",
+        name
+    );
+    Ok(quote! {
+        #[doc = #mutex_doc]
+        #vis trait #mutex_trait #impl_generics #where_clause {
+            #(#lock_decls)*
+        }
+
+        #[automatically_derived]
+        impl #impl_generics #mutex_trait #ty_generics
+            for ::antidote::Mutex<#name #ty_generics> #where_clause
+        {
+            #(#lock_impls)*
+        }
+
+        #[doc = #rwlock_doc]
+        #vis trait #rwlock_trait #impl_generics #where_clause {
+            #(#rwlock_decls)*
+        }
+
+        #[automatically_derived]
+        impl #impl_generics #rwlock_trait #ty_generics
+            for ::antidote::RwLock<#name #ty_generics> #where_clause
+        {
+            #(#rwlock_impls)*
+        }
+    })
+}
+
+// Parses the field's `lock_project` attributes, returning whether it is
+// skipped.
+fn skipped(field: &Field) -> Result<bool, Error> {
+    let mut skip = false;
+    for attr in &field.attrs {
+        if !attr.path().is_ident("lock_project") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported lock_project attribute"))
+            }
+        })?;
+    }
+    Ok(skip)
+}
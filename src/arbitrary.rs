@@ -0,0 +1,102 @@
+//! Implementations of the `Arbitrary` traits of property testing and fuzzing
+//! crates, generating a new, unlocked lock around an arbitrary value.
+
+use crate::{Mutex, RwLock};
+
+#[cfg(feature = "arbitrary")]
+mod for_arbitrary {
+    use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+    use super::*;
+
+    impl<'a, T: Arbitrary<'a>> Arbitrary<'a> for Mutex<T> {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<Mutex<T>> {
+            T::arbitrary(u).map(Mutex::new)
+        }
+
+        fn arbitrary_take_rest(u: Unstructured<'a>) -> Result<Mutex<T>> {
+            T::arbitrary_take_rest(u).map(Mutex::new)
+        }
+
+        #[inline]
+        fn size_hint(depth: usize) -> (usize, Option<usize>) {
+            T::size_hint(depth)
+        }
+    }
+
+    impl<'a, T: Arbitrary<'a>> Arbitrary<'a> for RwLock<T> {
+        fn arbitrary(u: &mut Unstructured<'a>) -> Result<RwLock<T>> {
+            T::arbitrary(u).map(RwLock::new)
+        }
+
+        fn arbitrary_take_rest(u: Unstructured<'a>) -> Result<RwLock<T>> {
+            T::arbitrary_take_rest(u).map(RwLock::new)
+        }
+
+        #[inline]
+        fn size_hint(depth: usize) -> (usize, Option<usize>) {
+            T::size_hint(depth)
+        }
+    }
+}
+
+#[cfg(feature = "proptest")]
+mod for_proptest {
+    use ::proptest::{
+        arbitrary::{any_with, Arbitrary},
+        strategy::{Map, Strategy},
+    };
+
+    use super::*;
+
+    impl<T: Arbitrary> Arbitrary for Mutex<T> {
+        type Parameters = T::Parameters;
+        type Strategy = Map<T::Strategy, fn(T) -> Mutex<T>>;
+
+        /// Generates a mutex around a value generated with `args`, which
+        /// shrinks as the value does.
+        fn arbitrary_with(args: T::Parameters) -> Self::Strategy {
+            any_with::<T>(args).prop_map(Mutex::new as fn(T) -> Mutex<T>)
+        }
+    }
+
+    impl<T: Arbitrary> Arbitrary for RwLock<T> {
+        type Parameters = T::Parameters;
+        type Strategy = Map<T::Strategy, fn(T) -> RwLock<T>>;
+
+        /// Generates a lock around a value generated with `args`, which
+        /// shrinks as the value does.
+        fn arbitrary_with(args: T::Parameters) -> Self::Strategy {
+            any_with::<T>(args).prop_map(RwLock::new as fn(T) -> RwLock<T>)
+        }
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+mod for_quickcheck {
+    use ::quickcheck::{Arbitrary, Gen};
+
+    use super::*;
+
+    impl<T: Arbitrary> Arbitrary for Mutex<T> {
+        fn arbitrary(g: &mut Gen) -> Mutex<T> {
+            Mutex::new(T::arbitrary(g))
+        }
+
+        /// Shrinks the value, taken while holding the mutex.
+        fn shrink(&self) -> Box<dyn Iterator<Item = Mutex<T>>> {
+            Box::new(self.lock().shrink().map(Mutex::new))
+        }
+    }
+
+    impl<T: Arbitrary> Arbitrary for RwLock<T> {
+        fn arbitrary(g: &mut Gen) -> RwLock<T> {
+            RwLock::new(T::arbitrary(g))
+        }
+
+        /// Shrinks the value, taken while holding shared access to the lock.
+        fn shrink(&self) -> Box<dyn Iterator<Item = RwLock<T>>> {
+            Box::new(self.read().shrink().map(RwLock::new))
+        }
+    }
+}
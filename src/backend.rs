@@ -0,0 +1,452 @@
+//! The lock underneath `Mutex`.
+//!
+//! By default this is the standard library's mutex, whose poisoning is
+//! ignored, or assumed away when nothing can unwind. With the `parking_lot`
+//! feature it is `parking_lot`'s mutex, which is a single byte and never
+//! poisons in the first place. With the `futex` feature, and without
+//! `parking_lot`, it is a mutex of this crate's own, a 32-bit word with no
+//! poison bookkeeping at all. Its waiters sleep on the word itself with the
+//! futex of Linux and Android, `WaitOnAddress` on Windows and `__ulock_wait`
+//! on Apple platforms, and in the crate's parking lot elsewhere. Either way,
+//! `Mutex` only goes through the functions below, so its behavior does not
+//! depend on the backend.
+
+#[cfg(not(any(feature = "parking_lot", feature = "futex")))]
+mod imp {
+    use std::{hint, sync};
+
+    pub(crate) type Mutex<T> = sync::Mutex<T>;
+    pub(crate) type MutexGuard<'a, T> = sync::MutexGuard<'a, T>;
+
+    #[inline]
+    pub(crate) const fn new<T>(t: T) -> Mutex<T> {
+        sync::Mutex::new(t)
+    }
+
+    #[cfg(panic = "unwind")]
+    #[inline]
+    pub(crate) fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+        mutex.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Without unwinding, no thread ever panics while holding the mutex, so it
+    // is never poisoned and the check can be skipped.
+    #[cfg(not(panic = "unwind"))]
+    #[inline]
+    pub(crate) fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+        // SAFETY: as above.
+        unsafe { mutex.lock().unwrap_unchecked() }
+    }
+
+    #[cfg(panic = "unwind")]
+    #[inline]
+    pub(crate) fn try_lock<T: ?Sized>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+        match mutex.try_lock() {
+            Ok(t) => Some(t),
+            Err(sync::TryLockError::Poisoned(e)) => Some(e.into_inner()),
+            Err(sync::TryLockError::WouldBlock) => None,
+        }
+    }
+
+    #[cfg(not(panic = "unwind"))]
+    #[inline]
+    pub(crate) fn try_lock<T: ?Sized>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+        // SAFETY: the mutex is never poisoned, as above.
+        unsafe { try_lock_unpoisoned(mutex) }
+    }
+
+    // The caller guarantees that the mutex is not poisoned.
+    #[inline]
+    pub(crate) unsafe fn try_lock_unpoisoned<T: ?Sized>(
+        mutex: &Mutex<T>,
+    ) -> Option<MutexGuard<'_, T>> {
+        match mutex.try_lock() {
+            Ok(t) => Some(t),
+            // SAFETY: guaranteed by the caller.
+            Err(sync::TryLockError::Poisoned(_)) => hint::unreachable_unchecked(),
+            Err(sync::TryLockError::WouldBlock) => None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn into_inner<T>(mutex: Mutex<T>) -> T {
+        mutex.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[inline]
+    pub(crate) fn get_mut<T: ?Sized>(mutex: &mut Mutex<T>) -> &mut T {
+        mutex.get_mut().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // The standard library's mutex cannot be inspected, so it is probed.
+    #[inline]
+    pub(crate) fn is_locked<T: ?Sized>(mutex: &Mutex<T>) -> bool {
+        try_lock(mutex).is_none()
+    }
+}
+
+#[cfg(feature = "parking_lot")]
+mod imp {
+    pub(crate) type Mutex<T> = parking_lot::Mutex<T>;
+    pub(crate) type MutexGuard<'a, T> = parking_lot::MutexGuard<'a, T>;
+
+    #[inline]
+    pub(crate) const fn new<T>(t: T) -> Mutex<T> {
+        parking_lot::Mutex::new(t)
+    }
+
+    #[inline]
+    pub(crate) fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+        mutex.lock()
+    }
+
+    #[inline]
+    pub(crate) fn try_lock<T: ?Sized>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+        mutex.try_lock()
+    }
+
+    // Nothing to check, as the mutex never poisons.
+    #[inline]
+    pub(crate) unsafe fn try_lock_unpoisoned<T: ?Sized>(
+        mutex: &Mutex<T>,
+    ) -> Option<MutexGuard<'_, T>> {
+        try_lock(mutex)
+    }
+
+    #[inline]
+    pub(crate) fn into_inner<T>(mutex: Mutex<T>) -> T {
+        mutex.into_inner()
+    }
+
+    #[inline]
+    pub(crate) fn get_mut<T: ?Sized>(mutex: &mut Mutex<T>) -> &mut T {
+        mutex.get_mut()
+    }
+
+    #[inline]
+    pub(crate) fn is_locked<T: ?Sized>(mutex: &Mutex<T>) -> bool {
+        mutex.is_locked()
+    }
+
+    #[inline]
+    pub(crate) fn data_ptr<T: ?Sized>(mutex: &Mutex<T>) -> *mut T {
+        mutex.data_ptr()
+    }
+
+    #[inline]
+    pub(crate) unsafe fn force_unlock<T: ?Sized>(mutex: &Mutex<T>) {
+        mutex.force_unlock();
+    }
+}
+
+#[cfg(all(feature = "futex", not(feature = "parking_lot")))]
+mod imp {
+    use std::{
+        cell::UnsafeCell,
+        fmt, hint,
+        marker::PhantomData,
+        ops::{Deref, DerefMut},
+        sync::atomic::{AtomicU32, Ordering},
+    };
+
+    const UNLOCKED: u32 = 0;
+    const LOCKED: u32 = 1;
+    // Locked, with threads possibly waiting for it.
+    const CONTENDED: u32 = 2;
+
+    // How many times a waiter checks the state before going to sleep.
+    const SPINS: u32 = 100;
+
+    // Public in this private module, as `raw::MutexStorage` names it.
+    pub struct Mutex<T: ?Sized> {
+        state: AtomicU32,
+        data: UnsafeCell<T>,
+    }
+
+    unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+    unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+    impl<T: Default> Default for Mutex<T> {
+        #[inline]
+        fn default() -> Mutex<T> {
+            new(T::default())
+        }
+    }
+
+    pub struct MutexGuard<'a, T: ?Sized> {
+        mutex: &'a Mutex<T>,
+        // Like the other backends' guards, this one is not `Send`, unless the
+        // `send-guard` feature is enabled, as the mutex can be released by
+        // any thread.
+        _marker: crate::held::NotSend,
+    }
+
+    unsafe impl<T: ?Sized + Sync> Sync for MutexGuard<'_, T> {}
+
+    impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+        #[inline]
+        fn drop(&mut self) {
+            // SAFETY: the guard holds the mutex.
+            unsafe { force_unlock(self.mutex) };
+        }
+    }
+
+    impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+        type Target = T;
+
+        #[inline]
+        fn deref(&self) -> &T {
+            // SAFETY: the guard holds the mutex.
+            unsafe { &*self.mutex.data.get() }
+        }
+    }
+
+    impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+        #[inline]
+        fn deref_mut(&mut self) -> &mut T {
+            // SAFETY: the guard holds the mutex.
+            unsafe { &mut *self.mutex.data.get() }
+        }
+    }
+
+    impl<T: ?Sized + fmt::Debug> fmt::Debug for MutexGuard<'_, T> {
+        fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+            fmt::Debug::fmt(&**self, fmt)
+        }
+    }
+
+    #[inline]
+    pub(crate) const fn new<T>(t: T) -> Mutex<T> {
+        Mutex {
+            state: AtomicU32::new(UNLOCKED),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+        if mutex
+            .state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            lock_contended(&mutex.state);
+        }
+        guard(mutex)
+    }
+
+    #[cold]
+    fn lock_contended(state: &AtomicU32) {
+        // Spins while the holder is alone, as it should release the mutex
+        // shortly.
+        for _ in 0..SPINS {
+            match state.compare_exchange_weak(
+                UNLOCKED,
+                LOCKED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(CONTENDED) => break,
+                Err(_) => hint::spin_loop(),
+            }
+        }
+        // Whoever acquires the mutex from here on cannot know whether other
+        // threads are still waiting, so it marks it contended.
+        while state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            wait(state, CONTENDED);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn try_lock<T: ?Sized>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+        mutex
+            .state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| guard(mutex))
+    }
+
+    // Nothing to check, as the mutex never poisons.
+    #[inline]
+    pub(crate) unsafe fn try_lock_unpoisoned<T: ?Sized>(
+        mutex: &Mutex<T>,
+    ) -> Option<MutexGuard<'_, T>> {
+        try_lock(mutex)
+    }
+
+    #[inline]
+    fn guard<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+        MutexGuard {
+            mutex,
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn into_inner<T>(mutex: Mutex<T>) -> T {
+        mutex.data.into_inner()
+    }
+
+    #[inline]
+    pub(crate) fn get_mut<T: ?Sized>(mutex: &mut Mutex<T>) -> &mut T {
+        mutex.data.get_mut()
+    }
+
+    #[inline]
+    pub(crate) fn is_locked<T: ?Sized>(mutex: &Mutex<T>) -> bool {
+        mutex.state.load(Ordering::Relaxed) != UNLOCKED
+    }
+
+    #[inline]
+    pub(crate) fn data_ptr<T: ?Sized>(mutex: &Mutex<T>) -> *mut T {
+        mutex.data.get()
+    }
+
+    // The caller guarantees that the mutex is locked, and that it may release
+    // it.
+    #[inline]
+    pub(crate) unsafe fn force_unlock<T: ?Sized>(mutex: &Mutex<T>) {
+        if mutex.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            wake_one(&mutex.state);
+        }
+    }
+
+    // Sleeps until woken, unless `state` no longer holds `expected`.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn wait(state: &AtomicU32, expected: u32) {
+        // Interruptions and spurious wakeups are handled by the caller's loop.
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                state as *const AtomicU32,
+                libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG,
+                expected,
+                std::ptr::null::<libc::timespec>(),
+            );
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn wake_one(state: &AtomicU32) {
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                state as *const AtomicU32,
+                libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG,
+                1,
+            );
+        }
+    }
+
+    // Windows 8 and later wait on the address of the state directly.
+    #[cfg(windows)]
+    #[link(name = "synchronization")]
+    extern "system" {
+        fn WaitOnAddress(
+            address: *const std::ffi::c_void,
+            compare: *const std::ffi::c_void,
+            size: usize,
+            milliseconds: u32,
+        ) -> i32;
+        fn WakeByAddressSingle(address: *const std::ffi::c_void);
+    }
+
+    #[cfg(windows)]
+    fn wait(state: &AtomicU32, expected: u32) {
+        // Spurious wakeups are handled by the caller's loop.
+        unsafe {
+            WaitOnAddress(
+                state as *const AtomicU32 as *const std::ffi::c_void,
+                &expected as *const u32 as *const std::ffi::c_void,
+                std::mem::size_of::<u32>(),
+                u32::MAX,
+            );
+        }
+    }
+
+    #[cfg(windows)]
+    fn wake_one(state: &AtomicU32) {
+        unsafe { WakeByAddressSingle(state as *const AtomicU32 as *const std::ffi::c_void) };
+    }
+
+    // Apple platforms use the `ulock` calls of their kernel, which the
+    // standard library's locks are built on as well, and which are available
+    // since macOS 10.12 and iOS 10.
+    #[cfg(target_vendor = "apple")]
+    extern "C" {
+        fn __ulock_wait(
+            operation: u32,
+            address: *mut std::ffi::c_void,
+            value: u64,
+            timeout_us: u32,
+        ) -> libc::c_int;
+        fn __ulock_wake(
+            operation: u32,
+            address: *mut std::ffi::c_void,
+            wake_value: u64,
+        ) -> libc::c_int;
+    }
+
+    #[cfg(target_vendor = "apple")]
+    const UL_COMPARE_AND_WAIT: u32 = 1;
+    // Returns errors instead of setting `errno`.
+    #[cfg(target_vendor = "apple")]
+    const ULF_NO_ERRNO: u32 = 0x0100_0000;
+
+    #[cfg(target_vendor = "apple")]
+    fn wait(state: &AtomicU32, expected: u32) {
+        // Interruptions and spurious wakeups are handled by the caller's loop.
+        unsafe {
+            __ulock_wait(
+                UL_COMPARE_AND_WAIT | ULF_NO_ERRNO,
+                state as *const AtomicU32 as *mut std::ffi::c_void,
+                u64::from(expected),
+                0,
+            );
+        }
+    }
+
+    #[cfg(target_vendor = "apple")]
+    fn wake_one(state: &AtomicU32) {
+        unsafe {
+            __ulock_wake(
+                UL_COMPARE_AND_WAIT | ULF_NO_ERRNO,
+                state as *const AtomicU32 as *mut std::ffi::c_void,
+                0,
+            );
+        }
+    }
+
+    // Other platforms wait in the crate's parking lot, keyed by the address of
+    // the state.
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        windows,
+        target_vendor = "apple"
+    )))]
+    fn wait(state: &AtomicU32, expected: u32) {
+        crate::parking::park(
+            state as *const AtomicU32 as usize,
+            || state.load(Ordering::Relaxed) == expected,
+            || {},
+            |_, _| {},
+            None,
+        );
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        windows,
+        target_vendor = "apple"
+    )))]
+    fn wake_one(state: &AtomicU32) {
+        crate::parking::unpark_one(state as *const AtomicU32 as usize, |_| {
+            crate::parking::DEFAULT_TOKEN
+        });
+    }
+}
+
+pub(crate) use imp::*;
@@ -0,0 +1,139 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock, poison::PoisonPolicy, raw::MutexStorage, Mutex, MutexGuard, RwLock, RwLockReadGuard,
+    RwLockWriteGuard,
+};
+
+/// How an acquisition with backoff retries a lock it finds held, given to
+/// [`Mutex::try_lock_with_backoff`] and its `RwLock` equivalents.
+///
+/// The acquisition first retries `spins` times, spinning for exponentially
+/// longer in between, then `yields` times, yielding the rest of the thread's
+/// time slice in between, and then puts the thread to sleep until the lock is
+/// released. It gives up once `max_wait` has passed since it started, whatever
+/// stage it is in, or never if `max_wait` is `None`.
+///
+/// Spinning suits locks which are only ever held very briefly, and yielding
+/// locks whose holders may need the processor to make progress. The default
+/// spins a few times and yields a few times before sleeping, without a limit
+/// on the wait.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Backoff {
+    /// The number of retries spinning in between.
+    pub spins: u32,
+    /// The number of retries yielding in between, once spinning is over.
+    pub yields: u32,
+    /// The longest the acquisition waits for the lock before giving up.
+    pub max_wait: Option<Duration>,
+}
+
+impl Default for Backoff {
+    #[inline]
+    fn default() -> Backoff {
+        Backoff {
+            spins: 6,
+            yields: 4,
+            max_wait: None,
+        }
+    }
+}
+
+// The progress of an acquisition with backoff through its retries.
+struct Retry {
+    backoff: Backoff,
+    deadline: Option<Instant>,
+    spin: crate::spin::Backoff,
+    retries: u32,
+}
+
+impl Retry {
+    fn new(backoff: Backoff) -> Retry {
+        Retry {
+            backoff,
+            // A deadline too far in the future to represent is treated as no
+            // deadline at all.
+            deadline: backoff
+                .max_wait
+                .and_then(|max_wait| clock::now().checked_add(max_wait)),
+            spin: crate::spin::Backoff::new(),
+            retries: 0,
+        }
+    }
+
+    // Pauses before the next retry, or returns `false` if the thread should go
+    // to sleep instead.
+    fn pause(&mut self) -> bool {
+        if self.retries < self.backoff.spins {
+            self.spin.spin();
+        } else if self.retries - self.backoff.spins < self.backoff.yields
+            && self
+                .deadline
+                .map_or(true, |deadline| clock::now() < deadline)
+        {
+            thread::yield_now();
+        } else {
+            return false;
+        }
+        self.retries += 1;
+        true
+    }
+}
+
+impl<T: ?Sized, P: PoisonPolicy, R: MutexStorage> Mutex<T, P, R> {
+    /// Attempts to acquire the mutex, retrying as configured by `backoff` while
+    /// it is held, and returns `None` if it gave up.
+    ///
+    /// See [`Backoff`].
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_lock_with_backoff(&self, backoff: Backoff) -> Option<MutexGuard<'_, T, P, R>> {
+        let mut retry = Retry::new(backoff);
+        loop {
+            if let Ok(guard) = self.try_lock() {
+                return Some(guard);
+            }
+            if !retry.pause() {
+                return self.try_lock_until_internal(retry.deadline, None);
+            }
+        }
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Attempts to acquire shared access, retrying as configured by `backoff`
+    /// while the lock is held exclusively, and returns `None` if it gave up.
+    ///
+    /// See [`Backoff`].
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_read_with_backoff(&self, backoff: Backoff) -> Option<RwLockReadGuard<'_, T>> {
+        let mut retry = Retry::new(backoff);
+        loop {
+            if let Ok(guard) = self.try_read() {
+                return Some(guard);
+            }
+            if !retry.pause() {
+                return self.try_read_until_internal(retry.deadline, None);
+            }
+        }
+    }
+
+    /// Attempts to acquire exclusive access, retrying as configured by
+    /// `backoff` while the lock is held, and returns `None` if it gave up.
+    ///
+    /// See [`Backoff`].
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_write_with_backoff(&self, backoff: Backoff) -> Option<RwLockWriteGuard<'_, T>> {
+        let mut retry = Retry::new(backoff);
+        loop {
+            if let Ok(guard) = self.try_write() {
+                return Some(guard);
+            }
+            if !retry.pause() {
+                return self.try_write_until_internal(retry.deadline, None);
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{clock, Condvar, Mutex};
+
+/// Like `std::sync::Barrier`, with a timed wait.
+///
+/// A thread which gives up waiting with
+/// [`wait_timeout`](Barrier::wait_timeout) withdraws from the current
+/// generation, which then needs another call to `wait` or `wait_timeout` to
+/// trip, so a participant which is stuck cannot hang the others forever.
+pub struct Barrier {
+    inner: DynamicBarrier,
+}
+
+impl Barrier {
+    /// Like `std::sync::Barrier::new`.
+    #[inline]
+    pub const fn new(n: usize) -> Barrier {
+        Barrier {
+            inner: DynamicBarrier::new(n),
+        }
+    }
+
+    /// Like `std::sync::Barrier::wait`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than `n` threads wait at once.
+    #[inline]
+    pub fn wait(&self) -> BarrierWaitResult {
+        self.inner.wait()
+    }
+
+    /// Like `wait`, but gives up waiting after `timeout`, returning `None` if
+    /// the barrier did not trip by then.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than `n` threads wait at once.
+    #[inline]
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<BarrierWaitResult> {
+        self.inner.wait_timeout(timeout)
+    }
+}
+
+impl fmt::Debug for Barrier {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.inner.state.lock();
+        fmt.debug_struct("Barrier")
+            .field("parties", &state.parties)
+            .field("arrived", &state.arrived)
+            .finish()
+    }
+}
+
+/// A barrier whose set of parties can change at runtime.
+///
+/// Like `std::sync::Barrier`, except that the number of parties is not fixed:
+/// threads join with [`register`](DynamicBarrier::register) and leave with
+/// [`deregister`](DynamicBarrier::deregister), and each generation of the
+/// barrier trips once every currently registered party has called
+/// [`wait`](DynamicBarrier::wait).
+pub struct DynamicBarrier {
+    state: Mutex<State>,
+    tripped: Condvar,
+}
+
+struct State {
+    parties: usize,
+    arrived: usize,
+    generation: u64,
+    // The generation tripped by `deregister`, whose leader is the first of its
+    // waiters to wake up.
+    unclaimed_leader: Option<u64>,
+}
+
+impl DynamicBarrier {
+    /// Creates a new barrier with `parties` registered parties.
+    #[inline]
+    pub const fn new(parties: usize) -> DynamicBarrier {
+        DynamicBarrier {
+            state: Mutex::new(State {
+                parties,
+                arrived: 0,
+                generation: 0,
+                unclaimed_leader: None,
+            }),
+            tripped: Condvar::new(),
+        }
+    }
+
+    /// Registers a new party.
+    ///
+    /// If a generation is in progress, it will not trip until the new party
+    /// has arrived as well.
+    pub fn register(&self) {
+        self.state.lock().parties += 1;
+    }
+
+    /// Deregisters a party.
+    ///
+    /// If every other registered party is already waiting, this trips the
+    /// barrier.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no parties are registered.
+    pub fn deregister(&self) {
+        let mut state = self.state.lock();
+        assert!(
+            state.parties != 0,
+            "no parties are registered with the barrier"
+        );
+        state.parties -= 1;
+        if state.arrived != 0 && state.arrived == state.parties {
+            state.unclaimed_leader = Some(state.generation);
+            self.trip(&mut state);
+        }
+    }
+
+    /// Blocks the current thread until every registered party has called this
+    /// method.
+    ///
+    /// As with `std::sync::Barrier`, a single thread of each generation is
+    /// told that it is the leader.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more threads arrive than are registered.
+    pub fn wait(&self) -> BarrierWaitResult {
+        // Without a deadline, the wait never times out.
+        self.wait_until_internal(None).unwrap()
+    }
+
+    /// Like `wait`, but gives up waiting after `timeout`, returning `None` if
+    /// the barrier did not trip by then.
+    ///
+    /// A thread which gives up no longer counts as arrived, so the generation
+    /// only trips once it or another thread waits again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more threads arrive than are registered.
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<BarrierWaitResult> {
+        // A deadline too far in the future to represent is treated as no
+        // deadline at all.
+        self.wait_until_internal(clock::now().checked_add(timeout))
+    }
+
+    fn wait_until_internal(&self, deadline: Option<Instant>) -> Option<BarrierWaitResult> {
+        let mut state = self.state.lock();
+        assert!(
+            state.arrived < state.parties,
+            "more threads are waiting on the barrier than are registered"
+        );
+        state.arrived += 1;
+        if state.arrived == state.parties {
+            self.trip(&mut state);
+            return Some(BarrierWaitResult(true));
+        }
+
+        let generation = state.generation;
+        while state.generation == generation {
+            match deadline {
+                Some(deadline) => {
+                    let (new, result) = self.tripped.wait_until(state, deadline);
+                    state = new;
+                    if result.timed_out() && state.generation == generation {
+                        state.arrived -= 1;
+                        return None;
+                    }
+                }
+                None => state = self.tripped.wait(state),
+            }
+        }
+        if state.unclaimed_leader == Some(generation) {
+            state.unclaimed_leader = None;
+            return Some(BarrierWaitResult(true));
+        }
+        Some(BarrierWaitResult(false))
+    }
+
+    /// Returns the number of registered parties.
+    #[inline]
+    pub fn parties(&self) -> usize {
+        self.state.lock().parties
+    }
+
+    fn trip(&self, state: &mut State) {
+        state.arrived = 0;
+        state.generation += 1;
+        self.tripped.notify_all();
+    }
+}
+
+impl fmt::Debug for DynamicBarrier {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock();
+        fmt.debug_struct("DynamicBarrier")
+            .field("parties", &state.parties)
+            .field("arrived", &state.arrived)
+            .finish()
+    }
+}
+
+/// Like `std::sync::BarrierWaitResult`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    /// Like `std::sync::BarrierWaitResult::is_leader`.
+    #[inline]
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
@@ -0,0 +1,120 @@
+//! Guards branded with the identity of their lock.
+//!
+//! [`Mutex::branded`] gives a closure a [`Branded`] handle to the mutex, tagged
+//! with a fresh, invariant `'brand` lifetime that no other lock shares. Guards
+//! acquired through the handle carry the same brand, so a function taking a
+//! `&mut BrandedMutexGuard<'_, 'brand, T>` as proof of access can only be given
+//! a guard of that exact mutex. Handing it a guard of another mutex, even one
+//! of the same type, fails to compile.
+
+use std::{
+    fmt,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{Mutex, MutexGuard, TryLockResult};
+
+// Invariant in `'brand`, so brands can neither be shortened nor extended.
+type Brand<'brand> = PhantomData<fn(&'brand ()) -> &'brand ()>;
+
+/// A handle to a `Mutex` tagged with a unique brand.
+///
+/// Created by [`Mutex::branded`].
+pub struct Branded<'brand, 'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+    _brand: Brand<'brand>,
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Calls `f` with a handle to the mutex carrying a brand unique to this
+    /// call.
+    ///
+    /// See the [`brand`](crate::brand) module for details.
+    #[inline]
+    pub fn branded<R>(&self, f: impl for<'brand> FnOnce(Branded<'brand, '_, T>) -> R) -> R {
+        f(Branded {
+            mutex: self,
+            _brand: PhantomData,
+        })
+    }
+}
+
+impl<'brand, 'a, T: ?Sized> Branded<'brand, 'a, T> {
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so.
+    #[inline]
+    pub fn lock(self) -> BrandedMutexGuard<'a, 'brand, T> {
+        BrandedMutexGuard {
+            guard: self.mutex.lock(),
+            _brand: PhantomData,
+        }
+    }
+
+    /// Attempts to acquire the mutex without blocking.
+    #[inline]
+    pub fn try_lock(self) -> TryLockResult<BrandedMutexGuard<'a, 'brand, T>> {
+        self.mutex.try_lock().map(|guard| BrandedMutexGuard {
+            guard,
+            _brand: PhantomData,
+        })
+    }
+
+    /// Returns the underlying mutex.
+    #[inline]
+    pub fn mutex(self) -> &'a Mutex<T> {
+        self.mutex
+    }
+}
+
+impl<T: ?Sized> Clone for Branded<'_, '_, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for Branded<'_, '_, T> {}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Branded<'_, '_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("Branded").field(&self.mutex).finish()
+    }
+}
+
+/// A `MutexGuard` carrying the brand of its mutex.
+#[must_use]
+pub struct BrandedMutexGuard<'a, 'brand, T: ?Sized + 'a> {
+    guard: MutexGuard<'a, T>,
+    _brand: Brand<'brand>,
+}
+
+impl<'a, T: ?Sized> BrandedMutexGuard<'a, '_, T> {
+    /// Discards the brand, returning the plain guard.
+    #[inline]
+    pub fn into_inner(self) -> MutexGuard<'a, T> {
+        self.guard
+    }
+}
+
+impl<T: ?Sized> Deref for BrandedMutexGuard<'_, '_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized> DerefMut for BrandedMutexGuard<'_, '_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for BrandedMutexGuard<'_, '_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.guard, fmt)
+    }
+}
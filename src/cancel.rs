@@ -0,0 +1,101 @@
+use std::{
+    fmt,
+    sync::{
+        self,
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use crate::parking::{self, DEFAULT_TOKEN};
+
+/// A handle which interrupts the acquisitions waiting on it once it is
+/// cancelled.
+///
+/// It is passed to methods such as
+/// [`Mutex::lock_cancellable`](crate::Mutex::lock_cancellable), which block
+/// like their plain counterparts until [`cancel`](CancelToken::cancel) is
+/// called, from any thread, and then give up with
+/// [`Cancelled`](crate::Cancelled). Clones share the same state, so one can be
+/// handed to every thread which should stop waiting at shutdown. Cancellation
+/// is permanent.
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    // The keys the threads waiting on this token are parked on. A key appears
+    // once per waiter.
+    keys: sync::Mutex<Vec<usize>>,
+}
+
+impl CancelToken {
+    /// Creates a token which has not been cancelled.
+    #[inline]
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Cancels the token, waking every thread waiting on it.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        // A waiter records its key before it last checks the token with its
+        // bucket locked, so either it sees the token cancelled or its key is
+        // here.
+        let keys = self.keys().clone();
+        for key in keys {
+            // The primitive parked on the key is expected to cope with its
+            // waiters being woken early, as they only come back to wait again.
+            parking::unpark_all(key, |_| DEFAULT_TOKEN);
+        }
+    }
+
+    /// Returns `true` if the token has been cancelled.
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    // Records that the current thread is about to wait on `key`, until the
+    // returned value is dropped.
+    pub(crate) fn register(&self, key: usize) -> Registration<'_> {
+        self.keys().push(key);
+        Registration { token: self, key }
+    }
+
+    fn keys(&self) -> sync::MutexGuard<'_, Vec<usize>> {
+        self.inner.keys.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for CancelToken {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("CancelToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+pub(crate) struct Registration<'a> {
+    token: &'a CancelToken,
+    key: usize,
+}
+
+impl Registration<'_> {
+    #[inline]
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        let mut keys = self.token.keys();
+        if let Some(idx) = keys.iter().position(|&key| key == self.key) {
+            keys.swap_remove(idx);
+        }
+    }
+}
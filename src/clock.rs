@@ -0,0 +1,56 @@
+//! The time source used by timed operations.
+//!
+//! Everything in this crate that waits with a timeout reads the time and sleeps
+//! through the current thread's clock. That is the system clock unless a mock
+//! clock has been installed on the thread with the `test-util` feature.
+
+use std::{thread, time::Instant};
+
+pub(crate) trait Clock {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+
+    /// Blocks the current thread until `deadline` passes, the thread is
+    /// unparked, or it wakes up spuriously.
+    fn park_until(&self, deadline: Instant);
+}
+
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    #[inline]
+    fn park_until(&self, deadline: Instant) {
+        let now = Instant::now();
+        if deadline > now {
+            thread::park_timeout(deadline - now);
+        }
+    }
+}
+
+/// Calls `f` with the current thread's clock.
+#[cfg(not(feature = "test-util"))]
+#[inline]
+pub(crate) fn with_current<R>(f: impl FnOnce(&dyn Clock) -> R) -> R {
+    f(&SystemClock)
+}
+
+/// Calls `f` with the current thread's clock.
+#[cfg(feature = "test-util")]
+#[inline]
+pub(crate) fn with_current<R>(f: impl FnOnce(&dyn Clock) -> R) -> R {
+    match crate::test_util::installed_clock() {
+        Some(clock) => f(&clock),
+        None => f(&SystemClock),
+    }
+}
+
+/// Returns the current time according to the current thread's clock.
+#[inline]
+pub(crate) fn now() -> Instant {
+    with_current(|clock| clock.now())
+}
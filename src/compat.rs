@@ -0,0 +1,322 @@
+//! Drop-in replacements for the locks of `std::sync`, returning `Result`s.
+//!
+//! The types in this module are the crate's locks with the signatures of the
+//! standard library's: `lock`, `read` and `write` return a [`LockResult`], and
+//! the `try_` methods a [`TryLockResult`], both of the standard library, so
+//! that code written against `std::sync` compiles unchanged after replacing
+//! `use std::sync::*` with `use antidote::compat::*`. The rest of `std::sync`
+//! is re-exported as it is. The results are never `Err(PoisonError)`: the
+//! locks do not poison themselves, so every `.unwrap()`, `?` and match on a
+//! poisoned lock is simply never taken, and can be removed at leisure.
+//!
+//! Each lock dereferences to the crate's lock it wraps, so code moved over to
+//! the crate's own API can be written one call site at a time, and the guards
+//! are the crate's guards to begin with. Once nothing needs the `Result`s
+//! anymore, the imports can be switched to the crate's types.
+
+pub use std::sync::{
+    atomic, mpsc, Arc, Barrier, BarrierWaitResult, LockResult, Once, OnceState, PoisonError,
+    TryLockError, TryLockResult, Weak,
+};
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    time::Duration,
+};
+
+pub use crate::{
+    LazyLock, MutexGuard, OnceLock, RwLockReadGuard, RwLockWriteGuard, WaitTimeoutResult,
+};
+
+/// Like `std::sync::Mutex`, built on the crate's [`Mutex`](crate::Mutex).
+#[derive(Default)]
+pub struct Mutex<T: ?Sized> {
+    inner: crate::Mutex<T>,
+}
+
+impl<T> Mutex<T> {
+    /// Like `std::sync::Mutex::new`.
+    #[inline]
+    pub const fn new(t: T) -> Mutex<T> {
+        Mutex {
+            inner: crate::Mutex::new(t),
+        }
+    }
+
+    /// Like `std::sync::Mutex::into_inner`, but never fails.
+    #[inline]
+    pub fn into_inner(self) -> LockResult<T> {
+        Ok(self.inner.into_inner())
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Like `std::sync::Mutex::lock`, but never fails.
+    #[inline]
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+        Ok(self.inner.lock())
+    }
+
+    /// Like `std::sync::Mutex::try_lock`, but only fails with
+    /// `TryLockError::WouldBlock`.
+    #[inline]
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        self.inner.try_lock().map_err(TryLockError::from)
+    }
+
+    /// Like `std::sync::Mutex::is_poisoned`, but always returns `false`.
+    ///
+    /// Whether a panicking thread held the mutex is still recorded, and
+    /// reported by [`crate::Mutex::was_poisoned`].
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        false
+    }
+
+    /// Like `std::sync::Mutex::clear_poison`.
+    #[inline]
+    pub fn clear_poison(&self) {
+        self.inner.clear_poison();
+    }
+
+    /// Like `std::sync::Mutex::get_mut`, but never fails.
+    #[inline]
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        Ok(self.inner.get_mut())
+    }
+}
+
+impl<T: ?Sized> Deref for Mutex<T> {
+    type Target = crate::Mutex<T>;
+
+    #[inline]
+    fn deref(&self) -> &crate::Mutex<T> {
+        &self.inner
+    }
+}
+
+impl<T: ?Sized> DerefMut for Mutex<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut crate::Mutex<T> {
+        &mut self.inner
+    }
+}
+
+impl<T> From<T> for Mutex<T> {
+    /// Like `std::sync::Mutex::from`.
+    #[inline]
+    fn from(t: T) -> Mutex<T> {
+        Mutex::new(t)
+    }
+}
+
+impl<T> From<crate::Mutex<T>> for Mutex<T> {
+    #[inline]
+    fn from(inner: crate::Mutex<T>) -> Mutex<T> {
+        Mutex { inner }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    #[inline]
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(fmt)
+    }
+}
+
+/// Like `std::sync::RwLock`, built on the crate's [`RwLock`](crate::RwLock).
+#[derive(Default)]
+pub struct RwLock<T: ?Sized> {
+    inner: crate::RwLock<T>,
+}
+
+impl<T> RwLock<T> {
+    /// Like `std::sync::RwLock::new`.
+    #[inline]
+    pub const fn new(t: T) -> RwLock<T> {
+        RwLock {
+            inner: crate::RwLock::new(t),
+        }
+    }
+
+    /// Like `std::sync::RwLock::into_inner`, but never fails.
+    #[inline]
+    pub fn into_inner(self) -> LockResult<T> {
+        Ok(self.inner.into_inner())
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Like `std::sync::RwLock::read`, but never fails.
+    #[inline]
+    pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        Ok(self.inner.read())
+    }
+
+    /// Like `std::sync::RwLock::try_read`, but only fails with
+    /// `TryLockError::WouldBlock`.
+    #[inline]
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        self.inner.try_read().map_err(TryLockError::from)
+    }
+
+    /// Like `std::sync::RwLock::write`, but never fails.
+    #[inline]
+    pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
+        Ok(self.inner.write())
+    }
+
+    /// Like `std::sync::RwLock::try_write`, but only fails with
+    /// `TryLockError::WouldBlock`.
+    #[inline]
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        self.inner.try_write().map_err(TryLockError::from)
+    }
+
+    /// Like `std::sync::RwLock::is_poisoned`, but always returns `false`.
+    ///
+    /// Whether a panicking thread held the lock is still recorded, and
+    /// reported by [`crate::RwLock::was_poisoned`].
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        false
+    }
+
+    /// Like `std::sync::RwLock::clear_poison`.
+    #[inline]
+    pub fn clear_poison(&self) {
+        self.inner.clear_poison();
+    }
+
+    /// Like `std::sync::RwLock::get_mut`, but never fails.
+    #[inline]
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        Ok(self.inner.get_mut())
+    }
+}
+
+impl<T: ?Sized> Deref for RwLock<T> {
+    type Target = crate::RwLock<T>;
+
+    #[inline]
+    fn deref(&self) -> &crate::RwLock<T> {
+        &self.inner
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLock<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut crate::RwLock<T> {
+        &mut self.inner
+    }
+}
+
+impl<T> From<T> for RwLock<T> {
+    /// Like `std::sync::RwLock::from`.
+    #[inline]
+    fn from(t: T) -> RwLock<T> {
+        RwLock::new(t)
+    }
+}
+
+impl<T> From<crate::RwLock<T>> for RwLock<T> {
+    #[inline]
+    fn from(inner: crate::RwLock<T>) -> RwLock<T> {
+        RwLock { inner }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    #[inline]
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(fmt)
+    }
+}
+
+/// Like `std::sync::Condvar`, built on the crate's [`Condvar`](crate::Condvar).
+#[derive(Debug, Default)]
+pub struct Condvar {
+    inner: crate::Condvar,
+}
+
+impl Condvar {
+    /// Like `std::sync::Condvar::new`.
+    #[inline]
+    pub const fn new() -> Condvar {
+        Condvar {
+            inner: crate::Condvar::new(),
+        }
+    }
+
+    /// Like `std::sync::Condvar::wait`, but never fails.
+    // The signature is that of the standard library.
+    #[allow(clippy::result_large_err)]
+    #[inline]
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> LockResult<MutexGuard<'a, T>> {
+        Ok(self.inner.wait(guard))
+    }
+
+    /// Like `std::sync::Condvar::wait_while`, but never fails.
+    // The signature is that of the standard library.
+    #[allow(clippy::result_large_err)]
+    #[inline]
+    pub fn wait_while<'a, T, F>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        condition: F,
+    ) -> LockResult<MutexGuard<'a, T>>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        Ok(self.inner.wait_while(guard, condition))
+    }
+
+    /// Like `std::sync::Condvar::wait_timeout`, but never fails.
+    // The signature is that of the standard library.
+    #[allow(clippy::result_large_err)]
+    #[inline]
+    pub fn wait_timeout<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        dur: Duration,
+    ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)> {
+        Ok(self.inner.wait_timeout(guard, dur))
+    }
+
+    /// Like `std::sync::Condvar::wait_timeout_while`, but never fails.
+    // The signature is that of the standard library.
+    #[allow(clippy::result_large_err)]
+    #[inline]
+    pub fn wait_timeout_while<'a, T, F>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        dur: Duration,
+        condition: F,
+    ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        Ok(self.inner.wait_timeout_while(guard, dur, condition))
+    }
+
+    /// Like `std::sync::Condvar::notify_one`.
+    #[inline]
+    pub fn notify_one(&self) {
+        self.inner.notify_one();
+    }
+
+    /// Like `std::sync::Condvar::notify_all`.
+    #[inline]
+    pub fn notify_all(&self) {
+        self.inner.notify_all();
+    }
+}
+
+impl Deref for Condvar {
+    type Target = crate::Condvar;
+
+    #[inline]
+    fn deref(&self) -> &crate::Condvar {
+        &self.inner
+    }
+}
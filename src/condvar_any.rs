@@ -0,0 +1,169 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock,
+    parking::{self, ParkResult, DEFAULT_TOKEN},
+    poison::PoisonPolicy,
+    raw::{MutexStorage, RwLockBackend},
+    MutexGuard, RwLockReadGuard, RwLockUpgradableReadGuard, RwLockWriteGuard, WaitTimeoutResult,
+};
+
+/// A guard which can release its lock for a while and acquire it again.
+///
+/// This is what [`CondvarAny`] needs of the guards it waits with. It is
+/// implemented for the guards of [`Mutex`](crate::Mutex),
+/// [`RwLock`](crate::RwLock), [`FairMutex`](crate::FairMutex) and
+/// [`HybridMutex`](crate::HybridMutex), and can be implemented for other guards
+/// as well.
+pub trait Unlock {
+    /// Releases the lock, runs `f`, and acquires the lock again with the same
+    /// kind of access before returning what `f` returned.
+    ///
+    /// The lock must be acquired again even if `f` panics.
+    fn unlocked<U>(&mut self, f: impl FnOnce() -> U) -> U;
+}
+
+impl<T: ?Sized, P: PoisonPolicy, R: MutexStorage> Unlock for MutexGuard<'_, T, P, R> {
+    #[inline]
+    fn unlocked<U>(&mut self, f: impl FnOnce() -> U) -> U {
+        MutexGuard::unlocked(self, f)
+    }
+}
+
+impl<T: ?Sized, R: RwLockBackend> Unlock for RwLockReadGuard<'_, T, R> {
+    #[inline]
+    fn unlocked<U>(&mut self, f: impl FnOnce() -> U) -> U {
+        let lock = self.lock;
+        crate::unlocked(self, || lock.read(), f)
+    }
+}
+
+impl<T: ?Sized> Unlock for RwLockUpgradableReadGuard<'_, T> {
+    #[inline]
+    fn unlocked<U>(&mut self, f: impl FnOnce() -> U) -> U {
+        let lock = self.lock;
+        crate::unlocked(self, || lock.upgradable_read(), f)
+    }
+}
+
+impl<T: ?Sized, R: RwLockBackend> Unlock for RwLockWriteGuard<'_, T, R> {
+    #[inline]
+    fn unlocked<U>(&mut self, f: impl FnOnce() -> U) -> U {
+        RwLockWriteGuard::unlocked(self, f)
+    }
+}
+
+/// A condition variable which works with any guard implementing [`Unlock`],
+/// such as those of [`RwLock`](crate::RwLock).
+///
+/// Unlike [`Condvar`](crate::Condvar), it takes the guard by mutable
+/// reference, and a wait may return spuriously when another thread is
+/// notified at the same time. As with any condition variable, the condition
+/// waited for should be checked in a loop, which
+/// [`wait_while`](CondvarAny::wait_while) does. A thread changing the
+/// condition must hold the lock while doing so, or notifications may be
+/// missed.
+pub struct CondvarAny {
+    // Bumped by every notification, so a waiter which released its lock before
+    // it was queued sees that it missed one. Waiters park on its address.
+    seq: AtomicUsize,
+}
+
+impl CondvarAny {
+    /// Creates a new condition variable.
+    #[inline]
+    pub const fn new() -> CondvarAny {
+        CondvarAny {
+            seq: AtomicUsize::new(0),
+        }
+    }
+
+    /// Releases the lock behind `guard` and blocks the current thread until
+    /// it is notified, then acquires the lock again.
+    #[inline]
+    pub fn wait<G: Unlock>(&self, guard: &mut G) {
+        self.wait_until_internal(guard, None);
+    }
+
+    /// Like `wait`, but gives up waiting after `timeout`.
+    #[inline]
+    pub fn wait_for<G: Unlock>(&self, guard: &mut G, timeout: Duration) -> WaitTimeoutResult {
+        // A deadline too far in the future to represent is treated as no
+        // deadline at all.
+        self.wait_until_internal(guard, clock::now().checked_add(timeout))
+    }
+
+    /// Like `wait`, but gives up waiting once `deadline` has passed.
+    #[inline]
+    pub fn wait_until<G: Unlock>(&self, guard: &mut G, deadline: Instant) -> WaitTimeoutResult {
+        self.wait_until_internal(guard, Some(deadline))
+    }
+
+    /// Waits for as long as `condition` returns `true`, checking it with the
+    /// lock held before each wait.
+    pub fn wait_while<G: Unlock>(&self, guard: &mut G, mut condition: impl FnMut(&mut G) -> bool) {
+        while condition(guard) {
+            self.wait(guard);
+        }
+    }
+
+    pub(crate) fn wait_until_internal<G: Unlock>(
+        &self,
+        guard: &mut G,
+        deadline: Option<Instant>,
+    ) -> WaitTimeoutResult {
+        // Read while the lock is still held, so any notification sent after
+        // the lock is released changes it.
+        let seq = self.seq.load(Ordering::Relaxed);
+        let result = guard.unlocked(|| {
+            parking::park(
+                self.key(),
+                || self.seq.load(Ordering::Relaxed) == seq,
+                || {},
+                |_, _| {},
+                deadline,
+            )
+        });
+        WaitTimeoutResult(result == ParkResult::TimedOut)
+    }
+
+    /// Wakes up one blocked thread.
+    #[inline]
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        // The bucket is always locked, even if nothing seems to be parked: a
+        // waiter either sees the new `seq` while validating, or was queued
+        // before the bucket is locked here.
+        parking::unpark_one(self.key(), |_| DEFAULT_TOKEN);
+    }
+
+    /// Wakes up every blocked thread.
+    #[inline]
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        // See `notify_one`.
+        parking::unpark_all(self.key(), |_| DEFAULT_TOKEN);
+    }
+
+    #[inline]
+    fn key(&self) -> usize {
+        &self.seq as *const AtomicUsize as usize
+    }
+}
+
+impl Default for CondvarAny {
+    #[inline]
+    fn default() -> CondvarAny {
+        CondvarAny::new()
+    }
+}
+
+impl fmt::Debug for CondvarAny {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("CondvarAny").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,198 @@
+//! Process-wide tunables.
+
+use std::{
+    env,
+    error::Error,
+    ffi::{OsStr, OsString},
+    fmt,
+    str::FromStr,
+    sync::{
+        self,
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Once,
+    },
+    time::Duration,
+};
+
+static CONFIG: Config = Config {
+    spin_iterations: AtomicU32::new(0),
+    wait_threshold: AtomicU64::new(10_000_000),
+    hold_threshold: AtomicU64::new(10_000_000),
+};
+
+/// Returns the process-wide configuration.
+///
+/// On the first call, the defaults are overridden by any of the following
+/// environment variables which are set:
+///
+/// * `ANTIDOTE_SPIN_ITERATIONS` - see [`Config::set_spin_iterations`].
+/// * `ANTIDOTE_WAIT_THRESHOLD` - see [`Config::set_wait_threshold`].
+/// * `ANTIDOTE_HOLD_THRESHOLD` - see [`Config::set_hold_threshold`].
+///
+/// Durations are given as an integer followed by one of the units `ns`, `us`,
+/// `ms` or `s`, such as `250us`. Invalid values are ignored, and returned by
+/// [`Config::invalid_env_vars`] for the program to report as it sees fit.
+pub fn config() -> &'static Config {
+    static LOAD_ENV: Once = Once::new();
+
+    LOAD_ENV.call_once(|| CONFIG.load_env());
+    &CONFIG
+}
+
+// The environment variables ignored by `config`. This is the standard
+// library's mutex, as the crate's own locks read the configuration.
+static INVALID_ENV_VARS: sync::Mutex<Vec<InvalidEnvVar>> = sync::Mutex::new(Vec::new());
+
+/// An environment variable ignored by [`config`], as its value is invalid.
+#[derive(Debug, Clone)]
+pub struct InvalidEnvVar {
+    name: &'static str,
+    value: OsString,
+}
+
+impl InvalidEnvVar {
+    /// Returns the name of the variable.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the invalid value of the variable.
+    pub fn value(&self) -> &OsStr {
+        &self.value
+    }
+}
+
+impl fmt::Display for InvalidEnvVar {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "invalid value {:?} for {}", self.value, self.name)
+    }
+}
+
+impl Error for InvalidEnvVar {}
+
+/// Process-wide tunables, returned by [`config`].
+///
+/// Changes take effect immediately for every lock.
+pub struct Config {
+    spin_iterations: AtomicU32,
+    wait_threshold: AtomicU64,
+    hold_threshold: AtomicU64,
+}
+
+impl Config {
+    /// Returns the number of times a blocking acquisition retries before the
+    /// thread goes to sleep.
+    pub fn spin_iterations(&self) -> u32 {
+        self.spin_iterations.load(Ordering::Relaxed)
+    }
+
+    /// Sets the number of times a blocking acquisition of a `Mutex` or
+    /// `RwLock` retries, spinning briefly in between, before the thread goes
+    /// to sleep.
+    ///
+    /// Spinning can help locks which are only ever held very briefly. The
+    /// default is 0, leaving it to the standard library's locks, which spin a
+    /// little on their own.
+    pub fn set_spin_iterations(&self, iterations: u32) {
+        self.spin_iterations.store(iterations, Ordering::Relaxed);
+    }
+
+    /// Returns how long an acquisition must wait before instrumentation treats
+    /// it as a long wait.
+    pub fn wait_threshold(&self) -> Duration {
+        Duration::from_nanos(self.wait_threshold.load(Ordering::Relaxed))
+    }
+
+    /// Sets how long an acquisition must wait before instrumentation treats it
+    /// as a long wait, such as the `opentelemetry` module reporting it as a
+    /// span.
+    ///
+    /// The default is 10 milliseconds.
+    pub fn set_wait_threshold(&self, threshold: Duration) {
+        self.wait_threshold
+            .store(nanos(threshold), Ordering::Relaxed);
+    }
+
+    /// Returns how long a lock must be held before instrumentation treats it
+    /// as a long hold.
+    pub fn hold_threshold(&self) -> Duration {
+        Duration::from_nanos(self.hold_threshold.load(Ordering::Relaxed))
+    }
+
+    /// Sets how long a lock must be held before instrumentation treats it as
+    /// a long hold, such as the `opentelemetry` module reporting it as a span.
+    ///
+    /// The default is 10 milliseconds.
+    pub fn set_hold_threshold(&self, threshold: Duration) {
+        self.hold_threshold
+            .store(nanos(threshold), Ordering::Relaxed);
+    }
+
+    /// Returns the environment variables which were ignored when the
+    /// configuration was loaded, as their values are invalid.
+    ///
+    /// The crate does not report them on its own.
+    pub fn invalid_env_vars(&self) -> Vec<InvalidEnvVar> {
+        INVALID_ENV_VARS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn load_env(&self) {
+        let mut invalid = Vec::new();
+        if let Some(iterations) = var("ANTIDOTE_SPIN_ITERATIONS", |s| s.parse().ok(), &mut invalid)
+        {
+            self.set_spin_iterations(iterations);
+        }
+        if let Some(threshold) = var("ANTIDOTE_WAIT_THRESHOLD", parse_duration, &mut invalid) {
+            self.set_wait_threshold(threshold);
+        }
+        if let Some(threshold) = var("ANTIDOTE_HOLD_THRESHOLD", parse_duration, &mut invalid) {
+            self.set_hold_threshold(threshold);
+        }
+        *INVALID_ENV_VARS.lock().unwrap_or_else(|e| e.into_inner()) = invalid;
+    }
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Config")
+            .field("spin_iterations", &self.spin_iterations())
+            .field("wait_threshold", &self.wait_threshold())
+            .field("hold_threshold", &self.hold_threshold())
+            .finish()
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+fn var<T>(
+    name: &'static str,
+    parse: impl FnOnce(&str) -> Option<T>,
+    invalid: &mut Vec<InvalidEnvVar>,
+) -> Option<T> {
+    let value = env::var_os(name)?;
+    match value.to_str().and_then(|value| parse(value.trim())) {
+        Some(value) => Some(value),
+        None => {
+            invalid.push(InvalidEnvVar { name, value });
+            None
+        }
+    }
+}
+
+fn parse_duration(s: &str) -> Option<Duration> {
+    let split = s.find(|c: char| !c.is_ascii_digit())?;
+    let (value, unit) = s.split_at(split);
+    let value = u64::from_str(value).ok()?;
+    match unit {
+        "ns" => Some(Duration::from_nanos(value)),
+        "us" => Some(Duration::from_micros(value)),
+        "ms" => Some(Duration::from_millis(value)),
+        "s" => Some(Duration::from_secs(value)),
+        _ => None,
+    }
+}
@@ -0,0 +1,112 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use crate::{clock, Condvar, Mutex, ReadMostly};
+
+/// A shared value which is read constantly and replaced rarely, such as a
+/// configuration which can be reloaded while the program runs.
+///
+/// [`load`](ConfigCell::load) never blocks nor contends with other readers: it
+/// only touches a couple of atomic counters to clone the current `Arc`.
+/// [`store`](ConfigCell::store) replaces the value and wakes every thread
+/// blocked in [`wait_for_update`](ConfigCell::wait_for_update), so listeners
+/// can react to each new value without polling. Stores are serialized, and each
+/// one waits for the loads already in progress to finish cloning the value it
+/// replaces, which takes no longer than a few instructions per load.
+pub struct ConfigCell<T> {
+    value: ReadMostly<T>,
+    // The number of stores so far, which also serializes stores.
+    version: Mutex<u64>,
+    updated: Condvar,
+}
+
+impl<T> ConfigCell<T> {
+    /// Creates a new cell holding `value`.
+    pub fn new(value: T) -> ConfigCell<T> {
+        ConfigCell {
+            value: ReadMostly::new(value),
+            version: Mutex::new(0),
+            updated: Condvar::new(),
+        }
+    }
+
+    /// Returns the current value.
+    #[inline]
+    pub fn load(&self) -> Arc<T> {
+        self.value.load()
+    }
+
+    /// Replaces the value, waking every thread waiting for an update.
+    pub fn store(&self, value: T) {
+        let mut version = self.version.lock();
+        self.value.store(value);
+        *version += 1;
+        drop(version);
+        self.updated.notify_all();
+    }
+
+    /// Returns the number of times the value has been replaced.
+    #[inline]
+    pub fn version(&self) -> u64 {
+        *self.version.lock()
+    }
+
+    /// Blocks the current thread until the value is replaced after `seen`,
+    /// then returns the current value and updates `seen` to its version.
+    ///
+    /// `seen` is typically initialized with [`version`](ConfigCell::version)
+    /// and handed back on every call, so each listener sees every update at
+    /// least once, although updates made in quick succession may be observed
+    /// as one.
+    pub fn wait_for_update(&self, seen: &mut u64) -> Arc<T> {
+        let mut version = self.version.lock();
+        while *version == *seen {
+            version = self.updated.wait(version);
+        }
+        *seen = *version;
+        drop(version);
+        self.load()
+    }
+
+    /// Like [`wait_for_update`](ConfigCell::wait_for_update), but gives up
+    /// and returns `None` if the value is not replaced within `timeout`.
+    pub fn wait_for_update_timeout(&self, seen: &mut u64, timeout: Duration) -> Option<Arc<T>> {
+        let deadline = clock::now().checked_add(timeout);
+        let mut version = self.version.lock();
+        while *version == *seen {
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(clock::now()),
+                None => Duration::MAX,
+            };
+            if remaining == Duration::ZERO {
+                return None;
+            }
+            version = self.updated.wait_timeout(version, remaining).0;
+        }
+        *seen = *version;
+        drop(version);
+        Some(self.load())
+    }
+}
+
+impl<T: Default> Default for ConfigCell<T> {
+    #[inline]
+    fn default() -> ConfigCell<T> {
+        ConfigCell::new(T::default())
+    }
+}
+
+impl<T> From<T> for ConfigCell<T> {
+    #[inline]
+    fn from(t: T) -> ConfigCell<T> {
+        ConfigCell::new(t)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ConfigCell<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("ConfigCell")
+            .field("value", &self.load())
+            .field("version", &self.version())
+            .finish()
+    }
+}
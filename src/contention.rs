@@ -0,0 +1,46 @@
+//! Advisory bookkeeping of threads blocked on a lock.
+//!
+//! Counts live in a fixed table indexed by the lock's address rather than in
+//! the locks themselves, so the lock types stay no larger than the locks they
+//! are built on. Unrelated locks can hash to the same slot, so
+//! the counts only ever err towards reporting contention.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const SLOT_BITS: u32 = 6;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+
+static WAITING: [AtomicUsize; 1 << SLOT_BITS] = [ZERO; 1 << SLOT_BITS];
+
+fn slot(addr: usize) -> &'static AtomicUsize {
+    // Fibonacci hashing; truncating the multiplier on 32-bit targets is harmless.
+    let hash = addr.wrapping_mul(0x9E37_79B9_7F4A_7C15_u64 as usize);
+    &WAITING[hash >> (usize::BITS - SLOT_BITS)]
+}
+
+/// Marks a thread as blocked on the lock at `addr` for as long as it is alive.
+pub(crate) struct Waiting(&'static AtomicUsize);
+
+impl Waiting {
+    #[inline]
+    pub(crate) fn new(addr: usize) -> Waiting {
+        let slot = slot(addr);
+        slot.fetch_add(1, Ordering::Relaxed);
+        Waiting(slot)
+    }
+}
+
+impl Drop for Waiting {
+    #[inline]
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns whether any thread may be blocked on the lock at `addr`.
+#[inline]
+pub(crate) fn is_waiting(addr: usize) -> bool {
+    slot(addr).load(Ordering::Relaxed) != 0
+}
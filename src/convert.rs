@@ -0,0 +1,139 @@
+//! Conversions between the locks and those of the standard library.
+//!
+//! Every lock can be converted to and from its standard library counterpart by
+//! value. With the `std-backend` feature, `Mutex` is guaranteed to be built on
+//! the standard library's mutex, which it can then also lend out, along with
+//! its guards. `RwLock` and `Condvar` are built on locks of the crate's own, so
+//! they cannot.
+
+use std::sync;
+#[cfg(feature = "std-backend")]
+use std::{mem::ManuallyDrop, ptr};
+
+#[cfg(feature = "std-backend")]
+use crate::{parking, MutexGuard};
+use crate::{poison::PoisonPolicy, Condvar, Mutex, RwLock};
+
+impl<T> Mutex<T> {
+    /// Creates a mutex holding the value of a standard library mutex.
+    ///
+    /// If `mutex` was poisoned, the new mutex is marked as held by a panicking
+    /// thread, as reported by [`Mutex::was_poisoned`].
+    #[inline]
+    pub fn from_std(mutex: sync::Mutex<T>) -> Mutex<T> {
+        let poisoned = mutex.is_poisoned();
+        let mutex = Mutex::new(mutex.into_inner().unwrap_or_else(|e| e.into_inner()));
+        if poisoned {
+            mutex.poison.set();
+        }
+        mutex
+    }
+}
+
+impl<T, P: PoisonPolicy> Mutex<T, P> {
+    /// Returns a standard library mutex holding the value of this one.
+    ///
+    /// The standard library mutex is never poisoned, whether or not this one
+    /// was marked by a panicking thread.
+    #[inline]
+    pub fn into_std(self) -> sync::Mutex<T> {
+        sync::Mutex::new(self.into_inner())
+    }
+}
+
+#[cfg(feature = "std-backend")]
+impl<T: ?Sized, P: PoisonPolicy> Mutex<T, P> {
+    /// Returns the standard library mutex underneath this one.
+    ///
+    /// This lets the mutex be passed to code which takes a
+    /// `&std::sync::Mutex<T>`. Both are the same lock, so each excludes the
+    /// other. Acquisitions made through the standard library mutex are not
+    /// seen by this crate, though: they skip the poison policy and the
+    /// instrumentation, and a panic while holding one of its guards is not
+    /// reported by [`Mutex::was_poisoned`]. The standard library mutex is
+    /// poisoned by panics while holding either kind of guard, as usual.
+    ///
+    /// This needs the `std-backend` feature, which guarantees that the mutex
+    /// is built on the standard library's.
+    #[cfg_attr(docsrs, doc(cfg(feature = "std-backend")))]
+    #[inline]
+    pub fn as_std(&self) -> &sync::Mutex<T> {
+        &self.inner
+    }
+}
+
+#[cfg(feature = "std-backend")]
+impl<'a, T: ?Sized, P: PoisonPolicy> MutexGuard<'a, T, P> {
+    /// Converts the guard into a guard of the standard library mutex
+    /// underneath, returned by [`Mutex::as_std`], without releasing the lock.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`. From then on, the mutex is no longer reported as held by the
+    /// instrumentation, and a panic while holding the returned guard is not
+    /// reported by [`Mutex::was_poisoned`].
+    ///
+    /// This needs the `std-backend` feature, which guarantees that the mutex
+    /// is built on the standard library's.
+    #[cfg_attr(docsrs, doc(cfg(feature = "std-backend")))]
+    pub fn into_std(s: Self) -> sync::MutexGuard<'a, T> {
+        let mut s = ManuallyDrop::new(s);
+        s.lock.poison.done(&s.poison);
+        // Condvar waiters requeued onto the mutex are only woken by its guards,
+        // so they are all woken now to block on the standard library mutex
+        // instead.
+        if parking::may_have_parked(s.lock.addr()) {
+            parking::unpark_all(s.lock.addr(), |_| parking::DEFAULT_TOKEN);
+        }
+        // SAFETY: the guard is never used or dropped again, so the token and
+        // the backend guard are moved out of it only once.
+        let held = unsafe { ptr::read(&s._held) };
+        held.releasing();
+        // SAFETY: as above.
+        unsafe { ManuallyDrop::take(&mut s.guard) }
+    }
+}
+
+impl<T> RwLock<T> {
+    /// Creates a lock holding the value of a standard library lock.
+    ///
+    /// If `rwlock` was poisoned, the new lock is marked as held by a panicking
+    /// thread, as reported by [`RwLock::was_poisoned`].
+    #[inline]
+    pub fn from_std(rwlock: sync::RwLock<T>) -> RwLock<T> {
+        let poisoned = rwlock.is_poisoned();
+        let rwlock = RwLock::new(rwlock.into_inner().unwrap_or_else(|e| e.into_inner()));
+        if poisoned {
+            rwlock.poison.set();
+        }
+        rwlock
+    }
+
+    /// Returns a standard library lock holding the value of this one.
+    ///
+    /// The standard library lock is never poisoned, whether or not this one
+    /// was marked by a panicking thread.
+    #[inline]
+    pub fn into_std(self) -> sync::RwLock<T> {
+        sync::RwLock::new(self.into_inner())
+    }
+}
+
+impl Condvar {
+    /// Creates a condition variable in place of a standard library one.
+    ///
+    /// No thread can be waiting on a condition variable which is moved, so
+    /// this is the same as [`Condvar::new`].
+    #[inline]
+    pub fn from_std(_: sync::Condvar) -> Condvar {
+        Condvar::new()
+    }
+
+    /// Returns a standard library condition variable in place of this one.
+    ///
+    /// No thread can be waiting on a condition variable which is moved, so
+    /// this is the same as `std::sync::Condvar::new`.
+    #[inline]
+    pub fn into_std(self) -> sync::Condvar {
+        sync::Condvar::new()
+    }
+}
@@ -0,0 +1,235 @@
+//! The mutex built on the `critical-section` crate.
+//!
+//! On a bare-metal target, the code holding a lock can be interrupted by a
+//! handler which wants the same lock, and spinning there would wait forever
+//! for code which cannot run until the handler returns. This mutex instead
+//! holds a critical section, as provided by the `critical-section`
+//! implementation linked into the program, for as long as it is locked, which
+//! typically masks interrupts on a single core, and takes a hardware spinlock
+//! besides on multi-core chips. Nothing else can then contend for the mutex,
+//! so it never waits: acquiring it again while it is held can only be a
+//! reentrant acquisition, and panics.
+
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    panic::{RefUnwindSafe, UnwindSafe},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use critical_section::RestoreState;
+
+#[cfg(feature = "std")]
+use crate::Lock;
+use crate::{LockOperation, TryLockError, TryLockResult};
+
+/// A mutex which holds a critical section while it is locked.
+///
+/// It is meant for interrupt-driven targets, where code holding a lock can be
+/// interrupted by a handler which wants it too, and has the same basic API as
+/// [`Mutex`](crate::Mutex). See the [`critical-section`] crate for how
+/// critical sections are provided. Locking the mutex while it is already held
+/// by the same context, including a handler which interrupted its holder on a
+/// target where critical sections do not mask that interrupt, panics rather
+/// than waiting forever.
+///
+/// Critical sections are meant to nest: guards should be dropped in the
+/// reverse order of their acquisition, and held for as short a time as
+/// possible, as they delay interrupts.
+///
+/// In `no_std` builds with the `critical-section` feature enabled, this is
+/// the crate's `Mutex`.
+///
+/// [`critical-section`]: https://docs.rs/critical-section
+#[derive(Default)]
+pub struct CriticalSectionMutex<T: ?Sized> {
+    // Only accessed inside a critical section, but atomic so that guards
+    // dropped out of order cannot race with a handler.
+    locked: AtomicBool,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: the mutex hands out `&mut T` to one context at a time.
+unsafe impl<T: ?Sized + Send> Send for CriticalSectionMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for CriticalSectionMutex<T> {}
+
+impl<T: ?Sized> UnwindSafe for CriticalSectionMutex<T> {}
+impl<T: ?Sized> RefUnwindSafe for CriticalSectionMutex<T> {}
+
+impl<T> CriticalSectionMutex<T> {
+    /// Like `std::sync::Mutex::new`.
+    #[inline]
+    pub const fn new(t: T) -> CriticalSectionMutex<T> {
+        CriticalSectionMutex {
+            locked: AtomicBool::new(false),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Like `std::sync::Mutex::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T> From<T> for CriticalSectionMutex<T> {
+    /// Like `std::sync::Mutex::from`.
+    #[inline]
+    fn from(t: T) -> CriticalSectionMutex<T> {
+        CriticalSectionMutex::new(t)
+    }
+}
+
+impl<T: ?Sized> CriticalSectionMutex<T> {
+    /// Enters a critical section and acquires the mutex.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is already held, as whoever holds it cannot release
+    /// it until the current context is done.
+    #[inline]
+    #[track_caller]
+    pub fn lock(&self) -> CriticalSectionMutexGuard<'_, T> {
+        match self.try_lock() {
+            Ok(guard) => guard,
+            Err(_) => reentrant(),
+        }
+    }
+
+    /// Enters a critical section and acquires the mutex if it is not already
+    /// held.
+    #[inline]
+    pub fn try_lock(&self) -> TryLockResult<CriticalSectionMutexGuard<'_, T>> {
+        // SAFETY: the critical section is released by the guard, or right
+        // below.
+        let state = unsafe { critical_section::acquire() };
+        if self.locked.load(Ordering::Relaxed) {
+            // SAFETY: `state` was returned by the matching `acquire`.
+            unsafe { critical_section::release(state) };
+            return Err(TryLockError::new(LockOperation::Lock));
+        }
+        self.locked.store(true, Ordering::Relaxed);
+        Ok(CriticalSectionMutexGuard {
+            lock: self,
+            state,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Like `std::sync::Mutex::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Returns `true` if the mutex is currently held.
+    ///
+    /// Outside of a critical section, the answer may be stale as soon as it is
+    /// returned.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+}
+
+#[cold]
+#[track_caller]
+fn reentrant() -> ! {
+    panic!("deadlock: locking a CriticalSectionMutex which the current context already holds")
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for CriticalSectionMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        // Named after what it is exported as.
+        let mut d = fmt.debug_struct(if cfg!(feature = "std") {
+            "CriticalSectionMutex"
+        } else {
+            "Mutex"
+        });
+        match self.try_lock() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(_) => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+/// A guard for a [`CriticalSectionMutex`], holding its critical section.
+///
+/// In `no_std` builds with the `critical-section` feature enabled, this is the
+/// crate's `MutexGuard`.
+#[must_use]
+pub struct CriticalSectionMutexGuard<'a, T: ?Sized + 'a> {
+    lock: &'a CriticalSectionMutex<T>,
+    state: RestoreState,
+    // A critical section must be released by the context which entered it.
+    _not_send: PhantomData<*const ()>,
+}
+
+// SAFETY: the guard only hands out `&T` through shared references to it.
+unsafe impl<T: ?Sized + Sync> Sync for CriticalSectionMutexGuard<'_, T> {}
+
+impl<T: ?Sized> Drop for CriticalSectionMutexGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+        // SAFETY: `state` was returned by the `acquire` this guard was created
+        // after.
+        unsafe { critical_section::release(self.state) };
+    }
+}
+
+impl<T: ?Sized> Deref for CriticalSectionMutexGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the lock.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for CriticalSectionMutexGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the lock.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for CriticalSectionMutexGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let name = if cfg!(feature = "std") {
+            "CriticalSectionMutexGuard"
+        } else {
+            "MutexGuard"
+        };
+        fmt.debug_tuple(name).field(&&**self).finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: ?Sized> Lock<T> for CriticalSectionMutex<T> {
+    type Guard<'a>
+        = CriticalSectionMutexGuard<'a, T>
+    where
+        T: 'a;
+
+    #[inline]
+    fn lock(&self) -> CriticalSectionMutexGuard<'_, T> {
+        self.lock()
+    }
+
+    #[inline]
+    fn try_lock(&self) -> TryLockResult<CriticalSectionMutexGuard<'_, T>> {
+        self.try_lock()
+    }
+
+    #[inline]
+    fn get_mut(&mut self) -> &mut T {
+        self.get_mut()
+    }
+}
@@ -0,0 +1,200 @@
+//! Detection of deadlocks between threads at runtime.
+//!
+//! This relies on the bookkeeping of the `tracking` feature, which records
+//! the locks each thread holds and the lock each thread is blocked on. Nothing
+//! runs in the background: call [`check`] periodically, for example from a
+//! watchdog thread, to find the threads which can no longer make progress.
+
+use std::{
+    fmt,
+    thread::{Thread, ThreadId},
+};
+
+use crate::{
+    debug::{self, LockInfo},
+    held, Mutex, RwLock,
+};
+
+/// Returns every deadlock between the threads currently blocked on a lock.
+///
+/// Each deadlock is a cycle of threads, in which every thread is blocked
+/// acquiring a lock held by the next one, and the last thread is blocked on a
+/// lock held by the first. A thread blocked on a lock it already holds itself
+/// forms a cycle on its own.
+///
+/// The locks are inspected one thread at a time while the other threads keep
+/// running, so a cycle may be reported just as it is broken by a thread
+/// giving up a timed acquisition, such as
+/// [`try_lock_for`](crate::Mutex::try_lock_for).
+pub fn check() -> Vec<Vec<DeadlockedThread>> {
+    let threads = held::snapshot();
+
+    // In the wait-for graph, a thread blocked on a lock waits for every
+    // thread holding it.
+    let edges = threads
+        .iter()
+        .map(|thread| {
+            if thread.waiting == 0 {
+                return vec![];
+            }
+            threads
+                .iter()
+                .enumerate()
+                .filter(|(_, holder)| holder.held.iter().any(|held| held.addr == thread.waiting))
+                .map(|(idx, _)| idx)
+                .collect()
+        })
+        .collect::<Vec<Vec<usize>>>();
+
+    let mut search = Search {
+        edges: &edges,
+        state: vec![Visit::New; threads.len()],
+        path: vec![],
+        cycles: vec![],
+    };
+    for idx in 0..threads.len() {
+        if search.state[idx] == Visit::New {
+            search.visit(idx);
+        }
+    }
+
+    search
+        .cycles
+        .into_iter()
+        .map(|cycle| {
+            cycle
+                .into_iter()
+                .map(|idx| {
+                    let thread = &threads[idx];
+                    DeadlockedThread {
+                        thread: thread.thread.clone(),
+                        waiting_for: thread.waiting,
+                        waiting_for_name: name(&threads, thread.waiting),
+                        held: thread
+                            .held
+                            .iter()
+                            .copied()
+                            .map(LockInfo::from_held)
+                            .collect(),
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Returns the name of the lock at `addr`, which is held by one of `threads`
+// since a thread in the cycle waits for it.
+fn name(threads: &[held::Snapshot], addr: usize) -> Option<&'static str> {
+    #[cfg(feature = "names")]
+    {
+        threads
+            .iter()
+            .flat_map(|thread| &thread.held)
+            .find(|held| held.addr == addr)
+            .and_then(|held| held.name)
+    }
+    #[cfg(not(feature = "names"))]
+    {
+        let _ = (threads, addr);
+        None
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    New,
+    // On the current search path.
+    Active,
+    Done,
+}
+
+// A depth-first search reporting the cycle closed by each edge back onto the
+// current path.
+struct Search<'a> {
+    edges: &'a [Vec<usize>],
+    state: Vec<Visit>,
+    path: Vec<usize>,
+    cycles: Vec<Vec<usize>>,
+}
+
+impl Search<'_> {
+    fn visit(&mut self, idx: usize) {
+        self.state[idx] = Visit::Active;
+        self.path.push(idx);
+        for &next in &self.edges[idx] {
+            match self.state[next] {
+                Visit::New => self.visit(next),
+                Visit::Active => {
+                    let start = self.path.iter().position(|&idx| idx == next).unwrap();
+                    self.cycles.push(self.path[start..].to_vec());
+                }
+                Visit::Done => {}
+            }
+        }
+        self.path.pop();
+        self.state[idx] = Visit::Done;
+    }
+}
+
+/// A thread taking part in a deadlock, as reported by [`check`].
+#[derive(Clone)]
+pub struct DeadlockedThread {
+    thread: Thread,
+    waiting_for: usize,
+    waiting_for_name: Option<&'static str>,
+    held: Vec<LockInfo>,
+}
+
+impl DeadlockedThread {
+    /// Returns the thread.
+    pub fn thread(&self) -> &Thread {
+        &self.thread
+    }
+
+    /// Returns the id of the thread.
+    pub fn thread_id(&self) -> ThreadId {
+        self.thread.id()
+    }
+
+    /// Returns the address of the lock the thread is blocked on.
+    pub fn waiting_for(&self) -> usize {
+        self.waiting_for
+    }
+
+    /// Returns the name of the lock the thread is blocked on, if it was
+    /// created with `new_named` and the `names` feature is enabled.
+    pub fn waiting_for_name(&self) -> Option<&'static str> {
+        self.waiting_for_name
+    }
+
+    /// Returns `true` if the thread is blocked on the given mutex.
+    pub fn is_waiting_for_mutex<T: ?Sized>(&self, mutex: &Mutex<T>) -> bool {
+        self.waiting_for == mutex.addr()
+    }
+
+    /// Returns `true` if the thread is blocked on the given `RwLock`.
+    pub fn is_waiting_for_rwlock<T: ?Sized>(&self, rwlock: &RwLock<T>) -> bool {
+        self.waiting_for == rwlock.addr()
+    }
+
+    /// Returns the locks held by the thread, in the order they were acquired.
+    pub fn held_locks(&self) -> &[LockInfo] {
+        &self.held
+    }
+}
+
+impl fmt::Debug for DeadlockedThread {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_struct("DeadlockedThread");
+        d.field("thread", &format_args!("{}", debug::name(&self.thread)));
+        match self.waiting_for_name {
+            Some(name) => d.field(
+                "waiting_for",
+                &format_args!("'{}' at {:#x}", name, self.waiting_for),
+            ),
+            None => d.field("waiting_for", &format_args!("{:#x}", self.waiting_for)),
+        };
+        d.field("held", &self.held).finish()
+    }
+}
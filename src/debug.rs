@@ -0,0 +1,163 @@
+//! Introspection of the locks held by the current thread.
+//!
+//! This relies on the bookkeeping of the `tracking` feature, which records
+//! every `Mutex` and `RwLock` guard for as long as it is alive, along with the
+//! lock each thread is blocked on.
+
+use std::{
+    fmt::{self, Write},
+    panic::{self, Location},
+    sync::Once,
+    thread::{self, Thread},
+};
+
+use crate::{held, Mutex, RwLock};
+
+/// Returns the locks held by the current thread, in the order they were
+/// acquired.
+///
+/// A lock acquired for shared access by several guards at once is listed once
+/// per guard.
+pub fn current_thread_held_locks() -> Vec<LockInfo> {
+    held::current()
+        .into_iter()
+        .map(LockInfo::from_held)
+        .collect()
+}
+
+/// A lock held by a thread.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LockInfo {
+    addr: usize,
+    shared: bool,
+    name: Option<&'static str>,
+    location: Option<&'static Location<'static>>,
+}
+
+impl LockInfo {
+    pub(crate) fn from_held(held: held::Held) -> LockInfo {
+        LockInfo {
+            addr: held.addr,
+            shared: held.access == held::Access::Shared,
+            #[cfg(feature = "names")]
+            name: held.name,
+            #[cfg(not(feature = "names"))]
+            name: None,
+            #[cfg(feature = "holder-location")]
+            location: Some(held.location),
+            #[cfg(not(feature = "holder-location"))]
+            location: None,
+        }
+    }
+
+    /// Returns the address of the lock.
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+
+    /// Returns the name of the lock, if it was created with `new_named` and
+    /// the `names` feature is enabled.
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// Returns the source location at which the lock was acquired, if the
+    /// `holder-location` feature is enabled.
+    pub fn location(&self) -> Option<&'static Location<'static>> {
+        self.location
+    }
+
+    /// Returns `true` if the lock is held for shared access only.
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    /// Returns `true` if this is the given mutex.
+    pub fn is_mutex<T: ?Sized>(&self, mutex: &Mutex<T>) -> bool {
+        self.addr == mutex.addr()
+    }
+
+    /// Returns `true` if this is the given `RwLock`.
+    pub fn is_rwlock<T: ?Sized>(&self, rwlock: &RwLock<T>) -> bool {
+        self.addr == rwlock.addr()
+    }
+}
+
+/// Installs a panic hook listing the locks held by the panicking thread.
+///
+/// The hook runs the previously installed hook first, then prints each lock
+/// the panicking thread holds along with the threads blocked acquiring it.
+/// Installing the hook more than once has no further effect.
+///
+/// With the `panic-hook` feature, this is called the first time a thread
+/// acquires a `Mutex` or `RwLock`, so the hook only needs to be installed by
+/// hand to be in place earlier. Either way, a hook set afterwards replaces it
+/// unless it calls the hook it replaced.
+pub fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            previous(info);
+            let held = current_thread_held_locks();
+            if !held.is_empty() {
+                eprint!("{}", describe_held(&held));
+            }
+        }));
+    });
+}
+
+fn describe_held(held: &[LockInfo]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "note: thread {} held {} antidote lock{} when it panicked:",
+        name(&thread::current()),
+        held.len(),
+        if held.len() == 1 { "" } else { "s" },
+    );
+    for lock in held {
+        let access = if lock.shared { "shared" } else { "exclusive" };
+        match lock.name {
+            Some(name) => {
+                let _ = write!(out, "  '{}' at {:#x} ({})", name, lock.addr, access);
+            }
+            None => {
+                let _ = write!(out, "  {:#x} ({})", lock.addr, access);
+            }
+        }
+        if let Some(location) = lock.location {
+            let _ = write!(out, ", acquired at {}", location);
+        }
+        let waiters = held::waiters(lock.addr);
+        if !waiters.is_empty() {
+            let waiters = waiters.iter().map(name).collect::<Vec<_>>();
+            let _ = write!(out, ", blocking {}", waiters.join(", "));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub(crate) fn name(thread: &Thread) -> String {
+    match thread.name() {
+        Some(name) => format!("'{}'", name),
+        None => format!("{:?}", thread.id()),
+    }
+}
+
+impl fmt::Debug for LockInfo {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_struct("LockInfo");
+        if let Some(name) = self.name {
+            d.field("name", &name);
+        }
+        d.field("addr", &format_args!("{:#x}", self.addr))
+            .field("shared", &self.shared);
+        if let Some(location) = self.location {
+            d.field("location", &format_args!("{}", location));
+        }
+        d.finish()
+    }
+}
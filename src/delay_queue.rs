@@ -0,0 +1,215 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{clock, Condvar, Mutex, MutexGuard};
+
+/// A queue of items which each become available once their deadline passes.
+///
+/// [`pop`](DelayQueue::pop) blocks until the earliest deadline has passed and
+/// returns that item. Inserting an item due earlier than every other one wakes
+/// a thread waiting for the previous earliest deadline, so it never oversleeps.
+/// Items with the same deadline are returned in insertion order.
+///
+/// Deadlines are read from the clock used by the crate's other timed
+/// operations, so they follow a mock clock installed with the `test-util`
+/// feature.
+pub struct DelayQueue<T> {
+    state: Mutex<State<T>>,
+    available: Condvar,
+}
+
+struct State<T> {
+    heap: BinaryHeap<Entry<T>>,
+    next_seq: u64,
+}
+
+struct Entry<T> {
+    deadline: Instant,
+    seq: u64,
+    item: T,
+}
+
+// `BinaryHeap` is a max-heap, so the earliest entry must compare greatest.
+impl<T> Ord for Entry<T> {
+    fn cmp(&self, other: &Entry<T>) -> Ordering {
+        (other.deadline, other.seq).cmp(&(self.deadline, self.seq))
+    }
+}
+
+impl<T> PartialOrd for Entry<T> {
+    fn partial_cmp(&self, other: &Entry<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> PartialEq for Entry<T> {
+    fn eq(&self, other: &Entry<T>) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Entry<T> {}
+
+impl<T> DelayQueue<T> {
+    /// Creates a new empty queue.
+    #[inline]
+    pub fn new() -> DelayQueue<T> {
+        DelayQueue {
+            state: Mutex::new(State {
+                heap: BinaryHeap::new(),
+                next_seq: 0,
+            }),
+            available: Condvar::new(),
+        }
+    }
+
+    /// Inserts `item`, to become available once `deadline` passes.
+    pub fn push(&self, item: T, deadline: Instant) {
+        let mut state = self.state.lock();
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        let earliest = match state.heap.peek() {
+            Some(head) => deadline < head.deadline,
+            None => true,
+        };
+        state.heap.push(Entry {
+            deadline,
+            seq,
+            item,
+        });
+        drop(state);
+        if earliest {
+            // Whoever waits for the previous earliest deadline would sleep
+            // past this one.
+            self.available.notify_one();
+        }
+    }
+
+    /// Inserts `item`, to become available once `delay` has elapsed.
+    ///
+    /// A delay too long to be represented as a deadline is shortened to about
+    /// thirty years.
+    pub fn push_after(&self, item: T, delay: Duration) {
+        let now = clock::now();
+        let deadline = now.checked_add(delay).unwrap_or_else(|| far_future(now));
+        self.push(item, deadline);
+    }
+
+    /// Removes the item with the earliest deadline, blocking the current
+    /// thread until that deadline has passed.
+    ///
+    /// If the queue is empty, this blocks until an item is inserted and its
+    /// deadline has passed.
+    pub fn pop(&self) -> T {
+        match self.pop_until_internal(None) {
+            Some(item) => item,
+            None => unreachable!("pop without a deadline timed out"),
+        }
+    }
+
+    /// Removes the item with the earliest deadline if that deadline has
+    /// passed, without blocking.
+    pub fn try_pop(&self) -> Option<T> {
+        let state = self.state.lock();
+        match state.heap.peek() {
+            Some(head) if head.deadline <= clock::now() => Some(self.take(state)),
+            _ => None,
+        }
+    }
+
+    /// Like [`pop`](DelayQueue::pop), but gives up and returns `None` if no
+    /// item becomes available within `timeout`.
+    pub fn pop_timeout(&self, timeout: Duration) -> Option<T> {
+        // A deadline too far in the future to represent is treated as no
+        // deadline at all.
+        self.pop_until_internal(clock::now().checked_add(timeout))
+    }
+
+    fn pop_until_internal(&self, deadline: Option<Instant>) -> Option<T> {
+        let mut state = self.state.lock();
+        loop {
+            let now = clock::now();
+            let wake = match state.heap.peek() {
+                Some(head) if head.deadline <= now => return Some(self.take(state)),
+                Some(head) => Some(match deadline {
+                    Some(deadline) => deadline.min(head.deadline),
+                    None => head.deadline,
+                }),
+                None => deadline,
+            };
+            if let Some(deadline) = deadline {
+                if deadline <= now {
+                    let more = !state.heap.is_empty();
+                    drop(state);
+                    if more {
+                        // This thread may have been woken for the earliest
+                        // item, so hand that wakeup on.
+                        self.available.notify_one();
+                    }
+                    return None;
+                }
+            }
+            state = self.available.wait_until_internal(state, wake).0;
+        }
+    }
+
+    fn take(&self, mut state: MutexGuard<'_, State<T>>) -> T {
+        let item = state.heap.pop().unwrap().item;
+        let more = !state.heap.is_empty();
+        drop(state);
+        if more {
+            // The next item may be due already, and its waiter was not told.
+            self.available.notify_one();
+        }
+        item
+    }
+
+    /// Returns the deadline of the earliest item, or `None` if the queue is
+    /// empty.
+    #[inline]
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.state.lock().heap.peek().map(|head| head.deadline)
+    }
+
+    /// Returns the number of items in the queue, whether their deadline has
+    /// passed or not.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.state.lock().heap.len()
+    }
+
+    /// Returns `true` if the queue holds no items.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().heap.is_empty()
+    }
+}
+
+// Roughly thirty years, which every platform can add to a recent `Instant`.
+fn far_future(now: Instant) -> Instant {
+    now + Duration::from_secs(86400 * 365 * 30)
+}
+
+impl<T> Default for DelayQueue<T> {
+    #[inline]
+    fn default() -> DelayQueue<T> {
+        DelayQueue::new()
+    }
+}
+
+impl<T> fmt::Debug for DelayQueue<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock();
+        fmt.debug_struct("DelayQueue")
+            .field("len", &state.heap.len())
+            .field(
+                "next_deadline",
+                &state.heap.peek().map(|head| head.deadline),
+            )
+            .finish()
+    }
+}
@@ -0,0 +1,46 @@
+//! Adapters for [`embassy-sync`](embassy_sync).
+//!
+//! embassy-sync's mutexes are generic over a [`RawMutex`] deciding what they
+//! may be shared between. [`BlockingRawMutex`] is backed by this crate's
+//! [`Mutex`], so embassy mutexes using it can be shared between OS threads,
+//! for example between async tasks and ordinary blocking code on hosted
+//! targets.
+//!
+//! Unlike embassy's `CriticalSectionRawMutex`, this provides no protection
+//! against interrupt handlers, and it must not be locked reentrantly.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+
+use crate::Mutex;
+
+/// An embassy-sync [`RawMutex`] which blocks the calling thread while another
+/// thread holds it.
+#[derive(Debug, Default)]
+pub struct BlockingRawMutex(Mutex<()>);
+
+impl BlockingRawMutex {
+    /// Creates a new, unlocked `BlockingRawMutex`.
+    #[inline]
+    pub const fn new() -> BlockingRawMutex {
+        BlockingRawMutex(Mutex::new(()))
+    }
+}
+
+// SAFETY: the closure only runs while the inner mutex is held, which excludes
+// every other thread.
+unsafe impl RawMutex for BlockingRawMutex {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: BlockingRawMutex = BlockingRawMutex::new();
+
+    #[inline]
+    fn lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _guard = self.0.lock();
+        f()
+    }
+}
+
+/// An embassy-sync blocking mutex backed by [`BlockingRawMutex`].
+pub type BlockingMutex<T> = embassy_sync::blocking_mutex::Mutex<BlockingRawMutex, T>;
+
+/// An embassy-sync async mutex backed by [`BlockingRawMutex`].
+pub type AsyncMutex<T> = embassy_sync::mutex::Mutex<BlockingRawMutex, T>;
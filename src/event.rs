@@ -0,0 +1,383 @@
+use std::{fmt, time::Duration};
+
+use crate::{clock, Condvar, Mutex};
+
+/// A manual-reset event, which threads can block on until it is set.
+///
+/// The event is either set or unset. Setting it wakes every thread waiting for
+/// it, and it stays set until [`reset`](Event::reset) is called, so a thread
+/// which only starts waiting after the event was set does not miss it. This is
+/// the in-process counterpart of `ipc::Event`, and behaves like a Windows
+/// manual-reset event: every thread waiting when the event is set is
+/// released, even if it is reset again before the thread gets to run.
+pub struct Event {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+struct State {
+    set: bool,
+    // Bumped every time the event is set, so that waiters can tell that it
+    // was set while they slept even if it has been reset since.
+    generation: u64,
+}
+
+impl Event {
+    /// Creates a new event which is not set.
+    #[inline]
+    pub const fn new() -> Event {
+        Event {
+            state: Mutex::new(State {
+                set: false,
+                generation: 0,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Sets the event, waking every thread waiting for it.
+    pub fn set(&self) {
+        let mut state = self.state.lock();
+        if !state.set {
+            state.set = true;
+            state.generation += 1;
+            drop(state);
+            self.changed.notify_all();
+        }
+    }
+
+    /// Unsets the event, so that threads calling [`wait`](Event::wait) block
+    /// again until it is next set.
+    #[inline]
+    pub fn reset(&self) {
+        self.state.lock().set = false;
+    }
+
+    /// Returns `true` if the event is currently set.
+    #[inline]
+    pub fn is_set(&self) -> bool {
+        self.state.lock().set
+    }
+
+    /// Blocks the current thread until the event is set.
+    ///
+    /// This returns immediately if the event is already set.
+    pub fn wait(&self) {
+        let state = self.state.lock();
+        let generation = state.generation;
+        let _state = self
+            .changed
+            .wait_while(state, |state| !state.set && state.generation == generation);
+    }
+
+    /// Blocks the current thread until the event is set or `timeout` elapses,
+    /// returning `true` if the event was set.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let state = self.state.lock();
+        let generation = state.generation;
+        let unset = |state: &mut State| !state.set && state.generation == generation;
+        // A deadline too far in the future to represent is treated as no
+        // deadline at all.
+        let state = match clock::now().checked_add(timeout) {
+            Some(deadline) => self.changed.wait_while_until(state, deadline, unset).0,
+            None => self.changed.wait_while(state, unset),
+        };
+        state.set || state.generation != generation
+    }
+}
+
+impl Default for Event {
+    #[inline]
+    fn default() -> Event {
+        Event::new()
+    }
+}
+
+impl fmt::Debug for Event {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Event")
+            .field("set", &self.is_set())
+            .finish()
+    }
+}
+
+/// A set of up to 64 auto-reset events, which a thread can block on until any
+/// of them is set.
+///
+/// Events are identified by their number, from 0 to
+/// [`CAPACITY`](EventSet::CAPACITY) excluded. Setting an event wakes a thread
+/// waiting on the set, and the event stays set until a wait returns it, which
+/// resets it. This lets a coordinator thread block on several unrelated
+/// conditions at once and learn which one to look at, rather than sharing a
+/// single condition variable between all of them and checking every
+/// predicate after each wakeup.
+pub struct EventSet {
+    // One bit per event, set while the event is.
+    state: Mutex<u64>,
+    changed: Condvar,
+}
+
+impl EventSet {
+    /// The number of events in a set.
+    pub const CAPACITY: usize = 64;
+
+    /// Creates a new set in which no event is set.
+    #[inline]
+    pub const fn new() -> EventSet {
+        EventSet {
+            state: Mutex::new(0),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Sets event `event`, waking a thread waiting on the set.
+    ///
+    /// Setting an event which is already set has no effect.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `event` is not below [`CAPACITY`](EventSet::CAPACITY).
+    pub fn set(&self, event: usize) {
+        let bit = bit(event);
+        let mut state = self.state.lock();
+        if *state & bit == 0 {
+            *state |= bit;
+            drop(state);
+            self.changed.notify_one();
+        }
+    }
+
+    /// Unsets event `event` without waiting for it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `event` is not below [`CAPACITY`](EventSet::CAPACITY).
+    #[inline]
+    pub fn reset(&self, event: usize) {
+        *self.state.lock() &= !bit(event);
+    }
+
+    /// Returns `true` if event `event` is currently set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `event` is not below [`CAPACITY`](EventSet::CAPACITY).
+    #[inline]
+    pub fn is_set(&self, event: usize) -> bool {
+        *self.state.lock() & bit(event) != 0
+    }
+
+    /// Blocks the current thread until any event is set, then resets and
+    /// returns it.
+    ///
+    /// If several events are set, the lowest-numbered one is returned and the
+    /// others stay set, so lower numbers take priority when events are set
+    /// faster than they are waited for.
+    pub fn wait_any(&self) -> usize {
+        let state = self.state.lock();
+        let mut state = self.changed.wait_while(state, |state| *state == 0);
+        take(&mut state).unwrap()
+    }
+
+    /// Resets and returns the lowest-numbered event which is set, without
+    /// blocking.
+    #[inline]
+    pub fn try_wait_any(&self) -> Option<usize> {
+        take(&mut self.state.lock())
+    }
+
+    /// Blocks the current thread until any event is set or `timeout` elapses,
+    /// like [`wait_any`](EventSet::wait_any), returning `None` if it timed
+    /// out.
+    pub fn wait_any_timeout(&self, timeout: Duration) -> Option<usize> {
+        let state = self.state.lock();
+        let unset = |state: &mut u64| *state == 0;
+        // A deadline too far in the future to represent is treated as no
+        // deadline at all.
+        let mut state = match clock::now().checked_add(timeout) {
+            Some(deadline) => self.changed.wait_while_until(state, deadline, unset).0,
+            None => self.changed.wait_while(state, unset),
+        };
+        take(&mut state)
+    }
+}
+
+#[inline]
+fn bit(event: usize) -> u64 {
+    assert!(
+        event < EventSet::CAPACITY,
+        "event {} out of range for an EventSet of {} events",
+        event,
+        EventSet::CAPACITY,
+    );
+    1 << event
+}
+
+// Resets and returns the lowest-numbered event which is set.
+#[inline]
+fn take(state: &mut u64) -> Option<usize> {
+    if *state == 0 {
+        return None;
+    }
+    let event = state.trailing_zeros() as usize;
+    *state &= !(1 << event);
+    Some(event)
+}
+
+impl Default for EventSet {
+    #[inline]
+    fn default() -> EventSet {
+        EventSet::new()
+    }
+}
+
+impl fmt::Debug for EventSet {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let state = *self.state.lock();
+        let set = (0..EventSet::CAPACITY)
+            .filter(|&event| state & 1 << event != 0)
+            .collect::<Vec<_>>();
+        fmt.debug_struct("EventSet").field("set", &set).finish()
+    }
+}
+
+/// A notification without a value attached, which threads can block on.
+///
+/// [`notify_one`](Notify::notify_one) wakes a single waiting thread or, if no
+/// thread is waiting, leaves a permit which the next call to
+/// [`wait`](Notify::wait) takes and returns right away, so a notification sent
+/// just before the receiving thread starts waiting is not lost. At most one
+/// permit is stored, however many notifications are sent meanwhile.
+/// [`notify_all`](Notify::notify_all) wakes every thread waiting at the time,
+/// and leaves no permit. Unlike a [`Condvar`], this needs no mutex and
+/// predicate of the caller's to be used correctly.
+pub struct Notify {
+    state: Mutex<NotifyState>,
+    changed: Condvar,
+}
+
+struct NotifyState {
+    permit: bool,
+    // The number of threads waiting since the last `notify_all`.
+    waiters: usize,
+    // The number of waiters released by `notify_one` which have yet to wake
+    // up and take their wakeup.
+    wakeups: usize,
+    // Bumped by every `notify_all`, which releases every waiter which started
+    // waiting before.
+    generation: u64,
+}
+
+impl Notify {
+    /// Creates a new notification without a permit.
+    #[inline]
+    pub const fn new() -> Notify {
+        Notify {
+            state: Mutex::new(NotifyState {
+                permit: false,
+                waiters: 0,
+                wakeups: 0,
+                generation: 0,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Wakes a thread waiting for the notification, or stores a permit for the
+    /// next one to wait if none is.
+    pub fn notify_one(&self) {
+        let mut state = self.state.lock();
+        if state.waiters > state.wakeups {
+            state.wakeups += 1;
+            drop(state);
+            self.changed.notify_one();
+        } else {
+            state.permit = true;
+        }
+    }
+
+    /// Wakes every thread waiting for the notification.
+    ///
+    /// This stores no permit, so threads which start waiting afterwards block
+    /// until the next notification.
+    pub fn notify_all(&self) {
+        let mut state = self.state.lock();
+        if state.waiters > 0 {
+            state.generation += 1;
+            state.waiters = 0;
+            state.wakeups = 0;
+            drop(state);
+            self.changed.notify_all();
+        }
+    }
+
+    /// Blocks the current thread until it is notified.
+    ///
+    /// This returns immediately, taking the permit, if there is one.
+    pub fn wait(&self) {
+        let mut state = self.state.lock();
+        if state.permit {
+            state.permit = false;
+            return;
+        }
+        state.waiters += 1;
+        let generation = state.generation;
+        let mut state = self.changed.wait_while(state, |state| {
+            state.generation == generation && state.wakeups == 0
+        });
+        Notify::woken(&mut state, generation);
+    }
+
+    /// Blocks the current thread until it is notified or `timeout` elapses,
+    /// returning `true` if it was notified.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let mut state = self.state.lock();
+        if state.permit {
+            state.permit = false;
+            return true;
+        }
+        state.waiters += 1;
+        let generation = state.generation;
+        let pending =
+            |state: &mut NotifyState| state.generation == generation && state.wakeups == 0;
+        // A deadline too far in the future to represent is treated as no
+        // deadline at all.
+        let mut state = match clock::now().checked_add(timeout) {
+            Some(deadline) => self.changed.wait_while_until(state, deadline, pending).0,
+            None => self.changed.wait_while(state, pending),
+        };
+        // A notification which raced with the timeout is taken rather than
+        // left for a thread which may never come.
+        if pending(&mut state) {
+            state.waiters -= 1;
+            return false;
+        }
+        Notify::woken(&mut state, generation);
+        true
+    }
+
+    // Takes the wakeup of a waiter which started waiting at `generation`.
+    fn woken(state: &mut NotifyState, generation: u64) {
+        if state.generation == generation {
+            state.waiters -= 1;
+            state.wakeups -= 1;
+        }
+    }
+}
+
+impl Default for Notify {
+    #[inline]
+    fn default() -> Notify {
+        Notify::new()
+    }
+}
+
+impl fmt::Debug for Notify {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock();
+        fmt.debug_struct("Notify")
+            .field("permit", &state.permit)
+            .field("waiters", &state.waiters)
+            .finish()
+    }
+}
@@ -0,0 +1,89 @@
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// A wrapper which is `Sync` whatever it contains, because it only gives out
+/// mutable access.
+///
+/// A shared reference to an `Exclusive` is useless: the value can only be
+/// reached through `&mut Exclusive<T>` or by value. Sharing one between threads
+/// therefore cannot let them access the value concurrently, so a `!Sync` field,
+/// such as a `Cell` or a `dyn Future + Send`, can be stored in a struct which
+/// needs to be `Sync` without any locking. This is the same as the standard
+/// library's unstable `Exclusive`.
+#[derive(Default)]
+#[repr(transparent)]
+pub struct Exclusive<T: ?Sized> {
+    inner: T,
+}
+
+// SAFETY: `&Exclusive<T>` gives no access to the `T`.
+unsafe impl<T: ?Sized> Sync for Exclusive<T> {}
+
+impl<T> Exclusive<T> {
+    /// Wraps a value.
+    #[inline]
+    pub const fn new(t: T) -> Exclusive<T> {
+        Exclusive { inner: t }
+    }
+
+    /// Unwraps the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: ?Sized> Exclusive<T> {
+    /// Returns a mutable reference to the value.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Returns a pinned mutable reference to the value.
+    #[inline]
+    pub fn get_pin_mut(self: Pin<&mut Self>) -> Pin<&mut T> {
+        // SAFETY: the value is pinned whenever the wrapper is.
+        unsafe { self.map_unchecked_mut(|exclusive| &mut exclusive.inner) }
+    }
+
+    /// Treats a mutable reference to a value as one to an `Exclusive`.
+    #[inline]
+    pub fn from_mut(t: &mut T) -> &mut Exclusive<T> {
+        // SAFETY: `Exclusive` is a transparent wrapper.
+        unsafe { &mut *(t as *mut T as *mut Exclusive<T>) }
+    }
+
+    /// Treats a pinned mutable reference to a value as one to an `Exclusive`.
+    #[inline]
+    pub fn from_pin_mut(t: Pin<&mut T>) -> Pin<&mut Exclusive<T>> {
+        // SAFETY: the wrapper is pinned whenever the value is.
+        unsafe { t.map_unchecked_mut(Exclusive::from_mut) }
+    }
+}
+
+impl<T> From<T> for Exclusive<T> {
+    #[inline]
+    fn from(t: T) -> Exclusive<T> {
+        Exclusive::new(t)
+    }
+}
+
+impl<T: Future + ?Sized> Future for Exclusive<T> {
+    type Output = T::Output;
+
+    #[inline]
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T::Output> {
+        self.get_pin_mut().poll(cx)
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for Exclusive<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Exclusive").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,312 @@
+use std::{
+    cell::UnsafeCell,
+    fmt, hint,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    panic::{RefUnwindSafe, UnwindSafe},
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use crate::{
+    config,
+    parking::{self, ParkResult, DEFAULT_TOKEN},
+    Lock, LockOperation, TryLockError, TryLockResult, Unlock,
+};
+
+const LOCKED: u8 = 1;
+const PARKED: u8 = 2;
+
+// Handed to a parked thread which is given the lock directly.
+const HANDOFF: usize = 1;
+
+/// A mutex which is granted to waiting threads in the order they started
+/// waiting.
+///
+/// When [`Mutex`](crate::Mutex) is released, whichever thread gets to it first
+/// acquires it, which is usually the thread releasing it if it asks again
+/// right away. Under heavy contention this can keep other threads waiting for
+/// a long time. `FairMutex` instead hands the lock over to the thread which has
+/// been blocked on it the longest, and lets no thread take it while others are
+/// blocked. This guarantees progress to every thread, at the price of a
+/// context switch on every contended release, so it is slower than `Mutex` as
+/// long as nobody starves.
+///
+/// Like [`RawMutex`](crate::RawMutex), the lock is not seen by the `tracking`,
+/// `histogram` or `opentelemetry` instrumentation. It cannot be used with
+/// [`Condvar`](crate::Condvar), but can be with
+/// [`CondvarAny`](crate::CondvarAny).
+pub struct FairMutex<T: ?Sized> {
+    state: AtomicU8,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for FairMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for FairMutex<T> {}
+
+impl<T: ?Sized> UnwindSafe for FairMutex<T> {}
+impl<T: ?Sized> RefUnwindSafe for FairMutex<T> {}
+
+impl<T> FairMutex<T> {
+    /// Creates a new mutex in an unlocked state.
+    #[inline]
+    pub const fn new(t: T) -> FairMutex<T> {
+        FairMutex {
+            state: AtomicU8::new(0),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Consumes the mutex, returning the underlying data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> FairMutex<T> {
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so.
+    ///
+    /// Locking the mutex again on the thread which holds it deadlocks.
+    #[inline]
+    pub fn lock(&self) -> FairMutexGuard<'_, T> {
+        if self
+            .state
+            .compare_exchange_weak(0, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            self.lock_contended();
+        }
+        FairMutexGuard::new(self)
+    }
+
+    #[cold]
+    fn lock_contended(&self) {
+        let mut spins = config().spin_iterations();
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            // Once a thread is parked, the lock is only ever handed over, so
+            // it is never seen unlocked until the queue is empty.
+            if state == 0 {
+                match self.state.compare_exchange_weak(
+                    0,
+                    LOCKED,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => return,
+                    Err(actual) => state = actual,
+                }
+                continue;
+            }
+
+            if state & PARKED == 0 {
+                if spins > 0 {
+                    spins -= 1;
+                    hint::spin_loop();
+                    state = self.state.load(Ordering::Relaxed);
+                    continue;
+                }
+                if let Err(actual) = self.state.compare_exchange_weak(
+                    state,
+                    state | PARKED,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    state = actual;
+                    continue;
+                }
+            }
+
+            let result = parking::park(
+                self.key(),
+                || self.state.load(Ordering::Relaxed) == LOCKED | PARKED,
+                || {},
+                |_, _| {},
+                None,
+            );
+            if result == ParkResult::Unparked(HANDOFF) {
+                // The releasing thread left the lock locked for us.
+                self.state.load(Ordering::Acquire);
+                return;
+            }
+            state = self.state.load(Ordering::Relaxed);
+        }
+    }
+
+    /// Attempts to acquire the mutex without blocking.
+    ///
+    /// This fails while other threads are blocked on the mutex, since it is
+    /// theirs first.
+    #[inline]
+    pub fn try_lock(&self) -> TryLockResult<FairMutexGuard<'_, T>> {
+        match self
+            .state
+            .compare_exchange(0, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+        {
+            Ok(_) => Ok(FairMutexGuard::new(self)),
+            Err(_) => Err(TryLockError::new(LockOperation::Lock)),
+        }
+    }
+
+    // SAFETY: the mutex must be held by the caller.
+    #[inline]
+    unsafe fn unlock(&self) {
+        if self
+            .state
+            .compare_exchange(LOCKED, 0, Ordering::Release, Ordering::Relaxed)
+            .is_err()
+        {
+            self.unlock_contended();
+        }
+    }
+
+    #[cold]
+    fn unlock_contended(&self) {
+        parking::unpark_one(self.key(), |result| {
+            if result.unparked == 0 {
+                self.state.store(0, Ordering::Release);
+                return DEFAULT_TOKEN;
+            }
+            let state = if result.have_more {
+                LOCKED | PARKED
+            } else {
+                LOCKED
+            };
+            self.state.store(state, Ordering::Release);
+            HANDOFF
+        });
+    }
+
+    /// Returns `true` if the mutex is currently locked.
+    ///
+    /// The answer may be stale as soon as it is returned.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & LOCKED != 0
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// No locking is necessary since the borrow guarantees exclusive access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    #[inline]
+    fn key(&self) -> usize {
+        &self.state as *const AtomicU8 as usize
+    }
+}
+
+impl<T> From<T> for FairMutex<T> {
+    #[inline]
+    fn from(t: T) -> FairMutex<T> {
+        FairMutex::new(t)
+    }
+}
+
+impl<T: Default> Default for FairMutex<T> {
+    #[inline]
+    fn default() -> FairMutex<T> {
+        FairMutex::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for FairMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_struct("FairMutex");
+        match self.try_lock() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(_) => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+impl<T: ?Sized> Lock<T> for FairMutex<T> {
+    type Guard<'a>
+        = FairMutexGuard<'a, T>
+    where
+        T: 'a;
+
+    #[inline]
+    fn lock(&self) -> FairMutexGuard<'_, T> {
+        self.lock()
+    }
+
+    #[inline]
+    fn try_lock(&self) -> TryLockResult<FairMutexGuard<'_, T>> {
+        self.try_lock()
+    }
+
+    #[inline]
+    fn get_mut(&mut self) -> &mut T {
+        self.get_mut()
+    }
+}
+
+/// A guard for a [`FairMutex`].
+///
+/// The mutex may be released by another thread than the one which acquired
+/// it, so unlike `MutexGuard`, this guard can be sent to another thread.
+#[must_use]
+pub struct FairMutexGuard<'a, T: ?Sized + 'a> {
+    mutex: &'a FairMutex<T>,
+    // Keeps the guard from being `Sync` for a `T` which is only `Send`.
+    _marker: PhantomData<*const ()>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for FairMutexGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for FairMutexGuard<'_, T> {}
+
+impl<'a, T: ?Sized> FairMutexGuard<'a, T> {
+    #[inline]
+    fn new(mutex: &'a FairMutex<T>) -> FairMutexGuard<'a, T> {
+        FairMutexGuard {
+            mutex,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Unlock for FairMutexGuard<'_, T> {
+    #[inline]
+    fn unlocked<U>(&mut self, f: impl FnOnce() -> U) -> U {
+        let mutex = self.mutex;
+        crate::unlocked(self, || mutex.lock(), f)
+    }
+}
+
+impl<T: ?Sized> Drop for FairMutexGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: the guard holds the mutex.
+        unsafe { self.mutex.unlock() };
+    }
+}
+
+impl<T: ?Sized> Deref for FairMutexGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the mutex.
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for FairMutexGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the mutex.
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for FairMutexGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
@@ -0,0 +1,342 @@
+//! A C interface to `Mutex`, `RwLock` and `Condvar`.
+//!
+//! The functions in this module are exported unmangled, so C and C++ code
+//! linked into the same binary, such as through a `staticlib` or `cdylib`
+//! crate depending on this one, can create and use the very same locks as the
+//! Rust side. Each lock is an opaque handle created by a `*_new` function and
+//! released by the matching `*_free` function; the caller owns it in between.
+//!
+//! Locks taken through this interface must be released by the thread which
+//! took them, and must be released before the lock is freed. Passing a null or
+//! dangling handle is undefined behavior. Misuse which can be detected, such
+//! as unlocking a mutex which is not locked, aborts the process, since
+//! unwinding into C is not an option.
+
+use std::{
+    cell::{RefCell, UnsafeCell},
+    mem, process,
+    time::Duration,
+};
+
+use crate::{Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[cold]
+fn misuse(message: &str) -> ! {
+    eprintln!("antidote: {}", message);
+    process::abort();
+}
+
+/// An opaque handle to a `Mutex`.
+pub struct AntidoteMutex {
+    mutex: Mutex<()>,
+    // The guard of whoever holds the mutex through this interface. Only that
+    // thread touches it.
+    guard: UnsafeCell<Option<MutexGuard<'static, ()>>>,
+}
+
+// SAFETY: `guard` is only accessed by the thread holding the mutex.
+unsafe impl Sync for AntidoteMutex {}
+
+impl AntidoteMutex {
+    unsafe fn hold(&self, guard: MutexGuard<'_, ()>) {
+        // SAFETY: the handle outlives the guard, since it must not be freed
+        // while locked.
+        let guard = mem::transmute::<MutexGuard<'_, ()>, MutexGuard<'static, ()>>(guard);
+        *self.guard.get() = Some(guard);
+    }
+
+    unsafe fn release(&self) -> MutexGuard<'static, ()> {
+        match (*self.guard.get()).take() {
+            Some(guard) => guard,
+            None => misuse("mutex used as locked while it is not locked"),
+        }
+    }
+}
+
+/// Creates a new unlocked mutex.
+#[no_mangle]
+pub extern "C" fn antidote_mutex_new() -> *mut AntidoteMutex {
+    Box::into_raw(Box::new(AntidoteMutex {
+        mutex: Mutex::new(()),
+        guard: UnsafeCell::new(None),
+    }))
+}
+
+/// Frees a mutex.
+///
+/// # Safety
+///
+/// `mutex` must have been created by [`antidote_mutex_new`], must not be
+/// locked, and must not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_mutex_free(mutex: *mut AntidoteMutex) {
+    drop(Box::from_raw(mutex));
+}
+
+/// Locks a mutex, blocking the current thread until it is able to do so.
+///
+/// # Safety
+///
+/// `mutex` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_mutex_lock(mutex: *const AntidoteMutex) {
+    let mutex = &*mutex;
+    mutex.hold(mutex.mutex.lock());
+}
+
+/// Attempts to lock a mutex without blocking, returning whether it was
+/// locked.
+///
+/// # Safety
+///
+/// `mutex` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_mutex_trylock(mutex: *const AntidoteMutex) -> bool {
+    let mutex = &*mutex;
+    match mutex.mutex.try_lock() {
+        Ok(guard) => {
+            mutex.hold(guard);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Unlocks a mutex.
+///
+/// # Safety
+///
+/// `mutex` must be a live handle, locked by the current thread.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_mutex_unlock(mutex: *const AntidoteMutex) {
+    drop((*mutex).release());
+}
+
+/// An opaque handle to an `RwLock`.
+pub struct AntidoteRwLock {
+    rwlock: RwLock<()>,
+    // The guard of whoever holds the lock exclusively through this interface.
+    // Only that thread touches it.
+    write: UnsafeCell<Option<RwLockWriteGuard<'static, ()>>>,
+}
+
+// SAFETY: `write` is only accessed by the thread holding the lock exclusively.
+unsafe impl Sync for AntidoteRwLock {}
+
+thread_local! {
+    // The read guards the current thread holds through this interface, along
+    // with the address of the handle each belongs to.
+    static READ_GUARDS: RefCell<Vec<(usize, RwLockReadGuard<'static, ()>)>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+impl AntidoteRwLock {
+    unsafe fn hold_read(&self, guard: RwLockReadGuard<'_, ()>) {
+        // SAFETY: the handle outlives the guard, since it must not be freed
+        // while locked.
+        let guard = mem::transmute::<RwLockReadGuard<'_, ()>, RwLockReadGuard<'static, ()>>(guard);
+        let addr = self as *const AntidoteRwLock as usize;
+        READ_GUARDS.with(|guards| guards.borrow_mut().push((addr, guard)));
+    }
+
+    unsafe fn hold_write(&self, guard: RwLockWriteGuard<'_, ()>) {
+        // SAFETY: as in `hold_read`.
+        let guard =
+            mem::transmute::<RwLockWriteGuard<'_, ()>, RwLockWriteGuard<'static, ()>>(guard);
+        *self.write.get() = Some(guard);
+    }
+}
+
+/// Creates a new unlocked reader-writer lock.
+#[no_mangle]
+pub extern "C" fn antidote_rwlock_new() -> *mut AntidoteRwLock {
+    Box::into_raw(Box::new(AntidoteRwLock {
+        rwlock: RwLock::new(()),
+        write: UnsafeCell::new(None),
+    }))
+}
+
+/// Frees a reader-writer lock.
+///
+/// # Safety
+///
+/// `rwlock` must have been created by [`antidote_rwlock_new`], must not be
+/// locked, and must not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_rwlock_free(rwlock: *mut AntidoteRwLock) {
+    drop(Box::from_raw(rwlock));
+}
+
+/// Locks a reader-writer lock for reading, blocking the current thread until
+/// it is able to do so.
+///
+/// # Safety
+///
+/// `rwlock` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_rwlock_read_lock(rwlock: *const AntidoteRwLock) {
+    let rwlock = &*rwlock;
+    rwlock.hold_read(rwlock.rwlock.read());
+}
+
+/// Attempts to lock a reader-writer lock for reading without blocking,
+/// returning whether it was locked.
+///
+/// # Safety
+///
+/// `rwlock` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_rwlock_try_read_lock(rwlock: *const AntidoteRwLock) -> bool {
+    let rwlock = &*rwlock;
+    match rwlock.rwlock.try_read() {
+        Ok(guard) => {
+            rwlock.hold_read(guard);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Releases a read lock on a reader-writer lock.
+///
+/// # Safety
+///
+/// `rwlock` must be a live handle, locked for reading by the current thread.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_rwlock_read_unlock(rwlock: *const AntidoteRwLock) {
+    let addr = rwlock as usize;
+    let guard = READ_GUARDS.with(|guards| {
+        let mut guards = guards.borrow_mut();
+        let idx = guards.iter().rposition(|&(held, _)| held == addr)?;
+        Some(guards.remove(idx).1)
+    });
+    match guard {
+        Some(guard) => drop(guard),
+        None => misuse("rwlock read-unlocked by a thread which does not hold it"),
+    }
+}
+
+/// Locks a reader-writer lock for writing, blocking the current thread until
+/// it is able to do so.
+///
+/// # Safety
+///
+/// `rwlock` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_rwlock_write_lock(rwlock: *const AntidoteRwLock) {
+    let rwlock = &*rwlock;
+    rwlock.hold_write(rwlock.rwlock.write());
+}
+
+/// Attempts to lock a reader-writer lock for writing without blocking,
+/// returning whether it was locked.
+///
+/// # Safety
+///
+/// `rwlock` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_rwlock_try_write_lock(rwlock: *const AntidoteRwLock) -> bool {
+    let rwlock = &*rwlock;
+    match rwlock.rwlock.try_write() {
+        Ok(guard) => {
+            rwlock.hold_write(guard);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Releases a write lock on a reader-writer lock.
+///
+/// # Safety
+///
+/// `rwlock` must be a live handle, locked for writing by the current thread.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_rwlock_write_unlock(rwlock: *const AntidoteRwLock) {
+    match (*(*rwlock).write.get()).take() {
+        Some(guard) => drop(guard),
+        None => misuse("rwlock write-unlocked while it is not write-locked"),
+    }
+}
+
+/// An opaque handle to a `Condvar`.
+pub struct AntidoteCondvar {
+    condvar: Condvar,
+}
+
+/// Creates a new condition variable.
+#[no_mangle]
+pub extern "C" fn antidote_condvar_new() -> *mut AntidoteCondvar {
+    Box::into_raw(Box::new(AntidoteCondvar {
+        condvar: Condvar::new(),
+    }))
+}
+
+/// Frees a condition variable.
+///
+/// # Safety
+///
+/// `condvar` must have been created by [`antidote_condvar_new`], must have no
+/// waiters, and must not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_condvar_free(condvar: *mut AntidoteCondvar) {
+    drop(Box::from_raw(condvar));
+}
+
+/// Unlocks `mutex` and blocks the current thread until `condvar` is notified,
+/// then locks `mutex` again.
+///
+/// # Safety
+///
+/// Both handles must be live, and `mutex` must be locked by the current
+/// thread.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_condvar_wait(
+    condvar: *const AntidoteCondvar,
+    mutex: *const AntidoteMutex,
+) {
+    let mutex = &*mutex;
+    let guard = (*condvar).condvar.wait(mutex.release());
+    mutex.hold(guard);
+}
+
+/// Like [`antidote_condvar_wait`], but gives up waiting after
+/// `timeout_ms` milliseconds, returning whether it timed out.
+///
+/// # Safety
+///
+/// Both handles must be live, and `mutex` must be locked by the current
+/// thread.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_condvar_wait_timeout(
+    condvar: *const AntidoteCondvar,
+    mutex: *const AntidoteMutex,
+    timeout_ms: u64,
+) -> bool {
+    let mutex = &*mutex;
+    let (guard, result) = (*condvar)
+        .condvar
+        .wait_timeout(mutex.release(), Duration::from_millis(timeout_ms));
+    mutex.hold(guard);
+    result.timed_out()
+}
+
+/// Wakes one thread waiting on a condition variable.
+///
+/// # Safety
+///
+/// `condvar` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_condvar_notify_one(condvar: *const AntidoteCondvar) {
+    (*condvar).condvar.notify_one();
+}
+
+/// Wakes every thread waiting on a condition variable.
+///
+/// # Safety
+///
+/// `condvar` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_condvar_notify_all(condvar: *const AntidoteCondvar) {
+    (*condvar).condvar.notify_all();
+}
@@ -0,0 +1,126 @@
+//! Fork-safety support for `Mutex` and `RwLock`.
+//!
+//! A child process created by `fork` contains only the thread that called
+//! `fork`. Any lock held by another thread at that moment stays locked forever
+//! in the child. Locks registered here are acquired by the forking thread
+//! immediately before `fork` and released again in both the parent and the
+//! child immediately after it, so the child always starts with them unlocked.
+//!
+//! Registered locks are acquired in registration order. Register them in the
+//! order your program nests them to avoid deadlocking the `fork` call itself.
+//! Registering a lock again has no effect.
+//!
+//! `fork` must not be called by a thread holding a registered lock, as it
+//! would then wait for itself to release the lock. This deadlocks, or in
+//! builds with debug assertions, aborts the process.
+//!
+//! Locks that are not `static`, and so cannot be registered, can be reset in
+//! the child with [`Mutex::force_reinit`](crate::Mutex::force_reinit) and
+//! [`RwLock::force_reinit`](crate::RwLock::force_reinit).
+
+use std::{any::TypeId, cell::RefCell, sync};
+
+use crate::{Mutex, RwLock};
+
+static REGISTRY: sync::Mutex<Registry> = sync::Mutex::new(Vec::new());
+
+static INSTALL: sync::Once = sync::Once::new();
+
+type Registry = Vec<&'static (dyn Register + Sync)>;
+
+thread_local! {
+    static HELD: RefCell<Option<Held>> = const { RefCell::new(None) };
+}
+
+// The registry guard is held across the fork as well, so a concurrent
+// registration cannot leave it locked in the child.
+struct Held {
+    _registry: sync::MutexGuard<'static, Registry>,
+    guards: Vec<Box<dyn Erased>>,
+}
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        // Release in the reverse order of acquisition.
+        while let Some(guard) = self.guards.pop() {
+            drop(guard);
+        }
+    }
+}
+
+trait Register {
+    fn acquire(&'static self) -> Box<dyn Erased>;
+
+    // Tells locks apart, including a lock and another one at its start.
+    fn id(&self) -> (usize, TypeId);
+}
+
+impl<T: ?Sized + 'static> Register for Mutex<T> {
+    fn acquire(&'static self) -> Box<dyn Erased> {
+        Box::new(self.lock())
+    }
+
+    fn id(&self) -> (usize, TypeId) {
+        (
+            self as *const Self as *const () as usize,
+            TypeId::of::<Self>(),
+        )
+    }
+}
+
+impl<T: ?Sized + 'static> Register for RwLock<T> {
+    fn acquire(&'static self) -> Box<dyn Erased> {
+        Box::new(self.write())
+    }
+
+    fn id(&self) -> (usize, TypeId) {
+        (
+            self as *const Self as *const () as usize,
+            TypeId::of::<Self>(),
+        )
+    }
+}
+
+// A type-erased guard; dropping the box releases the lock.
+trait Erased {}
+
+impl<T: ?Sized> Erased for T {}
+
+/// Registers a `Mutex` to be held across every subsequent `fork`.
+pub fn register_mutex<T: Send + 'static>(mutex: &'static Mutex<T>) {
+    register(mutex)
+}
+
+/// Registers an `RwLock` to be write-locked across every subsequent `fork`.
+pub fn register_rwlock<T: Send + Sync + 'static>(rwlock: &'static RwLock<T>) {
+    register(rwlock)
+}
+
+fn register(lock: &'static (dyn Register + Sync)) {
+    INSTALL.call_once(|| {
+        let ret = unsafe { libc::pthread_atfork(Some(prepare), Some(release), Some(release)) };
+        assert_eq!(ret, 0, "pthread_atfork failed");
+    });
+    let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    // A lock registered twice would be acquired twice by `prepare`.
+    if registry
+        .iter()
+        .all(|registered| registered.id() != lock.id())
+    {
+        registry.push(lock);
+    }
+}
+
+extern "C" fn prepare() {
+    let registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    let guards = registry.iter().map(|lock| lock.acquire()).collect();
+    let held = Held {
+        _registry: registry,
+        guards,
+    };
+    HELD.with(|slot| *slot.borrow_mut() = Some(held));
+}
+
+extern "C" fn release() {
+    HELD.with(|slot| slot.borrow_mut().take());
+}
@@ -0,0 +1,372 @@
+//! Implementations of common traits on guards which forward to the value they
+//! guard, so a guard can be passed directly wherever, say, an `impl io::Write`
+//! or an `impl AsRef<T>` is expected, formatted with `{}`, or compared with a
+//! value directly.
+
+use std::{
+    borrow::{Borrow, BorrowMut},
+    cell::RefCell,
+    fmt,
+    io::{self, IoSlice, IoSliceMut, SeekFrom},
+};
+
+use crate::{
+    brand::BrandedMutexGuard,
+    future,
+    lease::{LeasedMutexGuard, LeasedRwLockReadGuard, LeasedRwLockWriteGuard},
+    order::{OrderedMutexGuard, OrderedRwLockReadGuard, OrderedRwLockWriteGuard},
+    poison::PoisonPolicy,
+    raw::{MutexStorage, RwLockBackend},
+    sharded::{ShardedRwLockReadGuard, ShardedRwLockWriteGuard},
+    FairMutexGuard, HybridMutexGuard, MappedMutexGuard, MappedRwLockReadGuard,
+    MappedRwLockWriteGuard, MutexGuard, OwnedMutexGuard, OwnedRwLockReadGuard,
+    OwnedRwLockWriteGuard, PinnedMutexGuard, ReentrantLockGuard, ReentrantMutexGuard,
+    RwLockReadGuard, RwLockUpgradableReadGuard, RwLockWriteGuard, SpinMutexGuard, SplitMutexGuard,
+    SplitRwLockReadGuard, SplitRwLockWriteGuard, TransactionalMutexGuard,
+};
+
+macro_rules! forward {
+    ($($(#[$attr:meta])* [$($gen:tt)*] $guard:ty => $t:ty),* $(,)?) => {$(
+        $(#[$attr])*
+        impl<$($gen)*> io::Read for $guard
+        where
+            $t: io::Read,
+        {
+            #[inline]
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                (**self).read(buf)
+            }
+
+            #[inline]
+            fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize> {
+                (**self).read_vectored(bufs)
+            }
+
+            #[inline]
+            fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+                (**self).read_to_end(buf)
+            }
+
+            #[inline]
+            fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+                (**self).read_to_string(buf)
+            }
+
+            #[inline]
+            fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+                (**self).read_exact(buf)
+            }
+        }
+
+        $(#[$attr])*
+        impl<$($gen)*> io::BufRead for $guard
+        where
+            $t: io::BufRead,
+        {
+            #[inline]
+            fn fill_buf(&mut self) -> io::Result<&[u8]> {
+                (**self).fill_buf()
+            }
+
+            #[inline]
+            fn consume(&mut self, amt: usize) {
+                (**self).consume(amt)
+            }
+
+            #[inline]
+            fn read_until(&mut self, byte: u8, buf: &mut Vec<u8>) -> io::Result<usize> {
+                (**self).read_until(byte, buf)
+            }
+
+            #[inline]
+            fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
+                (**self).read_line(buf)
+            }
+        }
+
+        $(#[$attr])*
+        impl<$($gen)*> io::Write for $guard
+        where
+            $t: io::Write,
+        {
+            #[inline]
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                (**self).write(buf)
+            }
+
+            #[inline]
+            fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+                (**self).write_vectored(bufs)
+            }
+
+            #[inline]
+            fn flush(&mut self) -> io::Result<()> {
+                (**self).flush()
+            }
+
+            #[inline]
+            fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+                (**self).write_all(buf)
+            }
+
+            #[inline]
+            fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> io::Result<()> {
+                (**self).write_fmt(args)
+            }
+        }
+
+        $(#[$attr])*
+        impl<$($gen)*> io::Seek for $guard
+        where
+            $t: io::Seek,
+        {
+            #[inline]
+            fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+                (**self).seek(pos)
+            }
+
+            #[inline]
+            fn stream_position(&mut self) -> io::Result<u64> {
+                (**self).stream_position()
+            }
+        }
+
+        $(#[$attr])*
+        impl<$($gen)*> fmt::Write for $guard
+        where
+            $t: fmt::Write,
+        {
+            #[inline]
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                (**self).write_str(s)
+            }
+
+            #[inline]
+            fn write_char(&mut self, c: char) -> fmt::Result {
+                (**self).write_char(c)
+            }
+
+            #[inline]
+            fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
+                (**self).write_fmt(args)
+            }
+        }
+
+        $(#[$attr])*
+        impl<$($gen)*> Iterator for $guard
+        where
+            $t: Iterator,
+        {
+            type Item = <$t as Iterator>::Item;
+
+            #[inline]
+            fn next(&mut self) -> Option<<$t as Iterator>::Item> {
+                (**self).next()
+            }
+
+            #[inline]
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (**self).size_hint()
+            }
+
+            #[inline]
+            fn nth(&mut self, n: usize) -> Option<<$t as Iterator>::Item> {
+                (**self).nth(n)
+            }
+        }
+
+        $(#[$attr])*
+        impl<$($gen)*> DoubleEndedIterator for $guard
+        where
+            $t: DoubleEndedIterator,
+        {
+            #[inline]
+            fn next_back(&mut self) -> Option<<$t as Iterator>::Item> {
+                (**self).next_back()
+            }
+
+            #[inline]
+            fn nth_back(&mut self, n: usize) -> Option<<$t as Iterator>::Item> {
+                (**self).nth_back(n)
+            }
+        }
+
+        $(#[$attr])*
+        impl<$($gen)*> ExactSizeIterator for $guard
+        where
+            $t: ExactSizeIterator,
+        {}
+    )*};
+}
+
+forward!(
+    [T: ?Sized, P: PoisonPolicy, R: MutexStorage] MutexGuard<'_, T, P, R> => T,
+    [T: ?Sized, U: ?Sized, P: PoisonPolicy, R: MutexStorage] MappedMutexGuard<'_, T, U, P, R> => U,
+    [T: ?Sized, U: ?Sized] SplitMutexGuard<'_, T, U> => U,
+    [T: ?Sized, R: RwLockBackend] RwLockWriteGuard<'_, T, R> => T,
+    [T: ?Sized, U: ?Sized, R: RwLockBackend] MappedRwLockWriteGuard<'_, T, U, R> => U,
+    [T: ?Sized, U: ?Sized] SplitRwLockWriteGuard<'_, T, U> => U,
+    [T: ?Sized + 'static] OwnedMutexGuard<T> => T,
+    [T: ?Sized + 'static] OwnedRwLockWriteGuard<T> => T,
+    [T: ?Sized] BrandedMutexGuard<'_, '_, T> => T,
+    [T: ?Sized, P: PoisonPolicy] LeasedMutexGuard<'_, T, P> => T,
+    [T: ?Sized] LeasedRwLockWriteGuard<'_, T> => T,
+    [T, P: PoisonPolicy] TransactionalMutexGuard<'_, T, P> => T,
+    [T: ?Sized] OrderedMutexGuard<'_, T> => T,
+    [T: ?Sized] OrderedRwLockWriteGuard<'_, T> => T,
+    [T: ?Sized] FairMutexGuard<'_, T> => T,
+    [T: ?Sized] HybridMutexGuard<'_, T> => T,
+    [T: ?Sized] SpinMutexGuard<'_, T> => T,
+    [T: ?Sized, const N: usize] ShardedRwLockWriteGuard<'_, T, N> => T,
+    [T: ?Sized] future::MutexGuard<'_, T> => T,
+    [T: ?Sized] future::RwLockWriteGuard<'_, T> => T,
+    #[cfg(all(unix, feature = "ipc"))]
+    [T] crate::ipc::RwLockWriteGuard<'_, T> => T,
+    #[cfg(all(unix, feature = "ipc"))]
+    [T] crate::ipc::SharedMutexGuard<'_, T> => T,
+    #[cfg(feature = "persistent")]
+    [T: serde::Serialize + serde::de::DeserializeOwned]
+    crate::persistent::PersistentMutexGuard<'_, T> => T,
+    #[cfg(all(target_vendor = "apple", feature = "os-unfair-lock"))]
+    [T: ?Sized] crate::unfair::UnfairMutexGuard<'_, T> => T,
+);
+
+macro_rules! forward_ref {
+    ($($(#[$attr:meta])* [$($gen:tt)*] $guard:ty => $t:ty),* $(,)?) => {$(
+        $(#[$attr])*
+        impl<$($gen)*> AsRef<$t> for $guard {
+            #[inline]
+            fn as_ref(&self) -> &$t {
+                &**self
+            }
+        }
+
+        $(#[$attr])*
+        impl<$($gen)*> Borrow<$t> for $guard {
+            #[inline]
+            fn borrow(&self) -> &$t {
+                &**self
+            }
+        }
+
+        $(#[$attr])*
+        impl<$($gen)*> fmt::Display for $guard
+        where
+            $t: fmt::Display,
+        {
+            #[inline]
+            fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(&**self, fmt)
+            }
+        }
+
+        $(#[$attr])*
+        impl<$($gen)*> PartialEq<$t> for $guard
+        where
+            $t: PartialEq,
+        {
+            #[inline]
+            fn eq(&self, other: &$t) -> bool {
+                **self == *other
+            }
+        }
+    )*};
+}
+
+macro_rules! forward_mut {
+    ($($(#[$attr:meta])* [$($gen:tt)*] $guard:ty => $t:ty),* $(,)?) => {$(
+        $(#[$attr])*
+        impl<$($gen)*> AsMut<$t> for $guard {
+            #[inline]
+            fn as_mut(&mut self) -> &mut $t {
+                &mut **self
+            }
+        }
+
+        $(#[$attr])*
+        impl<$($gen)*> BorrowMut<$t> for $guard {
+            #[inline]
+            fn borrow_mut(&mut self) -> &mut $t {
+                &mut **self
+            }
+        }
+    )*};
+}
+
+forward_ref!(
+    [T: ?Sized, P: PoisonPolicy, R: MutexStorage] MutexGuard<'_, T, P, R> => T,
+    [T: ?Sized, U: ?Sized, P: PoisonPolicy, R: MutexStorage] MappedMutexGuard<'_, T, U, P, R> => U,
+    [T: ?Sized, U: ?Sized] SplitMutexGuard<'_, T, U> => U,
+    [T: ?Sized, R: RwLockBackend] RwLockReadGuard<'_, T, R> => T,
+    [T: ?Sized, U: ?Sized, R: RwLockBackend] MappedRwLockReadGuard<'_, T, U, R> => U,
+    [T: ?Sized, U: ?Sized] SplitRwLockReadGuard<'_, T, U> => U,
+    [T: ?Sized] RwLockUpgradableReadGuard<'_, T> => T,
+    [T: ?Sized, R: RwLockBackend] RwLockWriteGuard<'_, T, R> => T,
+    [T: ?Sized, U: ?Sized, R: RwLockBackend] MappedRwLockWriteGuard<'_, T, U, R> => U,
+    [T: ?Sized, U: ?Sized] SplitRwLockWriteGuard<'_, T, U> => U,
+    [T: ?Sized + 'static] OwnedMutexGuard<T> => T,
+    [T: ?Sized + 'static] OwnedRwLockReadGuard<T> => T,
+    [T: ?Sized + 'static] OwnedRwLockWriteGuard<T> => T,
+    [T: ?Sized] ReentrantLockGuard<'_, T> => RefCell<T>,
+    [T: ?Sized] ReentrantMutexGuard<'_, T> => T,
+    [T: ?Sized] BrandedMutexGuard<'_, '_, T> => T,
+    [T: ?Sized, P: PoisonPolicy] LeasedMutexGuard<'_, T, P> => T,
+    [T: ?Sized] LeasedRwLockReadGuard<'_, T> => T,
+    [T: ?Sized] LeasedRwLockWriteGuard<'_, T> => T,
+    [T, P: PoisonPolicy] TransactionalMutexGuard<'_, T, P> => T,
+    [T: ?Sized] OrderedMutexGuard<'_, T> => T,
+    [T: ?Sized] OrderedRwLockReadGuard<'_, T> => T,
+    [T: ?Sized] OrderedRwLockWriteGuard<'_, T> => T,
+    [T: ?Sized] FairMutexGuard<'_, T> => T,
+    [T: ?Sized] HybridMutexGuard<'_, T> => T,
+    [T: ?Sized] PinnedMutexGuard<'_, T> => T,
+    [T: ?Sized] SpinMutexGuard<'_, T> => T,
+    [T: ?Sized, const N: usize] ShardedRwLockReadGuard<'_, T, N> => T,
+    [T: ?Sized, const N: usize] ShardedRwLockWriteGuard<'_, T, N> => T,
+    [T: ?Sized] future::MutexGuard<'_, T> => T,
+    [T: ?Sized] future::RwLockReadGuard<'_, T> => T,
+    [T: ?Sized] future::RwLockWriteGuard<'_, T> => T,
+    #[cfg(all(unix, feature = "ipc"))]
+    [T] crate::ipc::RwLockReadGuard<'_, T> => T,
+    #[cfg(all(unix, feature = "ipc"))]
+    [T] crate::ipc::RwLockWriteGuard<'_, T> => T,
+    #[cfg(all(unix, feature = "ipc"))]
+    [T] crate::ipc::SharedMutexGuard<'_, T> => T,
+    #[cfg(feature = "persistent")]
+    [T: serde::Serialize + serde::de::DeserializeOwned]
+    crate::persistent::PersistentMutexGuard<'_, T> => T,
+    #[cfg(all(target_vendor = "apple", feature = "os-unfair-lock"))]
+    [T: ?Sized] crate::unfair::UnfairMutexGuard<'_, T> => T,
+);
+
+forward_mut!(
+    [T: ?Sized, P: PoisonPolicy, R: MutexStorage] MutexGuard<'_, T, P, R> => T,
+    [T: ?Sized, U: ?Sized, P: PoisonPolicy, R: MutexStorage] MappedMutexGuard<'_, T, U, P, R> => U,
+    [T: ?Sized, U: ?Sized] SplitMutexGuard<'_, T, U> => U,
+    [T: ?Sized, R: RwLockBackend] RwLockWriteGuard<'_, T, R> => T,
+    [T: ?Sized, U: ?Sized, R: RwLockBackend] MappedRwLockWriteGuard<'_, T, U, R> => U,
+    [T: ?Sized, U: ?Sized] SplitRwLockWriteGuard<'_, T, U> => U,
+    [T: ?Sized + 'static] OwnedMutexGuard<T> => T,
+    [T: ?Sized + 'static] OwnedRwLockWriteGuard<T> => T,
+    [T: ?Sized] BrandedMutexGuard<'_, '_, T> => T,
+    [T: ?Sized, P: PoisonPolicy] LeasedMutexGuard<'_, T, P> => T,
+    [T: ?Sized] LeasedRwLockWriteGuard<'_, T> => T,
+    [T, P: PoisonPolicy] TransactionalMutexGuard<'_, T, P> => T,
+    [T: ?Sized] OrderedMutexGuard<'_, T> => T,
+    [T: ?Sized] OrderedRwLockWriteGuard<'_, T> => T,
+    [T: ?Sized] FairMutexGuard<'_, T> => T,
+    [T: ?Sized] HybridMutexGuard<'_, T> => T,
+    [T: ?Sized] SpinMutexGuard<'_, T> => T,
+    [T: ?Sized, const N: usize] ShardedRwLockWriteGuard<'_, T, N> => T,
+    [T: ?Sized] future::MutexGuard<'_, T> => T,
+    [T: ?Sized] future::RwLockWriteGuard<'_, T> => T,
+    #[cfg(all(unix, feature = "ipc"))]
+    [T] crate::ipc::RwLockWriteGuard<'_, T> => T,
+    #[cfg(all(unix, feature = "ipc"))]
+    [T] crate::ipc::SharedMutexGuard<'_, T> => T,
+    #[cfg(feature = "persistent")]
+    [T: serde::Serialize + serde::de::DeserializeOwned]
+    crate::persistent::PersistentMutexGuard<'_, T> => T,
+    #[cfg(all(target_vendor = "apple", feature = "os-unfair-lock"))]
+    [T: ?Sized] crate::unfair::UnfairMutexGuard<'_, T> => T,
+);
@@ -0,0 +1,683 @@
+//! Locks for asynchronous code.
+//!
+//! [`Mutex`] and [`RwLock`] are acquired by awaiting a future instead of
+//! blocking the thread, so a task waiting for a lock leaves its executor free
+//! to run other tasks. They work with any executor, as they only rely on the
+//! [`Waker`] of the task polling them. Like the crate's other locks, they are
+//! never poisoned: a guard dropped during a panic releases the lock as usual.
+//!
+//! Waiters are served in the order they started waiting, so a steady stream
+//! of readers cannot starve a writer. Each lock also offers `blocking_`
+//! methods, which acquire it from synchronous code by blocking the current
+//! thread instead. They must not be called from within an asynchronous task,
+//! since that blocks the executor.
+//!
+//! Dropping a future before it completes withdraws it from the queue of
+//! waiters.
+
+use std::{
+    cell::UnsafeCell,
+    fmt,
+    future::Future,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+};
+
+use crate::{LockOperation, TryLockError, TryLockResult};
+
+// The state of a lock which hands out shared and exclusive access to tasks.
+struct RawLock {
+    state: crate::Mutex<State>,
+}
+
+struct State {
+    readers: usize,
+    writer: bool,
+    // The tasks waiting for the lock, in arrival order. A waiter which has been
+    // granted the lock stays in the queue until its future observes it.
+    queue: Vec<Waiter>,
+    next_id: u64,
+}
+
+struct Waiter {
+    id: u64,
+    exclusive: bool,
+    granted: bool,
+    waker: Waker,
+}
+
+impl State {
+    fn can_acquire(&self, exclusive: bool) -> bool {
+        !self.writer && (!exclusive || self.readers == 0)
+    }
+
+    fn acquire(&mut self, exclusive: bool) {
+        if exclusive {
+            self.writer = true;
+        } else {
+            self.readers += 1;
+        }
+    }
+
+    fn release(&mut self, exclusive: bool) {
+        if exclusive {
+            self.writer = false;
+        } else {
+            self.readers -= 1;
+        }
+    }
+
+    fn has_waiters(&self) -> bool {
+        self.queue.iter().any(|waiter| !waiter.granted)
+    }
+
+    // Grants the lock to the waiters at the front of the queue for as long as
+    // it is available to them, returning the wakers of their tasks.
+    fn grant(&mut self) -> Vec<Waker> {
+        let mut wakers = vec![];
+        for idx in 0..self.queue.len() {
+            if self.queue[idx].granted {
+                continue;
+            }
+            let exclusive = self.queue[idx].exclusive;
+            if !self.can_acquire(exclusive) {
+                break;
+            }
+            self.acquire(exclusive);
+            self.queue[idx].granted = true;
+            wakers.push(self.queue[idx].waker.clone());
+        }
+        wakers
+    }
+}
+
+impl RawLock {
+    const fn new() -> RawLock {
+        RawLock {
+            state: crate::Mutex::new(State {
+                readers: 0,
+                writer: false,
+                queue: Vec::new(),
+                next_id: 0,
+            }),
+        }
+    }
+
+    fn try_acquire(&self, exclusive: bool) -> bool {
+        let mut state = self.state.lock();
+        if state.has_waiters() || !state.can_acquire(exclusive) {
+            return false;
+        }
+        state.acquire(exclusive);
+        true
+    }
+
+    fn release(&self, exclusive: bool) {
+        let wakers = {
+            let mut state = self.state.lock();
+            state.release(exclusive);
+            state.grant()
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    fn is_locked(&self) -> bool {
+        let state = self.state.lock();
+        state.writer || state.readers != 0
+    }
+}
+
+// A pending acquisition of a `RawLock`.
+struct Acquire<'a> {
+    lock: &'a RawLock,
+    exclusive: bool,
+    // The id of the waiter, once the acquisition was queued.
+    id: Option<u64>,
+    done: bool,
+}
+
+impl<'a> Acquire<'a> {
+    fn new(lock: &'a RawLock, exclusive: bool) -> Acquire<'a> {
+        Acquire {
+            lock,
+            exclusive,
+            id: None,
+            done: false,
+        }
+    }
+
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        assert!(!self.done, "lock future polled after completion");
+        let mut state = self.lock.state.lock();
+        let id = match self.id {
+            Some(id) => id,
+            None => {
+                if !state.has_waiters() && state.can_acquire(self.exclusive) {
+                    state.acquire(self.exclusive);
+                    self.done = true;
+                    return Poll::Ready(());
+                }
+                let id = state.next_id;
+                state.next_id += 1;
+                state.queue.push(Waiter {
+                    id,
+                    exclusive: self.exclusive,
+                    granted: false,
+                    waker: cx.waker().clone(),
+                });
+                self.id = Some(id);
+                return Poll::Pending;
+            }
+        };
+
+        let idx = state
+            .queue
+            .iter()
+            .position(|waiter| waiter.id == id)
+            .unwrap();
+        let waiter = &mut state.queue[idx];
+        if waiter.granted {
+            state.queue.remove(idx);
+            self.done = true;
+            Poll::Ready(())
+        } else {
+            if !waiter.waker.will_wake(cx.waker()) {
+                waiter.waker = cx.waker().clone();
+            }
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        let id = match self.id {
+            Some(id) if !self.done => id,
+            _ => return,
+        };
+        let wakers = {
+            let mut state = self.lock.state.lock();
+            let idx = state
+                .queue
+                .iter()
+                .position(|waiter| waiter.id == id)
+                .unwrap();
+            // A lock granted to this future, or a writer queued ahead of
+            // others, passes on to the next waiters.
+            if state.queue.remove(idx).granted {
+                state.release(self.exclusive);
+            }
+            state.grant()
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+// Unparks the thread blocked in `block_on`.
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// Runs `acquire` to completion, blocking the current thread while it waits.
+fn block_on(mut acquire: Acquire<'_>) {
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    while acquire.poll(&mut cx).is_pending() {
+        thread::park();
+    }
+}
+
+/// An asynchronous mutual exclusion lock.
+///
+/// Like [`crate::Mutex`], except that [`lock`](Mutex::lock) returns a future
+/// which resolves once the lock is acquired, and the guard can be held across
+/// `.await` points.
+pub struct Mutex<T: ?Sized> {
+    raw: RawLock,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: the lock hands out `&mut T` to one task at a time.
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    /// Creates a new mutex in an unlocked state.
+    #[inline]
+    pub const fn new(t: T) -> Mutex<T> {
+        Mutex {
+            raw: RawLock::new(),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Consumes the mutex, returning the protected value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Acquires the mutex, waiting asynchronously until it is able to do so.
+    #[inline]
+    pub fn lock(&self) -> MutexLockFuture<'_, T> {
+        MutexLockFuture {
+            mutex: self,
+            acquire: Acquire::new(&self.raw, true),
+        }
+    }
+
+    /// Attempts to acquire the mutex without waiting.
+    ///
+    /// This fails while other tasks are waiting for the mutex, even if it is
+    /// unlocked, so that it never overtakes them.
+    #[inline]
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        if self.raw.try_acquire(true) {
+            Ok(MutexGuard { mutex: self })
+        } else {
+            Err(TryLockError::new(LockOperation::Lock))
+        }
+    }
+
+    /// Acquires the mutex from synchronous code, blocking the current thread
+    /// until it is able to do so.
+    ///
+    /// This must not be called from within an asynchronous task.
+    #[inline]
+    pub fn blocking_lock(&self) -> MutexGuard<'_, T> {
+        block_on(Acquire::new(&self.raw, true));
+        MutexGuard { mutex: self }
+    }
+
+    /// Returns `true` if the mutex is currently held.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.raw.is_locked()
+    }
+
+    /// Returns a mutable reference to the protected value.
+    ///
+    /// Since this borrows the mutex mutably, no locking is needed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    #[inline]
+    fn default() -> Mutex<T> {
+        Mutex::new(T::default())
+    }
+}
+
+impl<T> From<T> for Mutex<T> {
+    #[inline]
+    fn from(t: T) -> Mutex<T> {
+        Mutex::new(t)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_struct("Mutex");
+        match self.try_lock() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(_) => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+/// The future returned by [`Mutex::lock`].
+#[must_use = "futures do nothing unless polled"]
+pub struct MutexLockFuture<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+    acquire: Acquire<'a>,
+}
+
+impl<'a, T: ?Sized> Future for MutexLockFuture<'a, T> {
+    type Output = MutexGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<MutexGuard<'a, T>> {
+        let mutex = self.mutex;
+        self.acquire.poll(cx).map(|()| MutexGuard { mutex })
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for MutexLockFuture<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("MutexLockFuture").finish_non_exhaustive()
+    }
+}
+
+/// A guard holding a [`Mutex`].
+///
+/// Unlike the guards of the crate's blocking locks, it can be sent to another
+/// thread, so that a task holding it can move between the threads of its
+/// executor.
+#[must_use = "if unused the Mutex will immediately unlock"]
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+}
+
+// SAFETY: the guard grants `&T` through a shared reference.
+unsafe impl<T: ?Sized + Sync> Sync for MutexGuard<'_, T> {}
+
+impl<T: ?Sized> MutexGuard<'_, T> {
+    /// Returns the mutex this guard holds.
+    #[inline]
+    pub fn mutex(&self) -> &Mutex<T> {
+        self.mutex
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds exclusive access.
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds exclusive access.
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.mutex.raw.release(true);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for MutexGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
+
+/// An asynchronous reader-writer lock.
+///
+/// Like [`crate::RwLock`], except that [`read`](RwLock::read) and
+/// [`write`](RwLock::write) return futures which resolve once the lock is
+/// acquired, and the guards can be held across `.await` points.
+pub struct RwLock<T: ?Sized> {
+    raw: RawLock,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: the lock hands out `&mut T` to one task at a time, or `&T` to any
+// number of tasks.
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    /// Creates a new lock in an unlocked state.
+    #[inline]
+    pub const fn new(t: T) -> RwLock<T> {
+        RwLock {
+            raw: RawLock::new(),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Consumes the lock, returning the protected value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Acquires shared access, waiting asynchronously until it is able to do
+    /// so.
+    #[inline]
+    pub fn read(&self) -> RwLockReadFuture<'_, T> {
+        RwLockReadFuture {
+            rwlock: self,
+            acquire: Acquire::new(&self.raw, false),
+        }
+    }
+
+    /// Attempts to acquire shared access without waiting.
+    ///
+    /// This fails while other tasks are waiting for the lock, so that it never
+    /// overtakes them.
+    #[inline]
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        if self.raw.try_acquire(false) {
+            Ok(RwLockReadGuard { rwlock: self })
+        } else {
+            Err(TryLockError::new(LockOperation::Read))
+        }
+    }
+
+    /// Acquires shared access from synchronous code, blocking the current
+    /// thread until it is able to do so.
+    ///
+    /// This must not be called from within an asynchronous task.
+    #[inline]
+    pub fn blocking_read(&self) -> RwLockReadGuard<'_, T> {
+        block_on(Acquire::new(&self.raw, false));
+        RwLockReadGuard { rwlock: self }
+    }
+
+    /// Acquires exclusive access, waiting asynchronously until it is able to
+    /// do so.
+    #[inline]
+    pub fn write(&self) -> RwLockWriteFuture<'_, T> {
+        RwLockWriteFuture {
+            rwlock: self,
+            acquire: Acquire::new(&self.raw, true),
+        }
+    }
+
+    /// Attempts to acquire exclusive access without waiting.
+    ///
+    /// This fails while other tasks are waiting for the lock, so that it never
+    /// overtakes them.
+    #[inline]
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        if self.raw.try_acquire(true) {
+            Ok(RwLockWriteGuard { rwlock: self })
+        } else {
+            Err(TryLockError::new(LockOperation::Write))
+        }
+    }
+
+    /// Acquires exclusive access from synchronous code, blocking the current
+    /// thread until it is able to do so.
+    ///
+    /// This must not be called from within an asynchronous task.
+    #[inline]
+    pub fn blocking_write(&self) -> RwLockWriteGuard<'_, T> {
+        block_on(Acquire::new(&self.raw, true));
+        RwLockWriteGuard { rwlock: self }
+    }
+
+    /// Returns `true` if the lock is currently held, for shared or exclusive
+    /// access.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.raw.is_locked()
+    }
+
+    /// Returns a mutable reference to the protected value.
+    ///
+    /// Since this borrows the lock mutably, no locking is needed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    #[inline]
+    fn default() -> RwLock<T> {
+        RwLock::new(T::default())
+    }
+}
+
+impl<T> From<T> for RwLock<T> {
+    #[inline]
+    fn from(t: T) -> RwLock<T> {
+        RwLock::new(t)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_struct("RwLock");
+        match self.try_read() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(_) => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+/// The future returned by [`RwLock::read`].
+#[must_use = "futures do nothing unless polled"]
+pub struct RwLockReadFuture<'a, T: ?Sized> {
+    rwlock: &'a RwLock<T>,
+    acquire: Acquire<'a>,
+}
+
+impl<'a, T: ?Sized> Future for RwLockReadFuture<'a, T> {
+    type Output = RwLockReadGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<RwLockReadGuard<'a, T>> {
+        let rwlock = self.rwlock;
+        self.acquire.poll(cx).map(|()| RwLockReadGuard { rwlock })
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for RwLockReadFuture<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("RwLockReadFuture").finish_non_exhaustive()
+    }
+}
+
+/// The future returned by [`RwLock::write`].
+#[must_use = "futures do nothing unless polled"]
+pub struct RwLockWriteFuture<'a, T: ?Sized> {
+    rwlock: &'a RwLock<T>,
+    acquire: Acquire<'a>,
+}
+
+impl<'a, T: ?Sized> Future for RwLockWriteFuture<'a, T> {
+    type Output = RwLockWriteGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<RwLockWriteGuard<'a, T>> {
+        let rwlock = self.rwlock;
+        self.acquire.poll(cx).map(|()| RwLockWriteGuard { rwlock })
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for RwLockWriteFuture<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("RwLockWriteFuture")
+            .finish_non_exhaustive()
+    }
+}
+
+/// A guard holding shared access to an [`RwLock`].
+///
+/// It can be sent to another thread, like [`MutexGuard`].
+#[must_use = "if unused the RwLock will immediately unlock"]
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    rwlock: &'a RwLock<T>,
+}
+
+// SAFETY: the guard only grants `&T`.
+unsafe impl<T: ?Sized + Sync> Send for RwLockReadGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for RwLockReadGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds shared access.
+        unsafe { &*self.rwlock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.rwlock.raw.release(false);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
+
+/// A guard holding exclusive access to an [`RwLock`].
+///
+/// It can be sent to another thread, like [`MutexGuard`].
+#[must_use = "if unused the RwLock will immediately unlock"]
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    rwlock: &'a RwLock<T>,
+}
+
+// SAFETY: the guard grants `&T` through a shared reference.
+unsafe impl<T: ?Sized + Sync> Sync for RwLockWriteGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds exclusive access.
+        unsafe { &*self.rwlock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds exclusive access.
+        unsafe { &mut *self.rwlock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.rwlock.raw.release(true);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
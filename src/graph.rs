@@ -0,0 +1,191 @@
+//! Detection of cycles in the order locks are acquired in.
+//!
+//! With the `cycle-detection` feature enabled, every blocking acquisition of a
+//! `Mutex` or `RwLock` records an edge from each lock the thread already holds
+//! to the lock it acquires, building a graph of the orders in which locks have
+//! been acquired across the whole process. An edge which would close a cycle
+//! means that threads taking the same locks in those orders at the same time
+//! could deadlock, so the acquisition panics instead, whether or not any
+//! thread got stuck this time.
+//!
+//! Locks are identified by an id assigned the first time they take part in an
+//! acquisition, so a lock created at the address of one which was dropped
+//! starts with no edges. The edges of dropped locks are kept, which is why
+//! this is meant for debugging and tests rather than production.
+
+use std::{
+    backtrace::Backtrace,
+    collections::HashMap,
+    fmt::Write,
+    sync::{
+        self,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use crate::held::{self, Meta};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+static GRAPH: sync::Mutex<Graph> = sync::Mutex::new(Graph {
+    edges: None,
+    labels: None,
+});
+
+/// The id of a lock in the graph, assigned lazily.
+pub(crate) struct Node {
+    id: AtomicUsize,
+}
+
+impl Node {
+    pub(crate) const fn new() -> Node {
+        Node {
+            id: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn id(&self) -> usize {
+        match self.id.load(Ordering::Relaxed) {
+            0 => self.assign(),
+            id => id,
+        }
+    }
+
+    #[cold]
+    fn assign(&self) -> usize {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        match self
+            .id
+            .compare_exchange(0, id, Ordering::Relaxed, Ordering::Relaxed)
+        {
+            Ok(_) => id,
+            Err(id) => id,
+        }
+    }
+}
+
+impl Default for Node {
+    #[inline]
+    fn default() -> Node {
+        Node::new()
+    }
+}
+
+struct Graph {
+    // For each lock, the locks acquired while it was held, with where that
+    // first happened.
+    edges: Option<HashMap<usize, HashMap<usize, Backtrace>>>,
+    // How to describe each lock which is part of an edge.
+    labels: Option<HashMap<usize, String>>,
+}
+
+impl Graph {
+    // Returns the path from `from` to `to`, if there is one.
+    fn path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let edges = self.edges.as_ref()?;
+        let mut stack = vec![vec![from]];
+        let mut seen = vec![from];
+        while let Some(path) = stack.pop() {
+            let last = *path.last().unwrap();
+            if last == to {
+                return Some(path);
+            }
+            for &next in edges.get(&last).into_iter().flat_map(HashMap::keys) {
+                if !seen.contains(&next) {
+                    seen.push(next);
+                    let mut path = path.clone();
+                    path.push(next);
+                    stack.push(path);
+                }
+            }
+        }
+        None
+    }
+
+    fn label(&self, id: usize) -> &str {
+        self.labels
+            .as_ref()
+            .and_then(|labels| labels.get(&id))
+            .map_or("a lock", String::as_str)
+    }
+}
+
+fn label(addr: usize, name: Option<&'static str>) -> String {
+    match name {
+        Some(name) => format!("lock '{}' at {:#x}", name, addr),
+        None => format!("lock at {:#x}", addr),
+    }
+}
+
+/// Records that the lock at `addr` is about to be acquired while the current
+/// thread holds the locks it holds, and panics if that closes a cycle.
+pub(crate) fn check(addr: usize, meta: Meta) {
+    // SAFETY: the lock outlives the acquisition.
+    let id = unsafe { (*meta.node).id() };
+    let mut held = held::current();
+    held.retain(|held| held.node != id);
+    if held.is_empty() {
+        return;
+    }
+
+    let mut graph = GRAPH.lock().unwrap_or_else(|e| e.into_inner());
+    for held in held {
+        let known = graph
+            .edges
+            .as_ref()
+            .and_then(|edges| edges.get(&held.node))
+            .map_or(false, |edges| edges.contains_key(&id));
+        if known {
+            continue;
+        }
+        if let Some(path) = graph.path(id, held.node) {
+            let message = describe(&graph, &path, id, held.node);
+            drop(graph);
+            panic!("{}", message);
+        }
+        #[cfg(feature = "names")]
+        let held_name = held.name;
+        #[cfg(not(feature = "names"))]
+        let held_name = None;
+        let labels = graph.labels.get_or_insert_with(HashMap::new);
+        labels.insert(held.node, label(held.addr, held_name));
+        labels.insert(id, label(addr, meta.name()));
+        graph
+            .edges
+            .get_or_insert_with(HashMap::new)
+            .entry(held.node)
+            .or_default()
+            .insert(id, Backtrace::force_capture());
+    }
+}
+
+// Describes the cycle closed by acquiring `id` while holding `held`, given the
+// existing path from `id` to `held`.
+fn describe(graph: &Graph, path: &[usize], id: usize, held: usize) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "lock order cycle: acquiring {} while holding {}, but they were acquired in the opposite \
+         order before",
+        graph.label(id),
+        graph.label(held),
+    );
+    let edges = graph.edges.as_ref().unwrap();
+    for pair in path.windows(2) {
+        let _ = writeln!(
+            out,
+            "\n{} was acquired while holding {} at:\n{}",
+            graph.label(pair[1]),
+            graph.label(pair[0]),
+            edges[&pair[0]][&pair[1]],
+        );
+    }
+    let _ = write!(
+        out,
+        "\n{} is being acquired while holding {} at:\n{}",
+        graph.label(id),
+        graph.label(held),
+        Backtrace::force_capture(),
+    );
+    out
+}
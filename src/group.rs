@@ -0,0 +1,287 @@
+//! Acquiring several independent locks as one.
+//!
+//! A [`LockGroup`] is built from a tuple of references to locks, which may be
+//! of different types. [`LockGroup::lock_all`] acquires every member and
+//! returns a tuple of their guards, taking a consistent cut across structures
+//! that are otherwise locked independently.
+//!
+//! Members are always acquired in order of their addresses rather than their
+//! position in the tuple, so groups sharing some of their locks cannot deadlock
+//! each other, whatever order their members were listed in.
+//!
+//! For one-off acquisitions, [`lock_both`] locks a pair of locks the same way
+//! without building a group, and [`lock_all`] locks any number of locks of the
+//! same type given as a slice.
+//!
+//! [`LockGroup::try_lock_all`] and the [`try_lock_all!`](crate::try_lock_all)
+//! macro instead attempt to acquire every member without blocking, and either
+//! succeed for all of them or release whatever they acquired. Wrapping an
+//! `RwLock` in [`Read`] makes it a member acquired for reading.
+
+use crate::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockResult};
+
+/// A lock that can be a member of a [`LockGroup`].
+pub trait Member {
+    /// The guard returned when the lock is acquired.
+    type Guard;
+
+    /// Returns the address which determines the order of acquisition.
+    fn addr(&self) -> usize;
+
+    /// Acquires the lock.
+    fn acquire(&self) -> Self::Guard;
+
+    /// Attempts to acquire the lock without blocking.
+    fn try_acquire(&self) -> TryLockResult<Self::Guard>;
+}
+
+impl<'a, T: ?Sized> Member for &'a Mutex<T> {
+    type Guard = MutexGuard<'a, T>;
+
+    #[inline]
+    fn addr(&self) -> usize {
+        Mutex::addr(self)
+    }
+
+    #[inline]
+    fn acquire(&self) -> MutexGuard<'a, T> {
+        Mutex::lock(self)
+    }
+
+    #[inline]
+    fn try_acquire(&self) -> TryLockResult<MutexGuard<'a, T>> {
+        Mutex::try_lock(self)
+    }
+}
+
+impl<'a, T: ?Sized> Member for &'a RwLock<T> {
+    type Guard = RwLockWriteGuard<'a, T>;
+
+    #[inline]
+    fn addr(&self) -> usize {
+        RwLock::addr(self)
+    }
+
+    #[inline]
+    fn acquire(&self) -> RwLockWriteGuard<'a, T> {
+        RwLock::write(self)
+    }
+
+    #[inline]
+    fn try_acquire(&self) -> TryLockResult<RwLockWriteGuard<'a, T>> {
+        RwLock::try_write(self)
+    }
+}
+
+/// An `RwLock` as a member acquired for reading rather than writing.
+#[derive(Debug)]
+pub struct Read<'a, T: ?Sized>(pub &'a RwLock<T>);
+
+impl<T: ?Sized> Clone for Read<'_, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for Read<'_, T> {}
+
+impl<'a, T: ?Sized> Member for Read<'a, T> {
+    type Guard = RwLockReadGuard<'a, T>;
+
+    #[inline]
+    fn addr(&self) -> usize {
+        RwLock::addr(self.0)
+    }
+
+    #[inline]
+    fn acquire(&self) -> RwLockReadGuard<'a, T> {
+        self.0.read()
+    }
+
+    #[inline]
+    fn try_acquire(&self) -> TryLockResult<RwLockReadGuard<'a, T>> {
+        self.0.try_read()
+    }
+}
+
+/// A tuple of [`Member`]s.
+///
+/// Implemented for tuples of up to eight members.
+pub trait Members {
+    /// The tuple of the members' guards.
+    type Guards;
+
+    /// Returns the members' addresses, in tuple order.
+    fn addrs(&self) -> Vec<usize>;
+
+    /// Acquires every member in address order.
+    fn acquire_all(&self) -> Self::Guards;
+
+    /// Attempts to acquire every member without blocking, releasing those
+    /// already acquired as soon as one fails.
+    fn try_acquire_all(&self) -> TryLockResult<Self::Guards>;
+}
+
+macro_rules! members {
+    ($($idx:tt $name:ident),+) => {
+        impl<$($name: Member),+> Members for ($($name,)+) {
+            type Guards = ($($name::Guard,)+);
+
+            fn addrs(&self) -> Vec<usize> {
+                vec![$(self.$idx.addr()),+]
+            }
+
+            fn acquire_all(&self) -> Self::Guards {
+                let mut order = [$((self.$idx.addr(), $idx)),+];
+                order.sort_unstable();
+                let mut guards = ($(None::<$name::Guard>,)+);
+                for &(_, idx) in &order {
+                    match idx {
+                        $($idx => guards.$idx = Some(self.$idx.acquire()),)+
+                        _ => unreachable!(),
+                    }
+                }
+                ($(guards.$idx.unwrap(),)+)
+            }
+
+            fn try_acquire_all(&self) -> TryLockResult<Self::Guards> {
+                // Guards acquired before a failure are dropped on return.
+                Ok(($(self.$idx.try_acquire()?,)+))
+            }
+        }
+    };
+}
+
+members!(0 A);
+members!(0 A, 1 B);
+members!(0 A, 1 B, 2 C);
+members!(0 A, 1 B, 2 C, 3 D);
+members!(0 A, 1 B, 2 C, 3 D, 4 E);
+members!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F);
+members!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G);
+members!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H);
+
+/// A fixed set of locks which are acquired together.
+#[derive(Debug, Clone)]
+pub struct LockGroup<M> {
+    members: M,
+}
+
+impl<M: Members> LockGroup<M> {
+    /// Creates a group from a tuple of locks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the same lock appears more than once, since acquiring the
+    /// group would then deadlock.
+    pub fn new(members: M) -> LockGroup<M> {
+        let mut addrs = members.addrs();
+        addrs.sort_unstable();
+        assert!(
+            addrs.windows(2).all(|w| w[0] != w[1]),
+            "lock group contains the same lock more than once"
+        );
+        LockGroup { members }
+    }
+
+    /// Acquires every lock in the group, blocking the current thread until it
+    /// is able to do so.
+    ///
+    /// Mutexes are locked and `RwLock`s are write-locked. The guards are
+    /// returned in the order the locks were listed in.
+    #[inline]
+    pub fn lock_all(&self) -> M::Guards {
+        self.members.acquire_all()
+    }
+
+    /// Attempts to acquire every lock in the group without blocking.
+    ///
+    /// Either every lock is acquired, or none is: if one of them is
+    /// unavailable, those acquired so far are released before an error is
+    /// returned.
+    #[inline]
+    pub fn try_lock_all(&self) -> TryLockResult<M::Guards> {
+        self.members.try_acquire_all()
+    }
+
+    /// Returns the members of the group.
+    #[inline]
+    pub fn members(&self) -> &M {
+        &self.members
+    }
+}
+
+/// Attempts to acquire several locks of different types without blocking,
+/// either acquiring all of them or none.
+///
+/// Each lock is given as a reference, optionally preceded by `read` or
+/// `write` for an `RwLock`, which is write-locked by default, as in
+/// `try_lock_all!(&accounts, read &index)`. The result is a `TryLockResult`
+/// of a tuple of the guards, in the order the locks were listed in, as from
+/// [`LockGroup::try_lock_all`].
+///
+/// # Panics
+///
+/// Panics if the same lock is listed more than once.
+#[macro_export]
+macro_rules! try_lock_all {
+    ($($locks:tt)+) => {
+        $crate::__try_lock_all!([] $($locks)+)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __try_lock_all {
+    ([$($members:expr,)*] read $lock:expr $(, $($rest:tt)*)?) => {
+        $crate::__try_lock_all!([$($members,)* $crate::group::Read($lock),] $($($rest)*)?)
+    };
+    ([$($members:expr,)*] write $lock:expr $(, $($rest:tt)*)?) => {
+        $crate::__try_lock_all!([$($members,)* $lock,] $($($rest)*)?)
+    };
+    ([$($members:expr,)*] $lock:expr $(, $($rest:tt)*)?) => {
+        $crate::__try_lock_all!([$($members,)* $lock,] $($($rest)*)?)
+    };
+    ([$($members:expr,)+]) => {
+        $crate::group::LockGroup::new(($($members,)+)).try_lock_all()
+    };
+}
+
+/// Acquires two locks in address order, returning their guards in the order
+/// they were given.
+///
+/// This is the same as `LockGroup::new((a, b)).lock_all()`, so two threads
+/// locking the same pair cannot deadlock however each lists them.
+///
+/// # Panics
+///
+/// Panics if `a` and `b` are the same lock.
+#[inline]
+pub fn lock_both<A: Member, B: Member>(a: A, b: B) -> (A::Guard, B::Guard) {
+    LockGroup::new((a, b)).lock_all()
+}
+
+/// Acquires every lock in `locks` in address order, returning their guards in
+/// the order the locks were given.
+///
+/// # Panics
+///
+/// Panics if the same lock appears more than once.
+pub fn lock_all<M: Member>(locks: &[M]) -> Vec<M::Guard> {
+    let mut order = locks
+        .iter()
+        .enumerate()
+        .map(|(idx, lock)| (lock.addr(), idx))
+        .collect::<Vec<_>>();
+    order.sort_unstable();
+    assert!(
+        order.windows(2).all(|w| w[0].0 != w[1].0),
+        "lock_all was given the same lock more than once"
+    );
+    let mut guards = locks.iter().map(|_| None).collect::<Vec<_>>();
+    for &(_, idx) in &order {
+        guards[idx] = Some(locks[idx].acquire());
+    }
+    guards.into_iter().map(Option::unwrap).collect()
+}
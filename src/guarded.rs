@@ -0,0 +1,114 @@
+use std::{fmt, marker::PhantomData};
+
+use crate::{Lock, Mutex, SharedLock, TryLockResult};
+
+/// A value of type `T` protected by a lock of type `L`.
+///
+/// The lock type is a type parameter, defaulting to [`Mutex`], so switching a
+/// field to another lock implementation only changes its type, not the code
+/// that accesses it.
+pub struct Guarded<T, L: Lock<T> = Mutex<T>> {
+    lock: L,
+    // `L` already owns the value and determines the auto traits.
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T, L: Lock<T>> Guarded<T, L> {
+    /// Creates a new `Guarded` protecting `t`.
+    #[inline]
+    pub fn new(t: T) -> Guarded<T, L>
+    where
+        L: From<T>,
+    {
+        Guarded::from_lock(L::from(t))
+    }
+
+    /// Creates a new `Guarded` from an existing lock.
+    #[inline]
+    pub const fn from_lock(lock: L) -> Guarded<T, L> {
+        Guarded {
+            lock,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Acquires the lock, blocking the current thread until it is able to do
+    /// so.
+    #[inline]
+    pub fn lock(&self) -> L::Guard<'_> {
+        self.lock.lock()
+    }
+
+    /// Attempts to acquire the lock without blocking.
+    #[inline]
+    pub fn try_lock(&self) -> TryLockResult<L::Guard<'_>> {
+        self.lock.try_lock()
+    }
+
+    /// Calls `f` with exclusive access to the value.
+    #[inline]
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.lock.lock())
+    }
+
+    /// Returns a mutable reference to the value.
+    ///
+    /// No locking is necessary since the borrow guarantees exclusive access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.lock.get_mut()
+    }
+
+    /// Returns a reference to the underlying lock.
+    #[inline]
+    pub fn as_lock(&self) -> &L {
+        &self.lock
+    }
+
+    /// Consumes the `Guarded`, returning the underlying lock.
+    #[inline]
+    pub fn into_lock(self) -> L {
+        self.lock
+    }
+}
+
+impl<T, L: SharedLock<T>> Guarded<T, L> {
+    /// Acquires shared access, blocking the current thread until it is able to
+    /// do so.
+    #[inline]
+    pub fn lock_shared(&self) -> L::SharedGuard<'_> {
+        self.lock.lock_shared()
+    }
+
+    /// Attempts to acquire shared access without blocking.
+    #[inline]
+    pub fn try_lock_shared(&self) -> TryLockResult<L::SharedGuard<'_>> {
+        self.lock.try_lock_shared()
+    }
+
+    /// Calls `f` with shared access to the value.
+    #[inline]
+    pub fn with_shared<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.lock.lock_shared())
+    }
+}
+
+impl<T, L: Lock<T> + From<T>> From<T> for Guarded<T, L> {
+    #[inline]
+    fn from(t: T) -> Guarded<T, L> {
+        Guarded::new(t)
+    }
+}
+
+impl<T, L: Lock<T> + Default> Default for Guarded<T, L> {
+    #[inline]
+    fn default() -> Guarded<T, L> {
+        Guarded::from_lock(L::default())
+    }
+}
+
+impl<T, L: Lock<T> + fmt::Debug> fmt::Debug for Guarded<T, L> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("Guarded").field(&self.lock).finish()
+    }
+}
@@ -0,0 +1,943 @@
+//! Bookkeeping for the locks held by each thread.
+//!
+//! Every guard of a `Mutex` or `RwLock` carries a `Token`. With the `tracking`
+//! feature enabled, it records the lock in a list belonging to the current
+//! thread for as long as the guard is alive. With the `histogram`, `metrics`,
+//! `opentelemetry` or `tracing` features enabled, it reports how long the
+//! acquisition waited and how long the lock was then held once the guard is
+//! dropped. With the `stats` feature enabled, it updates the lock's own
+//! counters in the same way, and with the `slow-warnings` feature enabled it
+//! checks both durations against the lock's thresholds.
+//! Without any of these features the token is empty in release builds and
+//! costs nothing.
+//!
+//! In builds with debug assertions, the token also records the level of a
+//! lock created with `with_level`, so that acquiring a lock out of order with
+//! the levels held by the thread panics, and the thread holding a lock
+//! exclusively, so that acquiring it again from that thread panics too.
+//!
+//! With the `holder-location` feature enabled, the token records where the
+//! lock was acquired on the lock itself, and in the list of the `tracking`
+//! feature.
+//!
+//! With the `test-util` feature enabled, acquisitions and releases are also
+//! where the `test_util` module injects delays and records or replays traces.
+//!
+//! With the `send-guard` feature enabled, a guard may be dropped on another
+//! thread than the one which acquired its lock, so the token keeps hold of the
+//! bookkeeping of the acquiring thread instead of looking up the current
+//! thread's when it is dropped.
+
+#[cfg(all(debug_assertions, not(feature = "send-guard")))]
+use std::cell::RefCell;
+use std::marker::PhantomData;
+#[cfg(feature = "holder-location")]
+use std::panic::Location;
+#[cfg(any(
+    all(debug_assertions, not(feature = "send-guard")),
+    feature = "tracking"
+))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(any(feature = "tracking", all(debug_assertions, feature = "send-guard")))]
+use std::sync::{self, Arc};
+#[cfg(any(feature = "histogram", feature = "metrics", feature = "opentelemetry"))]
+use std::time::Duration;
+#[cfg(any(
+    feature = "histogram",
+    feature = "metrics",
+    feature = "opentelemetry",
+    feature = "slow-warnings",
+    feature = "stats",
+    feature = "tracing"
+))]
+use std::time::Instant;
+#[cfg(feature = "tracking")]
+use std::{
+    sync::Weak,
+    thread::{self, Thread},
+};
+
+/// The kind of access a guard grants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Access {
+    Exclusive,
+    Shared,
+}
+
+/// The level of a lock created with `with_level`, or `None` for a lock which
+/// takes no part in the lock hierarchy.
+#[cfg(debug_assertions)]
+pub(crate) type Level = Option<u32>;
+
+/// Makes the guards of `Mutex` and `RwLock` `!Send`, unless the `send-guard`
+/// feature is enabled, as the bookkeeping of a thread is otherwise only
+/// updated by that thread.
+#[cfg(not(feature = "send-guard"))]
+pub(crate) type NotSend = PhantomData<*const ()>;
+#[cfg(feature = "send-guard")]
+pub(crate) type NotSend = PhantomData<()>;
+
+/// The thread holding a lock exclusively, in builds with debug assertions, so
+/// that a thread acquiring a lock it already holds panics instead of
+/// deadlocking. This is not tracked with the `send-guard` feature, as a guard
+/// sent to another thread leaves the thread which acquired it free to wait for
+/// the lock.
+#[cfg(all(debug_assertions, not(feature = "send-guard")))]
+#[derive(Default)]
+pub(crate) struct Owner {
+    // The address of the holder's `THREAD`, or 0.
+    thread: AtomicUsize,
+}
+
+#[cfg(all(debug_assertions, not(feature = "send-guard")))]
+impl Owner {
+    #[inline]
+    pub(crate) const fn new() -> Owner {
+        Owner {
+            thread: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    fn set(&self, thread: usize) {
+        self.thread.store(thread, Ordering::Relaxed);
+    }
+}
+
+#[cfg(all(debug_assertions, not(feature = "send-guard")))]
+thread_local! {
+    // Only its address matters, which is unique among the running threads.
+    static THREAD: u8 = const { 0 };
+}
+
+// Returns an identifier of the current thread which is never 0, or 0 if the
+// thread is being torn down.
+#[cfg(all(debug_assertions, not(feature = "send-guard")))]
+#[inline]
+fn current_thread() -> usize {
+    THREAD
+        .try_with(|thread| thread as *const u8 as usize)
+        .unwrap_or(0)
+}
+
+#[cfg(all(debug_assertions, not(feature = "send-guard")))]
+thread_local! {
+    // The levels of the leveled locks held by the current thread.
+    static LEVELS: RefCell<Vec<u32>> = const { RefCell::new(Vec::new()) };
+}
+
+// The levels are shared with the tokens of the thread's guards, which may be
+// dropped on other threads.
+#[cfg(all(debug_assertions, feature = "send-guard"))]
+type Levels = Arc<sync::Mutex<Vec<u32>>>;
+
+#[cfg(all(debug_assertions, feature = "send-guard"))]
+thread_local! {
+    // The levels of the leveled locks held by the current thread.
+    static LEVELS: Levels = Levels::default();
+}
+
+// Runs `f` on the levels held by the current thread, unless it is being torn
+// down.
+#[cfg(debug_assertions)]
+fn with_levels<R>(f: impl FnOnce(&mut Vec<u32>) -> R) -> Option<R> {
+    #[cfg(not(feature = "send-guard"))]
+    let r = LEVELS.try_with(|levels| f(&mut levels.borrow_mut()));
+    #[cfg(feature = "send-guard")]
+    let r = LEVELS.try_with(|levels| f(&mut levels.lock().unwrap_or_else(|e| e.into_inner())));
+    r.ok()
+}
+
+/// Called before every blocking acquisition of the lock at `addr`.
+///
+/// This panics, in builds with debug assertions, if the current thread holds
+/// the lock exclusively already, or holds a lock whose level is not strictly
+/// greater than that of the lock and, with the `cycle-detection` feature
+/// enabled, if acquiring the lock closes a cycle in the order locks are
+/// acquired in.
+#[inline]
+pub(crate) fn check_order(addr: usize, meta: Meta) {
+    #[cfg(all(debug_assertions, not(feature = "send-guard")))]
+    {
+        // SAFETY: the lock outlives the acquisition.
+        let owner = unsafe { (*meta.owner).thread.load(Ordering::Relaxed) };
+        if owner != 0 && owner == current_thread() {
+            relocked(meta.name());
+        }
+    }
+    #[cfg(debug_assertions)]
+    if let Some(level) = meta.level {
+        check_level_slow(level);
+    }
+    #[cfg(feature = "cycle-detection")]
+    crate::graph::check(addr, meta);
+    #[cfg(not(feature = "cycle-detection"))]
+    let _ = addr;
+    #[cfg(not(any(debug_assertions, feature = "cycle-detection")))]
+    let _ = meta;
+}
+
+#[cfg(all(debug_assertions, not(feature = "send-guard")))]
+#[cold]
+fn relocked(name: Option<&'static str>) -> ! {
+    let thread = std::thread::current();
+    let thread = thread.name().unwrap_or("<unnamed>");
+    match name {
+        Some(name) => panic!(
+            "deadlock: thread `{}` attempted to acquire lock `{}`, which it already holds",
+            thread, name,
+        ),
+        None => panic!(
+            "deadlock: thread `{}` attempted to acquire a lock which it already holds",
+            thread,
+        ),
+    }
+}
+
+#[cfg(debug_assertions)]
+#[cold]
+fn check_level_slow(level: u32) {
+    let held = with_levels(|levels| levels.iter().find(|&&held| held <= level).copied());
+    if let Some(held) = held.flatten() {
+        panic!(
+            "lock level violation: acquiring a lock at level {} while holding a lock at level {}, \
+             but locks must be acquired in strictly decreasing order of level",
+            level, held,
+        );
+    }
+}
+
+/// What a guard's token needs to know about its lock besides its address.
+#[derive(Clone, Copy)]
+pub(crate) struct Meta {
+    #[cfg(debug_assertions)]
+    pub(crate) level: Level,
+    #[cfg(all(debug_assertions, not(feature = "send-guard")))]
+    pub(crate) owner: *const Owner,
+    #[cfg(feature = "names")]
+    pub(crate) name: Option<&'static str>,
+    #[cfg(feature = "holder-location")]
+    pub(crate) holder: *const crate::holder::Site,
+    #[cfg(feature = "stats")]
+    pub(crate) stats: *const crate::stats::Counters,
+    #[cfg(feature = "slow-warnings")]
+    pub(crate) slow: *const crate::slow::Thresholds,
+    #[cfg(feature = "cycle-detection")]
+    pub(crate) node: *const crate::graph::Node,
+}
+
+impl Meta {
+    /// Returns the name of the lock, which is always `None` without the
+    /// `names` feature.
+    #[inline]
+    pub(crate) fn name(&self) -> Option<&'static str> {
+        #[cfg(feature = "names")]
+        {
+            self.name
+        }
+        #[cfg(not(feature = "names"))]
+        {
+            None
+        }
+    }
+
+    /// Returns where the lock was acquired, if it is held, which is always
+    /// `None` without the `holder-location` feature.
+    #[inline]
+    pub(crate) fn holder_location(&self) -> Option<&'static core::panic::Location<'static>> {
+        // SAFETY: the lock outlives its metadata.
+        #[cfg(feature = "holder-location")]
+        unsafe {
+            (*self.holder).get()
+        }
+        #[cfg(not(feature = "holder-location"))]
+        {
+            None
+        }
+    }
+}
+
+/// The bookkeeping a `Mutex` or `RwLock` keeps on itself for the features
+/// which instrument it, which `Meta` points into.
+pub(crate) struct Instrumentation {
+    #[cfg(debug_assertions)]
+    pub(crate) level: Level,
+    #[cfg(all(debug_assertions, not(feature = "send-guard")))]
+    pub(crate) owner: Owner,
+    #[cfg(feature = "names")]
+    pub(crate) name: Option<&'static str>,
+    #[cfg(feature = "holder-location")]
+    pub(crate) holder: crate::holder::Site,
+    #[cfg(feature = "stats")]
+    pub(crate) stats: crate::stats::Counters,
+    #[cfg(feature = "slow-warnings")]
+    pub(crate) slow: crate::slow::Thresholds,
+    #[cfg(feature = "cycle-detection")]
+    pub(crate) node: crate::graph::Node,
+}
+
+impl Instrumentation {
+    /// The bookkeeping of a new lock with the given name and level, which are
+    /// discarded without the features keeping them.
+    #[inline]
+    pub(crate) const fn new(name: Option<&'static str>, level: Option<u32>) -> Instrumentation {
+        #[cfg(not(feature = "names"))]
+        let _ = name;
+        #[cfg(not(debug_assertions))]
+        let _ = level;
+        Instrumentation {
+            #[cfg(debug_assertions)]
+            level,
+            #[cfg(all(debug_assertions, not(feature = "send-guard")))]
+            owner: Owner::new(),
+            #[cfg(feature = "names")]
+            name,
+            #[cfg(feature = "holder-location")]
+            holder: crate::holder::Site::new(),
+            #[cfg(feature = "stats")]
+            stats: crate::stats::Counters::new(),
+            #[cfg(feature = "slow-warnings")]
+            slow: crate::slow::Thresholds::new(),
+            #[cfg(feature = "cycle-detection")]
+            node: crate::graph::Node::new(),
+        }
+    }
+
+    /// The bookkeeping of a new lock with the same name and level as this
+    /// one, such as a clone of it.
+    #[inline]
+    pub(crate) fn fresh(&self) -> Instrumentation {
+        #[cfg(debug_assertions)]
+        let level = self.level;
+        #[cfg(not(debug_assertions))]
+        let level = None;
+        Instrumentation::new(self.meta().name(), level)
+    }
+
+    /// Forgets the thread holding the lock, for a lock which was reset.
+    #[inline]
+    pub(crate) fn forget_holder(&mut self) {
+        #[cfg(all(debug_assertions, not(feature = "send-guard")))]
+        {
+            self.owner = Owner::new();
+        }
+        #[cfg(feature = "holder-location")]
+        {
+            self.holder = crate::holder::Site::new();
+        }
+    }
+
+    #[inline]
+    pub(crate) fn meta(&self) -> Meta {
+        Meta {
+            #[cfg(debug_assertions)]
+            level: self.level,
+            #[cfg(all(debug_assertions, not(feature = "send-guard")))]
+            owner: &self.owner,
+            #[cfg(feature = "names")]
+            name: self.name,
+            #[cfg(feature = "holder-location")]
+            holder: &self.holder,
+            #[cfg(feature = "stats")]
+            stats: &self.stats,
+            #[cfg(feature = "slow-warnings")]
+            slow: &self.slow,
+            #[cfg(feature = "cycle-detection")]
+            node: &self.node,
+        }
+    }
+}
+
+impl Default for Instrumentation {
+    #[inline]
+    fn default() -> Instrumentation {
+        Instrumentation::new(None, None)
+    }
+}
+
+/// Measures how long an acquisition waited for the lock.
+pub(crate) struct Wait {
+    #[cfg(all(feature = "names", feature = "tracing"))]
+    name: Option<&'static str>,
+    // Where the holder the acquisition waited for acquired the lock.
+    #[cfg(all(feature = "holder-location", feature = "slow-warnings"))]
+    holder: Option<&'static Location<'static>>,
+    #[cfg(any(
+        feature = "histogram",
+        feature = "metrics",
+        feature = "opentelemetry",
+        feature = "slow-warnings",
+        feature = "stats",
+        feature = "tracing"
+    ))]
+    start: Option<Instant>,
+}
+
+impl Wait {
+    /// An acquisition which did not have to wait.
+    pub(crate) const NONE: Wait = Wait {
+        #[cfg(all(feature = "names", feature = "tracing"))]
+        name: None,
+        #[cfg(all(feature = "holder-location", feature = "slow-warnings"))]
+        holder: None,
+        #[cfg(any(
+            feature = "histogram",
+            feature = "metrics",
+            feature = "opentelemetry",
+            feature = "slow-warnings",
+            feature = "stats",
+            feature = "tracing"
+        ))]
+        start: None,
+    };
+
+    /// Starts measuring an acquisition of the lock at `addr` which is about
+    /// to block.
+    #[inline]
+    pub(crate) fn start(addr: usize, meta: Meta) -> Wait {
+        #[cfg(feature = "tracking")]
+        with_local(|local| local.waiting.store(addr, Ordering::Relaxed));
+        #[cfg(feature = "tracing")]
+        crate::tracing::contended(addr, meta.name());
+        #[cfg(not(any(feature = "tracking", feature = "tracing")))]
+        let _ = addr;
+        #[cfg(not(any(
+            all(feature = "holder-location", feature = "slow-warnings"),
+            feature = "tracing"
+        )))]
+        let _ = meta;
+        Wait {
+            #[cfg(all(feature = "names", feature = "tracing"))]
+            name: meta.name,
+            #[cfg(all(feature = "holder-location", feature = "slow-warnings"))]
+            holder: meta.holder_location(),
+            #[cfg(any(
+                feature = "histogram",
+                feature = "metrics",
+                feature = "opentelemetry",
+                feature = "slow-warnings",
+                feature = "stats",
+                feature = "tracing"
+            ))]
+            start: Some(Instant::now()),
+        }
+    }
+
+    /// Ends an acquisition of the lock at `addr` which gave up waiting.
+    #[inline]
+    pub(crate) fn give_up(self, addr: usize) {
+        #[cfg(feature = "tracking")]
+        with_local(|local| local.waiting.store(0, Ordering::Relaxed));
+        #[cfg(feature = "tracing")]
+        {
+            #[cfg(feature = "names")]
+            let name = self.name;
+            #[cfg(not(feature = "names"))]
+            let name = None;
+            crate::tracing::gave_up(addr, name, self.start.map(|start| start.elapsed()));
+        }
+        acquire_failed(addr);
+    }
+}
+
+/// Called before every attempt to acquire a `Mutex` or `RwLock`.
+#[inline]
+pub(crate) fn before_acquire() {
+    #[cfg(feature = "test-util")]
+    crate::test_util::before_acquire();
+}
+
+/// Called after a failed attempt to acquire the lock at `addr`.
+#[inline]
+pub(crate) fn acquire_failed(addr: usize) {
+    #[cfg(feature = "test-util")]
+    crate::test_util::acquire_failed(addr);
+    #[cfg(not(feature = "test-util"))]
+    let _ = addr;
+}
+
+/// Records a lock as held by the current thread until dropped.
+pub(crate) struct Token {
+    #[cfg(debug_assertions)]
+    level: Level,
+    // The owner of the lock, which outlives its guards.
+    #[cfg(all(debug_assertions, not(feature = "send-guard")))]
+    owner: *const Owner,
+    // The levels of the thread which acquired the lock, if it has a level.
+    #[cfg(all(debug_assertions, feature = "send-guard"))]
+    levels: Option<Levels>,
+    // The bookkeeping of the thread which acquired the lock.
+    #[cfg(all(feature = "tracking", feature = "send-guard"))]
+    local: Option<Arc<Local>>,
+    #[cfg(all(
+        feature = "names",
+        any(feature = "metrics", feature = "slow-warnings", feature = "tracing")
+    ))]
+    name: Option<&'static str>,
+    // The location of the lock, which outlives its guards, and where it was
+    // acquired.
+    #[cfg(feature = "holder-location")]
+    holder: *const crate::holder::Site,
+    #[cfg(feature = "holder-location")]
+    location: &'static Location<'static>,
+    // The counters of the lock, which outlives its guards.
+    #[cfg(feature = "stats")]
+    stats: *const crate::stats::Counters,
+    // The thresholds of the lock, which outlives its guards.
+    #[cfg(feature = "slow-warnings")]
+    slow: *const crate::slow::Thresholds,
+    #[cfg(any(
+        feature = "tracking",
+        feature = "histogram",
+        feature = "metrics",
+        feature = "opentelemetry",
+        feature = "slow-warnings",
+        feature = "test-util",
+        feature = "tracing"
+    ))]
+    addr: usize,
+    #[cfg(any(
+        feature = "histogram",
+        feature = "metrics",
+        feature = "opentelemetry",
+        feature = "slow-warnings",
+        feature = "stats",
+        feature = "tracing"
+    ))]
+    acquired: Instant,
+    // `None` if the acquisition did not block.
+    #[cfg(any(feature = "histogram", feature = "metrics", feature = "opentelemetry"))]
+    waited: Option<Duration>,
+}
+
+// SAFETY: the only fields which are not `Send` and `Sync` point to the lock's
+// owner, location, counters and thresholds, which are atomics.
+#[cfg(any(
+    all(debug_assertions, not(feature = "send-guard")),
+    feature = "holder-location",
+    feature = "slow-warnings",
+    feature = "stats"
+))]
+unsafe impl Send for Token {}
+#[cfg(any(
+    all(debug_assertions, not(feature = "send-guard")),
+    feature = "holder-location",
+    feature = "slow-warnings",
+    feature = "stats"
+))]
+unsafe impl Sync for Token {}
+
+impl Token {
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub(crate) fn new(addr: usize, access: Access, meta: Meta, wait: Wait) -> Token {
+        #[cfg(debug_assertions)]
+        if let Some(level) = meta.level {
+            with_levels(|levels| levels.push(level));
+        }
+        #[cfg(all(debug_assertions, not(feature = "send-guard")))]
+        if access == Access::Exclusive {
+            // SAFETY: the lock outlives its guards.
+            unsafe { (*meta.owner).set(current_thread()) };
+        }
+        #[cfg(feature = "holder-location")]
+        let location = Location::caller();
+        // SAFETY: the lock outlives its guards.
+        #[cfg(feature = "holder-location")]
+        unsafe {
+            (*meta.holder).set(location)
+        };
+        #[cfg(all(debug_assertions, feature = "send-guard"))]
+        let levels = match meta.level {
+            Some(_) => LEVELS.try_with(Arc::clone).ok(),
+            None => None,
+        };
+        #[cfg(all(feature = "tracking", feature = "send-guard"))]
+        let mut holder = None;
+        #[cfg(feature = "tracking")]
+        with_local(|local| {
+            #[cfg(feature = "send-guard")]
+            {
+                holder = Some(local.clone());
+            }
+            local.waiting.store(0, Ordering::Relaxed);
+            local.locks().push(Held {
+                addr,
+                access,
+                // SAFETY: the lock outlives its guards.
+                #[cfg(feature = "cycle-detection")]
+                node: unsafe { (*meta.node).id() },
+                #[cfg(feature = "names")]
+                name: meta.name,
+                #[cfg(feature = "holder-location")]
+                location,
+            });
+        });
+        #[cfg(not(any(
+            all(debug_assertions, not(feature = "send-guard")),
+            feature = "tracking",
+            feature = "tracing"
+        )))]
+        let _ = access;
+        #[cfg(feature = "test-util")]
+        crate::test_util::acquired(addr);
+        #[cfg(any(
+            feature = "histogram",
+            feature = "metrics",
+            feature = "opentelemetry",
+            feature = "slow-warnings",
+            feature = "stats",
+            feature = "tracing"
+        ))]
+        let (acquired, waited) = {
+            let acquired = Instant::now();
+            (acquired, wait.start.map(|start| acquired - start))
+        };
+        #[cfg(feature = "tracing")]
+        crate::tracing::acquired(addr, meta.name(), access, waited);
+        // SAFETY: the lock outlives its guards, and so this token.
+        #[cfg(feature = "stats")]
+        unsafe {
+            (*meta.stats).acquired(waited)
+        };
+        // SAFETY: as above.
+        #[cfg(feature = "slow-warnings")]
+        if let Some(waited) = waited {
+            #[cfg(feature = "holder-location")]
+            let holder = wait.holder;
+            #[cfg(not(feature = "holder-location"))]
+            let holder = None;
+            unsafe { (*meta.slow).waited(addr, meta.name(), waited, holder) };
+        }
+        #[cfg(not(any(
+            debug_assertions,
+            feature = "holder-location",
+            feature = "slow-warnings",
+            feature = "stats"
+        )))]
+        let _ = meta;
+        #[cfg(not(any(
+            feature = "histogram",
+            feature = "metrics",
+            feature = "opentelemetry",
+            feature = "slow-warnings",
+            feature = "stats",
+            feature = "tracing"
+        )))]
+        let _ = wait;
+        #[cfg(not(any(
+            feature = "tracking",
+            feature = "histogram",
+            feature = "metrics",
+            feature = "opentelemetry",
+            feature = "slow-warnings",
+            feature = "test-util",
+            feature = "tracing"
+        )))]
+        let _ = addr;
+
+        Token {
+            #[cfg(debug_assertions)]
+            level: meta.level,
+            #[cfg(all(debug_assertions, not(feature = "send-guard")))]
+            owner: meta.owner,
+            #[cfg(all(debug_assertions, feature = "send-guard"))]
+            levels,
+            #[cfg(all(feature = "tracking", feature = "send-guard"))]
+            local: holder,
+            #[cfg(all(
+                feature = "names",
+                any(feature = "metrics", feature = "slow-warnings", feature = "tracing")
+            ))]
+            name: meta.name,
+            #[cfg(feature = "holder-location")]
+            holder: meta.holder,
+            #[cfg(feature = "holder-location")]
+            location,
+            #[cfg(feature = "stats")]
+            stats: meta.stats,
+            #[cfg(feature = "slow-warnings")]
+            slow: meta.slow,
+            #[cfg(any(
+                feature = "tracking",
+                feature = "histogram",
+                feature = "metrics",
+                feature = "opentelemetry",
+                feature = "slow-warnings",
+                feature = "test-util",
+                feature = "tracing"
+            ))]
+            addr,
+            #[cfg(any(
+                feature = "histogram",
+                feature = "metrics",
+                feature = "opentelemetry",
+                feature = "slow-warnings",
+                feature = "stats",
+                feature = "tracing"
+            ))]
+            acquired,
+            #[cfg(any(feature = "histogram", feature = "metrics", feature = "opentelemetry"))]
+            waited,
+        }
+    }
+
+    /// Records that the lock is now held with `access`, after a guard was
+    /// downgraded without releasing it.
+    #[inline]
+    pub(crate) fn set_access(&mut self, access: Access) {
+        #[cfg(all(debug_assertions, not(feature = "send-guard")))]
+        {
+            let owner = match access {
+                Access::Exclusive => current_thread(),
+                Access::Shared => 0,
+            };
+            // SAFETY: the lock outlives its guards.
+            unsafe { (*self.owner).set(owner) };
+        }
+        #[cfg(feature = "tracking")]
+        self.with_holder(|local| {
+            let mut locks = local.locks();
+            if let Some(held) = locks.iter_mut().rev().find(|held| held.addr == self.addr) {
+                held.access = access;
+            }
+        });
+        #[cfg(not(feature = "tracking"))]
+        let _ = access;
+    }
+
+    /// Called by a guard right before it releases its lock, so that the lock
+    /// stops recording where it was acquired before another thread can
+    /// acquire it.
+    #[inline]
+    pub(crate) fn releasing(&self) {
+        // SAFETY: the lock outlives its guards.
+        #[cfg(feature = "holder-location")]
+        unsafe {
+            (*self.holder).clear(self.location)
+        };
+    }
+
+    // Runs `f` on the bookkeeping of the thread which acquired the lock.
+    #[cfg(feature = "tracking")]
+    #[inline]
+    fn with_holder(&self, f: impl FnOnce(&Local)) {
+        #[cfg(feature = "send-guard")]
+        if let Some(local) = &self.local {
+            f(local);
+        }
+        #[cfg(not(feature = "send-guard"))]
+        with_local(|local| f(local));
+    }
+}
+
+#[cfg(any(
+    debug_assertions,
+    feature = "tracking",
+    feature = "histogram",
+    feature = "metrics",
+    feature = "opentelemetry",
+    feature = "slow-warnings",
+    feature = "stats",
+    feature = "test-util",
+    feature = "tracing"
+))]
+impl Drop for Token {
+    fn drop(&mut self) {
+        // Clearing the owner of a lock held shared is harmless, as it has
+        // none.
+        // SAFETY: the lock outlives its guards.
+        #[cfg(all(debug_assertions, not(feature = "send-guard")))]
+        unsafe {
+            (*self.owner).set(0)
+        };
+        #[cfg(debug_assertions)]
+        if let Some(level) = self.level {
+            let remove = |levels: &mut Vec<u32>| {
+                if let Some(idx) = levels.iter().rposition(|&held| held == level) {
+                    levels.remove(idx);
+                }
+            };
+            #[cfg(not(feature = "send-guard"))]
+            with_levels(remove);
+            #[cfg(feature = "send-guard")]
+            if let Some(levels) = &self.levels {
+                remove(&mut levels.lock().unwrap_or_else(|e| e.into_inner()));
+            }
+        }
+        #[cfg(any(
+            feature = "histogram",
+            feature = "metrics",
+            feature = "opentelemetry",
+            feature = "slow-warnings",
+            feature = "stats",
+            feature = "tracing"
+        ))]
+        {
+            let held = self.acquired.elapsed();
+            #[cfg(feature = "histogram")]
+            crate::histogram::record(self.addr, self.waited.unwrap_or(Duration::ZERO), held);
+            #[cfg(all(
+                feature = "names",
+                any(feature = "metrics", feature = "slow-warnings", feature = "tracing")
+            ))]
+            let name = self.name;
+            #[cfg(all(
+                not(feature = "names"),
+                any(feature = "metrics", feature = "slow-warnings", feature = "tracing")
+            ))]
+            let name = None;
+            #[cfg(feature = "metrics")]
+            crate::metrics::record(self.addr, name, self.waited, held);
+            #[cfg(feature = "opentelemetry")]
+            crate::opentelemetry::record(self.addr, self.waited, held);
+            #[cfg(feature = "tracing")]
+            crate::tracing::released(self.addr, name, held);
+            // SAFETY: as in `new`.
+            #[cfg(feature = "stats")]
+            unsafe {
+                (*self.stats).released(held)
+            };
+            #[cfg(all(feature = "holder-location", feature = "slow-warnings"))]
+            let location = Some(self.location);
+            #[cfg(all(not(feature = "holder-location"), feature = "slow-warnings"))]
+            let location = None;
+            // SAFETY: as in `new`.
+            #[cfg(feature = "slow-warnings")]
+            unsafe {
+                (*self.slow).held(self.addr, name, held, location)
+            };
+        }
+        #[cfg(feature = "tracking")]
+        self.with_holder(|local| {
+            let mut locks = local.locks();
+            if let Some(idx) = locks.iter().rposition(|held| held.addr == self.addr) {
+                locks.remove(idx);
+            }
+        });
+        // Guards release their lock before dropping the token.
+        #[cfg(feature = "test-util")]
+        crate::test_util::released(self.addr);
+    }
+}
+
+#[cfg(feature = "tracking")]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Held {
+    pub(crate) addr: usize,
+    pub(crate) access: Access,
+    #[cfg(feature = "cycle-detection")]
+    pub(crate) node: usize,
+    #[cfg(feature = "names")]
+    pub(crate) name: Option<&'static str>,
+    #[cfg(feature = "holder-location")]
+    pub(crate) location: &'static Location<'static>,
+}
+
+#[cfg(feature = "tracking")]
+struct Local {
+    thread: Thread,
+    locks: sync::Mutex<Vec<Held>>,
+    // The address of the lock the thread is blocked on, or 0.
+    waiting: AtomicUsize,
+}
+
+#[cfg(feature = "tracking")]
+impl Local {
+    fn locks(&self) -> sync::MutexGuard<'_, Vec<Held>> {
+        self.locks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(feature = "tracking")]
+static THREADS: sync::Mutex<Vec<Weak<Local>>> = sync::Mutex::new(Vec::new());
+
+#[cfg(feature = "tracking")]
+fn with_local(f: impl FnOnce(&Arc<Local>)) {
+    thread_local! {
+        static LOCAL: Arc<Local> = {
+            // Setting a hook is not allowed while panicking, in which case it
+            // is left to a later thread.
+            #[cfg(feature = "panic-hook")]
+            if !thread::panicking() {
+                crate::debug::install_panic_hook();
+            }
+            let local = Arc::new(Local {
+                thread: thread::current(),
+                locks: sync::Mutex::new(Vec::new()),
+                waiting: AtomicUsize::new(0),
+            });
+            let mut threads = THREADS.lock().unwrap_or_else(|e| e.into_inner());
+            threads.retain(|thread| thread.strong_count() != 0);
+            threads.push(Arc::downgrade(&local));
+            local
+        };
+    }
+
+    // Locks used while the thread is being torn down go untracked.
+    let _ = LOCAL.try_with(|local| f(local));
+}
+
+/// Returns every thread currently holding the lock at `addr`.
+#[cfg(feature = "tracking")]
+#[cfg_attr(not(any(feature = "profiler", feature = "registry")), allow(dead_code))]
+pub(crate) fn holders(addr: usize) -> Vec<(Thread, Access)> {
+    let threads = THREADS.lock().unwrap_or_else(|e| e.into_inner());
+    threads
+        .iter()
+        .filter_map(Weak::upgrade)
+        .filter_map(|local| {
+            let held = local
+                .locks()
+                .iter()
+                .find(|held| held.addr == addr)
+                .copied()?;
+            Some((local.thread.clone(), held.access))
+        })
+        .collect()
+}
+
+/// Returns the locks held by the current thread, in the order they were
+/// acquired.
+#[cfg(feature = "tracking")]
+pub(crate) fn current() -> Vec<Held> {
+    let mut held = vec![];
+    with_local(|local| held = local.locks().clone());
+    held
+}
+
+/// Returns every thread currently blocked acquiring the lock at `addr`.
+#[cfg(feature = "tracking")]
+pub(crate) fn waiters(addr: usize) -> Vec<Thread> {
+    let threads = THREADS.lock().unwrap_or_else(|e| e.into_inner());
+    threads
+        .iter()
+        .filter_map(Weak::upgrade)
+        .filter(|local| local.waiting.load(Ordering::Relaxed) == addr)
+        .map(|local| local.thread.clone())
+        .collect()
+}
+
+/// What a thread holds and is blocked on at one point in time.
+#[cfg(feature = "deadlock-detection")]
+pub(crate) struct Snapshot {
+    pub(crate) thread: Thread,
+    pub(crate) held: Vec<Held>,
+    // The address of the lock the thread is blocked on, or 0.
+    pub(crate) waiting: usize,
+}
+
+/// Returns what every live thread holds and is blocked on.
+#[cfg(feature = "deadlock-detection")]
+pub(crate) fn snapshot() -> Vec<Snapshot> {
+    let threads = THREADS.lock().unwrap_or_else(|e| e.into_inner());
+    threads
+        .iter()
+        .filter_map(Weak::upgrade)
+        .map(|local| Snapshot {
+            thread: local.thread.clone(),
+            held: local.locks().clone(),
+            waiting: local.waiting.load(Ordering::Relaxed),
+        })
+        .collect()
+}
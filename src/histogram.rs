@@ -0,0 +1,262 @@
+//! Histograms of the time spent waiting for and holding locks.
+//!
+//! With the `histogram` feature enabled, every acquisition of a `Mutex` or
+//! `RwLock` measures how long the thread waited for the lock, and every release
+//! how long the lock was held. The durations are aggregated, in nanoseconds,
+//! into [`Histogram`]s: one pair covering every lock, and one for each lock
+//! registered with [`register_mutex`] or [`register_rwlock`]. Unlike a mean,
+//! these report the rare long waits accurately, so high percentiles such as
+//! the p99.9 can be read from them.
+//!
+//! Each thread records into histograms of its own, which are only merged when
+//! a [`snapshot`] is taken, so recording never contends across threads.
+//! Acquisitions that did not have to wait are recorded as a wait of zero.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        self,
+        atomic::{AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    time::Duration,
+};
+
+pub use hdrhistogram::Histogram;
+
+use crate::{Mutex, RwLock};
+
+static REGISTRY: sync::Mutex<Vec<(&'static str, usize)>> = sync::Mutex::new(Vec::new());
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+static STATE: sync::Mutex<State> = sync::Mutex::new(State {
+    threads: Vec::new(),
+    retired: None,
+});
+
+struct State {
+    threads: Vec<Weak<sync::Mutex<Recorder>>>,
+    // What threads which have since exited recorded.
+    retired: Option<Recorder>,
+}
+
+fn lock<T>(mutex: &sync::Mutex<T>) -> sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn register(name: &'static str, addr: usize) {
+    lock(&REGISTRY).push((name, addr));
+    GENERATION.fetch_add(1, Ordering::Release);
+}
+
+/// Registers a `Mutex` to get histograms of its own under the given name.
+///
+/// Only acquisitions made after registration are included in them.
+pub fn register_mutex<T: ?Sized>(name: &'static str, mutex: &'static Mutex<T>) {
+    register(name, mutex.addr());
+}
+
+/// Registers an `RwLock` to get histograms of its own under the given name.
+///
+/// Only acquisitions made after registration are included in them. Shared and
+/// exclusive acquisitions are recorded together.
+pub fn register_rwlock<T: ?Sized>(name: &'static str, rwlock: &'static RwLock<T>) {
+    register(name, rwlock.addr());
+}
+
+/// Returns the histograms recorded so far by every thread.
+pub fn snapshot() -> Snapshot {
+    let registry = lock(&REGISTRY).clone();
+    let state = lock(&STATE);
+    let recorders = state
+        .threads
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect::<Vec<_>>();
+    let recorders = recorders
+        .iter()
+        .map(|recorder| lock(recorder))
+        .collect::<Vec<_>>();
+    let recorders = || {
+        recorders
+            .iter()
+            .map(|recorder| &**recorder)
+            .chain(&state.retired)
+    };
+
+    let mut all = Latencies::new();
+    for recorder in recorders() {
+        all.add(&recorder.all);
+    }
+    let locks = registry
+        .into_iter()
+        .map(|(name, addr)| {
+            let mut latencies = Latencies::new();
+            for recorder in recorders() {
+                if let Some(recorded) = recorder.locks.get(&addr) {
+                    latencies.add(recorded);
+                }
+            }
+            (name, latencies)
+        })
+        .collect();
+
+    Snapshot { all, locks }
+}
+
+/// Discards everything recorded so far.
+pub fn reset() {
+    let mut state = lock(&STATE);
+    for recorder in state.threads.iter().filter_map(Weak::upgrade) {
+        lock(&recorder).clear();
+    }
+    state.retired = None;
+}
+
+/// Histograms of the time spent waiting for and holding locks, in nanoseconds.
+#[derive(Debug, Clone)]
+pub struct Latencies {
+    wait: Histogram<u64>,
+    hold: Histogram<u64>,
+}
+
+impl Latencies {
+    fn new() -> Latencies {
+        Latencies {
+            wait: Histogram::new(3).expect("valid precision"),
+            hold: Histogram::new(3).expect("valid precision"),
+        }
+    }
+
+    /// Returns the histogram of the time spent waiting to acquire a lock.
+    pub fn wait(&self) -> &Histogram<u64> {
+        &self.wait
+    }
+
+    /// Returns the histogram of the time a lock was held once acquired.
+    pub fn hold(&self) -> &Histogram<u64> {
+        &self.hold
+    }
+
+    // The histograms resize themselves as needed, so neither recording nor
+    // adding can fail.
+    fn record(&mut self, wait: Duration, hold: Duration) {
+        let _ = self.wait.record(nanos(wait));
+        let _ = self.hold.record(nanos(hold));
+    }
+
+    fn add(&mut self, other: &Latencies) {
+        let _ = self.wait.add(&other.wait);
+        let _ = self.hold.add(&other.hold);
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// The histograms returned by [`snapshot`].
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    all: Latencies,
+    locks: Vec<(&'static str, Latencies)>,
+}
+
+impl Snapshot {
+    /// Returns the histograms covering every lock.
+    pub fn all(&self) -> &Latencies {
+        &self.all
+    }
+
+    /// Returns the histograms of each registered lock along with its name, in
+    /// registration order.
+    pub fn locks(&self) -> &[(&'static str, Latencies)] {
+        &self.locks
+    }
+
+    /// Returns the histograms of the lock registered under `name`.
+    pub fn lock(&self, name: &str) -> Option<&Latencies> {
+        self.locks
+            .iter()
+            .find(|(registered, _)| *registered == name)
+            .map(|(_, latencies)| latencies)
+    }
+}
+
+struct Recorder {
+    all: Latencies,
+    locks: HashMap<usize, Latencies>,
+    generation: usize,
+    registered: HashSet<usize>,
+}
+
+impl Recorder {
+    fn new() -> Recorder {
+        Recorder {
+            all: Latencies::new(),
+            locks: HashMap::new(),
+            generation: 0,
+            registered: HashSet::new(),
+        }
+    }
+
+    fn record(&mut self, addr: usize, wait: Duration, hold: Duration) {
+        self.all.record(wait, hold);
+
+        let generation = GENERATION.load(Ordering::Acquire);
+        if generation != self.generation {
+            self.registered = lock(&REGISTRY).iter().map(|&(_, addr)| addr).collect();
+            self.generation = generation;
+        }
+        if self.registered.contains(&addr) {
+            self.locks
+                .entry(addr)
+                .or_insert_with(Latencies::new)
+                .record(wait, hold);
+        }
+    }
+
+    fn add(&mut self, other: &Recorder) {
+        self.all.add(&other.all);
+        for (addr, latencies) in &other.locks {
+            self.locks
+                .entry(*addr)
+                .or_insert_with(Latencies::new)
+                .add(latencies);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.all = Latencies::new();
+        self.locks.clear();
+    }
+}
+
+struct Local(Arc<sync::Mutex<Recorder>>);
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        let mut state = lock(&STATE);
+        let recorder = lock(&self.0);
+        state
+            .retired
+            .get_or_insert_with(Recorder::new)
+            .add(&recorder);
+        drop(recorder);
+        // Taking a snapshot must not count the recorder twice.
+        let this = Arc::downgrade(&self.0);
+        state.threads.retain(|thread| !thread.ptr_eq(&this));
+    }
+}
+
+pub(crate) fn record(addr: usize, wait: Duration, hold: Duration) {
+    thread_local! {
+        static LOCAL: Local = {
+            let recorder = Arc::new(sync::Mutex::new(Recorder::new()));
+            lock(&STATE).threads.push(Arc::downgrade(&recorder));
+            Local(recorder)
+        };
+    }
+
+    // Locks used while the thread is being torn down go unrecorded.
+    let _ = LOCAL.try_with(|local| lock(&local.0).record(addr, wait, hold));
+}
@@ -0,0 +1,116 @@
+//! Where the holder of each lock acquired it.
+//!
+//! With the `holder-location` feature enabled, every acquisition of a `Mutex`
+//! or `RwLock` records the source location of the call which acquired the
+//! lock, until its guard is dropped. [`Mutex::holder_location`] and
+//! [`RwLock::holder_location`] return it, and the crate's diagnostics report
+//! it wherever they mention a held lock:
+//!
+//! * a [`TryLockError`](crate::TryLockError) tells where the lock it could not
+//!   acquire was acquired, in
+//!   [`holder_location`](crate::TryLockError::holder_location) and in its
+//!   message;
+//! * the locks listed by the [`debug`](crate::debug) and
+//!   [`deadlock`](crate::deadlock) modules tell where they were acquired, in
+//!   [`LockInfo::location`](crate::debug::LockInfo::location);
+//! * with the `slow-warnings` feature, a slow acquisition warning tells where
+//!   the lock it waited for was acquired, and a long hold warning where the
+//!   guard held too long was created, in `lock.holder` with the `tracing`
+//!   feature.
+//!
+//! The location is that of the call into this crate, such as
+//! [`Mutex::lock`], so a lock acquired by a helper of the program's own
+//! reports the helper unless it is itself `#[track_caller]`. For an `RwLock`
+//! held shared by several threads, the location is that of the most recent
+//! acquisition, and it is cleared once that reader releases the lock even if
+//! others still hold it.
+//!
+//! Recording the location makes every acquisition and release somewhat more
+//! expensive, and every lock 8 bytes larger.
+
+use std::{
+    panic::Location,
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use crate::{
+    poison::PoisonPolicy,
+    raw::{MutexStorage, RwLockBackend},
+    Mutex, RwLock,
+};
+
+/// The location kept by each lock.
+pub(crate) struct Site {
+    // Null while the lock is free.
+    location: AtomicPtr<Location<'static>>,
+}
+
+impl Site {
+    pub(crate) const fn new() -> Site {
+        Site {
+            location: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns where the lock was acquired, if it is held.
+    #[inline]
+    pub(crate) fn get(&self) -> Option<&'static Location<'static>> {
+        let location = self.location.load(Ordering::Relaxed);
+        // SAFETY: the pointer is null or comes from a `&'static Location`.
+        unsafe { location.as_ref() }
+    }
+
+    /// Records that the lock was acquired at `location`.
+    #[inline]
+    pub(crate) fn set(&self, location: &'static Location<'static>) {
+        self.location.store(as_ptr(location), Ordering::Relaxed);
+    }
+
+    /// Records that the guard acquired at `location` released the lock,
+    /// unless another acquisition replaced it since.
+    #[inline]
+    pub(crate) fn clear(&self, location: &'static Location<'static>) {
+        let _ = self.location.compare_exchange(
+            as_ptr(location),
+            ptr::null_mut(),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+    }
+}
+
+impl Default for Site {
+    #[inline]
+    fn default() -> Site {
+        Site::new()
+    }
+}
+
+fn as_ptr(location: &'static Location<'static>) -> *mut Location<'static> {
+    location as *const Location<'static> as *mut Location<'static>
+}
+
+impl<T: ?Sized, P: PoisonPolicy, R: MutexStorage> Mutex<T, P, R> {
+    /// Returns the source location at which the current holder of the mutex
+    /// acquired it, or `None` if it is free.
+    ///
+    /// See the [`holder`](crate::holder) module.
+    #[cfg_attr(docsrs, doc(cfg(feature = "holder-location")))]
+    #[inline]
+    pub fn holder_location(&self) -> Option<&'static Location<'static>> {
+        self.instrumentation.holder.get()
+    }
+}
+
+impl<T: ?Sized, R: RwLockBackend> RwLock<T, R> {
+    /// Returns the source location at which the most recent holder of the lock
+    /// acquired it, or `None` if it is free.
+    ///
+    /// See the [`holder`](crate::holder) module.
+    #[cfg_attr(docsrs, doc(cfg(feature = "holder-location")))]
+    #[inline]
+    pub fn holder_location(&self) -> Option<&'static Location<'static>> {
+        self.instrumentation.holder.get()
+    }
+}
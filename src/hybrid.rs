@@ -0,0 +1,323 @@
+use std::{
+    cell::UnsafeCell,
+    fmt, hint,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    panic::{RefUnwindSafe, UnwindSafe},
+    sync::atomic::{AtomicU32, AtomicU8, Ordering},
+};
+
+use crate::{
+    parking::{self, DEFAULT_TOKEN},
+    Lock, LockOperation, TryLockError, TryLockResult, Unlock,
+};
+
+const LOCKED: u8 = 1;
+const PARKED: u8 = 2;
+
+// The most rounds a waiter spins for before parking, however long spinning
+// used to take.
+const MAX_SPINS: u32 = 100;
+// Rounds allowed on top of twice the estimate, so that a mutex whose estimate
+// dropped to zero still tries spinning.
+const MIN_SPINS: u32 = 8;
+// The longest round, as a power of two of `spin_loop` hints.
+const MAX_BACKOFF_SHIFT: u32 = 6;
+
+/// A mutex which spins before parking, for as long as spinning has recently
+/// paid off.
+///
+/// A thread finding the mutex locked retries with exponential backoff for a
+/// while before it goes to sleep. How long it is willing to spin adapts to how
+/// long the mutex was held by recent contended acquisitions: while spinning
+/// keeps succeeding quickly it stays cheap, and once waiters keep having to
+/// park anyway, they give up spinning sooner. This suits critical sections of
+/// well under a microsecond, such as hot counters, where going to sleep costs
+/// much more than the wait itself.
+///
+/// Unlike [`Mutex`](crate::Mutex), the spinning does not depend on
+/// [`Config::set_spin_iterations`](crate::Config::set_spin_iterations). Like
+/// [`RawMutex`](crate::RawMutex), the lock is not seen by the `tracking`,
+/// `histogram` or `opentelemetry` instrumentation, and it can be used with
+/// [`CondvarAny`](crate::CondvarAny) but not [`Condvar`](crate::Condvar).
+pub struct HybridMutex<T: ?Sized> {
+    state: AtomicU8,
+    // A moving average of the rounds contended acquisitions spun for.
+    spins: AtomicU32,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for HybridMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for HybridMutex<T> {}
+
+impl<T: ?Sized> UnwindSafe for HybridMutex<T> {}
+impl<T: ?Sized> RefUnwindSafe for HybridMutex<T> {}
+
+impl<T> HybridMutex<T> {
+    /// Creates a new mutex in an unlocked state.
+    #[inline]
+    pub const fn new(t: T) -> HybridMutex<T> {
+        HybridMutex {
+            state: AtomicU8::new(0),
+            spins: AtomicU32::new(0),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Consumes the mutex, returning the underlying data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> HybridMutex<T> {
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so.
+    ///
+    /// Locking the mutex again on the thread which holds it deadlocks.
+    #[inline]
+    pub fn lock(&self) -> HybridMutexGuard<'_, T> {
+        if self
+            .state
+            .compare_exchange_weak(0, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            self.lock_contended();
+        }
+        HybridMutexGuard::new(self)
+    }
+
+    #[cold]
+    fn lock_contended(&self) {
+        let estimate = self.spins.load(Ordering::Relaxed);
+        let limit = MAX_SPINS.min(estimate * 2 + MIN_SPINS);
+        let mut round = 0;
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & LOCKED == 0 {
+                match self.state.compare_exchange_weak(
+                    state,
+                    state | LOCKED,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => break,
+                    Err(actual) => state = actual,
+                }
+                continue;
+            }
+
+            if round < limit {
+                for _ in 0..1u32 << round.min(MAX_BACKOFF_SHIFT) {
+                    hint::spin_loop();
+                }
+                round += 1;
+                state = self.state.load(Ordering::Relaxed);
+                continue;
+            }
+
+            if state & PARKED == 0 {
+                if let Err(actual) = self.state.compare_exchange_weak(
+                    state,
+                    state | PARKED,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    state = actual;
+                    continue;
+                }
+            }
+
+            parking::park(
+                self.key(),
+                || self.state.load(Ordering::Relaxed) == LOCKED | PARKED,
+                || {},
+                |_, _| {},
+                None,
+            );
+            // Spinning did not pay off, so count the wait as the longest
+            // allowed, as the limit is never exceeded otherwise.
+            round = MAX_SPINS;
+            state = self.state.load(Ordering::Relaxed);
+        }
+
+        // Moves the estimate an eighth of the way towards this acquisition.
+        let estimate = estimate as i32;
+        let update = estimate + (round as i32 - estimate) / 8;
+        self.spins.store(update as u32, Ordering::Relaxed);
+    }
+
+    /// Attempts to acquire the mutex without blocking.
+    #[inline]
+    pub fn try_lock(&self) -> TryLockResult<HybridMutexGuard<'_, T>> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & LOCKED != 0 {
+                return Err(TryLockError::new(LockOperation::Lock));
+            }
+            match self.state.compare_exchange_weak(
+                state,
+                state | LOCKED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Ok(HybridMutexGuard::new(self)),
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    // SAFETY: the mutex must be held by the caller.
+    #[inline]
+    unsafe fn unlock(&self) {
+        if self
+            .state
+            .compare_exchange(LOCKED, 0, Ordering::Release, Ordering::Relaxed)
+            .is_err()
+        {
+            self.unlock_contended();
+        }
+    }
+
+    #[cold]
+    fn unlock_contended(&self) {
+        parking::unpark_one(self.key(), |result| {
+            let state = if result.have_more { PARKED } else { 0 };
+            self.state.store(state, Ordering::Release);
+            DEFAULT_TOKEN
+        });
+    }
+
+    /// Returns `true` if the mutex is currently locked.
+    ///
+    /// The answer may be stale as soon as it is returned.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & LOCKED != 0
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// No locking is necessary since the borrow guarantees exclusive access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    #[inline]
+    fn key(&self) -> usize {
+        &self.state as *const AtomicU8 as usize
+    }
+}
+
+impl<T> From<T> for HybridMutex<T> {
+    #[inline]
+    fn from(t: T) -> HybridMutex<T> {
+        HybridMutex::new(t)
+    }
+}
+
+impl<T: Default> Default for HybridMutex<T> {
+    #[inline]
+    fn default() -> HybridMutex<T> {
+        HybridMutex::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for HybridMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_struct("HybridMutex");
+        match self.try_lock() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(_) => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+impl<T: ?Sized> Lock<T> for HybridMutex<T> {
+    type Guard<'a>
+        = HybridMutexGuard<'a, T>
+    where
+        T: 'a;
+
+    #[inline]
+    fn lock(&self) -> HybridMutexGuard<'_, T> {
+        self.lock()
+    }
+
+    #[inline]
+    fn try_lock(&self) -> TryLockResult<HybridMutexGuard<'_, T>> {
+        self.try_lock()
+    }
+
+    #[inline]
+    fn get_mut(&mut self) -> &mut T {
+        self.get_mut()
+    }
+}
+
+/// A guard for a [`HybridMutex`].
+///
+/// Like [`FairMutexGuard`](crate::FairMutexGuard), it can be sent to another
+/// thread.
+#[must_use]
+pub struct HybridMutexGuard<'a, T: ?Sized + 'a> {
+    mutex: &'a HybridMutex<T>,
+    // Keeps the guard from being `Sync` for a `T` which is only `Send`.
+    _marker: PhantomData<*const ()>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for HybridMutexGuard<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for HybridMutexGuard<'_, T> {}
+
+impl<'a, T: ?Sized> HybridMutexGuard<'a, T> {
+    #[inline]
+    fn new(mutex: &'a HybridMutex<T>) -> HybridMutexGuard<'a, T> {
+        HybridMutexGuard {
+            mutex,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Unlock for HybridMutexGuard<'_, T> {
+    #[inline]
+    fn unlocked<U>(&mut self, f: impl FnOnce() -> U) -> U {
+        let mutex = self.mutex;
+        crate::unlocked(self, || mutex.lock(), f)
+    }
+}
+
+impl<T: ?Sized> Drop for HybridMutexGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: the guard holds the mutex.
+        unsafe { self.mutex.unlock() };
+    }
+}
+
+impl<T: ?Sized> Deref for HybridMutexGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the mutex.
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for HybridMutexGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the mutex.
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for HybridMutexGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
@@ -0,0 +1,799 @@
+//! Locks and notifications shared between processes.
+//!
+//! The types in this module live in memory shared between processes, such as
+//! a `MAP_SHARED` mapping of a file or of a POSIX shared memory object. They
+//! are backed by pthread objects initialized as `PTHREAD_PROCESS_SHARED`,
+//! which must not be moved once initialized, so they are never handled by
+//! value: one process initializes each of them in place with `init`, after
+//! which every process with the memory mapped accesses it through `from_ptr`.
+//!
+//! The protected value is read and written by several processes, so it must
+//! not contain pointers, references or anything else only meaningful within a
+//! single process. Plain old data such as integers, arrays and `#[repr(C)]`
+//! structs of them is fine.
+//!
+//! A process which exits while holding an [`RwLock`] leaves it held, so the
+//! other processes will block on it forever. On Linux, a [`SharedMutex`] is
+//! robust instead: when its holder dies, the next process to lock it acquires
+//! it and is told so, like a mutex which does not poison itself. Elsewhere, it
+//! is left held like an `RwLock`.
+
+use std::{
+    cell::UnsafeCell,
+    fmt, io,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    panic::{RefUnwindSafe, UnwindSafe},
+    ptr,
+    time::Duration,
+};
+
+use crate::{LockOperation, TryLockError, TryLockResult, WaitTimeoutResult};
+
+#[track_caller]
+fn check(ret: libc::c_int, function: &str) {
+    if ret != 0 {
+        fail(ret, function);
+    }
+}
+
+#[cold]
+#[track_caller]
+fn fail(ret: libc::c_int, function: &str) -> ! {
+    panic!("{} failed: {}", function, io::Error::from_raw_os_error(ret));
+}
+
+/// A reader-writer lock which can be shared between processes.
+///
+/// Readers in different processes hold the lock concurrently, so state which
+/// is mostly read, such as a cache or a lookup table, does not need an
+/// exclusive lock for every read.
+#[repr(C)]
+pub struct RwLock<T> {
+    lock: UnsafeCell<libc::pthread_rwlock_t>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+impl<T> UnwindSafe for RwLock<T> {}
+impl<T> RefUnwindSafe for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    /// Initializes a lock protecting `t` in place at `place`, returning a
+    /// reference to it.
+    ///
+    /// # Safety
+    ///
+    /// `place` must be valid for writes and suitably aligned for a
+    /// `RwLock<T>`, and must stay mapped for `'a`. No other process may use the
+    /// lock until this returns, and it must not be initialized again while any
+    /// process still uses it.
+    pub unsafe fn init<'a>(place: *mut RwLock<T>, t: T) -> &'a RwLock<T> {
+        let mut attr = MaybeUninit::<libc::pthread_rwlockattr_t>::uninit();
+        check(
+            libc::pthread_rwlockattr_init(attr.as_mut_ptr()),
+            "pthread_rwlockattr_init",
+        );
+        let ret =
+            libc::pthread_rwlockattr_setpshared(attr.as_mut_ptr(), libc::PTHREAD_PROCESS_SHARED);
+        if ret == 0 {
+            let lock = ptr::addr_of_mut!((*place).lock) as *mut libc::pthread_rwlock_t;
+            let ret = libc::pthread_rwlock_init(lock, attr.as_ptr());
+            libc::pthread_rwlockattr_destroy(attr.as_mut_ptr());
+            check(ret, "pthread_rwlock_init");
+        } else {
+            libc::pthread_rwlockattr_destroy(attr.as_mut_ptr());
+            check(ret, "pthread_rwlockattr_setpshared");
+        }
+        ptr::write(ptr::addr_of_mut!((*place).data) as *mut T, t);
+        &*place
+    }
+
+    /// Returns a reference to a lock initialized by [`init`](RwLock::init),
+    /// possibly in another process.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a lock which has been initialized and not
+    /// destroyed, and must stay mapped for `'a`.
+    #[inline]
+    pub unsafe fn from_ptr<'a>(ptr: *const RwLock<T>) -> &'a RwLock<T> {
+        &*ptr
+    }
+
+    /// Destroys the lock at `place`, dropping the protected value.
+    ///
+    /// # Safety
+    ///
+    /// `place` must point to a lock which has been initialized and not
+    /// destroyed, and which no process will use again.
+    pub unsafe fn destroy(place: *mut RwLock<T>) {
+        let lock = ptr::addr_of_mut!((*place).lock) as *mut libc::pthread_rwlock_t;
+        libc::pthread_rwlock_destroy(lock);
+        ptr::drop_in_place(ptr::addr_of_mut!((*place).data) as *mut T);
+    }
+
+    /// Acquires shared access, blocking the current thread until it is able to
+    /// do so.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread already holds the lock exclusively, or if
+    /// the maximum number of readers has been reached.
+    #[inline]
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        check(
+            unsafe { libc::pthread_rwlock_rdlock(self.lock.get()) },
+            "pthread_rwlock_rdlock",
+        );
+        RwLockReadGuard::new(self)
+    }
+
+    /// Attempts to acquire shared access without blocking.
+    #[inline]
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        match unsafe { libc::pthread_rwlock_tryrdlock(self.lock.get()) } {
+            0 => Ok(RwLockReadGuard::new(self)),
+            libc::EBUSY | libc::EAGAIN => Err(TryLockError::new(LockOperation::Read)),
+            ret => fail(ret, "pthread_rwlock_tryrdlock"),
+        }
+    }
+
+    /// Acquires exclusive access, blocking the current thread until it is able
+    /// to do so.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread already holds the lock.
+    #[inline]
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        check(
+            unsafe { libc::pthread_rwlock_wrlock(self.lock.get()) },
+            "pthread_rwlock_wrlock",
+        );
+        RwLockWriteGuard::new(self)
+    }
+
+    /// Attempts to acquire exclusive access without blocking.
+    #[inline]
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        match unsafe { libc::pthread_rwlock_trywrlock(self.lock.get()) } {
+            0 => Ok(RwLockWriteGuard::new(self)),
+            libc::EBUSY => Err(TryLockError::new(LockOperation::Write)),
+            ret => fail(ret, "pthread_rwlock_trywrlock"),
+        }
+    }
+
+    #[inline]
+    fn unlock(&self) {
+        unsafe { libc::pthread_rwlock_unlock(self.lock.get()) };
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_struct("RwLock");
+        match self.try_read() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(_) => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+/// A guard granting shared access to an [`RwLock`].
+///
+/// The lock must be released by the thread which acquired it, so the guard can
+/// never be sent to another thread.
+#[must_use]
+pub struct RwLockReadGuard<'a, T> {
+    lock: &'a RwLock<T>,
+    _not_send: PhantomData<*const ()>,
+}
+
+unsafe impl<T: Sync> Sync for RwLockReadGuard<'_, T> {}
+
+impl<'a, T> RwLockReadGuard<'a, T> {
+    #[inline]
+    fn new(lock: &'a RwLock<T>) -> RwLockReadGuard<'a, T> {
+        RwLockReadGuard {
+            lock,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<T> Drop for RwLockReadGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.unlock();
+    }
+}
+
+impl<T> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
+
+/// A guard granting exclusive access to an [`RwLock`].
+///
+/// The lock must be released by the thread which acquired it, so the guard can
+/// never be sent to another thread.
+#[must_use]
+pub struct RwLockWriteGuard<'a, T> {
+    lock: &'a RwLock<T>,
+    _not_send: PhantomData<*const ()>,
+}
+
+unsafe impl<T: Sync> Sync for RwLockWriteGuard<'_, T> {}
+
+impl<'a, T> RwLockWriteGuard<'a, T> {
+    #[inline]
+    fn new(lock: &'a RwLock<T>) -> RwLockWriteGuard<'a, T> {
+        RwLockWriteGuard {
+            lock,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<T> Drop for RwLockWriteGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.unlock();
+    }
+}
+
+impl<T> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
+
+/// A mutex which can be shared between processes.
+///
+/// On Linux, the mutex is robust: if a process dies while holding it, the next
+/// thread to lock it acquires it as usual, and
+/// [`SharedMutexGuard::owner_died`] reports it. The data is left as the dead
+/// process left it, so this follows this crate's approach to poisoning, except
+/// that the guard says so. Together with [`SharedCondvar`], it lets processes
+/// block until state in shared memory changes.
+#[repr(C)]
+pub struct SharedMutex<T> {
+    mutex: UnsafeCell<libc::pthread_mutex_t>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for SharedMutex<T> {}
+unsafe impl<T: Send> Sync for SharedMutex<T> {}
+
+impl<T> UnwindSafe for SharedMutex<T> {}
+impl<T> RefUnwindSafe for SharedMutex<T> {}
+
+impl<T> SharedMutex<T> {
+    /// Initializes a mutex protecting `t` in place at `place`, returning a
+    /// reference to it.
+    ///
+    /// # Safety
+    ///
+    /// `place` must be valid for writes and suitably aligned for a
+    /// `SharedMutex<T>`, and must stay mapped for `'a`. No other process may
+    /// use the mutex until this returns, and it must not be initialized again
+    /// while any process still uses it.
+    pub unsafe fn init<'a>(place: *mut SharedMutex<T>, t: T) -> &'a SharedMutex<T> {
+        let mut attr = MaybeUninit::<libc::pthread_mutexattr_t>::uninit();
+        check(
+            libc::pthread_mutexattr_init(attr.as_mut_ptr()),
+            "pthread_mutexattr_init",
+        );
+        let mut ret =
+            libc::pthread_mutexattr_setpshared(attr.as_mut_ptr(), libc::PTHREAD_PROCESS_SHARED);
+        // Relocking from the holding thread fails instead of deadlocking.
+        if ret == 0 {
+            ret =
+                libc::pthread_mutexattr_settype(attr.as_mut_ptr(), libc::PTHREAD_MUTEX_ERRORCHECK);
+        }
+        #[cfg(target_os = "linux")]
+        if ret == 0 {
+            ret = libc::pthread_mutexattr_setrobust(attr.as_mut_ptr(), libc::PTHREAD_MUTEX_ROBUST);
+        }
+        if ret == 0 {
+            let mutex = ptr::addr_of_mut!((*place).mutex) as *mut libc::pthread_mutex_t;
+            ret = libc::pthread_mutex_init(mutex, attr.as_ptr());
+        }
+        libc::pthread_mutexattr_destroy(attr.as_mut_ptr());
+        check(ret, "pthread_mutex_init");
+        ptr::write(ptr::addr_of_mut!((*place).data) as *mut T, t);
+        &*place
+    }
+
+    /// Returns a reference to a mutex initialized by
+    /// [`init`](SharedMutex::init), possibly in another process.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a mutex which has been initialized and not
+    /// destroyed, and must stay mapped for `'a`.
+    #[inline]
+    pub unsafe fn from_ptr<'a>(ptr: *const SharedMutex<T>) -> &'a SharedMutex<T> {
+        &*ptr
+    }
+
+    /// Destroys the mutex at `place`, dropping the protected value.
+    ///
+    /// # Safety
+    ///
+    /// `place` must point to a mutex which has been initialized and not
+    /// destroyed, and which no process will use again.
+    pub unsafe fn destroy(place: *mut SharedMutex<T>) {
+        let mutex = ptr::addr_of_mut!((*place).mutex) as *mut libc::pthread_mutex_t;
+        libc::pthread_mutex_destroy(mutex);
+        ptr::drop_in_place(ptr::addr_of_mut!((*place).data) as *mut T);
+    }
+
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread already holds the mutex.
+    #[inline]
+    pub fn lock(&self) -> SharedMutexGuard<'_, T> {
+        let ret = unsafe { libc::pthread_mutex_lock(self.mutex.get()) };
+        let owner_died = self.acquired(ret, "pthread_mutex_lock");
+        SharedMutexGuard::new(self, owner_died)
+    }
+
+    /// Attempts to acquire the mutex without blocking.
+    #[inline]
+    pub fn try_lock(&self) -> TryLockResult<SharedMutexGuard<'_, T>> {
+        match unsafe { libc::pthread_mutex_trylock(self.mutex.get()) } {
+            libc::EBUSY => Err(TryLockError::new(LockOperation::Lock)),
+            ret => {
+                let owner_died = self.acquired(ret, "pthread_mutex_trylock");
+                Ok(SharedMutexGuard::new(self, owner_died))
+            }
+        }
+    }
+
+    // Checks the result of a call which acquires the mutex, returning whether
+    // the previous holder died while holding it.
+    #[inline]
+    #[track_caller]
+    fn acquired(&self, ret: libc::c_int, function: &str) -> bool {
+        match ret {
+            0 => false,
+            #[cfg(target_os = "linux")]
+            libc::EOWNERDEAD => {
+                check(
+                    unsafe { libc::pthread_mutex_consistent(self.mutex.get()) },
+                    "pthread_mutex_consistent",
+                );
+                true
+            }
+            ret => fail(ret, function),
+        }
+    }
+
+    #[inline]
+    fn unlock(&self) {
+        unsafe { libc::pthread_mutex_unlock(self.mutex.get()) };
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SharedMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_struct("SharedMutex");
+        match self.try_lock() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(_) => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+/// A guard for a [`SharedMutex`].
+///
+/// The mutex must be released by the thread which acquired it, so the guard
+/// can never be sent to another thread.
+#[must_use]
+pub struct SharedMutexGuard<'a, T> {
+    mutex: &'a SharedMutex<T>,
+    owner_died: bool,
+    _not_send: PhantomData<*const ()>,
+}
+
+unsafe impl<T: Sync> Sync for SharedMutexGuard<'_, T> {}
+
+impl<'a, T> SharedMutexGuard<'a, T> {
+    #[inline]
+    fn new(mutex: &'a SharedMutex<T>, owner_died: bool) -> SharedMutexGuard<'a, T> {
+        SharedMutexGuard {
+            mutex,
+            owner_died,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Returns `true` if the mutex had been left held by a process or thread
+    /// which died, and was recovered when the guard acquired it, or when a
+    /// [`SharedCondvar`] wait reacquired it.
+    ///
+    /// The data may then have been left half updated. This is an associated
+    /// function so that it does not shadow a method of `T`.
+    #[inline]
+    pub fn owner_died(s: &Self) -> bool {
+        s.owner_died
+    }
+}
+
+impl<T> Drop for SharedMutexGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+impl<T> Deref for SharedMutexGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for SharedMutexGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SharedMutexGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
+
+/// A condition variable which can be shared between processes, for use with a
+/// [`SharedMutex`].
+///
+/// Like the crate's [`Condvar`](crate::Condvar), its waits are subject to
+/// spurious wakeups, so the condition must be checked again in a loop.
+#[repr(C)]
+pub struct SharedCondvar {
+    cond: UnsafeCell<libc::pthread_cond_t>,
+}
+
+unsafe impl Send for SharedCondvar {}
+unsafe impl Sync for SharedCondvar {}
+
+impl SharedCondvar {
+    /// Initializes a condition variable in place at `place`, returning a
+    /// reference to it.
+    ///
+    /// # Safety
+    ///
+    /// `place` must be valid for writes and suitably aligned for a
+    /// `SharedCondvar`, and must stay mapped for `'a`. No other process may use
+    /// the condition variable until this returns, and it must not be
+    /// initialized again while any process still uses it.
+    pub unsafe fn init<'a>(place: *mut SharedCondvar) -> &'a SharedCondvar {
+        init_cond(ptr::addr_of_mut!((*place).cond) as *mut libc::pthread_cond_t);
+        &*place
+    }
+
+    /// Returns a reference to a condition variable initialized by
+    /// [`init`](SharedCondvar::init), possibly in another process.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a condition variable which has been initialized and
+    /// not destroyed, and must stay mapped for `'a`.
+    #[inline]
+    pub unsafe fn from_ptr<'a>(ptr: *const SharedCondvar) -> &'a SharedCondvar {
+        &*ptr
+    }
+
+    /// Destroys the condition variable at `place`.
+    ///
+    /// # Safety
+    ///
+    /// `place` must point to a condition variable which has been initialized
+    /// and not destroyed, and which no process will use again.
+    pub unsafe fn destroy(place: *mut SharedCondvar) {
+        libc::pthread_cond_destroy(ptr::addr_of_mut!((*place).cond) as *mut libc::pthread_cond_t);
+    }
+
+    /// Blocks the current thread until this condition variable receives a
+    /// notification.
+    ///
+    /// The mutex is released while the thread waits and reacquired before this
+    /// returns. Every thread waiting on the condition variable at the same
+    /// time must use the same mutex.
+    pub fn wait<'a, T>(&self, mut guard: SharedMutexGuard<'a, T>) -> SharedMutexGuard<'a, T> {
+        let mutex = guard.mutex;
+        let ret = unsafe { libc::pthread_cond_wait(self.cond.get(), mutex.mutex.get()) };
+        guard.owner_died |= mutex.acquired(ret, "pthread_cond_wait");
+        guard
+    }
+
+    /// Like [`wait`](SharedCondvar::wait), but gives up once `timeout` has
+    /// elapsed.
+    pub fn wait_timeout<'a, T>(
+        &self,
+        mut guard: SharedMutexGuard<'a, T>,
+        timeout: Duration,
+    ) -> (SharedMutexGuard<'a, T>, WaitTimeoutResult) {
+        let deadline = deadline(timeout);
+        let mutex = guard.mutex;
+        let ret =
+            unsafe { libc::pthread_cond_timedwait(self.cond.get(), mutex.mutex.get(), &deadline) };
+        let timed_out = ret == libc::ETIMEDOUT;
+        if !timed_out {
+            guard.owner_died |= mutex.acquired(ret, "pthread_cond_timedwait");
+        }
+        (guard, WaitTimeoutResult(timed_out))
+    }
+
+    /// Wakes up one thread blocked on this condition variable, in any process.
+    #[inline]
+    pub fn notify_one(&self) {
+        check(
+            unsafe { libc::pthread_cond_signal(self.cond.get()) },
+            "pthread_cond_signal",
+        );
+    }
+
+    /// Wakes up every thread blocked on this condition variable, in any
+    /// process.
+    #[inline]
+    pub fn notify_all(&self) {
+        check(
+            unsafe { libc::pthread_cond_broadcast(self.cond.get()) },
+            "pthread_cond_broadcast",
+        );
+    }
+}
+
+impl fmt::Debug for SharedCondvar {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("SharedCondvar").finish_non_exhaustive()
+    }
+}
+
+/// A notification which processes can block on until it is set.
+///
+/// The event is either set or unset. Setting it wakes every thread waiting for
+/// it, in any process, and it stays set until [`reset`](Event::reset) is
+/// called, so a waiter which only starts waiting after the event was set does
+/// not miss it. This lets cooperating processes block until the state guarded
+/// by a lock in shared memory changes, instead of polling it.
+#[repr(C)]
+pub struct Event {
+    mutex: UnsafeCell<libc::pthread_mutex_t>,
+    cond: UnsafeCell<libc::pthread_cond_t>,
+    set: UnsafeCell<bool>,
+}
+
+unsafe impl Send for Event {}
+unsafe impl Sync for Event {}
+
+// The clock timed waits are measured against. macOS cannot select a clock for
+// condition variables, so waits there follow the wall clock.
+#[cfg(not(target_vendor = "apple"))]
+const CLOCK: libc::clockid_t = libc::CLOCK_MONOTONIC;
+#[cfg(target_vendor = "apple")]
+const CLOCK: libc::clockid_t = libc::CLOCK_REALTIME;
+
+impl Event {
+    /// Initializes an unset event in place at `place`, returning a reference
+    /// to it.
+    ///
+    /// # Safety
+    ///
+    /// `place` must be valid for writes and suitably aligned for an `Event`,
+    /// and must stay mapped for `'a`. No other process may use the event until
+    /// this returns, and it must not be initialized again while any process
+    /// still uses it.
+    pub unsafe fn init<'a>(place: *mut Event) -> &'a Event {
+        let mut attr = MaybeUninit::<libc::pthread_mutexattr_t>::uninit();
+        check(
+            libc::pthread_mutexattr_init(attr.as_mut_ptr()),
+            "pthread_mutexattr_init",
+        );
+        let mut ret =
+            libc::pthread_mutexattr_setpshared(attr.as_mut_ptr(), libc::PTHREAD_PROCESS_SHARED);
+        if ret == 0 {
+            let mutex = ptr::addr_of_mut!((*place).mutex) as *mut libc::pthread_mutex_t;
+            ret = libc::pthread_mutex_init(mutex, attr.as_ptr());
+        }
+        libc::pthread_mutexattr_destroy(attr.as_mut_ptr());
+        check(ret, "pthread_mutex_init");
+
+        init_cond(ptr::addr_of_mut!((*place).cond) as *mut libc::pthread_cond_t);
+
+        ptr::write(ptr::addr_of_mut!((*place).set) as *mut bool, false);
+        &*place
+    }
+
+    /// Returns a reference to an event initialized by [`init`](Event::init),
+    /// possibly in another process.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to an event which has been initialized and not
+    /// destroyed, and must stay mapped for `'a`.
+    #[inline]
+    pub unsafe fn from_ptr<'a>(ptr: *const Event) -> &'a Event {
+        &*ptr
+    }
+
+    /// Destroys the event at `place`.
+    ///
+    /// # Safety
+    ///
+    /// `place` must point to an event which has been initialized and not
+    /// destroyed, and which no process will use again.
+    pub unsafe fn destroy(place: *mut Event) {
+        libc::pthread_cond_destroy(ptr::addr_of_mut!((*place).cond) as *mut libc::pthread_cond_t);
+        libc::pthread_mutex_destroy(ptr::addr_of_mut!((*place).mutex) as *mut libc::pthread_mutex_t);
+    }
+
+    /// Sets the event, waking every thread waiting for it.
+    pub fn set(&self) {
+        let _locked = self.lock();
+        unsafe {
+            *self.set.get() = true;
+            check(
+                libc::pthread_cond_broadcast(self.cond.get()),
+                "pthread_cond_broadcast",
+            );
+        }
+    }
+
+    /// Unsets the event, so that threads calling [`wait`](Event::wait) block
+    /// again until it is next set.
+    pub fn reset(&self) {
+        let _locked = self.lock();
+        unsafe { *self.set.get() = false };
+    }
+
+    /// Returns `true` if the event is currently set.
+    pub fn is_set(&self) -> bool {
+        let _locked = self.lock();
+        unsafe { *self.set.get() }
+    }
+
+    /// Blocks the current thread until the event is set.
+    ///
+    /// This returns immediately if the event is already set.
+    pub fn wait(&self) {
+        let _locked = self.lock();
+        while !unsafe { *self.set.get() } {
+            check(
+                unsafe { libc::pthread_cond_wait(self.cond.get(), self.mutex.get()) },
+                "pthread_cond_wait",
+            );
+        }
+    }
+
+    /// Blocks the current thread until the event is set or `timeout` elapses,
+    /// returning whether the event is set.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = deadline(timeout);
+        let _locked = self.lock();
+        while !unsafe { *self.set.get() } {
+            match unsafe {
+                libc::pthread_cond_timedwait(self.cond.get(), self.mutex.get(), &deadline)
+            } {
+                0 => {}
+                libc::ETIMEDOUT => return unsafe { *self.set.get() },
+                ret => fail(ret, "pthread_cond_timedwait"),
+            }
+        }
+        true
+    }
+
+    fn lock(&self) -> EventLocked<'_> {
+        check(
+            unsafe { libc::pthread_mutex_lock(self.mutex.get()) },
+            "pthread_mutex_lock",
+        );
+        EventLocked(self)
+    }
+}
+
+impl fmt::Debug for Event {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Event")
+            .field("set", &self.is_set())
+            .finish()
+    }
+}
+
+struct EventLocked<'a>(&'a Event);
+
+impl Drop for EventLocked<'_> {
+    fn drop(&mut self) {
+        unsafe { libc::pthread_mutex_unlock(self.0.mutex.get()) };
+    }
+}
+
+// Initializes a process-shared condition variable measuring timeouts against
+// `CLOCK`.
+unsafe fn init_cond(cond: *mut libc::pthread_cond_t) {
+    let mut attr = MaybeUninit::<libc::pthread_condattr_t>::uninit();
+    check(
+        libc::pthread_condattr_init(attr.as_mut_ptr()),
+        "pthread_condattr_init",
+    );
+    let mut ret =
+        libc::pthread_condattr_setpshared(attr.as_mut_ptr(), libc::PTHREAD_PROCESS_SHARED);
+    #[cfg(not(target_vendor = "apple"))]
+    if ret == 0 {
+        ret = libc::pthread_condattr_setclock(attr.as_mut_ptr(), CLOCK);
+    }
+    if ret == 0 {
+        ret = libc::pthread_cond_init(cond, attr.as_ptr());
+    }
+    libc::pthread_condattr_destroy(attr.as_mut_ptr());
+    check(ret, "pthread_cond_init");
+}
+
+fn deadline(timeout: Duration) -> libc::timespec {
+    let mut now = MaybeUninit::<libc::timespec>::uninit();
+    let ret = unsafe { libc::clock_gettime(CLOCK, now.as_mut_ptr()) };
+    assert_eq!(ret, 0, "clock_gettime failed");
+    let now = unsafe { now.assume_init() };
+
+    let mut nanos = now.tv_nsec as u32 + timeout.subsec_nanos();
+    let mut secs = timeout.as_secs();
+    if nanos >= 1_000_000_000 {
+        nanos -= 1_000_000_000;
+        secs = secs.saturating_add(1);
+    }
+    let secs = libc::time_t::try_from(secs)
+        .ok()
+        .and_then(|secs| now.tv_sec.checked_add(secs));
+    match secs {
+        Some(tv_sec) => libc::timespec {
+            tv_sec,
+            tv_nsec: nanos as _,
+        },
+        None => libc::timespec {
+            tv_sec: libc::time_t::MAX,
+            tv_nsec: 999_999_999,
+        },
+    }
+}
@@ -0,0 +1,187 @@
+use std::{fmt, time::Duration};
+
+use crate::{clock, Condvar, Mutex};
+
+/// A latch that releases waiting threads once its count reaches zero.
+///
+/// The count can be raised again with [`count_up`](CountDownLatch::count_up)
+/// for as long as the latch has not been released, so work discovered while
+/// processing can still be waited for. Once the count reaches zero the latch
+/// stays released for good.
+pub struct CountDownLatch {
+    count: Mutex<usize>,
+    released: Condvar,
+}
+
+impl CountDownLatch {
+    /// Creates a new latch with the given count.
+    ///
+    /// A latch created with a count of zero is released immediately.
+    #[inline]
+    pub const fn new(count: usize) -> CountDownLatch {
+        CountDownLatch {
+            count: Mutex::new(count),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Decrements the count, releasing every waiting thread if it reaches
+    /// zero.
+    ///
+    /// This does nothing if the latch has already been released.
+    pub fn count_down(&self) {
+        let mut count = self.count.lock();
+        match *count {
+            0 => {}
+            1 => {
+                *count = 0;
+                drop(count);
+                self.released.notify_all();
+            }
+            _ => *count -= 1,
+        }
+    }
+
+    /// Increments the count.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the latch has already been released.
+    pub fn count_up(&self) {
+        let mut count = self.count.lock();
+        assert!(*count != 0, "the latch has already been released");
+        *count += 1;
+    }
+
+    /// Blocks the current thread until the latch is released.
+    pub fn wait(&self) {
+        let mut count = self.count.lock();
+        while *count != 0 {
+            count = self.released.wait(count);
+        }
+    }
+
+    /// Blocks the current thread until the latch is released or `timeout` has
+    /// passed, returning `true` if it was released.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        wait_for_zero(&self.count, &self.released, timeout)
+    }
+
+    /// Returns the current count.
+    #[inline]
+    pub fn count(&self) -> usize {
+        *self.count.lock()
+    }
+}
+
+impl fmt::Debug for CountDownLatch {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("CountDownLatch")
+            .field("count", &self.count())
+            .finish()
+    }
+}
+
+/// A counter of outstanding work which threads can wait on, like Go's
+/// `sync.WaitGroup`.
+///
+/// Work is registered with [`add`](WaitGroup::add) and marked finished with
+/// [`done`](WaitGroup::done). [`wait`](WaitGroup::wait) blocks until no work
+/// is outstanding. Unlike [`CountDownLatch`], the group can be reused: once
+/// the count has dropped to zero, more work can be added and waited for.
+pub struct WaitGroup {
+    count: Mutex<usize>,
+    finished: Condvar,
+}
+
+impl WaitGroup {
+    /// Creates a new group with no outstanding work.
+    #[inline]
+    pub const fn new() -> WaitGroup {
+        WaitGroup {
+            count: Mutex::new(0),
+            finished: Condvar::new(),
+        }
+    }
+
+    /// Registers `n` more pieces of outstanding work.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the count overflows.
+    pub fn add(&self, n: usize) {
+        let mut count = self.count.lock();
+        *count = count.checked_add(n).expect("WaitGroup count overflowed");
+    }
+
+    /// Marks a piece of work as finished, waking every waiting thread if it
+    /// was the last one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no work is outstanding.
+    pub fn done(&self) {
+        let mut count = self.count.lock();
+        assert!(*count != 0, "WaitGroup::done called more times than added");
+        *count -= 1;
+        if *count == 0 {
+            drop(count);
+            self.finished.notify_all();
+        }
+    }
+
+    /// Blocks the current thread until no work is outstanding.
+    pub fn wait(&self) {
+        let mut count = self.count.lock();
+        while *count != 0 {
+            count = self.finished.wait(count);
+        }
+    }
+
+    /// Blocks the current thread until no work is outstanding or `timeout`
+    /// has passed, returning `true` if no work is outstanding.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        wait_for_zero(&self.count, &self.finished, timeout)
+    }
+
+    /// Returns the amount of outstanding work.
+    #[inline]
+    pub fn count(&self) -> usize {
+        *self.count.lock()
+    }
+}
+
+impl Default for WaitGroup {
+    #[inline]
+    fn default() -> WaitGroup {
+        WaitGroup::new()
+    }
+}
+
+impl fmt::Debug for WaitGroup {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("WaitGroup")
+            .field("count", &self.count())
+            .finish()
+    }
+}
+
+fn wait_for_zero(count: &Mutex<usize>, zero: &Condvar, timeout: Duration) -> bool {
+    // A deadline too far in the future to represent is treated as no deadline
+    // at all.
+    let deadline = clock::now().checked_add(timeout);
+    let mut count = count.lock();
+    while *count != 0 {
+        match deadline {
+            Some(deadline) => {
+                let (new, result) = zero.wait_until(count, deadline);
+                count = new;
+                if result.timed_out() {
+                    return *count == 0;
+                }
+            }
+            None => count = zero.wait(count),
+        }
+    }
+    true
+}
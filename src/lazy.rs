@@ -0,0 +1,86 @@
+use std::{fmt, ops::Deref};
+
+use crate::OnceLock;
+
+/// A value which is initialized on first access, typically for lazily
+/// initialized globals.
+///
+/// Like `std::sync::LazyLock`, except that it is never poisoned: if the
+/// initializer panics, the value is left uninitialized and the next access
+/// runs the initializer again. Since it may be called more than once, the
+/// initializer must be `Fn` rather than `FnOnce`.
+pub struct LazyLock<T, F = fn() -> T> {
+    cell: OnceLock<T>,
+    init: F,
+}
+
+impl<T, F> LazyLock<T, F> {
+    /// Creates a new lazy value which is initialized with `f`.
+    #[inline]
+    pub const fn new(f: F) -> LazyLock<T, F> {
+        LazyLock {
+            cell: OnceLock::new(),
+            init: f,
+        }
+    }
+
+    /// Returns a reference to the value, or `None` if it has not been
+    /// initialized yet.
+    ///
+    /// This is an associated function, so that it does not shadow a method of
+    /// `T`.
+    #[inline]
+    pub fn get(this: &LazyLock<T, F>) -> Option<&T> {
+        this.cell.get()
+    }
+
+    /// Consumes the lazy value, returning the value if it was initialized, or
+    /// the initializer otherwise.
+    #[inline]
+    pub fn into_inner(this: LazyLock<T, F>) -> Result<T, F> {
+        match this.cell.into_inner() {
+            Some(value) => Ok(value),
+            None => Err(this.init),
+        }
+    }
+}
+
+impl<T, F: Fn() -> T> LazyLock<T, F> {
+    /// Returns a reference to the value, initializing it if it has not been
+    /// initialized yet.
+    ///
+    /// This behaves like dereferencing the lazy value. If the initializer
+    /// panics, the panic is propagated and the value is left uninitialized.
+    #[inline]
+    pub fn force(this: &LazyLock<T, F>) -> &T {
+        this.cell.get_or_init(&this.init)
+    }
+}
+
+impl<T, F: Fn() -> T> Deref for LazyLock<T, F> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        LazyLock::force(self)
+    }
+}
+
+impl<T: Default> Default for LazyLock<T> {
+    /// Creates a new lazy value which is initialized with `T::default`.
+    #[inline]
+    fn default() -> LazyLock<T> {
+        LazyLock::new(T::default)
+    }
+}
+
+impl<T: fmt::Debug, F> fmt::Debug for LazyLock<T, F> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_tuple("LazyLock");
+        match self.cell.get() {
+            Some(value) => d.field(value),
+            None => d.field(&format_args!("<uninit>")),
+        };
+        d.finish()
+    }
+}
@@ -0,0 +1,376 @@
+//! Guards with a bounded hold time.
+//!
+//! A guard returned by [`Mutex::lock_leased`],
+//! [`RwLock::read_leased`](crate::RwLock::read_leased) or
+//! [`RwLock::write_leased`](crate::RwLock::write_leased) is registered with a
+//! process-wide watchdog thread. If the guard is still alive when its lease
+//! runs out, the watchdog fires the action configured with [`set_action`] once
+//! for that guard. The `_with` variants of those methods, such as
+//! [`Mutex::lock_leased_with`], give the guard an action of its own instead, so
+//! a lock whose holders must never overrun their budget can abort the process
+//! while the others only log. The watchdog thread is spawned the first time a
+//! leased guard is created.
+
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    panic::{self, AssertUnwindSafe, Location},
+    process,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Once,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    poison::{Ignore, PoisonPolicy},
+    Condvar, Mutex, MutexGuard, RwLockReadGuard, RwLockWriteGuard,
+};
+
+/// What the watchdog does when a lease expires.
+#[derive(Clone)]
+pub enum LeaseAction {
+    /// Print a message to standard error.
+    Log,
+    /// Invoke a callback on the watchdog thread.
+    ///
+    /// A panic in the callback is caught, after the panic hook has reported
+    /// it, so that the watchdog goes on firing the other leases.
+    Callback(Arc<dyn Fn(&LeaseExpired) + Send + Sync>),
+    /// Print a message to standard error and abort the process.
+    Abort,
+}
+
+impl fmt::Debug for LeaseAction {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LeaseAction::Log => fmt.write_str("Log"),
+            LeaseAction::Callback(_) => fmt.write_str("Callback(..)"),
+            LeaseAction::Abort => fmt.write_str("Abort"),
+        }
+    }
+}
+
+/// Information about a guard that outlived its lease.
+#[derive(Debug, Clone)]
+pub struct LeaseExpired {
+    name: Option<&'static str>,
+    max_hold: Duration,
+    location: &'static Location<'static>,
+    thread: Option<String>,
+}
+
+impl LeaseExpired {
+    /// Returns the name of the lock, if it was created with `new_named` and
+    /// the `names` feature is enabled.
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// Returns the lease duration the guard was created with.
+    pub fn max_hold(&self) -> Duration {
+        self.max_hold
+    }
+
+    /// Returns the location at which the guard was acquired.
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    /// Returns the name of the thread that acquired the guard, if it has one.
+    pub fn thread(&self) -> Option<&str> {
+        self.thread.as_deref()
+    }
+}
+
+impl fmt::Display for LeaseExpired {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("lock ")?;
+        if let Some(name) = self.name {
+            write!(fmt, "'{}' ", name)?;
+        }
+        write!(
+            fmt,
+            "acquired at {} by thread '{}' held past its {:?} lease",
+            self.location,
+            self.thread.as_deref().unwrap_or("<unnamed>"),
+            self.max_hold,
+        )
+    }
+}
+
+/// Sets the action taken when a lease expires, unless the guard was given an
+/// action of its own.
+///
+/// The default action is [`LeaseAction::Log`].
+pub fn set_action(action: LeaseAction) {
+    *watchdog().action.lock() = action;
+}
+
+struct Watchdog {
+    // Sorted by key.
+    leases: Mutex<Vec<Entry>>,
+    next_id: AtomicU64,
+    changed: Condvar,
+    action: Mutex<LeaseAction>,
+}
+
+struct Entry {
+    // The deadline, and an id telling apart leases with the same deadline.
+    key: (Instant, u64),
+    expired: LeaseExpired,
+    // The action of the guard, if it has one.
+    action: Option<LeaseAction>,
+}
+
+static WATCHDOG: Watchdog = Watchdog {
+    leases: Mutex::new(Vec::new()),
+    next_id: AtomicU64::new(0),
+    changed: Condvar::new(),
+    action: Mutex::new(LeaseAction::Log),
+};
+
+fn watchdog() -> &'static Watchdog {
+    static SPAWN: Once = Once::new();
+
+    SPAWN.call_once(|| {
+        thread::Builder::new()
+            .name("antidote-lease-watchdog".to_string())
+            .spawn(|| WATCHDOG.run())
+            .expect("failed to spawn lease watchdog thread");
+    });
+    &WATCHDOG
+}
+
+impl Watchdog {
+    fn run(&self) {
+        let mut leases = self.leases.lock();
+        loop {
+            let deadline = match leases.first() {
+                Some(entry) => entry.key.0,
+                None => {
+                    leases = self.changed.wait(leases);
+                    continue;
+                }
+            };
+
+            let now = Instant::now();
+            if deadline > now {
+                leases = self.changed.wait_timeout(leases, deadline - now).0;
+                continue;
+            }
+
+            let entry = leases.remove(0);
+            drop(leases);
+            self.fire(&entry.expired, entry.action);
+            leases = self.leases.lock();
+        }
+    }
+
+    fn fire(&self, expired: &LeaseExpired, action: Option<LeaseAction>) {
+        let action = action.unwrap_or_else(|| self.action.lock().clone());
+        match action {
+            LeaseAction::Log => eprintln!("antidote: {}", expired),
+            LeaseAction::Callback(callback) => {
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| callback(expired)));
+            }
+            LeaseAction::Abort => {
+                eprintln!("antidote: {}; aborting", expired);
+                process::abort();
+            }
+        }
+    }
+}
+
+struct Lease {
+    // `None` for a lease which never expires, and so is not registered.
+    key: Option<(Instant, u64)>,
+}
+
+impl Lease {
+    fn new(
+        name: Option<&'static str>,
+        max_hold: Duration,
+        location: &'static Location<'static>,
+        action: Option<LeaseAction>,
+    ) -> Lease {
+        // A deadline too far in the future to represent is never reached.
+        let deadline = match Instant::now().checked_add(max_hold) {
+            Some(deadline) => deadline,
+            None => return Lease { key: None },
+        };
+        let watchdog = watchdog();
+        let id = watchdog.next_id.fetch_add(1, Ordering::Relaxed);
+        let key = (deadline, id);
+        let expired = LeaseExpired {
+            name,
+            max_hold,
+            location,
+            thread: thread::current().name().map(str::to_string),
+        };
+
+        let mut leases = watchdog.leases.lock();
+        let idx = leases.partition_point(|entry| entry.key < key);
+        leases.insert(
+            idx,
+            Entry {
+                key,
+                expired,
+                action,
+            },
+        );
+        if idx == 0 {
+            watchdog.changed.notify_one();
+        }
+
+        Lease { key: Some(key) }
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        let key = match self.key {
+            Some(key) => key,
+            None => return,
+        };
+        let mut leases = WATCHDOG.leases.lock();
+        if let Ok(idx) = leases.binary_search_by(|entry| entry.key.cmp(&key)) {
+            leases.remove(idx);
+        }
+    }
+}
+
+/// A `MutexGuard` registered with the lease watchdog.
+///
+/// Created by [`Mutex::lock_leased`] and [`Mutex::lock_leased_with`].
+#[must_use]
+pub struct LeasedMutexGuard<'a, T: ?Sized + 'a, P: PoisonPolicy = Ignore> {
+    // Declared first so the lease is withdrawn before the lock is released.
+    _lease: Lease,
+    guard: MutexGuard<'a, T, P>,
+}
+
+impl<'a, T: ?Sized, P: PoisonPolicy> LeasedMutexGuard<'a, T, P> {
+    #[track_caller]
+    pub(crate) fn new(
+        guard: MutexGuard<'a, T, P>,
+        max_hold: Duration,
+        action: Option<LeaseAction>,
+    ) -> LeasedMutexGuard<'a, T, P> {
+        LeasedMutexGuard {
+            _lease: Lease::new(guard.lock.name(), max_hold, Location::caller(), action),
+            guard,
+        }
+    }
+}
+
+impl<T: ?Sized, P: PoisonPolicy> Deref for LeasedMutexGuard<'_, T, P> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized, P: PoisonPolicy> DerefMut for LeasedMutexGuard<'_, T, P> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, P: PoisonPolicy> fmt::Debug for LeasedMutexGuard<'_, T, P> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.guard, fmt)
+    }
+}
+
+/// An `RwLockReadGuard` registered with the lease watchdog.
+///
+/// Created by [`RwLock::read_leased`](crate::RwLock::read_leased) and
+/// [`RwLock::read_leased_with`](crate::RwLock::read_leased_with).
+#[must_use]
+pub struct LeasedRwLockReadGuard<'a, T: ?Sized + 'a> {
+    // Declared first so the lease is withdrawn before the lock is released.
+    _lease: Lease,
+    guard: RwLockReadGuard<'a, T>,
+}
+
+impl<'a, T: ?Sized> LeasedRwLockReadGuard<'a, T> {
+    #[track_caller]
+    pub(crate) fn new(
+        guard: RwLockReadGuard<'a, T>,
+        max_hold: Duration,
+        action: Option<LeaseAction>,
+    ) -> LeasedRwLockReadGuard<'a, T> {
+        LeasedRwLockReadGuard {
+            _lease: Lease::new(guard.lock.name(), max_hold, Location::caller(), action),
+            guard,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for LeasedRwLockReadGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for LeasedRwLockReadGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.guard, fmt)
+    }
+}
+
+/// An `RwLockWriteGuard` registered with the lease watchdog.
+///
+/// Created by [`RwLock::write_leased`](crate::RwLock::write_leased) and
+/// [`RwLock::write_leased_with`](crate::RwLock::write_leased_with).
+#[must_use]
+pub struct LeasedRwLockWriteGuard<'a, T: ?Sized + 'a> {
+    // Declared first so the lease is withdrawn before the lock is released.
+    _lease: Lease,
+    guard: RwLockWriteGuard<'a, T>,
+}
+
+impl<'a, T: ?Sized> LeasedRwLockWriteGuard<'a, T> {
+    #[track_caller]
+    pub(crate) fn new(
+        guard: RwLockWriteGuard<'a, T>,
+        max_hold: Duration,
+        action: Option<LeaseAction>,
+    ) -> LeasedRwLockWriteGuard<'a, T> {
+        LeasedRwLockWriteGuard {
+            _lease: Lease::new(guard.lock.name(), max_hold, Location::caller(), action),
+            guard,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for LeasedRwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized> DerefMut for LeasedRwLockWriteGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for LeasedRwLockWriteGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.guard, fmt)
+    }
+}
@@ -0,0 +1,144 @@
+//! Mutexes with a lock ordering checked at compile time.
+//!
+//! Each [`LeveledMutex`] is assigned a level as a const parameter, and may only
+//! be locked while every lock the thread already holds has a lower level.
+//! Locking is done through a [`LevelToken`], which records the level of the
+//! most recently acquired lock: a thread starts with [`LevelToken::root`] at
+//! level 0, and each acquisition returns a token at the new lock's level
+//! alongside the guard. Since the tokens borrow each other, a thread can only
+//! ever use the token of the innermost lock it holds.
+//!
+//! Acquiring a lock whose level is not strictly greater than the token's fails
+//! to compile. The check happens during monomorphization, so it is reported by
+//! `cargo build` but not necessarily by `cargo check`.
+//!
+//! Levels start at 1; a lock at level 0 can never be acquired.
+
+use std::{cell::Cell, fmt, marker::PhantomData};
+
+use crate::{Mutex, MutexGuard, TryLockResult};
+
+thread_local! {
+    static HAS_ROOT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Proof that the current thread holds no leveled lock at level `LEVEL` or
+/// above.
+///
+/// Tokens are tied to the thread that created them.
+pub struct LevelToken<'a, const LEVEL: u8> {
+    root: bool,
+    _marker: PhantomData<(&'a mut (), *const ())>,
+}
+
+impl LevelToken<'static, 0> {
+    /// Returns the token of a thread which holds no leveled locks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the current thread already has a root token.
+    pub fn root() -> LevelToken<'static, 0> {
+        let had_root = HAS_ROOT.with(|has_root| has_root.replace(true));
+        assert!(
+            !had_root,
+            "the current thread already has a root LevelToken"
+        );
+        LevelToken {
+            root: true,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<const LEVEL: u8> LevelToken<'_, LEVEL> {
+    fn new() -> Self {
+        LevelToken {
+            root: false,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<const LEVEL: u8> Drop for LevelToken<'_, LEVEL> {
+    fn drop(&mut self) {
+        if self.root {
+            HAS_ROOT.with(|has_root| has_root.set(false));
+        }
+    }
+}
+
+impl<const LEVEL: u8> fmt::Debug for LevelToken<'_, LEVEL> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("LevelToken").field(&LEVEL).finish()
+    }
+}
+
+struct Ordered<const HELD: u8, const LEVEL: u8>;
+
+impl<const HELD: u8, const LEVEL: u8> Ordered<HELD, LEVEL> {
+    const CHECK: () = assert!(HELD < LEVEL, "leveled lock acquired out of order");
+}
+
+/// A `Mutex` at level `LEVEL` of the program's lock hierarchy.
+#[derive(Debug, Default)]
+pub struct LeveledMutex<T: ?Sized, const LEVEL: u8>(Mutex<T>);
+
+impl<T, const LEVEL: u8> LeveledMutex<T, LEVEL> {
+    /// Creates a new leveled mutex in an unlocked state.
+    #[inline]
+    pub const fn new(t: T) -> LeveledMutex<T, LEVEL> {
+        LeveledMutex(Mutex::new(t))
+    }
+
+    /// Consumes the mutex, returning the underlying data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0.into_inner()
+    }
+}
+
+impl<T: ?Sized, const LEVEL: u8> LeveledMutex<T, LEVEL> {
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so.
+    ///
+    /// `token` must be at a lower level than the mutex. It stays borrowed for
+    /// as long as the guard and the returned token, which must be used to
+    /// acquire any further leveled locks, are alive.
+    #[inline]
+    pub fn lock<'a, const HELD: u8>(
+        &'a self,
+        _token: &'a mut LevelToken<'_, HELD>,
+    ) -> (MutexGuard<'a, T>, LevelToken<'a, LEVEL>) {
+        #[allow(clippy::let_unit_value)]
+        let () = Ordered::<HELD, LEVEL>::CHECK;
+        (self.0.lock(), LevelToken::new())
+    }
+
+    /// Attempts to acquire the mutex without blocking.
+    ///
+    /// See [`lock`](LeveledMutex::lock) for the role of `token`.
+    #[inline]
+    pub fn try_lock<'a, const HELD: u8>(
+        &'a self,
+        _token: &'a mut LevelToken<'_, HELD>,
+    ) -> TryLockResult<(MutexGuard<'a, T>, LevelToken<'a, LEVEL>)> {
+        #[allow(clippy::let_unit_value)]
+        let () = Ordered::<HELD, LEVEL>::CHECK;
+        self.0.try_lock().map(|guard| (guard, LevelToken::new()))
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// No locking is necessary since the borrow guarantees exclusive access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.0.get_mut()
+    }
+}
+
+impl<T, const LEVEL: u8> From<T> for LeveledMutex<T, LEVEL> {
+    #[inline]
+    fn from(t: T) -> LeveledMutex<T, LEVEL> {
+        LeveledMutex::new(t)
+    }
+}
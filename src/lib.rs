@@ -2,234 +2,3068 @@
 //!
 //! These types expose identical APIs to the standard library `Mutex` and
 //! `RwLock` except that they do not return `PoisonError`s.
+//!
+//! # Panics
+//!
+//! A guard dropped while its thread unwinds from a panic releases its lock
+//! like any other, and the lock stays usable by every other thread. Just like
+//! the standard library's types, `Mutex` and `RwLock` are `UnwindSafe` and
+//! `RefUnwindSafe`, so they can be used from within `catch_unwind`. However,
+//! since nothing is poisoned, nothing stops other threads from using a value
+//! left half-updated by a panicking thread either: code which panics while
+//! holding a guard must leave the value in a state other threads can cope
+//! with, or be run under [`Mutex::lock_transactional`], which puts the value
+//! back as it was if its thread panics. The locks do remember such panics, so
+//! that code which cares can check [`Mutex::was_poisoned`] or
+//! [`MutexGuard::recovered_from_poison`], and their `RwLock` counterparts, and
+//! a `Mutex` can be given a policy reacting to them, as described in the
+//! [`poison`] module.
+//!
+//! Code written against the `Result`s returned by the standard library's
+//! locks can switch to the types of the [`compat`] module first, which keep
+//! those signatures, and move to the crate's own types one call site at a
+//! time.
+//!
+//! # `no_std`
+//!
+//! Without the default `std` feature, the crate is `no_std`. It then only
+//! provides [`Mutex`] and [`RwLock`], with their guards, the [`Lock`] and
+//! [`SharedLock`] traits, [`Exclusive`], [`SeqLock`], and [`SpinMutex`], which
+//! is then the same type as `Mutex`. Since there is no way to put a thread to
+//! sleep, those locks spin with exponential backoff while they wait, and they
+//! only offer the methods which do not depend on the standard library.
+//!
+//! On bare-metal targets, where the holder of a lock can be interrupted by a
+//! handler which wants it, spinning would never end. The `critical-section`
+//! feature then makes `Mutex` the same type as [`CriticalSectionMutex`]
+//! instead, which holds a critical section while it is locked, so the holder
+//! cannot be interrupted. `RwLock` still spins. Every other feature requires
+//! `std`.
+//!
+//! # WebAssembly
+//!
+//! With the `atomics` target feature, and shared memory, the locks block on
+//! `wasm32` as they do elsewhere. Without it there is only ever one thread, so
+//! a lock can only be contended by the thread holding it, and nothing could
+//! ever wake a thread waiting for a notification. Instead of hanging, the
+//! locks and the other blocking primitives then panic wherever they would have
+//! to wait, and cost no more than their uncontended paths otherwise. Timed
+//! waits are no exception, as `wasm32-unknown-unknown` has no clock.
+//!
+//! # Model checking with `loom` or `shuttle`
+//!
+//! When built with `RUSTFLAGS="--cfg loom"`, [`Mutex`], [`RwLock`] and
+//! [`Condvar`] delegate to `loom::sync`, so code using them can be model
+//! checked with [`loom`](https://docs.rs/loom). Their basic methods are kept,
+//! but they cannot be created in a `const` context, and the rest of the crate
+//! is unavailable, including every optional feature.
+//!
+//! Likewise, when built with `RUSTFLAGS="--cfg shuttle"`, they delegate to
+//! `shuttle::sync`, so that [`shuttle`](https://docs.rs/shuttle) can explore
+//! randomly chosen schedules of programs too large for `loom` to check
+//! exhaustively. The same API is kept in that case. `loom` takes precedence if
+//! both are set.
+//!
+//! # Cargo features
+//!
+//! * `arbitrary` - Implements `arbitrary::Arbitrary` for [`Mutex`] and
+//!   [`RwLock`], for fuzzing.
+//! * `critical-section` - Enables [`CriticalSectionMutex`], and makes it the
+//!   `Mutex` of `no_std` builds, as described in the [`no_std`](#no_std)
+//!   section. A critical section implementation has to be linked in, see the
+//!   [`critical-section`](https://docs.rs/critical-section) crate.
+//! * `cycle-detection` - Panics when a `Mutex` or `RwLock` is acquired while
+//!   holding a lock which was itself acquired while holding it before, anywhere
+//!   in the process, as threads taking locks in opposite orders can deadlock.
+//!   The panic message shows where both orders were first seen. This makes
+//!   blocking acquisitions made while holding other locks much more expensive,
+//!   and is meant for debugging. Implies `tracking`.
+//! * `deadlock-detection` - Enables the [`deadlock`] module. Implies
+//!   `tracking`.
+//! * `derive` - Enables [`derive(LockProject)`](LockProject), generating
+//!   accessors which lock a `Mutex` or `RwLock` holding a struct and return a
+//!   guard for one of its fields. It requires Rust 1.71.
+//! * `embassy` - Enables the [`embassy`] module.
+//! * `ffi` - Enables the [`ffi`] module.
+//! * `fork` - Enables the [`fork`] module on Unix targets.
+//! * `futex` - Builds [`Mutex`] on a futex of the crate's own instead of the
+//!   standard library's mutex, skipping its poison checks. Its waiters sleep
+//!   with the futex of Linux and Android, `WaitOnAddress` on Windows 8 and
+//!   later, and `__ulock_wait` on Apple platforms. Other platforms wait in the
+//!   crate's parking lot instead. `parking_lot` takes precedence over it.
+//! * `histogram` - Enables the [`histogram`] module. This makes every
+//!   acquisition and release somewhat more expensive.
+//! * `holder-location` - Enables the [`holder`] module. This makes every
+//!   acquisition and release somewhat more expensive, and every lock larger.
+//! * `ipc` - Enables the [`ipc`] module on Unix targets.
+//! * `lock_api` - Enables the [`lock_api`](mod@lock_api) module.
+//! * `metrics` - Enables the [`metrics`](mod@metrics) module. This makes every
+//!   acquisition and release somewhat more expensive.
+//! * `names` - Keeps the names given to locks with [`Mutex::new_named`] and
+//!   [`RwLock::new_named`] for diagnostics. This makes every lock larger.
+//! * `opentelemetry` - Enables the [`opentelemetry`](mod@opentelemetry) module.
+//!   This makes every acquisition and release somewhat more expensive.
+//! * `os-unfair-lock` - Enables the `unfair` module on Apple targets.
+//! * `panic-hook` - Installs [`debug::install_panic_hook`] the first time a
+//!   thread acquires a `Mutex` or `RwLock`. Implies `tracking`.
+//! * `parking_lot` - Builds [`Mutex`] on `parking_lot`'s mutex instead of the
+//!   standard library's.
+//! * `persistent` - Enables the [`persistent`] module.
+//! * `profiler` - Enables the [`profiler`] module. Implies `tracking`.
+//! * `proptest` - Implements `proptest::arbitrary::Arbitrary` for [`Mutex`] and
+//!   [`RwLock`].
+//! * `quickcheck` - Implements `quickcheck::Arbitrary` for [`Mutex`] and
+//!   [`RwLock`].
+//! * `rayon` - Enables parallel processing of slices of locks in the
+//!   [`slice`](mod@slice) module.
+//! * `registry` - Enables the [`registry`] module. Implies `tracking`.
+//! * `serde` - Implements `Serialize` and `Deserialize` for [`Mutex`] and
+//!   [`RwLock`]. Serializing a lock acquires it.
+//! * `send-guard` - Makes the guards of [`Mutex`] and [`RwLock`] `Send`, so
+//!   that a lock can be released by another thread than the one which acquired
+//!   it. The bookkeeping of the other features is still that of the acquiring
+//!   thread until then. Implies `futex`, as the standard library's mutex has to
+//!   be released by the thread which locked it.
+//! * `signal` - Enables the [`signal`] module on Unix targets.
+//! * `slow-warnings` - Enables the [`slow`] module. This makes every
+//!   acquisition and release somewhat more expensive, and every lock larger.
+//! * `stable_deref_trait` - Implements `StableDeref` for the guards of
+//!   [`Mutex`] and [`RwLock`], including their mapped and owned guards, so that
+//!   they can be kept alongside references derived from them by crates such as
+//!   `owning_ref`.
+//! * `stats` - Enables the [`stats`] module. This makes every acquisition and
+//!   release somewhat more expensive, and every lock larger.
+//! * `std` - Enabled by default. Builds the locks on the standard library and
+//!   enables everything that is not listed in the [`no_std`](#no_std) section.
+//! * `std-backend` - Guarantees that [`Mutex`] is built on the standard
+//!   library's mutex, and enables `Mutex::as_std` and `MutexGuard::into_std`,
+//!   which lend it out. It cannot be combined with `parking_lot`, `futex` or
+//!   `send-guard`, which build the mutex on another lock: rather than removing
+//!   these functions from under the crates which call them, the build fails.
+//! * `test-util` - Enables the [`test_util`] module.
+//! * `tracing` - Enables the [`tracing`](mod@tracing) module. This makes every
+//!   acquisition and release somewhat more expensive.
+//! * `tracking` - Records the locks held by each thread, and enables the
+//!   [`debug`] module. This makes every acquisition and release somewhat more
+//!   expensive.
+#![cfg_attr(docsrs, feature(doc_cfg))]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(all(
+    feature = "std-backend",
+    any(feature = "parking_lot", feature = "futex")
+))]
+compile_error!(
+    "the `std-backend` feature cannot be combined with `parking_lot`, `futex` or `send-guard`, \
+     which build `Mutex` on another lock than the standard library's"
+);
 
-#[doc(inline)]
-pub use std::sync::WaitTimeoutResult;
+#[cfg(any(not(feature = "std"), loom, shuttle))]
+use core::fmt;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
 use std::{
+    cell::UnsafeCell,
     fmt,
+    hash::{Hash, Hasher},
+    hint,
+    marker::PhantomData,
+    mem::{self, ManuallyDrop},
     ops::{Deref, DerefMut},
-    sync,
-    time::Duration,
+    panic::{RefUnwindSafe, UnwindSafe},
+    ptr,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+use crate::{
+    parking::{ParkResult, UnparkResult},
+    poison::{Ignore, PoisonPolicy},
 };
 
-#[derive(Debug, Default)]
-#[repr(transparent)]
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+pub mod brand;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+pub mod compat;
+#[cfg(feature = "deadlock-detection")]
+#[cfg_attr(docsrs, doc(cfg(feature = "deadlock-detection")))]
+pub mod deadlock;
+#[cfg(feature = "tracking")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tracking")))]
+pub mod debug;
+#[cfg(feature = "embassy")]
+#[cfg_attr(docsrs, doc(cfg(feature = "embassy")))]
+pub mod embassy;
+#[cfg(feature = "ffi")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ffi")))]
+pub mod ffi;
+#[cfg(all(unix, feature = "fork"))]
+#[cfg_attr(docsrs, doc(cfg(all(unix, feature = "fork"))))]
+pub mod fork;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+pub mod future;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+pub mod group;
+#[cfg(feature = "histogram")]
+#[cfg_attr(docsrs, doc(cfg(feature = "histogram")))]
+pub mod histogram;
+#[cfg(feature = "holder-location")]
+#[cfg_attr(docsrs, doc(cfg(feature = "holder-location")))]
+pub mod holder;
+#[cfg(all(unix, feature = "ipc"))]
+#[cfg_attr(docsrs, doc(cfg(all(unix, feature = "ipc"))))]
+pub mod ipc;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+pub mod lease;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+pub mod level;
+#[cfg(feature = "lock_api")]
+#[cfg_attr(docsrs, doc(cfg(feature = "lock_api")))]
+pub mod lock_api;
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub mod metrics;
+#[cfg(feature = "opentelemetry")]
+#[cfg_attr(docsrs, doc(cfg(feature = "opentelemetry")))]
+pub mod opentelemetry;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+pub mod order;
+#[cfg(feature = "persistent")]
+#[cfg_attr(docsrs, doc(cfg(feature = "persistent")))]
+pub mod persistent;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+pub mod poison;
+#[cfg(feature = "profiler")]
+#[cfg_attr(docsrs, doc(cfg(feature = "profiler")))]
+pub mod profiler;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+pub mod queue;
+#[cfg(feature = "registry")]
+#[cfg_attr(docsrs, doc(cfg(feature = "registry")))]
+pub mod registry;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+pub mod sharded;
+#[cfg(all(unix, feature = "signal"))]
+#[cfg_attr(docsrs, doc(cfg(all(unix, feature = "signal"))))]
+pub mod signal;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+pub mod slice;
+#[cfg(feature = "slow-warnings")]
+#[cfg_attr(docsrs, doc(cfg(feature = "slow-warnings")))]
+pub mod slow;
+#[cfg(feature = "stats")]
+#[cfg_attr(docsrs, doc(cfg(feature = "stats")))]
+pub mod stats;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+pub mod stop_the_world;
+#[cfg(feature = "test-util")]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+pub mod test_util;
+#[cfg(feature = "tracing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tracing")))]
+pub mod tracing;
+#[cfg(all(target_vendor = "apple", feature = "os-unfair-lock"))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(target_vendor = "apple", feature = "os-unfair-lock")))
+)]
+pub mod unfair;
+
+#[cfg(all(
+    any(feature = "arbitrary", feature = "proptest", feature = "quickcheck"),
+    not(any(loom, shuttle))
+))]
+mod arbitrary;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod backend;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod backoff;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod barrier;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod cancel;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod clock;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod condvar_any;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod config;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod config_cell;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod contention;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod convert;
+#[cfg(all(
+    feature = "critical-section",
+    any(not(feature = "std"), not(any(loom, shuttle)))
+))]
+mod critical;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod delay_queue;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod event;
+#[cfg(not(any(loom, shuttle)))]
+mod exclusive;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod fair;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod forward;
+#[cfg(feature = "cycle-detection")]
+mod graph;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod guarded;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod held;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod hybrid;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod latch;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod lazy;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod mapped;
+#[cfg(all(feature = "std", any(loom, shuttle)))]
+mod model;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod once;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod owned;
+#[cfg(not(any(loom, shuttle)))]
+mod padded;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod parking;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod pinned;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod pool;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod raw;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod read_mostly;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod read_only;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod reentrant;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod semaphore;
+#[cfg(not(any(loom, shuttle)))]
+mod seqlock;
+#[cfg(feature = "serde")]
+mod serde;
+#[cfg(any(not(feature = "std"), not(any(loom, shuttle))))]
+mod spin;
+#[cfg(not(feature = "std"))]
+mod spin_rwlock;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod split;
+#[cfg(all(feature = "stable_deref_trait", not(any(loom, shuttle))))]
+mod stable_deref;
+#[cfg(not(any(loom, shuttle)))]
+mod traits;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod transaction;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+mod watch;
+
+#[cfg(all(feature = "std", any(loom, shuttle)))]
+pub use crate::model::{
+    Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard, WaitTimeoutResult,
+};
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+pub use crate::{
+    backoff::Backoff,
+    barrier::{Barrier, BarrierWaitResult, DynamicBarrier},
+    cancel::CancelToken,
+    condvar_any::{CondvarAny, Unlock},
+    config::{config, Config, InvalidEnvVar},
+    config_cell::ConfigCell,
+    delay_queue::DelayQueue,
+    event::{Event, EventSet, Notify},
+    fair::{FairMutex, FairMutexGuard},
+    group::{lock_all, lock_both},
+    guarded::Guarded,
+    hybrid::{HybridMutex, HybridMutexGuard},
+    latch::{CountDownLatch, WaitGroup},
+    lazy::LazyLock,
+    mapped::{MappedMutexGuard, MappedRwLockReadGuard, MappedRwLockWriteGuard},
+    once::{Once, OnceLock},
+    owned::{OwnedMutexGuard, OwnedRwLockReadGuard, OwnedRwLockWriteGuard},
+    pinned::{PinnedMutex, PinnedMutexGuard},
+    pool::{LockedPool, PoolItem},
+    raw::{
+        BuiltinMutex, Fairness, MutexBackend, MutexStorage, RawMutex, RawMutexGuard, RawRwLock,
+        RawRwLockReadGuard, RawRwLockUpgradableGuard, RawRwLockWriteGuard, RwLockBackend,
+    },
+    read_mostly::ReadMostly,
+    read_only::{ReadHandle, ReadOnly},
+    reentrant::{ReentrantLock, ReentrantLockGuard, ReentrantMutex, ReentrantMutexGuard},
+    semaphore::{Semaphore, SemaphorePermit, TryAcquireError},
+    split::{IterLocked, IterRead, SplitMutexGuard, SplitRwLockReadGuard, SplitRwLockWriteGuard},
+    transaction::TransactionalMutexGuard,
+    watch::Watch,
+};
+
+/// A [`Mutex`] on a cache line of its own.
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+pub type PaddedMutex<T> = CachePadded<Mutex<T>>;
+
+/// An [`RwLock`] on a cache line of its own.
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+pub type PaddedRwLock<T> = CachePadded<RwLock<T>>;
+#[cfg(all(feature = "derive", not(any(loom, shuttle))))]
+#[cfg_attr(docsrs, doc(cfg(feature = "derive")))]
+pub use antidote_derive::LockProject;
+
+#[cfg(all(feature = "critical-section", not(any(loom, shuttle))))]
+#[cfg_attr(docsrs, doc(cfg(feature = "critical-section")))]
+pub use crate::critical::{CriticalSectionMutex, CriticalSectionMutexGuard};
+#[cfg(all(not(feature = "std"), feature = "critical-section"))]
+pub use crate::critical::{CriticalSectionMutex as Mutex, CriticalSectionMutexGuard as MutexGuard};
+#[cfg(all(not(feature = "std"), not(feature = "critical-section")))]
+pub use crate::spin::{SpinMutex as Mutex, SpinMutexGuard as MutexGuard};
+#[cfg(not(feature = "std"))]
+pub use crate::spin_rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(not(any(loom, shuttle)))]
+pub use crate::{
+    exclusive::Exclusive,
+    padded::CachePadded,
+    seqlock::SeqLock,
+    spin::{SpinMutex, SpinMutexGuard},
+    traits::{Lock, SharedLock},
+};
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+#[derive(Default)]
 /// Like `std::sync::Mutex` except that it does not poison itself.
-pub struct Mutex<T: ?Sized>(sync::Mutex<T>);
+///
+/// It wraps the standard library's mutex, `parking_lot`'s with the
+/// `parking_lot` feature, or a futex with the `futex` feature. The API and
+/// behavior are the same either way, except that the standard library's mutex
+/// can neither be released without a guard, with `force_unlock`, nor lend out
+/// a pointer to its data, with `data_ptr`.
+///
+/// The policy `P` decides what happens when the mutex is acquired after a
+/// panic while it was held. See the [`poison`] module.
+///
+/// A mutex created with [`with_raw`](Mutex::with_raw) is instead built on
+/// another raw mutex implementing [`MutexBackend`], which leaves out the few
+/// methods relying on the built-in one.
+pub struct Mutex<T: ?Sized, P: PoisonPolicy = Ignore, R: MutexStorage = BuiltinMutex> {
+    instrumentation: held::Instrumentation,
+    poison: poison::Flag,
+    policy: P,
+    inner: R::Mutex<T>,
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized, P: PoisonPolicy, R: MutexStorage> UnwindSafe for Mutex<T, P, R> {}
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized, P: PoisonPolicy, R: MutexStorage> RefUnwindSafe for Mutex<T, P, R> {}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T, R: MutexBackend> Mutex<T, Ignore, R> {
+    /// Creates a new mutex built on `raw` instead of the built-in mutex.
+    ///
+    /// See [`MutexBackend`] for what such a mutex offers.
+    #[inline]
+    pub const fn with_raw(raw: R, t: T) -> Mutex<T, Ignore, R> {
+        Mutex {
+            instrumentation: held::Instrumentation::new(None, None),
+            poison: poison::Flag::new(),
+            policy: Ignore,
+            inner: raw::BackendMutex::new(raw, t),
+        }
+    }
+}
 
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
 impl<T> Mutex<T> {
     /// Like `std::sync::Mutex::new`.
     #[inline]
     pub const fn new(t: T) -> Mutex<T> {
-        Mutex(sync::Mutex::new(t))
+        Mutex {
+            instrumentation: held::Instrumentation::new(None, None),
+            poison: poison::Flag::new(),
+            policy: Ignore,
+            inner: backend::new(t),
+        }
     }
 
-    /// Like `std::sync::Mutex::into_inner`.
+    /// Creates a new mutex with a name identifying it in diagnostics.
+    ///
+    /// With the `names` feature enabled, the name appears in the mutex's
+    /// `Debug` output, in the events of the `tracing` feature, in the reports
+    /// of the [`debug`](mod@debug) and [`deadlock`](mod@deadlock) modules and
+    /// in expired [`lease`] warnings. Without it the name is discarded, so
+    /// that locks stay as small as possible.
     #[inline]
-    pub fn into_inner(self) -> T {
-        self.0.into_inner().unwrap_or_else(|e| e.into_inner())
+    pub const fn new_named(name: &'static str, t: T) -> Mutex<T> {
+        Mutex {
+            instrumentation: held::Instrumentation::new(Some(name), None),
+            poison: poison::Flag::new(),
+            policy: Ignore,
+            inner: backend::new(t),
+        }
     }
-}
 
-impl<T: ?Sized> Mutex<T> {
-    /// Like `std::sync::Mutex::lock`.
+    /// Creates a new mutex at `level` in the lock hierarchy.
+    ///
+    /// A thread may only acquire a leveled lock while every leveled lock it
+    /// already holds has a strictly higher level, so locks are always taken in
+    /// decreasing order of level. In builds with debug assertions, blocking to
+    /// acquire the mutex out of that order panics, turning a potential
+    /// deadlock into an immediate failure. [`try_lock`](Mutex::try_lock) never
+    /// panics, since it cannot deadlock. Without debug assertions the level is
+    /// ignored.
+    ///
+    /// Locks created with [`new`](Mutex::new) have no level and are never
+    /// checked.
     #[inline]
-    pub fn lock(&self) -> MutexGuard<'_, T> {
-        MutexGuard(self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    pub const fn with_level(level: u32, t: T) -> Mutex<T> {
+        Mutex {
+            instrumentation: held::Instrumentation::new(None, Some(level)),
+            poison: poison::Flag::new(),
+            policy: Ignore,
+            inner: backend::new(t),
+        }
     }
 
-    /// Like `std::sync::Mutex::try_lock`.
+    /// Makes the mutex call `hook` every time it is acquired after a panic
+    /// while it was held, with its name if it has one.
+    ///
+    /// This keeps the name and level the mutex was created with, and is
+    /// shorthand for the [`poison::Callback`] policy. To be told about every
+    /// lock instead, see [`poison::set_hook`].
     #[inline]
-    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
-        match self.0.try_lock() {
-            Ok(t) => Ok(MutexGuard(t)),
-            Err(sync::TryLockError::Poisoned(e)) => Ok(MutexGuard(e.into_inner())),
-            Err(sync::TryLockError::WouldBlock) => Err(TryLockError(())),
+    pub fn on_poison_recovered(self, hook: fn(Option<&'static str>)) -> Mutex<T, poison::Callback> {
+        Mutex {
+            instrumentation: self.instrumentation,
+            poison: self.poison,
+            policy: poison::Callback(hook),
+            inner: self.inner,
         }
     }
 
-    /// Like `std::sync::Mutex::get_mut`.
+    /// Creates a new mutex which calls `repair` on its value the first time it
+    /// is acquired after a panic while it was held.
+    ///
+    /// This is meant to restore the invariants of the value, for example by
+    /// rolling back a half-applied change, before any other code can see it.
+    /// `repair` runs with the mutex held, in the thread acquiring it, and then
+    /// the mark is cleared, so it runs once for each panic. If `repair` itself
+    /// panics, the mutex is released and marked again, and the next thread to
+    /// acquire it tries again.
     #[inline]
-    pub fn get_mut(&mut self) -> &mut T {
-        self.0.get_mut().unwrap_or_else(|e| e.into_inner())
+    pub const fn with_repair(t: T, repair: fn(&mut T)) -> Mutex<T, poison::Repair<T>> {
+        Mutex::with_policy(poison::Repair::new(repair), t)
     }
 }
 
-#[derive(Debug)]
-#[repr(transparent)]
-#[must_use]
-/// Like `std::sync::MutexGuard`.
-pub struct MutexGuard<'a, T: ?Sized + 'a>(sync::MutexGuard<'a, T>);
-
-impl<T: ?Sized> Deref for MutexGuard<'_, T> {
-    type Target = T;
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T, P: PoisonPolicy> Mutex<T, P> {
+    /// Creates a new mutex which reacts to being acquired after a panic while
+    /// it was held as `policy` decides.
+    #[inline]
+    pub const fn with_policy(policy: P, t: T) -> Mutex<T, P> {
+        Mutex {
+            instrumentation: held::Instrumentation::new(None, None),
+            poison: poison::Flag::new(),
+            policy,
+            inner: backend::new(t),
+        }
+    }
 
+    /// Resets the mutex to a fresh, unlocked state, keeping the protected
+    /// value.
+    ///
+    /// This is an escape hatch for a child process created by `fork` while
+    /// another thread held the lock. The lock state is discarded without
+    /// being inspected, and so are the record of a panic while it was held and
+    /// of its holder, as reported by [`was_poisoned`](Mutex::was_poisoned) and
+    /// the debugging features. Its name, level, policy and statistics are kept.
+    ///
+    /// As it takes `&mut self`, it cannot reset a lock in a `static`. Those
+    /// should be registered with the `fork` module instead, which keeps
+    /// them from being inherited locked in the first place.
     #[inline]
-    fn deref(&self) -> &T {
-        self.0.deref()
+    pub fn force_reinit(&mut self) {
+        // SAFETY: the old mutex is consumed exactly once and immediately replaced.
+        // Neither `into_inner` nor `new` can panic, so `self` is never observed
+        // in a moved-from state.
+        unsafe {
+            let t = backend::into_inner(ptr::read(&self.inner));
+            ptr::write(&mut self.inner, backend::new(t));
+        }
+        self.poison = poison::Flag::new();
+        self.instrumentation.forget_holder();
     }
 }
 
-impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T, P: PoisonPolicy, R: MutexStorage> Mutex<T, P, R> {
+    /// Like `std::sync::Mutex::into_inner`.
     #[inline]
-    fn deref_mut(&mut self) -> &mut T {
-        self.0.deref_mut()
+    pub fn into_inner(self) -> T
+    where
+        // Always true, but not implied by `T: Sized` for any `R`.
+        R::Mutex<T>: Sized,
+    {
+        R::into_inner(self.inner)
     }
-}
 
-#[derive(Debug, Default)]
-#[repr(transparent)]
-/// Like `std::sync::Condvar`.
-pub struct Condvar(sync::Condvar);
+    /// Replaces the value with `new` if it is equal to `current`, all under a
+    /// single acquisition of the lock.
+    ///
+    /// On success, returns the previous value. Otherwise, `new` is returned
+    /// and the value is left unchanged.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn compare_exchange(&self, current: &T, new: T) -> Result<T, T>
+    where
+        T: PartialEq,
+    {
+        let mut guard = self.lock();
+        if *guard == *current {
+            Ok(mem::replace(&mut *guard, new))
+        } else {
+            Err(new)
+        }
+    }
 
-impl Condvar {
-    /// Like `std::sync::Condvar::new`.
+    /// Stores `value`, dropping the previous one after the lock is released.
     #[inline]
-    pub const fn new() -> Condvar {
-        Condvar(sync::Condvar::new())
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn set(&self, value: T) {
+        drop(self.replace(value));
     }
 
-    /// Like `std::sync::Condvar::wait`.
+    /// Stores `value` and returns the previous one.
     #[inline]
-    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
-        MutexGuard(self.0.wait(guard.0).unwrap_or_else(|e| e.into_inner()))
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn replace(&self, value: T) -> T {
+        mem::replace(&mut *self.lock(), value)
     }
 
-    /// Like `std::sync::Condvar::wait_timeout`.
+    /// Takes the value, leaving `T::default()` in its place.
     #[inline]
-    pub fn wait_timeout<'a, T>(
-        &self,
-        guard: MutexGuard<'a, T>,
-        dur: Duration,
-    ) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
-        let (guard, result) = self
-            .0
-            .wait_timeout(guard.0, dur)
-            .unwrap_or_else(|e| e.into_inner());
-        (MutexGuard(guard), result)
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn take(&self) -> T
+    where
+        T: Default,
+    {
+        mem::take(&mut *self.lock())
     }
 
-    /// Like `std::sync::Condvar::notify_one`.
+    /// Returns a clone of the value.
     #[inline]
-    pub fn notify_one(&self) {
-        self.0.notify_one()
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn get_cloned(&self) -> T
+    where
+        T: Clone,
+    {
+        self.lock().clone()
     }
 
-    /// Like `std::sync::Condvar::notify_all`.
+    /// Updates the value with `f` under a single acquisition of the mutex, and
+    /// returns a clone of the value from before the update.
+    ///
+    /// If `f` panics, the value is left as `f` left it.
     #[inline]
-    pub fn notify_all(&self) {
-        self.0.notify_all()
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn fetch_update(&self, f: impl FnOnce(&mut T)) -> T
+    where
+        T: Clone,
+    {
+        let mut guard = self.lock();
+        let previous = guard.clone();
+        f(&mut guard);
+        previous
     }
 }
 
-/// Like `std::sync::TryLockResult`.
-pub type TryLockResult<T> = Result<T, TryLockError>;
+// Only for the built-in mutex, so that `Mutex::from(t)` does not leave the
+// backend to be inferred.
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T> From<T> for Mutex<T> {
+    /// Like `std::sync::Mutex::from`.
+    #[inline]
+    fn from(t: T) -> Mutex<T> {
+        Mutex::new(t)
+    }
+}
 
-/// Like `std::sync::TryLockError`.
-#[derive(Debug)]
-pub struct TryLockError(());
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: Clone, P: PoisonPolicy + Clone> Clone for Mutex<T, P> {
+    /// Creates an unlocked mutex holding a clone of the value, taken while
+    /// holding this one.
+    ///
+    /// The new mutex has the same name, level and policy, but starts afresh
+    /// otherwise, without statistics, thresholds or a mark left by a panic.
+    fn clone(&self) -> Mutex<T, P> {
+        Mutex {
+            instrumentation: self.instrumentation.fresh(),
+            poison: poison::Flag::new(),
+            policy: self.policy.clone(),
+            inner: backend::new(self.get_cloned()),
+        }
+    }
+}
 
-impl fmt::Display for TryLockError {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.write_str("lock call failed because the operation would block")
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: Clone, P: PoisonPolicy + Clone, R: MutexBackend + Clone> Clone for Mutex<T, P, R> {
+    /// Creates an unlocked mutex holding a clone of the value, taken while
+    /// holding this one.
+    ///
+    /// The new mutex has the same name, level and policy, and is built on a
+    /// clone of the raw mutex. It starts afresh otherwise, without statistics,
+    /// thresholds or a mark left by a panic.
+    fn clone(&self) -> Mutex<T, P, R> {
+        Mutex {
+            instrumentation: self.instrumentation.fresh(),
+            poison: poison::Flag::new(),
+            policy: self.policy.clone(),
+            inner: raw::BackendMutex::new(self.inner.raw().clone(), self.get_cloned()),
+        }
     }
 }
 
-#[derive(Debug, Default)]
-#[repr(transparent)]
-/// Like `std::sync::RwLock` except that it does not poison itself.
-pub struct RwLock<T: ?Sized>(sync::RwLock<T>);
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized + PartialEq, P: PoisonPolicy, R: MutexStorage> PartialEq for Mutex<T, P, R> {
+    /// Compares the values of the mutexes while holding both.
+    ///
+    /// The mutexes are acquired in order of their addresses, so that threads
+    /// comparing the same mutexes the other way around cannot deadlock. A
+    /// mutex compared with itself is only acquired once.
+    fn eq(&self, other: &Mutex<T, P, R>) -> bool {
+        if self.addr() == other.addr() {
+            let guard = self.lock();
+            // Still compared, for values such as NaN which are not equal to
+            // themselves.
+            return T::eq(&guard, &guard);
+        }
+        if self.addr() < other.addr() {
+            let a = self.lock();
+            let b = other.lock();
+            *a == *b
+        } else {
+            let b = other.lock();
+            let a = self.lock();
+            *a == *b
+        }
+    }
+}
 
-impl<T> RwLock<T> {
-    /// Like `std::sync::RwLock::new`.
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized + Eq, P: PoisonPolicy, R: MutexStorage> Eq for Mutex<T, P, R> {}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized + Hash, P: PoisonPolicy, R: MutexStorage> Hash for Mutex<T, P, R> {
+    /// Hashes the value while holding the mutex.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.lock().hash(state);
+    }
+}
+
+// How long a timed acquisition sleeps at most before checking the mutex again.
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+const TIMED_LOCK_RECHECK: Duration = Duration::from_millis(1);
+
+// The standard library's mutex cannot wait with a timeout, and the same path
+// serves every backend, so timed acquisitions park on the address of the mutex
+// instead, where its guards look for requeued condition variable waiters as
+// they release it. A release racing with a thread about to park may not see it,
+// so parked threads also retry `try_lock` every `TIMED_LOCK_RECHECK`.
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+#[cold]
+fn lock_until_contended<G>(
+    addr: usize,
+    mut try_lock: impl FnMut() -> Option<G>,
+    deadline: Option<Instant>,
+    cancel: Option<&CancelToken>,
+) -> Option<G> {
+    for _ in 0..config().spin_iterations() {
+        hint::spin_loop();
+        if let Some(t) = try_lock() {
+            return Some(t);
+        }
+    }
+    let cancel = cancel.map(|token| token.register(addr));
+    let cancelled = || {
+        cancel
+            .as_ref()
+            .map_or(false, |cancel| cancel.is_cancelled())
+    };
+    loop {
+        let now = clock::now();
+        if deadline.map_or(false, |deadline| now >= deadline) || cancelled() {
+            return None;
+        }
+        let recheck = now.checked_add(TIMED_LOCK_RECHECK);
+        let wake = match (deadline, recheck) {
+            (Some(deadline), Some(recheck)) => Some(deadline.min(recheck)),
+            (deadline, recheck) => deadline.or(recheck),
+        };
+        let mut acquired = None;
+        parking::park(
+            addr,
+            || {
+                acquired = try_lock();
+                acquired.is_none() && !cancelled()
+            },
+            || {},
+            |_, _| {},
+            wake,
+        );
+        if let Some(t) = acquired.or_else(&mut try_lock) {
+            return Some(t);
+        }
+    }
+}
+
+// Whether the target has no threads, such as `wasm32-unknown-unknown` without
+// the `atomics` target feature. A thread which has to wait for a lock or a
+// notification there could never be woken, so it panics with `deadlock`
+// instead of hanging.
+#[cfg(any(not(feature = "std"), not(any(loom, shuttle))))]
+const NO_THREADS: bool = cfg!(all(target_arch = "wasm32", not(target_feature = "atomics")));
+
+#[cfg(any(not(feature = "std"), not(any(loom, shuttle))))]
+#[cold]
+#[track_caller]
+fn deadlock() -> ! {
+    panic!("deadlock: waiting for another thread on a target without threads")
+}
+
+// Drops the guard behind `guard`, runs `f`, and puts the guard returned by
+// `relock` back in its place, even if `f` panics.
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+fn unlocked<G, U>(guard: &mut G, relock: impl FnOnce() -> G, f: impl FnOnce() -> U) -> U {
+    struct Relock<'g, G, R: FnOnce() -> G> {
+        guard: &'g mut G,
+        relock: Option<R>,
+    }
+
+    impl<G, R: FnOnce() -> G> Drop for Relock<'_, G, R> {
+        fn drop(&mut self) {
+            let relock = self.relock.take().unwrap();
+            // The caller drops the guard again once this returns, so there is
+            // no way to go on without one.
+            match std::panic::catch_unwind(std::panic::AssertUnwindSafe(relock)) {
+                // SAFETY: the old guard was dropped in place below.
+                Ok(new) => unsafe { ptr::write(self.guard, new) },
+                Err(_) => std::process::abort(),
+            }
+        }
+    }
+
+    // SAFETY: the guard is exclusively borrowed until `Relock` replaces it, so
+    // nothing sees it in between.
+    unsafe { ptr::drop_in_place(guard) };
+    let _relock = Relock {
+        guard,
+        relock: Some(relock),
+    };
+    f()
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized, P: PoisonPolicy, R: MutexStorage> Mutex<T, P, R> {
+    /// Like `std::sync::Mutex::lock`.
+    ///
+    /// In builds with debug assertions, and without the `send-guard` feature,
+    /// this panics if the current thread already holds the mutex, instead of
+    /// deadlocking. So do the other blocking acquisitions.
     #[inline]
-    pub const fn new(t: T) -> RwLock<T> {
-        RwLock(sync::RwLock::new(t))
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn lock(&self) -> MutexGuard<'_, T, P, R> {
+        MutexGuard::checked(self.lock_quiet())
     }
 
-    /// Like `std::sync::RwLock::into_inner`.
+    // Like `lock`, but leaves consulting the policy to the caller.
     #[inline]
-    pub fn into_inner(self) -> T
-    where
-        T: Sized,
-    {
-        self.0.into_inner().unwrap_or_else(|e| e.into_inner())
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    fn lock_quiet(&self) -> MutexGuard<'_, T, P, R> {
+        held::check_order(self.addr(), self.meta());
+        held::before_acquire();
+        match R::try_lock(&self.inner) {
+            Some(t) => MutexGuard::new(self, t, held::Wait::NONE),
+            None => self.lock_contended(),
+        }
     }
-}
 
-impl<T: ?Sized> RwLock<T> {
-    /// Like `std::sync::RwLock::read`.
+    #[cold]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    fn lock_contended(&self) -> MutexGuard<'_, T, P, R> {
+        let wait = held::Wait::start(self.addr(), self.meta());
+        for _ in 0..config().spin_iterations() {
+            hint::spin_loop();
+            if let Some(t) = R::try_lock(&self.inner) {
+                return MutexGuard::new(self, t, wait);
+            }
+        }
+        let _waiting = contention::Waiting::new(self.addr());
+        let guard = R::lock(&self.inner);
+        MutexGuard::new(self, guard, wait)
+    }
+
+    /// Like [`lock`](Mutex::lock), but assumes that the standard library's
+    /// mutex underneath is not poisoned, so that the fast path skips checking
+    /// it.
+    ///
+    /// The check is already skipped by `lock` when the program is built with
+    /// `panic = "abort"`, as no thread can then panic while holding the mutex,
+    /// and there is none to skip with the `futex` and `parking_lot` features,
+    /// whose mutexes do not poison. This is then the same as `lock`.
+    ///
+    /// # Safety
+    ///
+    /// No thread may have panicked while holding the mutex, whatever its
+    /// [`PoisonPolicy`], since it was created.
     #[inline]
-    pub fn read(&self) -> RwLockReadGuard<'_, T> {
-        RwLockReadGuard(self.0.read().unwrap_or_else(|e| e.into_inner()))
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub unsafe fn lock_unchecked(&self) -> MutexGuard<'_, T, P, R> {
+        held::check_order(self.addr(), self.meta());
+        held::before_acquire();
+        // SAFETY: guaranteed by the caller.
+        let guard = match unsafe { R::try_lock_unpoisoned(&self.inner) } {
+            Some(t) => MutexGuard::new(self, t, held::Wait::NONE),
+            None => self.lock_contended(),
+        };
+        MutexGuard::checked(guard)
     }
 
-    /// Like `std::sync::RwLock::try_read`.
+    /// Like `std::sync::Mutex::try_lock`.
     #[inline]
-    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
-        match self.0.try_read() {
-            Ok(t) => Ok(RwLockReadGuard(t)),
-            Err(sync::TryLockError::Poisoned(e)) => Ok(RwLockReadGuard(e.into_inner())),
-            Err(sync::TryLockError::WouldBlock) => Err(TryLockError(())),
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T, P, R>> {
+        held::before_acquire();
+        match R::try_lock(&self.inner) {
+            Some(t) => Ok(MutexGuard::checked(MutexGuard::new(
+                self,
+                t,
+                held::Wait::NONE,
+            ))),
+            None => {
+                held::acquire_failed(self.addr());
+                Err(TryLockError::failed(LockOperation::Lock, self.meta()))
+            }
         }
     }
 
-    /// Like `std::sync::RwLock::write`.
+    /// Calls `f` with exclusive access to the value, releasing the mutex as
+    /// soon as it returns.
+    ///
+    /// Since the guard never escapes, it cannot be held across an `.await` or
+    /// for longer than intended by mistake.
     #[inline]
-    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
-        RwLockWriteGuard(self.0.write().unwrap_or_else(|e| e.into_inner()))
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> U {
+        f(&mut self.lock())
     }
 
-    /// Like `std::sync::RwLock::try_write`.
+    /// Updates the value with `f` under a single acquisition of the mutex, and
+    /// returns what `f` returns.
+    ///
+    /// This is [`with_lock`](Mutex::with_lock) under the name of the atomic
+    /// types' read-modify-write operations.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn update<U>(&self, f: impl FnOnce(&mut T) -> U) -> U {
+        self.with_lock(f)
+    }
+
+    /// Attempts to acquire the mutex, blocking the current thread for at most
+    /// `timeout`, and returns `None` if it could not.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_lock_for(&self, timeout: Duration) -> Option<MutexGuard<'_, T, P, R>> {
+        // A deadline too far in the future to represent is treated as no deadline at
+        // all.
+        self.try_lock_until_internal(clock::now().checked_add(timeout), None)
+    }
+
+    /// Attempts to acquire the mutex, blocking the current thread until at most
+    /// `deadline`, and returns `None` if it could not.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_lock_until(&self, deadline: Instant) -> Option<MutexGuard<'_, T, P, R>> {
+        self.try_lock_until_internal(Some(deadline), None)
+    }
+
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so or `token` is cancelled.
+    ///
+    /// A mutex which is free is acquired even if `token` has already been
+    /// cancelled: cancellation only interrupts waiting.
     #[inline]
-    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
-        match self.0.try_write() {
-            Ok(t) => Ok(RwLockWriteGuard(t)),
-            Err(sync::TryLockError::Poisoned(e)) => Ok(RwLockWriteGuard(e.into_inner())),
-            Err(sync::TryLockError::WouldBlock) => Err(TryLockError(())),
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn lock_cancellable(
+        &self,
+        token: &CancelToken,
+    ) -> Result<MutexGuard<'_, T, P, R>, Cancelled> {
+        self.try_lock_until_internal(None, Some(token))
+            .ok_or(Cancelled(()))
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    fn try_lock_until_internal(
+        &self,
+        deadline: Option<Instant>,
+        cancel: Option<&CancelToken>,
+    ) -> Option<MutexGuard<'_, T, P, R>> {
+        held::check_order(self.addr(), self.meta());
+        held::before_acquire();
+        if let Some(t) = R::try_lock(&self.inner) {
+            return Some(MutexGuard::checked(MutexGuard::new(
+                self,
+                t,
+                held::Wait::NONE,
+            )));
+        }
+        let wait = held::Wait::start(self.addr(), self.meta());
+        let _waiting = contention::Waiting::new(self.addr());
+        match lock_until_contended(self.addr(), || R::try_lock(&self.inner), deadline, cancel) {
+            Some(t) => Some(MutexGuard::checked(MutexGuard::new(self, t, wait))),
+            None => {
+                wait.give_up(self.addr());
+                None
+            }
         }
     }
 
-    /// Like `std::sync::RwLock::get_mut`.
+    /// Returns `true` if other threads appear to be blocked waiting to acquire
+    /// the mutex.
+    ///
+    /// This is an advisory hint for cooperative code that wants to shorten its
+    /// critical section when others are waiting. The answer may be stale as
+    /// soon as it is returned, and it may occasionally report contention
+    /// caused by an unrelated lock.
+    #[inline]
+    pub fn is_contended(&self) -> bool {
+        contention::is_waiting(self.addr())
+    }
+
+    /// Returns `true` if the mutex is currently held.
+    ///
+    /// Unlike [`try_lock`](Mutex::try_lock), this is not seen as an
+    /// acquisition by the instrumentation. The standard library's mutex
+    /// cannot be inspected, so without the `parking_lot` feature it is still
+    /// acquired and released right away if it is free. The answer may be stale
+    /// as soon as it is returned.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        R::is_locked(&self.inner)
+    }
+
+    /// Returns `true` if a guard of the mutex was dropped by a panicking
+    /// thread since it was created or last passed to
+    /// [`clear_poison`](Mutex::clear_poison).
+    ///
+    /// The mutex is not poisoned either way: this only tells that the value
+    /// may have been left half-updated, for example to log it.
+    #[inline]
+    pub fn was_poisoned(&self) -> bool {
+        self.poison.get()
+    }
+
+    /// Forgets that a panicking thread held the mutex, once the value is
+    /// known to be consistent again.
+    #[inline]
+    pub fn clear_poison(&self) {
+        self.poison.clear();
+    }
+
+    #[cold]
+    fn unpark_requeued(&self) {
+        parking::unpark_one(self.addr(), |_| parking::DEFAULT_TOKEN);
+    }
+
+    /// Like `std::sync::Mutex::get_mut`.
     #[inline]
     pub fn get_mut(&mut self) -> &mut T {
-        self.0.get_mut().unwrap_or_else(|e| e.into_inner())
+        R::get_mut(&mut self.inner)
+    }
+
+    #[inline]
+    fn addr(&self) -> usize {
+        self as *const Self as *const () as usize
+    }
+
+    /// Returns the name given to the lock with `new_named`, if any.
+    ///
+    /// This is always `None` without the `names` feature.
+    #[inline]
+    pub fn name(&self) -> Option<&'static str> {
+        self.meta().name()
+    }
+
+    #[inline]
+    fn meta(&self) -> held::Meta {
+        self.instrumentation.meta()
     }
 }
 
-#[derive(Debug)]
-#[repr(transparent)]
-#[must_use]
-/// Like `std::sync::RwLockReadGuard`.
-pub struct RwLockReadGuard<'a, T: ?Sized + 'a>(sync::RwLockReadGuard<'a, T>);
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized, P: PoisonPolicy> Mutex<T, P> {
+    /// Acquires the mutex, registering the guard with the lease watchdog.
+    ///
+    /// If the guard is still alive after `max_hold`, the action configured with
+    /// [`lease::set_action`] fires. See the [`lease`] module for details.
+    #[inline]
+    #[track_caller]
+    pub fn lock_leased(&self, max_hold: Duration) -> lease::LeasedMutexGuard<'_, T, P> {
+        lease::LeasedMutexGuard::new(self.lock(), max_hold, None)
+    }
 
-impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
-    type Target = T;
+    /// Like [`lock_leased`](Mutex::lock_leased), but fires `action` instead of
+    /// the one configured with [`lease::set_action`] if the lease runs out.
+    #[inline]
+    #[track_caller]
+    pub fn lock_leased_with(
+        &self,
+        max_hold: Duration,
+        action: lease::LeaseAction,
+    ) -> lease::LeasedMutexGuard<'_, T, P> {
+        lease::LeasedMutexGuard::new(self.lock(), max_hold, Some(action))
+    }
 
+    /// Returns a raw pointer to the data, without locking the mutex.
+    ///
+    /// Reading or writing through the pointer is only sound while the mutex
+    /// is held by whoever does so. The standard library's mutex does not lend
+    /// out its data this way, so this needs the `parking_lot` or `futex`
+    /// feature, unless the mutex is built on a [`MutexBackend`].
+    #[cfg(any(feature = "parking_lot", feature = "futex"))]
+    #[cfg_attr(docsrs, doc(cfg(any(feature = "parking_lot", feature = "futex"))))]
     #[inline]
-    fn deref(&self) -> &T {
-        self.0.deref()
+    pub fn data_ptr(&self) -> *mut T {
+        backend::data_ptr(&self.inner)
+    }
+
+    /// Releases the mutex without a guard.
+    ///
+    /// This completes a handoff in which a guard was given up with
+    /// [`MutexGuard::leak`] and the lock passed on, for example to C code
+    /// which finishes on another thread. `leak` is preferable to
+    /// `mem::forget`, which leaves the lock reported as held by the thread
+    /// which took it. The standard library's mutex can only be released
+    /// through its guard, so this needs the `parking_lot` or `futex` feature,
+    /// unless the mutex is built on a [`MutexBackend`].
+    ///
+    /// # Safety
+    ///
+    /// The mutex must be locked, and the caller must be entitled to release
+    /// it: it acquired it itself, or took over from whoever did. In
+    /// particular, it must not be released this way while a guard for it is
+    /// alive.
+    #[cfg(any(feature = "parking_lot", feature = "futex"))]
+    #[cfg_attr(docsrs, doc(cfg(any(feature = "parking_lot", feature = "futex"))))]
+    #[inline]
+    pub unsafe fn force_unlock(&self) {
+        backend::force_unlock(&self.inner);
+        if parking::may_have_parked(self.addr()) {
+            self.unpark_requeued();
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized, P: PoisonPolicy, R: MutexBackend> Mutex<T, P, R> {
+    /// Returns a raw pointer to the data, without locking the mutex.
+    ///
+    /// Reading or writing through the pointer is only sound while the mutex
+    /// is held by whoever does so.
+    #[inline]
+    pub fn data_ptr(&self) -> *mut T {
+        self.inner.data_ptr()
+    }
+
+    /// Releases the mutex without a guard, by calling
+    /// [`MutexBackend::unlock`].
+    ///
+    /// This completes a handoff in which a guard was given up with
+    /// [`MutexGuard::leak`], like the `force_unlock` of the built-in mutex.
+    ///
+    /// # Safety
+    ///
+    /// The mutex must be locked, and the caller must be entitled to release
+    /// it: it acquired it itself, or took over from whoever did. In
+    /// particular, it must not be released this way while a guard for it is
+    /// alive.
+    #[inline]
+    pub unsafe fn force_unlock(&self) {
+        self.inner.raw().unlock();
+        if parking::may_have_parked(self.addr()) {
+            self.unpark_requeued();
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized + fmt::Debug, P: PoisonPolicy, R: MutexStorage> fmt::Debug for Mutex<T, P, R> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_struct("Mutex");
+        if let Some(name) = self.name() {
+            d.field("name", &name);
+        }
+        // The backend is used directly so that formatting is not seen as an
+        // acquisition by the instrumentation.
+        match R::try_lock(&self.inner) {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
     }
 }
 
-#[derive(Debug)]
-#[repr(transparent)]
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
 #[must_use]
-/// Like `std::sync::RwLockWriteGuard`.
-pub struct RwLockWriteGuard<'a, T: ?Sized + 'a>(sync::RwLockWriteGuard<'a, T>);
+/// Like `std::sync::MutexGuard`.
+pub struct MutexGuard<'a, T: ?Sized + 'a, P: PoisonPolicy = Ignore, R: MutexStorage = BuiltinMutex>
+{
+    lock: &'a Mutex<T, P, R>,
+    guard: ManuallyDrop<R::Guard<'a, T>>,
+    poison: poison::Guard,
+    _held: held::Token,
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<'a, T: ?Sized, P: PoisonPolicy, R: MutexStorage> MutexGuard<'a, T, P, R> {
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    fn new(
+        lock: &'a Mutex<T, P, R>,
+        guard: R::Guard<'a, T>,
+        wait: held::Wait,
+    ) -> MutexGuard<'a, T, P, R> {
+        MutexGuard {
+            lock,
+            guard: ManuallyDrop::new(guard),
+            poison: lock.poison.guard(),
+            _held: held::Token::new(lock.addr(), held::Access::Exclusive, lock.meta(), wait),
+        }
+    }
+
+    /// Returns the mutex this guard holds.
+    ///
+    /// This lets code given only the guard release and reacquire the mutex, or
+    /// tell which lock it holds, with `ptr::eq`. This is an associated
+    /// function so that it does not shadow a method of `T`.
+    #[inline]
+    pub fn mutex(s: &Self) -> &'a Mutex<T, P, R> {
+        s.lock
+    }
+
+    // Lets the policy of the mutex react if it was acquired after a panic
+    // while it was held. If the policy panics, the guard is dropped first.
+    #[inline]
+    fn checked(mut s: Self) -> Self {
+        if s.poison.recovered() {
+            Self::recover(&mut s);
+        }
+        s
+    }
+
+    #[cold]
+    fn recover(s: &mut Self) {
+        let lock = s.lock;
+        poison::report(lock.name());
+        // SAFETY: the guard is alive, and the data is the value of the mutex
+        // this is the policy of.
+        if unsafe { lock.policy.repair(&mut **s.guard as *mut T as *mut ()) } {
+            lock.poison.clear();
+        }
+        lock.policy.recovered(lock.name());
+    }
+
+    /// Releases the mutex, runs `f`, and acquires the mutex again before
+    /// returning what `f` returned.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`. It is meant for work like blocking I/O which should not hold the
+    /// lock, without giving up the guard. The mutex is acquired again even if
+    /// `f` panics, and other threads may change the value in the meantime.
+    pub fn unlocked<U>(s: &mut Self, f: impl FnOnce() -> U) -> U {
+        let lock = s.lock;
+        // The policy may panic, which it can only do once the guard is back.
+        let result = unlocked(s, || lock.lock_quiet(), f);
+        if s.poison.recovered() {
+            Self::recover(s);
+        }
+        result
+    }
+
+    /// Releases and immediately reacquires the mutex if other threads are
+    /// waiting for it, to give them a chance to acquire it first.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`. Whether a waiter gets in before the mutex is acquired again is up
+    /// to the scheduler.
+    pub fn bump(s: &mut Self) {
+        if s.lock.is_contended() {
+            Self::unlocked(s, thread::yield_now);
+        }
+    }
 
-impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    /// Returns `true` if the mutex was marked by a panicking thread when the
+    /// guard acquired it, as reported by [`Mutex::was_poisoned`].
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`.
+    #[inline]
+    pub fn recovered_from_poison(s: &Self) -> bool {
+        s.poison.recovered()
+    }
+
+    /// Releases the mutex, exactly like dropping the guard.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`. Spelled out at the call site, the release stands out in review
+    /// where `drop(guard)` is easy to miss, or to mistake for dropping the
+    /// data.
+    #[inline]
+    pub fn unlock(s: Self) {
+        drop(s);
+    }
+
+    /// Consumes the guard without releasing the mutex, returning a reference
+    /// to the data which lasts as long as the mutex is borrowed.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`. The mutex stays locked unless it is released with
+    /// [`Mutex::force_unlock`], and is no longer reported as held by the
+    /// instrumentation.
+    pub fn leak(s: Self) -> &'a mut T {
+        let mut s = ManuallyDrop::new(s);
+        // SAFETY: the guard is never used or dropped again, so the token is
+        // moved out of it only once, and the backend guard is never released.
+        let held = unsafe { ptr::read(&s._held) };
+        held.releasing();
+        let data: *mut T = &mut **s.guard;
+        // SAFETY: the mutex cannot be acquired again, and it outlives `'a`.
+        unsafe { &mut *data }
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized, P: PoisonPolicy, R: MutexStorage> Drop for MutexGuard<'_, T, P, R> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.poison.done(&self.poison);
+        self._held.releasing();
+        // SAFETY: the guard is never touched again.
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        // Condvar waiters requeued onto this mutex are woken one at a time as it is
+        // released.
+        if parking::may_have_parked(self.lock.addr()) {
+            self.lock.unpark_requeued();
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized, P: PoisonPolicy, R: MutexStorage> Deref for MutexGuard<'_, T, P, R> {
     type Target = T;
 
     #[inline]
     fn deref(&self) -> &T {
-        self.0.deref()
+        self.guard.deref()
     }
 }
 
-impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized, P: PoisonPolicy, R: MutexStorage> DerefMut for MutexGuard<'_, T, P, R> {
     #[inline]
     fn deref_mut(&mut self) -> &mut T {
-        self.0.deref_mut()
+        self.guard.deref_mut()
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized + fmt::Debug, P: PoisonPolicy, R: MutexStorage> fmt::Debug
+    for MutexGuard<'_, T, P, R>
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("MutexGuard").field(&&**self).finish()
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+#[derive(Default)]
+/// Like `std::sync::Condvar`.
+///
+/// Unlike the standard library's condition variable, waiters are queued by this
+/// crate, which allows the `*_counted` notification methods to report how many
+/// threads they woke, and `notify_all_requeue` to hand waiters over to a mutex.
+/// None of this relies on the platform's own condition variable. Waits do not
+/// return spuriously.
+pub struct Condvar {
+    // The number of queued waiters. Waiters park on the address of this field.
+    waiters: AtomicUsize,
+    // The address of the mutex used by the queued waiters, or `MIXED_MUTEXES`. Like `waiters`,
+    // only modified with the waiters' bucket locked.
+    mutex: AtomicUsize,
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+const MIXED_MUTEXES: usize = usize::MAX;
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl Condvar {
+    /// Like `std::sync::Condvar::new`.
+    #[inline]
+    pub const fn new() -> Condvar {
+        Condvar {
+            waiters: AtomicUsize::new(0),
+            mutex: AtomicUsize::new(0),
+        }
+    }
+
+    /// Like `std::sync::Condvar::wait`.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn wait<'a, T, P: PoisonPolicy, R: MutexStorage>(
+        &self,
+        guard: MutexGuard<'a, T, P, R>,
+    ) -> MutexGuard<'a, T, P, R> {
+        self.wait_until_internal(guard, None).0
+    }
+
+    /// Like `std::sync::Condvar::wait_timeout`.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn wait_timeout<'a, T, P: PoisonPolicy, R: MutexStorage>(
+        &self,
+        guard: MutexGuard<'a, T, P, R>,
+        dur: Duration,
+    ) -> (MutexGuard<'a, T, P, R>, WaitTimeoutResult) {
+        // A deadline too far in the future to represent is treated as no deadline at
+        // all.
+        self.wait_until_internal(guard, clock::now().checked_add(dur))
+    }
+
+    /// Like `std::sync::Condvar::wait_while`.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn wait_while<'a, T, P: PoisonPolicy, R: MutexStorage, F>(
+        &self,
+        mut guard: MutexGuard<'a, T, P, R>,
+        mut condition: F,
+    ) -> MutexGuard<'a, T, P, R>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    /// Like `std::sync::Condvar::wait_timeout_while`.
+    ///
+    /// The returned `WaitTimeoutResult` reports a timeout only if `condition`
+    /// still held when the wait gave up.
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn wait_timeout_while<'a, T, P: PoisonPolicy, R: MutexStorage, F>(
+        &self,
+        guard: MutexGuard<'a, T, P, R>,
+        dur: Duration,
+        condition: F,
+    ) -> (MutexGuard<'a, T, P, R>, WaitTimeoutResult)
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        self.wait_while_until_internal(guard, clock::now().checked_add(dur), condition)
+    }
+
+    /// Like `wait_timeout`, but gives up waiting once `deadline` has passed.
+    ///
+    /// Unlike a duration, the deadline stays put when a caller waits again
+    /// after a spurious wakeup, so repeated waits cannot extend it.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn wait_until<'a, T, P: PoisonPolicy, R: MutexStorage>(
+        &self,
+        guard: MutexGuard<'a, T, P, R>,
+        deadline: Instant,
+    ) -> (MutexGuard<'a, T, P, R>, WaitTimeoutResult) {
+        self.wait_until_internal(guard, Some(deadline))
+    }
+
+    /// Like `wait_timeout_while`, but gives up waiting once `deadline` has
+    /// passed.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn wait_while_until<'a, T, P: PoisonPolicy, R: MutexStorage, F>(
+        &self,
+        guard: MutexGuard<'a, T, P, R>,
+        deadline: Instant,
+        condition: F,
+    ) -> (MutexGuard<'a, T, P, R>, WaitTimeoutResult)
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        self.wait_while_until_internal(guard, Some(deadline), condition)
+    }
+
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    fn wait_while_until_internal<'a, T, P: PoisonPolicy, R: MutexStorage, F>(
+        &self,
+        mut guard: MutexGuard<'a, T, P, R>,
+        deadline: Option<Instant>,
+        mut condition: F,
+    ) -> (MutexGuard<'a, T, P, R>, WaitTimeoutResult)
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            let (new_guard, result) = self.wait_until_internal(guard, deadline);
+            guard = new_guard;
+            if result.timed_out() {
+                let timed_out = condition(&mut *guard);
+                return (guard, WaitTimeoutResult(timed_out));
+            }
+        }
+        (guard, WaitTimeoutResult(false))
+    }
+
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    fn wait_until_internal<'a, T, P: PoisonPolicy, R: MutexStorage>(
+        &self,
+        guard: MutexGuard<'a, T, P, R>,
+        deadline: Option<Instant>,
+    ) -> (MutexGuard<'a, T, P, R>, WaitTimeoutResult) {
+        let lock = guard.lock;
+        let mut requeued = false;
+        let result = parking::park(
+            self.key(),
+            || {
+                if self.waiters.fetch_add(1, Ordering::Relaxed) == 0 {
+                    self.mutex.store(lock.addr(), Ordering::Relaxed);
+                } else if self.mutex.load(Ordering::Relaxed) != lock.addr() {
+                    self.mutex.store(MIXED_MUTEXES, Ordering::Relaxed);
+                }
+                true
+            },
+            || drop(guard),
+            |key, _| {
+                if key == self.key() {
+                    self.remove_waiters(1);
+                } else {
+                    // We were notified and moved to the mutex's queue before timing out.
+                    requeued = true;
+                }
+            },
+            deadline,
+        );
+        let timed_out = result == ParkResult::TimedOut && !requeued;
+        (lock.lock(), WaitTimeoutResult(timed_out))
+    }
+
+    /// Like `std::sync::Condvar::notify_one`, but returns `true` if a thread
+    /// was woken.
+    ///
+    /// It returns `false` if no thread was waiting, in which case the
+    /// notification is lost, as with the standard library.
+    #[inline]
+    pub fn notify_one(&self) -> bool {
+        self.notify_one_counted() != 0
+    }
+
+    /// Like `std::sync::Condvar::notify_all`.
+    #[inline]
+    pub fn notify_all(&self) {
+        self.notify_all_counted();
+    }
+
+    /// Like `notify_one`, but returns the number of threads woken, which is
+    /// either 0 or 1.
+    #[inline]
+    pub fn notify_one_counted(&self) -> usize {
+        if self.waiters.load(Ordering::Relaxed) == 0 {
+            return 0;
+        }
+        parking::unpark_one(self.key(), |result| self.unparked(result)).unparked
+    }
+
+    /// Like `notify_all`, but returns the number of threads woken.
+    #[inline]
+    pub fn notify_all_counted(&self) -> usize {
+        if self.waiters.load(Ordering::Relaxed) == 0 {
+            return 0;
+        }
+        parking::unpark_all(self.key(), |result| self.unparked(result)).unparked
+    }
+
+    /// Wakes one waiter and moves the rest to the queue of `mutex`, returning
+    /// the number of threads notified.
+    ///
+    /// Unlike `notify_all`, this does not wake every waiter only for all but
+    /// one of them to block again on the mutex. The requeued waiters are
+    /// instead woken one at a time as the mutex is released. If the waiters
+    /// are not all using `mutex`, every one of them is woken instead.
+    pub fn notify_all_requeue<U: ?Sized, P: PoisonPolicy, R: MutexStorage>(
+        &self,
+        mutex: &Mutex<U, P, R>,
+    ) -> usize {
+        if self.waiters.load(Ordering::Relaxed) == 0 {
+            return 0;
+        }
+        let result = parking::unpark_requeue(
+            self.key(),
+            mutex.addr(),
+            || self.mutex.load(Ordering::Relaxed) == mutex.addr(),
+            |result| {
+                self.remove_waiters(result.unparked + result.requeued);
+                parking::DEFAULT_TOKEN
+            },
+        );
+        result.unparked + result.requeued
+    }
+
+    /// Returns the number of threads currently waiting on the condition
+    /// variable.
+    ///
+    /// The answer may be stale as soon as it is returned. A thread counts as
+    /// waiting from just before it releases its mutex until it is notified
+    /// or its wait times out.
+    #[inline]
+    pub fn waiters(&self) -> usize {
+        self.waiters.load(Ordering::Relaxed)
+    }
+
+    fn unparked(&self, result: UnparkResult) -> usize {
+        self.remove_waiters(result.unparked);
+        parking::DEFAULT_TOKEN
+    }
+
+    fn remove_waiters(&self, n: usize) {
+        if self.waiters.fetch_sub(n, Ordering::Relaxed) == n {
+            self.mutex.store(0, Ordering::Relaxed);
+        }
+    }
+
+    #[inline]
+    fn key(&self) -> usize {
+        &self.waiters as *const AtomicUsize as usize
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl fmt::Debug for Condvar {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Condvar").finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+/// Like `std::sync::WaitTimeoutResult`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct WaitTimeoutResult(bool);
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl WaitTimeoutResult {
+    /// Like `std::sync::WaitTimeoutResult::timed_out`.
+    #[inline]
+    pub fn timed_out(&self) -> bool {
+        self.0
+    }
+}
+
+/// Like `std::sync::TryLockResult`.
+pub type TryLockResult<T> = Result<T, TryLockError>;
+
+/// Like `std::sync::TryLockError`, for a lock which was not available.
+///
+/// It tells which operation failed, with the `names` feature the name of the
+/// lock, and with the `holder-location` feature where the lock was acquired,
+/// and converts into `std::sync::TryLockError::WouldBlock`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TryLockError {
+    operation: LockOperation,
+    name: Option<&'static str>,
+    holder: Option<&'static core::panic::Location<'static>>,
+}
+
+impl TryLockError {
+    #[inline]
+    pub(crate) const fn new(operation: LockOperation) -> TryLockError {
+        TryLockError {
+            operation,
+            name: None,
+            holder: None,
+        }
+    }
+
+    #[cfg(all(feature = "std", not(any(loom, shuttle))))]
+    #[inline]
+    fn failed(operation: LockOperation, meta: held::Meta) -> TryLockError {
+        TryLockError {
+            operation,
+            name: meta.name(),
+            holder: meta.holder_location(),
+        }
+    }
+
+    /// Returns the operation which failed.
+    #[inline]
+    pub fn operation(&self) -> LockOperation {
+        self.operation
+    }
+
+    /// Returns the name of the lock, if it has one.
+    ///
+    /// This is always `None` without the `names` feature, and for the locks
+    /// which cannot be named.
+    #[inline]
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// Returns the source location at which the lock was acquired by the
+    /// holder which made the operation fail.
+    ///
+    /// This is always `None` without the `holder-location` feature, for the
+    /// locks other than `Mutex` and `RwLock`, and if the holder released the
+    /// lock right after the operation failed. See the
+    /// [`holder`] module.
+    #[inline]
+    pub fn holder_location(&self) -> Option<&'static core::panic::Location<'static>> {
+        self.holder
+    }
+}
+
+impl fmt::Display for TryLockError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let access = match self.operation {
+            LockOperation::Lock => "",
+            LockOperation::Read => " for reading",
+            LockOperation::UpgradableRead => " for an upgradable read",
+            LockOperation::Write => " for writing",
+        };
+        match self.name {
+            Some(name) => write!(fmt, "locking '{}'{}", name, access)?,
+            None => write!(fmt, "locking{}", access)?,
+        }
+        fmt.write_str(" failed because the operation would block")?;
+        match self.holder {
+            Some(holder) => write!(fmt, ", as it is held by a guard acquired at {}", holder),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TryLockError {}
+
+#[cfg(feature = "std")]
+impl<T> From<TryLockError> for std::sync::TryLockError<T> {
+    #[inline]
+    fn from(_: TryLockError) -> std::sync::TryLockError<T> {
+        std::sync::TryLockError::WouldBlock
+    }
+}
+
+/// The kind of acquisition a [`TryLockError`] reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockOperation {
+    /// Exclusive access to a mutex.
+    Lock,
+    /// Shared access to a reader-writer lock.
+    Read,
+    /// Upgradable shared access to a reader-writer lock.
+    UpgradableRead,
+    /// Exclusive access to a reader-writer lock.
+    Write,
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+/// The error returned by a blocking primitive which has been closed.
+///
+/// Once a primitive is closed, every thread blocked in it is woken with this
+/// error, and so is every thread which tries to block in it afterwards.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Closed(());
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl fmt::Display for Closed {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("operation failed because the primitive was closed")
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+/// The error returned by an acquisition interrupted by a [`CancelToken`].
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Cancelled(());
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl fmt::Display for Cancelled {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("lock call failed because the operation was cancelled")
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+/// Like `std::sync::RwLock` except that it does not poison itself.
+///
+/// Unlike the standard library's lock, this one is built on [`RawRwLock`], so
+/// it behaves the same on every platform: once a thread has to wait for the
+/// lock, new readers queue up behind it, so a steady stream of readers cannot
+/// starve a writer. A lock created with
+/// [`with_fairness`](RwLock::with_fairness) can prefer readers or writers
+/// instead. It also offers upgradable access through
+/// [`upgradable_read`](RwLock::upgradable_read).
+///
+/// A lock created with [`with_raw`](RwLock::with_raw) is instead built on
+/// another raw lock implementing [`RwLockBackend`], which leaves it the core of
+/// the API.
+#[derive(Default)]
+pub struct RwLock<T: ?Sized, R: RwLockBackend = RawRwLock> {
+    raw: R,
+    instrumentation: held::Instrumentation,
+    poison: poison::Flag,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: the lock hands out `&mut T` to one thread at a time, or `&T` to any
+// number of threads.
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+unsafe impl<T: ?Sized + Send, R: RwLockBackend + Send> Send for RwLock<T, R> {}
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+unsafe impl<T: ?Sized + Send + Sync, R: RwLockBackend + Sync> Sync for RwLock<T, R> {}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized, R: RwLockBackend> UnwindSafe for RwLock<T, R> {}
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized, R: RwLockBackend> RefUnwindSafe for RwLock<T, R> {}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T, R: RwLockBackend> RwLock<T, R> {
+    /// Creates a new `RwLock` built on `raw` instead of [`RawRwLock`].
+    ///
+    /// See [`RwLockBackend`] for what such a lock offers.
+    #[inline]
+    pub const fn with_raw(raw: R, t: T) -> RwLock<T, R> {
+        RwLock {
+            raw,
+            instrumentation: held::Instrumentation::new(None, None),
+            poison: poison::Flag::new(),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Replaces the value with `new` if it is equal to `current`, all under a
+    /// single write lock.
+    ///
+    /// On success, returns the previous value. Otherwise, `new` is returned
+    /// and the value is left unchanged.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn compare_exchange(&self, current: &T, new: T) -> Result<T, T>
+    where
+        T: PartialEq,
+    {
+        let mut guard = self.write();
+        if *guard == *current {
+            Ok(mem::replace(&mut *guard, new))
+        } else {
+            Err(new)
+        }
+    }
+
+    /// Stores `value`, dropping the previous one after the lock is released.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn set(&self, value: T) {
+        drop(self.replace(value));
+    }
+
+    /// Stores `value` and returns the previous one.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn replace(&self, value: T) -> T {
+        mem::replace(&mut *self.write(), value)
+    }
+
+    /// Takes the value, leaving `T::default()` in its place.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn take(&self) -> T
+    where
+        T: Default,
+    {
+        mem::take(&mut *self.write())
+    }
+
+    /// Returns a clone of the value.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn get_cloned(&self) -> T
+    where
+        T: Clone,
+    {
+        self.read().clone()
+    }
+
+    /// Updates the value with `f` under a single write lock, and returns a
+    /// clone of the value from before the update, like
+    /// [`Mutex::fetch_update`].
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn fetch_update(&self, f: impl FnOnce(&mut T)) -> T
+    where
+        T: Clone,
+    {
+        let mut guard = self.write();
+        let previous = guard.clone();
+        f(&mut guard);
+        previous
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized, R: RwLockBackend> RwLock<T, R> {
+    /// Like `std::sync::RwLock::read`.
+    ///
+    /// As with the standard library, a thread that already holds a read guard
+    /// must not call this method again: if a writer is queued in between, the
+    /// second read may block behind it and deadlock. The crate's own raw lock
+    /// supports recursive reads through
+    /// [`read_recursive`](RwLock::read_recursive); a custom `RwLockBackend`
+    /// makes no such promise, so none is offered for it.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn read(&self) -> RwLockReadGuard<'_, T, R> {
+        held::check_order(self.addr(), self.meta());
+        held::before_acquire();
+        if self.raw.try_lock_shared() {
+            return RwLockReadGuard::new(self, held::Wait::NONE);
+        }
+        self.read_contended()
+    }
+
+    #[cold]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    fn read_contended(&self) -> RwLockReadGuard<'_, T, R> {
+        let wait = held::Wait::start(self.addr(), self.meta());
+        self.raw.lock_shared();
+        RwLockReadGuard::new(self, wait)
+    }
+
+    /// Like `std::sync::RwLock::try_read`.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T, R>> {
+        held::before_acquire();
+        if self.raw.try_lock_shared() {
+            Ok(RwLockReadGuard::new(self, held::Wait::NONE))
+        } else {
+            held::acquire_failed(self.addr());
+            Err(TryLockError::failed(LockOperation::Read, self.meta()))
+        }
+    }
+
+    /// Like `std::sync::RwLock::write`.
+    ///
+    /// In builds with debug assertions, and without the `send-guard` feature,
+    /// this and the other blocking acquisitions, including
+    /// [`read`](RwLock::read), panic if the current thread already holds the
+    /// write lock, instead of deadlocking.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn write(&self) -> RwLockWriteGuard<'_, T, R> {
+        held::check_order(self.addr(), self.meta());
+        held::before_acquire();
+        if self.raw.try_lock_exclusive() {
+            return RwLockWriteGuard::new(self, held::Wait::NONE);
+        }
+        self.write_contended()
+    }
+
+    #[cold]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    fn write_contended(&self) -> RwLockWriteGuard<'_, T, R> {
+        let wait = held::Wait::start(self.addr(), self.meta());
+        let _waiting = contention::Waiting::new(self.addr());
+        self.raw.lock_exclusive();
+        RwLockWriteGuard::new(self, wait)
+    }
+
+    /// Like `std::sync::RwLock::try_write`.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T, R>> {
+        held::before_acquire();
+        if self.raw.try_lock_exclusive() {
+            Ok(RwLockWriteGuard::new(self, held::Wait::NONE))
+        } else {
+            held::acquire_failed(self.addr());
+            Err(TryLockError::failed(LockOperation::Write, self.meta()))
+        }
+    }
+
+    /// Calls `f` with shared access to the value, releasing the lock as soon
+    /// as it returns, like [`Mutex::with_lock`].
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn with_read<U>(&self, f: impl FnOnce(&T) -> U) -> U {
+        f(&self.read())
+    }
+
+    /// Calls `f` with exclusive access to the value, releasing the lock as
+    /// soon as it returns, like [`Mutex::with_lock`].
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn with_write<U>(&self, f: impl FnOnce(&mut T) -> U) -> U {
+        f(&mut self.write())
+    }
+
+    /// Updates the value with `f` under a single write lock, and returns what
+    /// `f` returns, like [`Mutex::update`].
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn update<U>(&self, f: impl FnOnce(&mut T) -> U) -> U {
+        self.with_write(f)
+    }
+
+    /// Like `std::sync::RwLock::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Returns `true` if a writer appears to be blocked waiting to acquire the
+    /// lock.
+    ///
+    /// This is an advisory hint for readers that want to release the lock early
+    /// when a writer is waiting. The answer may be stale as soon as it is
+    /// returned, and it may occasionally report a waiting writer of an
+    /// unrelated lock.
+    #[inline]
+    pub fn writer_waiting(&self) -> bool {
+        contention::is_waiting(self.addr())
+    }
+
+    /// Returns a raw pointer to the data, without locking.
+    ///
+    /// Reading through the pointer is only sound while the lock is held, and
+    /// writing through it only while it is held exclusively, by whoever does
+    /// so.
+    #[inline]
+    pub fn data_ptr(&self) -> *mut T {
+        self.data.get()
+    }
+
+    /// Returns `true` if a write guard of the lock was dropped by a panicking
+    /// thread since it was created or last passed to
+    /// [`clear_poison`](RwLock::clear_poison).
+    ///
+    /// The lock is not poisoned either way: this only tells that the value
+    /// may have been left half-updated, for example to log it. Like the
+    /// standard library, only panics with exclusive access count.
+    #[inline]
+    pub fn was_poisoned(&self) -> bool {
+        self.poison.get()
+    }
+
+    /// Forgets that a panicking thread held the lock, once the value is known
+    /// to be consistent again.
+    #[inline]
+    pub fn clear_poison(&self) {
+        self.poison.clear();
+    }
+
+    /// Releases shared access without a guard.
+    ///
+    /// This completes a handoff in which a guard was given up with
+    /// [`RwLockReadGuard::leak`] and the lock passed on, possibly to another
+    /// thread.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held in shared mode, and the caller must be entitled
+    /// to release that access: it acquired it itself, or took over from
+    /// whoever did. In particular, it must not be released this way for a
+    /// guard which is still alive.
+    #[inline]
+    pub unsafe fn force_unlock_read(&self) {
+        self.raw.unlock_shared();
+    }
+
+    /// Releases exclusive access without a guard.
+    ///
+    /// This completes a handoff in which a guard was given up with
+    /// [`RwLockWriteGuard::leak`] and the lock passed on, possibly to another
+    /// thread.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held exclusively, and the caller must be entitled to
+    /// release it: it acquired it itself, or took over from whoever did. In
+    /// particular, it must not be released this way while a guard for it is
+    /// alive.
+    #[inline]
+    pub unsafe fn force_unlock_write(&self) {
+        self.raw.unlock_exclusive();
+    }
+
+    #[inline]
+    fn addr(&self) -> usize {
+        self as *const Self as *const () as usize
+    }
+
+    /// Returns the name given to the lock with `new_named`, if any.
+    ///
+    /// This is always `None` without the `names` feature.
+    #[inline]
+    pub fn name(&self) -> Option<&'static str> {
+        self.meta().name()
+    }
+
+    #[inline]
+    fn meta(&self) -> held::Meta {
+        self.instrumentation.meta()
+    }
+
+    /// Like `std::sync::RwLock::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T
+    where
+        T: Sized,
+    {
+        self.data.into_inner()
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T> RwLock<T> {
+    /// Like `std::sync::RwLock::new`.
+    #[inline]
+    pub const fn new(t: T) -> RwLock<T> {
+        RwLock {
+            raw: RawRwLock::new(),
+            instrumentation: held::Instrumentation::new(None, None),
+            poison: poison::Flag::new(),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Creates a new `RwLock` with a name identifying it in diagnostics.
+    ///
+    /// This follows the same rules as [`Mutex::new_named`].
+    #[inline]
+    pub const fn new_named(name: &'static str, t: T) -> RwLock<T> {
+        RwLock {
+            raw: RawRwLock::new(),
+            instrumentation: held::Instrumentation::new(Some(name), None),
+            poison: poison::Flag::new(),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Creates a new `RwLock` at `level` in the lock hierarchy.
+    ///
+    /// This follows the same rules as [`Mutex::with_level`], for shared and
+    /// exclusive access alike.
+    #[inline]
+    pub const fn with_level(level: u32, t: T) -> RwLock<T> {
+        RwLock {
+            raw: RawRwLock::new(),
+            instrumentation: held::Instrumentation::new(None, Some(level)),
+            poison: poison::Flag::new(),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Creates a new `RwLock` which arbitrates between readers and writers
+    /// according to `fairness`, rather than [`Fairness::Fair`].
+    #[inline]
+    pub const fn with_fairness(fairness: Fairness, t: T) -> RwLock<T> {
+        RwLock {
+            raw: RawRwLock::with_fairness(fairness),
+            instrumentation: held::Instrumentation::new(None, None),
+            poison: poison::Flag::new(),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Resets the lock to a fresh, unlocked state, keeping the protected value.
+    ///
+    /// This is an escape hatch for a child process created by `fork` while
+    /// another thread held the lock. The lock state is discarded without
+    /// being inspected, and so are the record of a panic while it was held and
+    /// of its holder, as reported by [`was_poisoned`](RwLock::was_poisoned)
+    /// and the debugging features. Its fairness, name, level and statistics
+    /// are kept.
+    ///
+    /// As it takes `&mut self`, it cannot reset a lock in a `static`. Those
+    /// should be registered with the `fork` module instead, which keeps
+    /// them from being inherited locked in the first place.
+    #[inline]
+    pub fn force_reinit(&mut self) {
+        self.raw = RawRwLock::with_fairness(self.raw.fairness());
+        self.poison = poison::Flag::new();
+        self.instrumentation.forget_holder();
+    }
+}
+
+// Only for the default raw lock, so that `RwLock::from(t)` does not leave it to
+// be inferred.
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T> From<T> for RwLock<T> {
+    /// Like `std::sync::RwLock::from`.
+    #[inline]
+    fn from(t: T) -> RwLock<T> {
+        RwLock::new(t)
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: Clone, R: RwLockBackend + Clone> Clone for RwLock<T, R> {
+    /// Creates an unlocked lock holding a clone of the value, taken while
+    /// holding shared access to this one.
+    ///
+    /// The new lock has the same name and level, and is built on a clone of
+    /// the raw lock, which keeps the fairness of a [`RawRwLock`]. It starts
+    /// afresh otherwise, without statistics, thresholds or a mark left by a
+    /// panic.
+    fn clone(&self) -> RwLock<T, R> {
+        RwLock {
+            raw: self.raw.clone(),
+            instrumentation: self.instrumentation.fresh(),
+            poison: poison::Flag::new(),
+            data: UnsafeCell::new(self.get_cloned()),
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized + PartialEq, R: RwLockBackend> PartialEq for RwLock<T, R> {
+    /// Compares the values of the locks while holding shared access to both.
+    ///
+    /// The locks are acquired in order of their addresses, so that threads
+    /// comparing the same locks the other way around cannot deadlock behind a
+    /// waiting writer. A lock compared with itself is only acquired once.
+    fn eq(&self, other: &RwLock<T, R>) -> bool {
+        if self.addr() == other.addr() {
+            let guard = self.read();
+            // Still compared, for values such as NaN which are not equal to
+            // themselves.
+            return T::eq(&guard, &guard);
+        }
+        if self.addr() < other.addr() {
+            let a = self.read();
+            let b = other.read();
+            *a == *b
+        } else {
+            let b = other.read();
+            let a = self.read();
+            *a == *b
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized + Eq, R: RwLockBackend> Eq for RwLock<T, R> {}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized + Hash, R: RwLockBackend> Hash for RwLock<T, R> {
+    /// Hashes the value while holding shared access to the lock.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.read().hash(state);
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized> RwLock<T> {
+    /// Acquires shared access without queueing behind threads waiting for
+    /// the lock, blocking the current thread only while it is held for
+    /// writing.
+    ///
+    /// Unlike `read`, this does not deadlock when the current thread already
+    /// holds a read guard of this lock while a writer is waiting, so callbacks
+    /// which may re-enter a read can use it. It can starve writers if readers
+    /// keep overlapping, so `read` should be preferred elsewhere. A lock with a
+    /// level is still checked against the levels held, so re-entering one
+    /// panics in debug builds as `read` does.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn read_recursive(&self) -> RwLockReadGuard<'_, T> {
+        held::check_order(self.addr(), self.meta());
+        held::before_acquire();
+        if self.raw.try_lock_shared_recursive() {
+            return RwLockReadGuard::new(self, held::Wait::NONE);
+        }
+        self.read_recursive_contended()
+    }
+
+    #[cold]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    fn read_recursive_contended(&self) -> RwLockReadGuard<'_, T> {
+        let wait = held::Wait::start(self.addr(), self.meta());
+        self.raw.lock_shared_recursive();
+        RwLockReadGuard::new(self, wait)
+    }
+
+    /// Attempts to acquire shared access like `read_recursive`, without
+    /// blocking.
+    ///
+    /// This only fails if the lock is held for writing.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_read_recursive(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        held::before_acquire();
+        if self.raw.try_lock_shared_recursive() {
+            Ok(RwLockReadGuard::new(self, held::Wait::NONE))
+        } else {
+            held::acquire_failed(self.addr());
+            Err(TryLockError::failed(LockOperation::Read, self.meta()))
+        }
+    }
+
+    /// Acquires upgradable access, blocking the current thread until it is
+    /// able to do so.
+    ///
+    /// Upgradable access coexists with readers but excludes writers and other
+    /// upgradable holders. The returned guard can later be turned into a write
+    /// guard with [`RwLockUpgradableReadGuard::upgrade`] without releasing the
+    /// lock in between, so nothing another thread does can invalidate what was
+    /// read before the upgrade. This makes check-then-modify sequences safe.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn upgradable_read(&self) -> RwLockUpgradableReadGuard<'_, T> {
+        held::check_order(self.addr(), self.meta());
+        held::before_acquire();
+        if self.raw.try_lock_upgradable() {
+            return RwLockUpgradableReadGuard::new(self, held::Wait::NONE);
+        }
+        self.upgradable_read_contended()
+    }
+
+    #[cold]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    fn upgradable_read_contended(&self) -> RwLockUpgradableReadGuard<'_, T> {
+        let wait = held::Wait::start(self.addr(), self.meta());
+        let _waiting = contention::Waiting::new(self.addr());
+        self.raw.lock_upgradable();
+        RwLockUpgradableReadGuard::new(self, wait)
+    }
+
+    /// Attempts to acquire upgradable access without blocking.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_upgradable_read(&self) -> TryLockResult<RwLockUpgradableReadGuard<'_, T>> {
+        held::before_acquire();
+        if self.raw.try_lock_upgradable() {
+            Ok(RwLockUpgradableReadGuard::new(self, held::Wait::NONE))
+        } else {
+            held::acquire_failed(self.addr());
+            Err(TryLockError::failed(
+                LockOperation::UpgradableRead,
+                self.meta(),
+            ))
+        }
+    }
+
+    /// Acquires shared access, registering the guard with the lease watchdog,
+    /// like [`Mutex::lock_leased`].
+    #[inline]
+    #[track_caller]
+    pub fn read_leased(&self, max_hold: Duration) -> lease::LeasedRwLockReadGuard<'_, T> {
+        lease::LeasedRwLockReadGuard::new(self.read(), max_hold, None)
+    }
+
+    /// Like [`read_leased`](RwLock::read_leased), but fires `action` instead of
+    /// the one configured with [`lease::set_action`] if the lease runs out.
+    #[inline]
+    #[track_caller]
+    pub fn read_leased_with(
+        &self,
+        max_hold: Duration,
+        action: lease::LeaseAction,
+    ) -> lease::LeasedRwLockReadGuard<'_, T> {
+        lease::LeasedRwLockReadGuard::new(self.read(), max_hold, Some(action))
+    }
+
+    /// Acquires exclusive access, registering the guard with the lease
+    /// watchdog, like [`Mutex::lock_leased`].
+    #[inline]
+    #[track_caller]
+    pub fn write_leased(&self, max_hold: Duration) -> lease::LeasedRwLockWriteGuard<'_, T> {
+        lease::LeasedRwLockWriteGuard::new(self.write(), max_hold, None)
+    }
+
+    /// Like [`write_leased`](RwLock::write_leased), but fires `action` instead
+    /// of the one configured with [`lease::set_action`] if the lease runs out.
+    #[inline]
+    #[track_caller]
+    pub fn write_leased_with(
+        &self,
+        max_hold: Duration,
+        action: lease::LeaseAction,
+    ) -> lease::LeasedRwLockWriteGuard<'_, T> {
+        lease::LeasedRwLockWriteGuard::new(self.write(), max_hold, Some(action))
+    }
+
+    /// Attempts to acquire shared access, blocking the current thread for at
+    /// most `timeout`, and returns `None` if it could not.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_read_for(&self, timeout: Duration) -> Option<RwLockReadGuard<'_, T>> {
+        // A deadline too far in the future to represent is treated as no deadline at
+        // all.
+        self.try_read_until_internal(clock::now().checked_add(timeout), None)
+    }
+
+    /// Attempts to acquire shared access, blocking the current thread until at
+    /// most `deadline`, and returns `None` if it could not.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_read_until(&self, deadline: Instant) -> Option<RwLockReadGuard<'_, T>> {
+        self.try_read_until_internal(Some(deadline), None)
+    }
+
+    /// Acquires shared access, blocking the current thread until it is able to
+    /// do so or `token` is cancelled.
+    ///
+    /// Like [`Mutex::lock_cancellable`], this acquires a free lock even if
+    /// `token` has already been cancelled.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn read_cancellable(
+        &self,
+        token: &CancelToken,
+    ) -> Result<RwLockReadGuard<'_, T>, Cancelled> {
+        self.try_read_until_internal(None, Some(token))
+            .ok_or(Cancelled(()))
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    fn try_read_until_internal(
+        &self,
+        deadline: Option<Instant>,
+        cancel: Option<&CancelToken>,
+    ) -> Option<RwLockReadGuard<'_, T>> {
+        held::check_order(self.addr(), self.meta());
+        held::before_acquire();
+        if self.raw.try_lock_shared() {
+            return Some(RwLockReadGuard::new(self, held::Wait::NONE));
+        }
+        let wait = held::Wait::start(self.addr(), self.meta());
+        let acquired = match (deadline, cancel) {
+            (Some(deadline), _) => self.raw.try_lock_shared_until(deadline),
+            (None, Some(token)) => self.raw.lock_shared_cancellable(token),
+            (None, None) => {
+                self.raw.lock_shared();
+                true
+            }
+        };
+        if acquired {
+            Some(RwLockReadGuard::new(self, wait))
+        } else {
+            wait.give_up(self.addr());
+            None
+        }
+    }
+
+    /// Attempts to acquire exclusive access, blocking the current thread for
+    /// at most `timeout`, and returns `None` if it could not.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_write_for(&self, timeout: Duration) -> Option<RwLockWriteGuard<'_, T>> {
+        // A deadline too far in the future to represent is treated as no deadline at
+        // all.
+        self.try_write_until_internal(clock::now().checked_add(timeout), None)
+    }
+
+    /// Attempts to acquire exclusive access, blocking the current thread until
+    /// at most `deadline`, and returns `None` if it could not.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_write_until(&self, deadline: Instant) -> Option<RwLockWriteGuard<'_, T>> {
+        self.try_write_until_internal(Some(deadline), None)
+    }
+
+    /// Acquires exclusive access, blocking the current thread until it is able
+    /// to do so or `token` is cancelled.
+    ///
+    /// Like [`Mutex::lock_cancellable`], this acquires a free lock even if
+    /// `token` has already been cancelled.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn write_cancellable(
+        &self,
+        token: &CancelToken,
+    ) -> Result<RwLockWriteGuard<'_, T>, Cancelled> {
+        self.try_write_until_internal(None, Some(token))
+            .ok_or(Cancelled(()))
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    fn try_write_until_internal(
+        &self,
+        deadline: Option<Instant>,
+        cancel: Option<&CancelToken>,
+    ) -> Option<RwLockWriteGuard<'_, T>> {
+        held::check_order(self.addr(), self.meta());
+        held::before_acquire();
+        if self.raw.try_lock_exclusive() {
+            return Some(RwLockWriteGuard::new(self, held::Wait::NONE));
+        }
+        let wait = held::Wait::start(self.addr(), self.meta());
+        let _waiting = contention::Waiting::new(self.addr());
+        let acquired = match (deadline, cancel) {
+            (Some(deadline), _) => self.raw.try_lock_exclusive_until(deadline),
+            (None, Some(token)) => self.raw.lock_exclusive_cancellable(token),
+            (None, None) => {
+                self.raw.lock_exclusive();
+                true
+            }
+        };
+        if acquired {
+            Some(RwLockWriteGuard::new(self, wait))
+        } else {
+            wait.give_up(self.addr());
+            None
+        }
+    }
+
+    /// Returns `true` if the lock is currently held, shared or exclusively.
+    ///
+    /// The answer may be stale as soon as it is returned.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.raw.is_locked()
+    }
+
+    /// Returns `true` if the lock is currently held exclusively.
+    ///
+    /// The answer may be stale as soon as it is returned.
+    #[inline]
+    pub fn is_write_locked(&self) -> bool {
+        self.raw.is_locked_exclusive()
+    }
+
+    /// Returns the number of threads currently holding shared access,
+    /// including the holder of upgradable access, if any.
+    ///
+    /// The answer may be stale as soon as it is returned.
+    #[inline]
+    pub fn reader_count(&self) -> usize {
+        self.raw.reader_count()
+    }
+
+    /// Returns the policy the lock arbitrates between readers and writers
+    /// with.
+    #[inline]
+    pub fn fairness(&self) -> Fairness {
+        self.raw.fairness()
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized + fmt::Debug, R: RwLockBackend> fmt::Debug for RwLock<T, R> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_struct("RwLock");
+        if let Some(name) = self.name() {
+            d.field("name", &name);
+        }
+        // The raw lock is used directly so that formatting is not seen as an
+        // acquisition by the instrumentation.
+        if self.raw.try_lock_shared() {
+            // SAFETY: shared access is held until the end of this block.
+            d.field("data", &unsafe { &*self.data.get() });
+            unsafe { self.raw.unlock_shared() };
+        } else {
+            d.field("data", &format_args!("<locked>"));
+        }
+        d.finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+#[must_use]
+/// Like `std::sync::RwLockReadGuard`.
+pub struct RwLockReadGuard<'a, T: ?Sized + 'a, R: RwLockBackend = RawRwLock> {
+    lock: &'a RwLock<T, R>,
+    _held: held::Token,
+    // Like the standard library's guards, this one must be dropped by the
+    // thread which acquired the lock, as the held-lock bookkeeping is per
+    // thread, unless the `send-guard` feature is enabled.
+    _not_send: held::NotSend,
+}
+
+// SAFETY: the guard only hands out `&T`.
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+unsafe impl<T: ?Sized + Sync, R: RwLockBackend + Sync> Sync for RwLockReadGuard<'_, T, R> {}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<'a, T: ?Sized, R: RwLockBackend> RwLockReadGuard<'a, T, R> {
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    fn new(lock: &'a RwLock<T, R>, wait: held::Wait) -> RwLockReadGuard<'a, T, R> {
+        RwLockReadGuard {
+            lock,
+            _held: held::Token::new(lock.addr(), held::Access::Shared, lock.meta(), wait),
+            _not_send: PhantomData,
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<'a, T: ?Sized, R: RwLockBackend> RwLockReadGuard<'a, T, R> {
+    /// Returns the lock this guard holds, like [`MutexGuard::mutex`].
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`.
+    #[inline]
+    pub fn rwlock(s: &Self) -> &'a RwLock<T, R> {
+        s.lock
+    }
+
+    /// Releases the lock, exactly like dropping the guard.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`. Spelled out at the call site, the release stands out in review
+    /// where `drop(guard)` is easy to miss, or to mistake for dropping the
+    /// data.
+    #[inline]
+    pub fn unlock(s: Self) {
+        drop(s);
+    }
+
+    /// Consumes the guard without releasing the lock, returning a reference to
+    /// the data which lasts as long as the lock is borrowed.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`. The lock stays held in shared mode unless it is released with
+    /// [`RwLock::force_unlock_read`], and is no longer reported as held by the
+    /// instrumentation.
+    pub fn leak(s: Self) -> &'a T {
+        let s = ManuallyDrop::new(s);
+        // SAFETY: the guard is never used or dropped again, so the token is
+        // moved out of it only once.
+        let held = unsafe { ptr::read(&s._held) };
+        held.releasing();
+        // SAFETY: shared access is never released.
+        unsafe { &*s.lock.data.get() }
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<'a, T: ?Sized> RwLockReadGuard<'a, T> {
+    /// Acquires shared access once more, returning a second guard which can
+    /// be handed to a helper while this one is kept.
+    ///
+    /// This never blocks: the guard already holds shared access, so no writer
+    /// can hold the lock, and one waiting for it is not waited for as `read`
+    /// would. The lock is released once both guards are dropped. This is an
+    /// associated function so that it does not shadow a method of `T`, which
+    /// is also why the guard does not implement `Clone`.
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn reacquire(s: &Self) -> RwLockReadGuard<'a, T> {
+        held::before_acquire();
+        let acquired = s.lock.raw.try_lock_shared_recursive();
+        assert!(acquired, "shared access held by a guard was lost");
+        RwLockReadGuard::new(s.lock, held::Wait::NONE)
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized, R: RwLockBackend> Drop for RwLockReadGuard<'_, T, R> {
+    #[inline]
+    fn drop(&mut self) {
+        self._held.releasing();
+        // SAFETY: the guard holds shared access.
+        unsafe { self.lock.raw.unlock_shared() };
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized, R: RwLockBackend> Deref for RwLockReadGuard<'_, T, R> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds shared access.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+#[must_use]
+/// A guard granting upgradable access to an [`RwLock`], returned by
+/// [`RwLock::upgradable_read`].
+///
+/// It dereferences to the value like a read guard, and can be turned into a
+/// write guard with [`upgrade`](RwLockUpgradableReadGuard::upgrade). The
+/// methods taking the guard are associated functions, so that they do not
+/// shadow methods of `T`.
+pub struct RwLockUpgradableReadGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+    held: ManuallyDrop<held::Token>,
+    // See `RwLockReadGuard`.
+    _not_send: held::NotSend,
+}
+
+// SAFETY: the guard only hands out `&T`.
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+unsafe impl<T: ?Sized + Sync> Sync for RwLockUpgradableReadGuard<'_, T> {}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<'a, T: ?Sized> RwLockUpgradableReadGuard<'a, T> {
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    fn new(lock: &'a RwLock<T>, wait: held::Wait) -> RwLockUpgradableReadGuard<'a, T> {
+        RwLockUpgradableReadGuard {
+            lock,
+            held: ManuallyDrop::new(held::Token::new(
+                lock.addr(),
+                held::Access::Shared,
+                lock.meta(),
+                wait,
+            )),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Returns the lock this guard holds, like [`MutexGuard::mutex`].
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`.
+    #[inline]
+    pub fn rwlock(s: &Self) -> &'a RwLock<T> {
+        s.lock
+    }
+
+    // Consumes the guard without releasing the lock, returning its token.
+    #[inline]
+    fn into_parts(s: Self) -> (&'a RwLock<T>, held::Token) {
+        let mut s = ManuallyDrop::new(s);
+        // SAFETY: the guard is never used again.
+        let held = unsafe { ManuallyDrop::take(&mut s.held) };
+        (s.lock, held)
+    }
+
+    // Consumes the guard without releasing the lock, ending its bookkeeping.
+    #[inline]
+    fn into_lock(s: Self) -> &'a RwLock<T> {
+        let (lock, held) = Self::into_parts(s);
+        held.releasing();
+        lock
+    }
+
+    /// Releases the lock, exactly like dropping the guard.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`. Spelled out at the call site, the release stands out in review
+    /// where `drop(guard)` is easy to miss, or to mistake for dropping the
+    /// data.
+    #[inline]
+    pub fn unlock(s: Self) {
+        drop(s);
+    }
+
+    /// Consumes the guard without releasing the lock, returning a reference to
+    /// the data which lasts as long as the lock is borrowed.
+    ///
+    /// The lock stays held in upgradable mode for good, so it can still be
+    /// read but never written, and is no longer reported as held by the
+    /// instrumentation.
+    pub fn leak(s: Self) -> &'a T {
+        let lock = Self::into_lock(s);
+        // SAFETY: upgradable access is never released.
+        unsafe { &*lock.data.get() }
+    }
+
+    /// Turns the upgradable access into exclusive access, blocking the current
+    /// thread until the readers have released the lock.
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn upgrade(s: Self) -> RwLockWriteGuard<'a, T> {
+        // SAFETY: the guard holds upgradable access, and is consumed if it is
+        // turned into exclusive access.
+        if unsafe { s.lock.raw.try_upgrade() } {
+            let lock = Self::into_lock(s);
+            return RwLockWriteGuard::new(lock, held::Wait::NONE);
+        }
+        let lock = Self::into_lock(s);
+        let wait = held::Wait::start(lock.addr(), lock.meta());
+        // SAFETY: upgradable access was held by the guard, which is gone.
+        unsafe { lock.raw.upgrade() };
+        RwLockWriteGuard::new(lock, wait)
+    }
+
+    /// Attempts to turn the upgradable access into exclusive access without
+    /// blocking, returning the guard back if readers hold the lock.
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_upgrade(s: Self) -> Result<RwLockWriteGuard<'a, T>, Self> {
+        // SAFETY: as in `upgrade`.
+        if unsafe { s.lock.raw.try_upgrade() } {
+            let lock = Self::into_lock(s);
+            Ok(RwLockWriteGuard::new(lock, held::Wait::NONE))
+        } else {
+            Err(s)
+        }
+    }
+
+    /// Attempts to turn the upgradable access into exclusive access, blocking
+    /// the current thread for at most `timeout` until the readers have
+    /// released the lock, and returning the guard back if it times out.
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_upgrade_for(s: Self, timeout: Duration) -> Result<RwLockWriteGuard<'a, T>, Self> {
+        // SAFETY: as in `upgrade`.
+        if unsafe { s.lock.raw.try_upgrade_for(timeout) } {
+            let lock = Self::into_lock(s);
+            Ok(RwLockWriteGuard::new(lock, held::Wait::NONE))
+        } else {
+            Err(s)
+        }
+    }
+
+    /// Turns the upgradable access into shared access, letting another thread
+    /// acquire upgradable access.
+    pub fn downgrade(s: Self) -> RwLockReadGuard<'a, T> {
+        let (lock, held) = Self::into_parts(s);
+        // SAFETY: upgradable access was held by the guard, which is gone.
+        unsafe { lock.raw.downgrade_upgradable() };
+        RwLockReadGuard {
+            lock,
+            _held: held,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized> Drop for RwLockUpgradableReadGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.held.releasing();
+        // SAFETY: the guard holds upgradable access.
+        unsafe { self.lock.raw.unlock_upgradable() };
+        // SAFETY: the token is never used again. It is dropped after the lock
+        // is released, like the tokens of the other guards.
+        unsafe { ManuallyDrop::drop(&mut self.held) };
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized> Deref for RwLockUpgradableReadGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds upgradable access, which excludes writers.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+#[must_use]
+/// Like `std::sync::RwLockWriteGuard`.
+pub struct RwLockWriteGuard<'a, T: ?Sized + 'a, R: RwLockBackend = RawRwLock> {
+    lock: &'a RwLock<T, R>,
+    poison: poison::Guard,
+    held: held::Token,
+    // See `RwLockReadGuard`.
+    _not_send: held::NotSend,
+}
+
+// SAFETY: the guard only hands out `&T` through shared references to it.
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+unsafe impl<T: ?Sized + Sync, R: RwLockBackend + Sync> Sync for RwLockWriteGuard<'_, T, R> {}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<'a, T: ?Sized, R: RwLockBackend> RwLockWriteGuard<'a, T, R> {
+    #[inline]
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    fn new(lock: &'a RwLock<T, R>, wait: held::Wait) -> RwLockWriteGuard<'a, T, R> {
+        let guard = RwLockWriteGuard {
+            lock,
+            poison: lock.poison.guard(),
+            held: held::Token::new(lock.addr(), held::Access::Exclusive, lock.meta(), wait),
+            _not_send: PhantomData,
+        };
+        // If the hook panics, the guard is dropped first.
+        if guard.poison.recovered() {
+            poison::report(lock.name());
+        }
+        guard
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<'a, T: ?Sized, R: RwLockBackend> RwLockWriteGuard<'a, T, R> {
+    /// Returns the lock this guard holds, like [`MutexGuard::mutex`].
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`.
+    #[inline]
+    pub fn rwlock(s: &Self) -> &'a RwLock<T, R> {
+        s.lock
+    }
+
+    // Consumes the guard without releasing the lock, returning its token.
+    #[inline]
+    fn into_parts(s: Self) -> (&'a RwLock<T, R>, held::Token) {
+        let s = ManuallyDrop::new(s);
+        // SAFETY: the guard is never used or dropped again, so the token is
+        // moved out of it only once.
+        (s.lock, unsafe { ptr::read(&s.held) })
+    }
+
+    /// Releases the lock, runs `f`, and acquires exclusive access again before
+    /// returning what `f` returned.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`. The lock is acquired again even if `f` panics, and other threads
+    /// may change the value in the meantime.
+    pub fn unlocked<U>(s: &mut Self, f: impl FnOnce() -> U) -> U {
+        let lock = s.lock;
+        unlocked(s, || lock.write(), f)
+    }
+
+    /// Returns `true` if the lock was marked by a panicking thread when the
+    /// guard acquired it, as reported by [`RwLock::was_poisoned`].
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`.
+    #[inline]
+    pub fn recovered_from_poison(s: &Self) -> bool {
+        s.poison.recovered()
+    }
+
+    /// Releases the lock, exactly like dropping the guard.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`. Spelled out at the call site, the release stands out in review
+    /// where `drop(guard)` is easy to miss, or to mistake for dropping the
+    /// data.
+    #[inline]
+    pub fn unlock(s: Self) {
+        drop(s);
+    }
+
+    /// Consumes the guard without releasing the lock, returning a reference to
+    /// the data which lasts as long as the lock is borrowed.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`. The lock stays held exclusively unless it is released with
+    /// [`RwLock::force_unlock_write`], and is no longer reported as held by the
+    /// instrumentation.
+    pub fn leak(s: Self) -> &'a mut T {
+        let (lock, held) = Self::into_parts(s);
+        held.releasing();
+        // SAFETY: exclusive access is never released.
+        unsafe { &mut *lock.data.get() }
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<'a, T: ?Sized> RwLockWriteGuard<'a, T> {
+    /// Releases and immediately reacquires the lock if other threads are
+    /// waiting for it, to give them a chance to acquire it first.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`. Whether a waiter gets in before the lock is acquired again is up to
+    /// the scheduler.
+    pub fn bump(s: &mut Self) {
+        if s.lock.raw.has_waiters() {
+            Self::unlocked(s, thread::yield_now);
+        }
+    }
+
+    /// Turns the exclusive access into shared access without releasing the
+    /// lock, so no writer can acquire it in between.
+    ///
+    /// Other readers waiting for the lock are let in.
+    pub fn downgrade(s: Self) -> RwLockReadGuard<'a, T> {
+        let (lock, mut held) = Self::into_parts(s);
+        // SAFETY: exclusive access was held by the guard, which is gone.
+        unsafe { lock.raw.downgrade() };
+        held.set_access(held::Access::Shared);
+        RwLockReadGuard {
+            lock,
+            _held: held,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Turns the exclusive access into upgradable access without releasing
+    /// the lock, so no writer can acquire it in between.
+    ///
+    /// Other readers waiting for the lock are let in, and the returned guard
+    /// can be upgraded again later.
+    pub fn downgrade_to_upgradable(s: Self) -> RwLockUpgradableReadGuard<'a, T> {
+        let (lock, mut held) = Self::into_parts(s);
+        // SAFETY: as in `downgrade`.
+        unsafe { lock.raw.downgrade_to_upgradable() };
+        held.set_access(held::Access::Shared);
+        RwLockUpgradableReadGuard {
+            lock,
+            held: ManuallyDrop::new(held),
+            _not_send: PhantomData,
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized, R: RwLockBackend> Drop for RwLockWriteGuard<'_, T, R> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.poison.done(&self.poison);
+        self.held.releasing();
+        // SAFETY: the guard holds exclusive access.
+        unsafe { self.lock.raw.unlock_exclusive() };
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized, R: RwLockBackend> Deref for RwLockWriteGuard<'_, T, R> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds exclusive access.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized, R: RwLockBackend> DerefMut for RwLockWriteGuard<'_, T, R> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds exclusive access.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized + fmt::Debug, R: RwLockBackend> fmt::Debug for RwLockReadGuard<'_, T, R> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("RwLockReadGuard").field(&&**self).finish()
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockUpgradableReadGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("RwLockUpgradableReadGuard")
+            .field(&&**self)
+            .finish()
+    }
+}
+
+#[cfg(all(feature = "std", not(any(loom, shuttle))))]
+impl<T: ?Sized + fmt::Debug, R: RwLockBackend> fmt::Debug for RwLockWriteGuard<'_, T, R> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("RwLockWriteGuard").field(&&**self).finish()
     }
 }
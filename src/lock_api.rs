@@ -0,0 +1,186 @@
+//! Implementations of the [`lock_api`] traits.
+//!
+//! [`RawMutex`] and [`RawRwLock`] implement the corresponding `lock_api`
+//! traits, so they can be plugged into code which is generic over a raw lock,
+//! and this module provides aliases for the `lock_api` locks built on them.
+//! Like the rest of this crate, those locks never poison.
+//!
+//! The `lock_api` locks are separate types from this crate's
+//! [`Mutex`](crate::Mutex) and [`RwLock`](crate::RwLock). As they are built on
+//! the raw locks, they are not seen by the `tracking`, `histogram` or
+//! `opentelemetry` instrumentation.
+
+use std::time::{Duration, Instant};
+
+use ::lock_api::{
+    GuardSend, RawMutex as RawMutexApi, RawRwLock as RawRwLockApi, RawRwLockDowngrade,
+    RawRwLockTimed, RawRwLockUpgrade, RawRwLockUpgradeDowngrade,
+};
+
+use crate::{RawMutex, RawRwLock};
+
+/// A `lock_api` mutex built on [`RawMutex`].
+pub type Mutex<T> = ::lock_api::Mutex<RawMutex, T>;
+
+/// A guard for a [`Mutex`].
+pub type MutexGuard<'a, T> = ::lock_api::MutexGuard<'a, RawMutex, T>;
+
+/// A `lock_api` reader-writer lock built on [`RawRwLock`].
+pub type RwLock<T> = ::lock_api::RwLock<RawRwLock, T>;
+
+/// A guard granting shared access to a [`RwLock`].
+pub type RwLockReadGuard<'a, T> = ::lock_api::RwLockReadGuard<'a, RawRwLock, T>;
+
+/// A guard granting upgradable access to a [`RwLock`].
+pub type RwLockUpgradableReadGuard<'a, T> = ::lock_api::RwLockUpgradableReadGuard<'a, RawRwLock, T>;
+
+/// A guard granting exclusive access to a [`RwLock`].
+pub type RwLockWriteGuard<'a, T> = ::lock_api::RwLockWriteGuard<'a, RawRwLock, T>;
+
+// SAFETY: `RawMutex` is a mutex which may be released by another thread than
+// the one which acquired it.
+unsafe impl RawMutexApi for RawMutex {
+    const INIT: RawMutex = RawMutex::new();
+
+    type GuardMarker = GuardSend;
+
+    #[inline]
+    fn lock(&self) {
+        RawMutex::lock(self);
+    }
+
+    #[inline]
+    fn try_lock(&self) -> bool {
+        RawMutex::try_lock(self)
+    }
+
+    #[inline]
+    unsafe fn unlock(&self) {
+        RawMutex::unlock(self);
+    }
+
+    #[inline]
+    fn is_locked(&self) -> bool {
+        RawMutex::is_locked(self)
+    }
+}
+
+// SAFETY: `RawRwLock` is a reader-writer lock which may be released by another
+// thread than the one which acquired it.
+unsafe impl RawRwLockApi for RawRwLock {
+    const INIT: RawRwLock = RawRwLock::new();
+
+    type GuardMarker = GuardSend;
+
+    #[inline]
+    fn lock_shared(&self) {
+        RawRwLock::lock_shared(self);
+    }
+
+    #[inline]
+    fn try_lock_shared(&self) -> bool {
+        RawRwLock::try_lock_shared(self)
+    }
+
+    #[inline]
+    unsafe fn unlock_shared(&self) {
+        RawRwLock::unlock_shared(self);
+    }
+
+    #[inline]
+    fn lock_exclusive(&self) {
+        RawRwLock::lock_exclusive(self);
+    }
+
+    #[inline]
+    fn try_lock_exclusive(&self) -> bool {
+        RawRwLock::try_lock_exclusive(self)
+    }
+
+    #[inline]
+    unsafe fn unlock_exclusive(&self) {
+        RawRwLock::unlock_exclusive(self);
+    }
+
+    #[inline]
+    fn is_locked(&self) -> bool {
+        RawRwLock::is_locked(self)
+    }
+
+    #[inline]
+    fn is_locked_exclusive(&self) -> bool {
+        RawRwLock::is_locked_exclusive(self)
+    }
+}
+
+// SAFETY: the operations are those of `RawRwLock` with the same requirements.
+unsafe impl RawRwLockUpgrade for RawRwLock {
+    #[inline]
+    fn lock_upgradable(&self) {
+        RawRwLock::lock_upgradable(self);
+    }
+
+    #[inline]
+    fn try_lock_upgradable(&self) -> bool {
+        RawRwLock::try_lock_upgradable(self)
+    }
+
+    #[inline]
+    unsafe fn unlock_upgradable(&self) {
+        RawRwLock::unlock_upgradable(self);
+    }
+
+    #[inline]
+    unsafe fn upgrade(&self) {
+        RawRwLock::upgrade(self);
+    }
+
+    #[inline]
+    unsafe fn try_upgrade(&self) -> bool {
+        RawRwLock::try_upgrade(self)
+    }
+}
+
+unsafe impl RawRwLockDowngrade for RawRwLock {
+    #[inline]
+    unsafe fn downgrade(&self) {
+        RawRwLock::downgrade(self);
+    }
+}
+
+unsafe impl RawRwLockUpgradeDowngrade for RawRwLock {
+    #[inline]
+    unsafe fn downgrade_upgradable(&self) {
+        RawRwLock::downgrade_upgradable(self);
+    }
+
+    #[inline]
+    unsafe fn downgrade_to_upgradable(&self) {
+        RawRwLock::downgrade_to_upgradable(self);
+    }
+}
+
+unsafe impl RawRwLockTimed for RawRwLock {
+    type Duration = Duration;
+    type Instant = Instant;
+
+    #[inline]
+    fn try_lock_shared_for(&self, timeout: Duration) -> bool {
+        RawRwLock::try_lock_shared_for(self, timeout)
+    }
+
+    #[inline]
+    fn try_lock_shared_until(&self, deadline: Instant) -> bool {
+        RawRwLock::try_lock_shared_until(self, deadline)
+    }
+
+    #[inline]
+    fn try_lock_exclusive_for(&self, timeout: Duration) -> bool {
+        RawRwLock::try_lock_exclusive_for(self, timeout)
+    }
+
+    #[inline]
+    fn try_lock_exclusive_until(&self, deadline: Instant) -> bool {
+        RawRwLock::try_lock_exclusive_until(self, deadline)
+    }
+}
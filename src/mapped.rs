@@ -0,0 +1,596 @@
+use std::{
+    any::Any,
+    fmt,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    time::Duration,
+};
+
+use crate::{
+    poison::{Ignore, PoisonPolicy},
+    raw::{BuiltinMutex, MutexStorage, RawRwLock, RwLockBackend},
+    Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockUpgradableReadGuard,
+    RwLockWriteGuard, WaitTimeoutResult,
+};
+
+impl<T: ?Sized, P: PoisonPolicy, R: MutexStorage> Mutex<T, P, R> {
+    /// Acquires the mutex and returns a guard granting access to a part of the
+    /// protected value, selected by `f`.
+    ///
+    /// This is a shorthand for `MutexGuard::map(mutex.lock(), f)`.
+    #[inline]
+    pub fn lock_map<U: ?Sized>(
+        &self,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> MappedMutexGuard<'_, T, U, P, R> {
+        MutexGuard::map(self.lock(), f)
+    }
+}
+
+impl<T: ?Sized, R: RwLockBackend> RwLock<T, R> {
+    /// Acquires shared access and returns a guard granting access to a part of
+    /// the protected value, selected by `f`.
+    ///
+    /// This is a shorthand for `RwLockReadGuard::map(rwlock.read(), f)`.
+    #[inline]
+    pub fn read_map<U: ?Sized>(
+        &self,
+        f: impl FnOnce(&T) -> &U,
+    ) -> MappedRwLockReadGuard<'_, T, U, R> {
+        RwLockReadGuard::map(self.read(), f)
+    }
+
+    /// Acquires exclusive access and returns a guard granting access to a part
+    /// of the protected value, selected by `f`.
+    ///
+    /// This is a shorthand for `RwLockWriteGuard::map(rwlock.write(), f)`.
+    #[inline]
+    pub fn write_map<U: ?Sized>(
+        &self,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> MappedRwLockWriteGuard<'_, T, U, R> {
+        RwLockWriteGuard::map(self.write(), f)
+    }
+}
+
+impl<T> RwLock<Option<T>> {
+    /// Returns shared access to the value in the slot, storing the value
+    /// returned by `f` first if the slot is empty.
+    ///
+    /// The slot is checked with shared access, so once it is filled this costs
+    /// about as much as [`read`](RwLock::read). Otherwise the current thread
+    /// acquires upgradable access and checks the slot again, since another
+    /// thread may have filled it in between. Readers are still let in while
+    /// `f` runs, and exclusive access is only held to store the value before it
+    /// is downgraded, so no writer can empty the slot before the guard is
+    /// returned. Threads calling this concurrently on an empty slot run `f`
+    /// only once between them, unless it panics, which leaves the slot empty.
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn get_or_init_with(
+        &self,
+        f: impl FnOnce() -> T,
+    ) -> MappedRwLockReadGuard<'_, Option<T>, T> {
+        if let Ok(value) = RwLockReadGuard::try_map(self.read(), Option::as_ref) {
+            return value;
+        }
+        let slot = self.upgradable_read();
+        let slot = if slot.is_some() {
+            RwLockUpgradableReadGuard::downgrade(slot)
+        } else {
+            let value = f();
+            let mut slot = RwLockUpgradableReadGuard::upgrade(slot);
+            *slot = Some(value);
+            RwLockWriteGuard::downgrade(slot)
+        };
+        match RwLockReadGuard::try_map(slot, Option::as_ref) {
+            Ok(value) => value,
+            Err(_) => unreachable!("filled slot emptied while held"),
+        }
+    }
+}
+
+impl<'a, T: ?Sized, P: PoisonPolicy, R: MutexStorage> MutexGuard<'a, T, P, R> {
+    /// Makes a guard granting access to a part of the locked value, selected by
+    /// `f`.
+    ///
+    /// The mutex stays locked until the returned guard is dropped. This is an
+    /// associated function so that it does not shadow a method of `T`.
+    #[inline]
+    pub fn map<U: ?Sized>(
+        mut orig: MutexGuard<'a, T, P, R>,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> MappedMutexGuard<'a, T, U, P, R> {
+        let data = NonNull::from(f(&mut *orig));
+        MappedMutexGuard::new(orig, data)
+    }
+
+    /// Like [`map`](MutexGuard::map), except that `f` may decline to select a
+    /// part by returning `None`, in which case the original guard is returned.
+    #[inline]
+    pub fn try_map<U: ?Sized>(
+        mut orig: MutexGuard<'a, T, P, R>,
+        f: impl FnOnce(&mut T) -> Option<&mut U>,
+    ) -> Result<MappedMutexGuard<'a, T, U, P, R>, Self> {
+        match f(&mut *orig).map(NonNull::from) {
+            Some(data) => Ok(MappedMutexGuard::new(orig, data)),
+            None => Err(orig),
+        }
+    }
+}
+
+impl<'a, T: ?Sized, R: RwLockBackend> RwLockReadGuard<'a, T, R> {
+    /// Makes a guard granting access to a part of the locked value, selected by
+    /// `f`.
+    ///
+    /// The lock stays held until the returned guard is dropped. This is an
+    /// associated function so that it does not shadow a method of `T`.
+    #[inline]
+    pub fn map<U: ?Sized>(
+        orig: RwLockReadGuard<'a, T, R>,
+        f: impl FnOnce(&T) -> &U,
+    ) -> MappedRwLockReadGuard<'a, T, U, R> {
+        let data = NonNull::from(f(&*orig));
+        MappedRwLockReadGuard::new(orig, data)
+    }
+
+    /// Like [`map`](RwLockReadGuard::map), except that `f` may decline to
+    /// select a part by returning `None`, in which case the original guard is
+    /// returned.
+    #[inline]
+    pub fn try_map<U: ?Sized>(
+        orig: RwLockReadGuard<'a, T, R>,
+        f: impl FnOnce(&T) -> Option<&U>,
+    ) -> Result<MappedRwLockReadGuard<'a, T, U, R>, RwLockReadGuard<'a, T, R>> {
+        match f(&*orig).map(NonNull::from) {
+            Some(data) => Ok(MappedRwLockReadGuard::new(orig, data)),
+            None => Err(orig),
+        }
+    }
+}
+
+impl<'a, T: ?Sized, R: RwLockBackend> RwLockWriteGuard<'a, T, R> {
+    /// Makes a guard granting access to a part of the locked value, selected by
+    /// `f`.
+    ///
+    /// The lock stays held until the returned guard is dropped. This is an
+    /// associated function so that it does not shadow a method of `T`.
+    #[inline]
+    pub fn map<U: ?Sized>(
+        mut orig: RwLockWriteGuard<'a, T, R>,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> MappedRwLockWriteGuard<'a, T, U, R> {
+        let data = NonNull::from(f(&mut *orig));
+        MappedRwLockWriteGuard::new(orig, data)
+    }
+
+    /// Like [`map`](RwLockWriteGuard::map), except that `f` may decline to
+    /// select a part by returning `None`, in which case the original guard is
+    /// returned.
+    #[inline]
+    pub fn try_map<U: ?Sized>(
+        mut orig: RwLockWriteGuard<'a, T, R>,
+        f: impl FnOnce(&mut T) -> Option<&mut U>,
+    ) -> Result<MappedRwLockWriteGuard<'a, T, U, R>, RwLockWriteGuard<'a, T, R>> {
+        match f(&mut *orig).map(NonNull::from) {
+            Some(data) => Ok(MappedRwLockWriteGuard::new(orig, data)),
+            None => Err(orig),
+        }
+    }
+}
+
+macro_rules! downcast {
+    ($($any:ty),*) => {$(
+        impl<'a, P: PoisonPolicy, R: MutexStorage> MutexGuard<'a, $any, P, R> {
+            /// Makes a guard granting access to the locked value as a `U`, or
+            /// returns the original guard if it is not one.
+            ///
+            /// Unlike `map`, this is a method, as `dyn Any` has none of that
+            /// name.
+            // The guard is given back as is, however large the features make it.
+            #[allow(clippy::result_large_err)]
+            #[inline]
+            pub fn downcast<U: Any>(self) -> Result<MappedMutexGuard<'a, $any, U, P, R>, Self> {
+                MutexGuard::try_map(self, |t| t.downcast_mut())
+            }
+        }
+
+        impl<'a, R: RwLockBackend> RwLockReadGuard<'a, $any, R> {
+            /// Makes a guard granting access to the locked value as a `U`, or
+            /// returns the original guard if it is not one.
+            ///
+            /// Unlike `map`, this is a method, as `dyn Any` has none of that
+            /// name.
+            // The guard is given back as is, however large the features make it.
+            #[allow(clippy::result_large_err)]
+            #[inline]
+            pub fn downcast<U: Any>(self) -> Result<MappedRwLockReadGuard<'a, $any, U, R>, Self> {
+                RwLockReadGuard::try_map(self, |t| t.downcast_ref())
+            }
+        }
+
+        impl<'a, R: RwLockBackend> RwLockWriteGuard<'a, $any, R> {
+            /// Makes a guard granting access to the locked value as a `U`, or
+            /// returns the original guard if it is not one.
+            ///
+            /// Unlike `map`, this is a method, as `dyn Any` has none of that
+            /// name.
+            // The guard is given back as is, however large the features make it.
+            #[allow(clippy::result_large_err)]
+            #[inline]
+            pub fn downcast<U: Any>(self) -> Result<MappedRwLockWriteGuard<'a, $any, U, R>, Self> {
+                RwLockWriteGuard::try_map(self, |t| t.downcast_mut())
+            }
+        }
+    )*};
+}
+
+downcast!(dyn Any, dyn Any + Send, dyn Any + Send + Sync);
+
+/// A guard granting access to a part of the value protected by a [`Mutex`],
+/// returned by [`MutexGuard::map`] and [`Mutex::lock_map`].
+///
+/// `T` is the type of the whole value, `U` the type of the part, and `P` and
+/// `R` the poison policy and storage of the mutex. Unlike `parking_lot`'s
+/// mapped guard, which only names the raw mutex besides the part, this one
+/// keeps `T`, so that it can be turned back into a [`MutexGuard`] with
+/// [`unmap`](MappedMutexGuard::unmap) and waited on with
+/// [`Condvar::wait_mapped`] and the related methods, which select the part
+/// again once the wait is over.
+#[must_use]
+pub struct MappedMutexGuard<
+    'a,
+    T: ?Sized + 'a,
+    U: ?Sized + 'a,
+    P: PoisonPolicy = Ignore,
+    R: MutexStorage = BuiltinMutex,
+> {
+    // Keeps the mutex locked. `data` points into the value it guards, which
+    // does not move along with the guard.
+    _guard: MutexGuard<'a, T, P, R>,
+    data: NonNull<U>,
+    _marker: PhantomData<&'a mut U>,
+}
+
+// SAFETY: the guard only hands out references to `U`.
+unsafe impl<T: ?Sized, U: ?Sized + Sync, P: PoisonPolicy, R: MutexStorage> Sync
+    for MappedMutexGuard<'_, T, U, P, R>
+{
+}
+
+// SAFETY: the guard hands out mutable references to `U`, and can only be sent
+// along with the guard keeping the mutex locked, which is `Send` with the
+// `send-guard` feature.
+unsafe impl<'a, T: ?Sized, U: ?Sized + Send, P: PoisonPolicy, R: MutexStorage> Send
+    for MappedMutexGuard<'a, T, U, P, R>
+where
+    MutexGuard<'a, T, P, R>: Send,
+{
+}
+
+impl<'a, T: ?Sized, U: ?Sized, P: PoisonPolicy, R: MutexStorage> MappedMutexGuard<'a, T, U, P, R> {
+    #[inline]
+    fn new(guard: MutexGuard<'a, T, P, R>, data: NonNull<U>) -> MappedMutexGuard<'a, T, U, P, R> {
+        MappedMutexGuard {
+            _guard: guard,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Releases the mutex, exactly like dropping the guard.
+    ///
+    /// See [`MutexGuard::unlock`].
+    #[inline]
+    pub fn unlock(s: Self) {
+        drop(s);
+    }
+
+    /// Makes a guard granting access to a part of the part this guard grants
+    /// access to, selected by `f`.
+    #[inline]
+    pub fn map<V: ?Sized>(
+        mut orig: MappedMutexGuard<'a, T, U, P, R>,
+        f: impl FnOnce(&mut U) -> &mut V,
+    ) -> MappedMutexGuard<'a, T, V, P, R> {
+        let data = NonNull::from(f(&mut *orig));
+        MappedMutexGuard::new(orig._guard, data)
+    }
+
+    /// Like [`map`](MappedMutexGuard::map), except that `f` may decline to
+    /// select a part by returning `None`, in which case the original guard is
+    /// returned.
+    #[inline]
+    pub fn try_map<V: ?Sized>(
+        mut orig: MappedMutexGuard<'a, T, U, P, R>,
+        f: impl FnOnce(&mut U) -> Option<&mut V>,
+    ) -> Result<MappedMutexGuard<'a, T, V, P, R>, Self> {
+        match f(&mut *orig).map(NonNull::from) {
+            Some(data) => Ok(MappedMutexGuard::new(orig._guard, data)),
+            None => Err(orig),
+        }
+    }
+
+    /// Returns a guard for the whole value, keeping the mutex locked.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `U`.
+    #[inline]
+    pub fn unmap(orig: MappedMutexGuard<'a, T, U, P, R>) -> MutexGuard<'a, T, P, R> {
+        orig._guard
+    }
+}
+
+impl Condvar {
+    /// Like [`wait`](Condvar::wait), but for a mapped guard.
+    ///
+    /// The mutex is released during the wait, so the part the guard was
+    /// mapped to may have been moved or removed by the time it is reacquired.
+    /// `f` selects the part again from the whole value before this returns.
+    #[inline]
+    pub fn wait_mapped<'a, T, U: ?Sized, P: PoisonPolicy, R: MutexStorage>(
+        &self,
+        guard: MappedMutexGuard<'a, T, U, P, R>,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> MappedMutexGuard<'a, T, U, P, R> {
+        let guard = self.wait(MappedMutexGuard::unmap(guard));
+        MutexGuard::map(guard, f)
+    }
+
+    /// Like [`wait_timeout`](Condvar::wait_timeout), but for a mapped guard,
+    /// like [`wait_mapped`](Condvar::wait_mapped).
+    #[inline]
+    pub fn wait_timeout_mapped<'a, T, U: ?Sized, P: PoisonPolicy, R: MutexStorage>(
+        &self,
+        guard: MappedMutexGuard<'a, T, U, P, R>,
+        dur: Duration,
+        f: impl FnOnce(&mut T) -> &mut U,
+    ) -> (MappedMutexGuard<'a, T, U, P, R>, WaitTimeoutResult) {
+        let (guard, result) = self.wait_timeout(MappedMutexGuard::unmap(guard), dur);
+        (MutexGuard::map(guard, f), result)
+    }
+
+    /// Like [`wait_while`](Condvar::wait_while), but for a mapped guard, like
+    /// [`wait_mapped`](Condvar::wait_mapped).
+    ///
+    /// `condition` is given the part selected by `f`.
+    pub fn wait_while_mapped<'a, T, U: ?Sized, P: PoisonPolicy, R: MutexStorage>(
+        &self,
+        mut guard: MappedMutexGuard<'a, T, U, P, R>,
+        mut f: impl FnMut(&mut T) -> &mut U,
+        mut condition: impl FnMut(&mut U) -> bool,
+    ) -> MappedMutexGuard<'a, T, U, P, R> {
+        while condition(&mut *guard) {
+            guard = self.wait_mapped(guard, &mut f);
+        }
+        guard
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, P: PoisonPolicy, R: MutexStorage> Deref
+    for MappedMutexGuard<'_, T, U, P, R>
+{
+    type Target = U;
+
+    #[inline]
+    fn deref(&self) -> &U {
+        // SAFETY: `data` was derived from the guard, which is still alive.
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, P: PoisonPolicy, R: MutexStorage> DerefMut
+    for MappedMutexGuard<'_, T, U, P, R>
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut U {
+        // SAFETY: `data` was derived mutably from the guard, which is still
+        // alive.
+        unsafe { self.data.as_mut() }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized + fmt::Debug, P: PoisonPolicy, R: MutexStorage> fmt::Debug
+    for MappedMutexGuard<'_, T, U, P, R>
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("MappedMutexGuard").field(&&**self).finish()
+    }
+}
+
+/// A guard granting shared access to a part of the value protected by an
+/// [`RwLock`], returned by [`RwLockReadGuard::map`] and [`RwLock::read_map`].
+///
+/// `T` is the type of the whole value, and `U` the type of the part.
+#[must_use]
+pub struct MappedRwLockReadGuard<'a, T: ?Sized + 'a, U: ?Sized + 'a, R: RwLockBackend = RawRwLock> {
+    _guard: RwLockReadGuard<'a, T, R>,
+    data: NonNull<U>,
+    _marker: PhantomData<&'a U>,
+}
+
+// SAFETY: the guard only hands out shared references to `U`.
+unsafe impl<T: ?Sized, U: ?Sized + Sync, R: RwLockBackend> Sync
+    for MappedRwLockReadGuard<'_, T, U, R>
+{
+}
+
+// SAFETY: the guard hands out shared references to `U`, and can only be sent
+// along with the guard keeping the lock held.
+unsafe impl<'a, T: ?Sized, U: ?Sized + Sync, R: RwLockBackend> Send
+    for MappedRwLockReadGuard<'a, T, U, R>
+where
+    RwLockReadGuard<'a, T, R>: Send,
+{
+}
+
+impl<'a, T: ?Sized, U: ?Sized, R: RwLockBackend> MappedRwLockReadGuard<'a, T, U, R> {
+    #[inline]
+    fn new(
+        guard: RwLockReadGuard<'a, T, R>,
+        data: NonNull<U>,
+    ) -> MappedRwLockReadGuard<'a, T, U, R> {
+        MappedRwLockReadGuard {
+            _guard: guard,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Releases the lock, exactly like dropping the guard.
+    ///
+    /// See [`RwLockReadGuard::unlock`].
+    #[inline]
+    pub fn unlock(s: Self) {
+        drop(s);
+    }
+
+    /// Makes a guard granting access to a part of the part this guard grants
+    /// access to, selected by `f`.
+    #[inline]
+    pub fn map<V: ?Sized>(
+        orig: MappedRwLockReadGuard<'a, T, U, R>,
+        f: impl FnOnce(&U) -> &V,
+    ) -> MappedRwLockReadGuard<'a, T, V, R> {
+        let data = NonNull::from(f(&*orig));
+        MappedRwLockReadGuard::new(orig._guard, data)
+    }
+
+    /// Like [`map`](MappedRwLockReadGuard::map), except that `f` may decline
+    /// to select a part by returning `None`, in which case the original guard
+    /// is returned.
+    #[inline]
+    pub fn try_map<V: ?Sized>(
+        orig: MappedRwLockReadGuard<'a, T, U, R>,
+        f: impl FnOnce(&U) -> Option<&V>,
+    ) -> Result<MappedRwLockReadGuard<'a, T, V, R>, MappedRwLockReadGuard<'a, T, U, R>> {
+        match f(&*orig).map(NonNull::from) {
+            Some(data) => Ok(MappedRwLockReadGuard::new(orig._guard, data)),
+            None => Err(orig),
+        }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, R: RwLockBackend> Deref for MappedRwLockReadGuard<'_, T, U, R> {
+    type Target = U;
+
+    #[inline]
+    fn deref(&self) -> &U {
+        // SAFETY: `data` was derived from the guard, which is still alive.
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized + fmt::Debug, R: RwLockBackend> fmt::Debug
+    for MappedRwLockReadGuard<'_, T, U, R>
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("MappedRwLockReadGuard")
+            .field(&&**self)
+            .finish()
+    }
+}
+
+/// A guard granting exclusive access to a part of the value protected by an
+/// [`RwLock`], returned by [`RwLockWriteGuard::map`] and
+/// [`RwLock::write_map`].
+///
+/// `T` is the type of the whole value, and `U` the type of the part.
+#[must_use]
+pub struct MappedRwLockWriteGuard<'a, T: ?Sized + 'a, U: ?Sized + 'a, R: RwLockBackend = RawRwLock>
+{
+    _guard: RwLockWriteGuard<'a, T, R>,
+    data: NonNull<U>,
+    _marker: PhantomData<&'a mut U>,
+}
+
+// SAFETY: the guard only hands out references to `U`.
+unsafe impl<T: ?Sized, U: ?Sized + Sync, R: RwLockBackend> Sync
+    for MappedRwLockWriteGuard<'_, T, U, R>
+{
+}
+
+// SAFETY: the guard hands out mutable references to `U`, and can only be sent
+// along with the guard keeping the lock held.
+unsafe impl<'a, T: ?Sized, U: ?Sized + Send, R: RwLockBackend> Send
+    for MappedRwLockWriteGuard<'a, T, U, R>
+where
+    RwLockWriteGuard<'a, T, R>: Send,
+{
+}
+
+impl<'a, T: ?Sized, U: ?Sized, R: RwLockBackend> MappedRwLockWriteGuard<'a, T, U, R> {
+    #[inline]
+    fn new(
+        guard: RwLockWriteGuard<'a, T, R>,
+        data: NonNull<U>,
+    ) -> MappedRwLockWriteGuard<'a, T, U, R> {
+        MappedRwLockWriteGuard {
+            _guard: guard,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Releases the lock, exactly like dropping the guard.
+    ///
+    /// See [`RwLockWriteGuard::unlock`].
+    #[inline]
+    pub fn unlock(s: Self) {
+        drop(s);
+    }
+
+    /// Makes a guard granting access to a part of the part this guard grants
+    /// access to, selected by `f`.
+    #[inline]
+    pub fn map<V: ?Sized>(
+        mut orig: MappedRwLockWriteGuard<'a, T, U, R>,
+        f: impl FnOnce(&mut U) -> &mut V,
+    ) -> MappedRwLockWriteGuard<'a, T, V, R> {
+        let data = NonNull::from(f(&mut *orig));
+        MappedRwLockWriteGuard::new(orig._guard, data)
+    }
+
+    /// Like [`map`](MappedRwLockWriteGuard::map), except that `f` may decline
+    /// to select a part by returning `None`, in which case the original guard
+    /// is returned.
+    #[inline]
+    pub fn try_map<V: ?Sized>(
+        mut orig: MappedRwLockWriteGuard<'a, T, U, R>,
+        f: impl FnOnce(&mut U) -> Option<&mut V>,
+    ) -> Result<MappedRwLockWriteGuard<'a, T, V, R>, MappedRwLockWriteGuard<'a, T, U, R>> {
+        match f(&mut *orig).map(NonNull::from) {
+            Some(data) => Ok(MappedRwLockWriteGuard::new(orig._guard, data)),
+            None => Err(orig),
+        }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, R: RwLockBackend> Deref for MappedRwLockWriteGuard<'_, T, U, R> {
+    type Target = U;
+
+    #[inline]
+    fn deref(&self) -> &U {
+        // SAFETY: `data` was derived from the guard, which is still alive.
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized, R: RwLockBackend> DerefMut for MappedRwLockWriteGuard<'_, T, U, R> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut U {
+        // SAFETY: `data` was derived mutably from the guard, which is still
+        // alive.
+        unsafe { self.data.as_mut() }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized + fmt::Debug, R: RwLockBackend> fmt::Debug
+    for MappedRwLockWriteGuard<'_, T, U, R>
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("MappedRwLockWriteGuard")
+            .field(&&**self)
+            .finish()
+    }
+}
@@ -0,0 +1,80 @@
+//! Reporting of lock behavior through the `metrics` facade.
+//!
+//! With the `metrics` feature enabled, every `Mutex` and `RwLock` guard
+//! reports to the recorder installed with the `metrics` crate once it is
+//! dropped:
+//!
+//! * the `antidote.lock.acquisitions` counter is incremented;
+//! * the time the acquisition waited for the lock is recorded, in seconds, in
+//!   the `antidote.lock.wait` histogram, as zero if it did not have to wait;
+//! * the time the lock was then held is recorded, in seconds, in the
+//!   `antidote.lock.hold` histogram.
+//!
+//! Locks registered with [`register_mutex`] or [`register_rwlock`] are
+//! identified by the `lock` label, as are locks created with `new_named` with
+//! the `names` feature enabled. Other locks are still reported, without the
+//! label, so they add up to a single series per metric. Exporters such as
+//! Prometheus's turn the dots of the names into underscores.
+//!
+//! Nothing is recorded until a recorder is installed, though each release
+//! still looks up the name of its lock. Shared and exclusive acquisitions are
+//! reported together.
+
+use std::{sync, time::Duration};
+
+use crate::{Mutex, RwLock};
+
+static REGISTRY: sync::RwLock<Vec<(&'static str, usize)>> = sync::RwLock::new(Vec::new());
+
+fn register(name: &'static str, addr: usize) {
+    REGISTRY
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((name, addr));
+}
+
+/// Registers a `Mutex` to be reported under the given name.
+///
+/// The name takes precedence over one given with `new_named`.
+pub fn register_mutex<T: ?Sized>(name: &'static str, mutex: &'static Mutex<T>) {
+    register(name, mutex.addr());
+}
+
+/// Registers an `RwLock` to be reported under the given name.
+///
+/// The name takes precedence over one given with `new_named`.
+pub fn register_rwlock<T: ?Sized>(name: &'static str, rwlock: &'static RwLock<T>) {
+    register(name, rwlock.addr());
+}
+
+// Returns the name the lock at `addr` was registered under, if any.
+fn lookup(addr: usize) -> Option<&'static str> {
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    registry
+        .iter()
+        .find(|&&(_, registered)| registered == addr)
+        .map(|&(name, _)| name)
+}
+
+/// Reports an acquisition of the lock at `addr`, which waited for `waited` if
+/// it blocked and then held the lock for `held`.
+pub(crate) fn record(
+    addr: usize,
+    name: Option<&'static str>,
+    waited: Option<Duration>,
+    held: Duration,
+) {
+    let waited = waited.unwrap_or(Duration::ZERO);
+    match lookup(addr).or(name) {
+        Some(name) => {
+            ::metrics::counter!("antidote.lock.acquisitions", "lock" => name).increment(1);
+            ::metrics::histogram!("antidote.lock.wait", "lock" => name).record(waited);
+            ::metrics::histogram!("antidote.lock.hold", "lock" => name).record(held);
+        }
+        None => {
+            ::metrics::counter!("antidote.lock.acquisitions").increment(1);
+            ::metrics::histogram!("antidote.lock.wait").record(waited);
+            ::metrics::histogram!("antidote.lock.hold").record(held);
+        }
+    }
+}
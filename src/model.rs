@@ -0,0 +1,339 @@
+//! The locks used when model checking with `loom` or `shuttle`.
+//!
+//! When built with `--cfg loom`, the locks delegate to their `loom::sync`
+//! counterparts, so that `loom` can explore every interleaving of the code
+//! using them. Only the API which `loom` can model is provided, and the types
+//! cannot be created in a `const` context.
+//!
+//! When built with `--cfg shuttle` instead, they delegate to `shuttle::sync`,
+//! whose API is a superset of `loom`'s, so that `shuttle` can explore random
+//! schedules. The same API is provided either way.
+
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    panic::{RefUnwindSafe, UnwindSafe},
+    sync::{LockResult, TryLockError as StdTryLockError},
+    time::Duration,
+};
+
+#[cfg(loom)]
+use loom::sync;
+#[cfg(all(shuttle, not(loom)))]
+use shuttle::sync;
+
+use crate::{LockOperation, TryLockError, TryLockResult};
+
+// `loom` and `shuttle` poison their locks like the standard library does.
+#[inline]
+fn ignore_poison<G>(result: LockResult<G>) -> G {
+    result.unwrap_or_else(|e| e.into_inner())
+}
+
+#[inline]
+fn try_ignore_poison<G>(
+    result: Result<G, StdTryLockError<G>>,
+    operation: LockOperation,
+) -> TryLockResult<G> {
+    match result {
+        Ok(t) => Ok(t),
+        Err(StdTryLockError::Poisoned(e)) => Ok(e.into_inner()),
+        Err(StdTryLockError::WouldBlock) => Err(TryLockError::new(operation)),
+    }
+}
+
+/// Like `std::sync::Mutex` except that it does not poison itself.
+///
+/// When model checking, this is `loom`'s or `shuttle`'s mutex.
+#[derive(Default)]
+pub struct Mutex<T: ?Sized>(sync::Mutex<T>);
+
+impl<T: ?Sized> UnwindSafe for Mutex<T> {}
+impl<T: ?Sized> RefUnwindSafe for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    /// Like `std::sync::Mutex::new`.
+    #[inline]
+    pub fn new(t: T) -> Mutex<T> {
+        Mutex(sync::Mutex::new(t))
+    }
+
+    /// Like `std::sync::Mutex::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        ignore_poison(self.0.into_inner())
+    }
+}
+
+impl<T> From<T> for Mutex<T> {
+    /// Like `std::sync::Mutex::from`.
+    #[inline]
+    fn from(t: T) -> Mutex<T> {
+        Mutex::new(t)
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Like `std::sync::Mutex::lock`.
+    #[inline]
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        MutexGuard(ignore_poison(self.0.lock()))
+    }
+
+    /// Like `std::sync::Mutex::try_lock`.
+    #[inline]
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        try_ignore_poison(self.0.try_lock(), LockOperation::Lock).map(MutexGuard)
+    }
+
+    /// Like `std::sync::Mutex::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        ignore_poison(self.0.get_mut())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Mutex").finish_non_exhaustive()
+    }
+}
+
+#[must_use]
+/// Like `std::sync::MutexGuard`.
+pub struct MutexGuard<'a, T: ?Sized + 'a>(sync::MutexGuard<'a, T>);
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for MutexGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("MutexGuard").field(&&**self).finish()
+    }
+}
+
+/// Like `std::sync::Condvar`.
+///
+/// When model checking, this is `loom`'s or `shuttle`'s condition variable.
+/// Neither ever times a wait out, so the timed waits only return once
+/// notified.
+#[derive(Default)]
+pub struct Condvar(sync::Condvar);
+
+impl Condvar {
+    /// Like `std::sync::Condvar::new`.
+    #[inline]
+    pub fn new() -> Condvar {
+        Condvar(sync::Condvar::new())
+    }
+
+    /// Like `std::sync::Condvar::wait`.
+    #[inline]
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        MutexGuard(ignore_poison(self.0.wait(guard.0)))
+    }
+
+    /// Like `std::sync::Condvar::wait_timeout`.
+    #[inline]
+    pub fn wait_timeout<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        dur: Duration,
+    ) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+        let (guard, result) = ignore_poison(self.0.wait_timeout(guard.0, dur));
+        (MutexGuard(guard), WaitTimeoutResult(result.timed_out()))
+    }
+
+    /// Like `std::sync::Condvar::wait_while`.
+    #[inline]
+    pub fn wait_while<'a, T, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: F,
+    ) -> MutexGuard<'a, T>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    /// Like `std::sync::Condvar::wait_timeout_while`.
+    pub fn wait_timeout_while<'a, T, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        dur: Duration,
+        mut condition: F,
+    ) -> (MutexGuard<'a, T>, WaitTimeoutResult)
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            let (new_guard, result) = self.wait_timeout(guard, dur);
+            guard = new_guard;
+            if result.timed_out() {
+                let timed_out = condition(&mut *guard);
+                return (guard, WaitTimeoutResult(timed_out));
+            }
+        }
+        (guard, WaitTimeoutResult(false))
+    }
+
+    /// Like `std::sync::Condvar::notify_one`.
+    #[inline]
+    pub fn notify_one(&self) {
+        self.0.notify_one();
+    }
+
+    /// Like `std::sync::Condvar::notify_all`.
+    #[inline]
+    pub fn notify_all(&self) {
+        self.0.notify_all();
+    }
+}
+
+impl fmt::Debug for Condvar {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Condvar").finish_non_exhaustive()
+    }
+}
+
+/// Like `std::sync::WaitTimeoutResult`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct WaitTimeoutResult(bool);
+
+impl WaitTimeoutResult {
+    /// Like `std::sync::WaitTimeoutResult::timed_out`.
+    #[inline]
+    pub fn timed_out(&self) -> bool {
+        self.0
+    }
+}
+
+/// Like `std::sync::RwLock` except that it does not poison itself.
+///
+/// When model checking, this is `loom`'s or `shuttle`'s lock. `loom`'s does
+/// not support unsized values, so neither does this.
+#[derive(Default)]
+pub struct RwLock<T>(sync::RwLock<T>);
+
+impl<T> UnwindSafe for RwLock<T> {}
+impl<T> RefUnwindSafe for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    /// Like `std::sync::RwLock::new`.
+    #[inline]
+    pub fn new(t: T) -> RwLock<T> {
+        RwLock(sync::RwLock::new(t))
+    }
+
+    /// Like `std::sync::RwLock::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        ignore_poison(self.0.into_inner())
+    }
+
+    /// Like `std::sync::RwLock::read`.
+    #[inline]
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        RwLockReadGuard(ignore_poison(self.0.read()))
+    }
+
+    /// Like `std::sync::RwLock::try_read`.
+    #[inline]
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        try_ignore_poison(self.0.try_read(), LockOperation::Read).map(RwLockReadGuard)
+    }
+
+    /// Like `std::sync::RwLock::write`.
+    #[inline]
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        RwLockWriteGuard(ignore_poison(self.0.write()))
+    }
+
+    /// Like `std::sync::RwLock::try_write`.
+    #[inline]
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        try_ignore_poison(self.0.try_write(), LockOperation::Write).map(RwLockWriteGuard)
+    }
+
+    /// Like `std::sync::RwLock::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        ignore_poison(self.0.get_mut())
+    }
+}
+
+impl<T> From<T> for RwLock<T> {
+    /// Like `std::sync::RwLock::from`.
+    #[inline]
+    fn from(t: T) -> RwLock<T> {
+        RwLock::new(t)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("RwLock").finish_non_exhaustive()
+    }
+}
+
+#[must_use]
+/// Like `std::sync::RwLockReadGuard`.
+pub struct RwLockReadGuard<'a, T: 'a>(sync::RwLockReadGuard<'a, T>);
+
+impl<T> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("RwLockReadGuard").field(&&**self).finish()
+    }
+}
+
+#[must_use]
+/// Like `std::sync::RwLockWriteGuard`.
+pub struct RwLockWriteGuard<'a, T: 'a>(sync::RwLockWriteGuard<'a, T>);
+
+impl<T> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("RwLockWriteGuard").field(&&**self).finish()
+    }
+}
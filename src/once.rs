@@ -0,0 +1,243 @@
+use std::{
+    cell::UnsafeCell,
+    convert::Infallible,
+    fmt,
+    mem::MaybeUninit,
+    panic::{RefUnwindSafe, UnwindSafe},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::Mutex;
+
+/// A synchronization primitive which runs a one-time initialization.
+///
+/// Like `std::sync::Once`, except that it is never poisoned: if the closure
+/// passed to [`call_once`](Once::call_once) panics, the `Once` is left
+/// incomplete and the next caller runs its own closure.
+pub struct Once {
+    // Serializes initializers, so only one of them runs at a time.
+    init: Mutex<()>,
+    complete: AtomicBool,
+}
+
+impl Once {
+    /// Creates a new `Once` which has not run yet.
+    #[inline]
+    pub const fn new() -> Once {
+        Once {
+            init: Mutex::new(()),
+            complete: AtomicBool::new(false),
+        }
+    }
+
+    /// Runs `f` if no closure has completed yet on this `Once`.
+    ///
+    /// If several threads call this concurrently, only one of them runs its
+    /// closure, and the others block until it is done. When this returns, a
+    /// closure has completed, and its effects are visible to the current
+    /// thread. If `f` panics, the panic is propagated and the `Once` is left
+    /// incomplete. Calling this again from within `f` deadlocks.
+    pub fn call_once(&self, f: impl FnOnce()) {
+        let result = self.try_call_once(|| {
+            f();
+            Ok::<(), Infallible>(())
+        });
+        match result {
+            Ok(()) => {}
+            Err(e) => match e {},
+        }
+    }
+
+    /// Returns `true` if a closure has completed on this `Once`.
+    #[inline]
+    pub fn is_completed(&self) -> bool {
+        self.complete.load(Ordering::Acquire)
+    }
+
+    // Runs `f` unless a closure has completed, marking the `Once` complete only
+    // if `f` succeeds.
+    #[inline]
+    fn try_call_once<E>(&self, f: impl FnOnce() -> Result<(), E>) -> Result<(), E> {
+        if self.is_completed() {
+            return Ok(());
+        }
+        self.call_slow(f)
+    }
+
+    #[cold]
+    fn call_slow<E>(&self, f: impl FnOnce() -> Result<(), E>) -> Result<(), E> {
+        let _init = self.init.lock();
+        if self.is_completed() {
+            return Ok(());
+        }
+        f()?;
+        self.complete.store(true, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl Default for Once {
+    #[inline]
+    fn default() -> Once {
+        Once::new()
+    }
+}
+
+impl fmt::Debug for Once {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Once")
+            .field("completed", &self.is_completed())
+            .finish()
+    }
+}
+
+/// A cell which is written to at most once, typically for lazily initialized
+/// globals.
+///
+/// Like `std::sync::OnceLock`, except that initialization can also fail:
+/// [`get_or_try_init`](OnceLock::get_or_try_init) leaves the cell empty if the
+/// initializer returns an error or panics, and the next caller tries again.
+/// Nothing is ever poisoned.
+pub struct OnceLock<T> {
+    once: Once,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// SAFETY: the value is only written once, by the closure run by `once`, and is
+// only shared once `once` has completed.
+unsafe impl<T: Send + Sync> Sync for OnceLock<T> {}
+unsafe impl<T: Send> Send for OnceLock<T> {}
+
+impl<T: UnwindSafe> UnwindSafe for OnceLock<T> {}
+impl<T: RefUnwindSafe + UnwindSafe> RefUnwindSafe for OnceLock<T> {}
+
+impl<T> OnceLock<T> {
+    /// Creates a new empty cell.
+    #[inline]
+    pub const fn new() -> OnceLock<T> {
+        OnceLock {
+            once: Once::new(),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Returns a reference to the value, or `None` if the cell is empty.
+    #[inline]
+    pub fn get(&self) -> Option<&T> {
+        if self.once.is_completed() {
+            // SAFETY: the value was initialized before `once` completed.
+            Some(unsafe { (*self.value.get()).assume_init_ref() })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the value, or `None` if the cell is
+    /// empty.
+    #[inline]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        if *self.once.complete.get_mut() {
+            // SAFETY: the value was initialized before `once` completed.
+            Some(unsafe { self.value.get_mut().assume_init_mut() })
+        } else {
+            None
+        }
+    }
+
+    /// Sets the value if the cell is empty, blocking while another thread is
+    /// initializing it.
+    ///
+    /// Returns `value` back if the cell was already set.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+        self.get_or_init(|| value.take().unwrap());
+        match value {
+            Some(value) => Err(value),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns a reference to the value, initializing it with `f` if the cell
+    /// is empty.
+    ///
+    /// If several threads call this concurrently, only one of them runs its
+    /// initializer, and the others block until it is done. If `f` panics, the
+    /// cell is left empty. Initializing the cell again from within `f`
+    /// deadlocks.
+    pub fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+        match self.get_or_try_init(|| Ok::<T, Infallible>(f())) {
+            Ok(value) => value,
+            Err(e) => match e {},
+        }
+    }
+
+    /// Returns a reference to the value, initializing it with `f` if the cell
+    /// is empty.
+    ///
+    /// If `f` returns an error, the cell is left empty and the error is
+    /// returned; the next call then runs its own initializer. Otherwise this
+    /// behaves like [`get_or_init`](OnceLock::get_or_init).
+    pub fn get_or_try_init<E>(&self, f: impl FnOnce() -> Result<T, E>) -> Result<&T, E> {
+        self.once.try_call_once(|| {
+            let value = f()?;
+            // SAFETY: `once` runs a single closure at a time and only until
+            // one succeeds, so nothing else can be accessing the empty value.
+            unsafe { (*self.value.get()).write(value) };
+            Ok(())
+        })?;
+        Ok(self.get().unwrap())
+    }
+
+    /// Takes the value out of the cell, leaving it empty.
+    #[inline]
+    pub fn take(&mut self) -> Option<T> {
+        if *self.once.complete.get_mut() {
+            *self.once.complete.get_mut() = false;
+            // SAFETY: the value was initialized, and the cell is now marked
+            // empty so it will not be read or dropped again.
+            Some(unsafe { self.value.get_mut().assume_init_read() })
+        } else {
+            None
+        }
+    }
+
+    /// Consumes the cell, returning the value, or `None` if the cell is empty.
+    #[inline]
+    pub fn into_inner(mut self) -> Option<T> {
+        self.take()
+    }
+}
+
+impl<T> Drop for OnceLock<T> {
+    fn drop(&mut self) {
+        drop(self.take());
+    }
+}
+
+impl<T> Default for OnceLock<T> {
+    #[inline]
+    fn default() -> OnceLock<T> {
+        OnceLock::new()
+    }
+}
+
+impl<T> From<T> for OnceLock<T> {
+    /// Creates a cell set to `t`.
+    #[inline]
+    fn from(t: T) -> OnceLock<T> {
+        let cell = OnceLock::new();
+        let _ = cell.set(t);
+        cell
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OnceLock<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_tuple("OnceLock");
+        match self.get() {
+            Some(value) => d.field(value),
+            None => d.field(&format_args!("<uninit>")),
+        };
+        d.finish()
+    }
+}
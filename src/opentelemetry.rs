@@ -0,0 +1,154 @@
+//! Reporting of lock behavior through OpenTelemetry.
+//!
+//! Once [`install`] has been called, every `Mutex` and `RwLock` acquisition
+//! which had to block increments the `antidote.lock.contended` counter, and
+//! waits and holds lasting longer than the configured thresholds are emitted
+//! as `antidote.lock.wait` and `antidote.lock.hold` spans. The thresholds are
+//! those of the process-wide [`Config`](crate::Config). The spans are
+//! children of the context current on the thread when the guard is dropped,
+//! so they show up within the traces of the code using the lock.
+//!
+//! Locks registered with [`register_mutex`] or [`register_rwlock`] are
+//! identified by the `lock.name` attribute on both the counter and the spans.
+//! Other locks are still reported, without the attribute.
+
+use std::{
+    borrow::Cow,
+    sync::{
+        self,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, SystemTime},
+};
+
+use ::opentelemetry::{
+    global::{self, BoxedTracer},
+    metrics::Counter,
+    trace::{Span, Tracer},
+    KeyValue,
+};
+
+use crate::{Mutex, RwLock};
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static INSTRUMENTS: sync::RwLock<Option<Instruments>> = sync::RwLock::new(None);
+static REGISTRY: sync::Mutex<Vec<(&'static str, usize)>> = sync::Mutex::new(Vec::new());
+
+struct Instruments {
+    tracer: BoxedTracer,
+    contended: Counter<u64>,
+}
+
+/// Starts reporting through the global tracer and meter providers.
+///
+/// The instruments are created from the providers installed at the time of
+/// the call, so this should be called after they have been set up. Calling it
+/// again picks up providers installed since.
+pub fn install() {
+    let meter = global::meter("antidote");
+    let contended = meter
+        .u64_counter("antidote.lock.contended")
+        .with_description("Lock acquisitions which had to block")
+        .build();
+    let instruments = Instruments {
+        tracer: global::tracer("antidote"),
+        contended,
+    };
+    *INSTRUMENTS.write().unwrap_or_else(|e| e.into_inner()) = Some(instruments);
+    INSTALLED.store(true, Ordering::Release);
+}
+
+/// Stops reporting.
+pub fn uninstall() {
+    INSTALLED.store(false, Ordering::Release);
+    *INSTRUMENTS.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+fn register(name: &'static str, addr: usize) {
+    REGISTRY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((name, addr));
+}
+
+/// Registers a `Mutex` to be reported under the given name.
+pub fn register_mutex<T: ?Sized>(name: &'static str, mutex: &'static Mutex<T>) {
+    register(name, mutex.addr());
+}
+
+/// Registers an `RwLock` to be reported under the given name.
+pub fn register_rwlock<T: ?Sized>(name: &'static str, rwlock: &'static RwLock<T>) {
+    register(name, rwlock.addr());
+}
+
+fn attributes(addr: usize) -> Vec<KeyValue> {
+    let registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    registry
+        .iter()
+        .find(|&&(_, registered)| registered == addr)
+        .map(|&(name, _)| KeyValue::new("lock.name", name))
+        .into_iter()
+        .collect()
+}
+
+pub(crate) fn record(addr: usize, waited: Option<Duration>, held: Duration) {
+    if !INSTALLED.load(Ordering::Relaxed) {
+        return;
+    }
+    if waited.is_some() || held >= crate::config().hold_threshold() {
+        report(addr, waited, held);
+    }
+}
+
+#[cold]
+fn report(addr: usize, waited: Option<Duration>, held: Duration) {
+    let instruments = INSTRUMENTS.read().unwrap_or_else(|e| e.into_inner());
+    let instruments = match &*instruments {
+        Some(instruments) => instruments,
+        None => return,
+    };
+    let attributes = attributes(addr);
+    if waited.is_some() {
+        instruments.contended.add(1, &attributes);
+    }
+
+    let released = SystemTime::now();
+    let acquired = released.checked_sub(held).unwrap_or(released);
+    if let Some(waited) = waited {
+        if waited >= crate::config().wait_threshold() {
+            let requested = acquired.checked_sub(waited).unwrap_or(acquired);
+            span(
+                instruments,
+                "antidote.lock.wait",
+                requested,
+                acquired,
+                &attributes,
+            );
+        }
+    }
+    if held >= crate::config().hold_threshold() {
+        span(
+            instruments,
+            "antidote.lock.hold",
+            acquired,
+            released,
+            &attributes,
+        );
+    }
+}
+
+fn span(
+    instruments: &Instruments,
+    name: &'static str,
+    start: SystemTime,
+    end: SystemTime,
+    attributes: &[KeyValue],
+) {
+    let mut span = instruments
+        .tracer
+        .span_builder(Cow::Borrowed(name))
+        .with_start_time(start)
+        .with_attributes(attributes.to_vec())
+        .start(&instruments.tracer);
+    span.end_with_timestamp(end);
+}
@@ -0,0 +1,467 @@
+//! Locks with a declared acquisition order checked at runtime.
+//!
+//! [`define_lock_order!`](crate::define_lock_order) declares a chain of lock
+//! classes, each of which must be acquired before the ones following it, as in
+//! `define_lock_order! { DB < CACHE < METRICS }`. This defines a `static`
+//! [`LockClass`] for each name, which is then assigned to locks with
+//! [`OrderedMutex::new`] or [`OrderedRwLock::new`]. In builds with debug
+//! assertions enabled, acquiring a lock while the thread holds a lock of a
+//! class declared after it panics, reporting where both locks were acquired.
+//! Without debug assertions the locks behave like plain `Mutex`es and
+//! `RwLock`s.
+//!
+//! Classes from separate chains are unordered with respect to each other, as
+//! are locks of the same class.
+
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    panic::Location,
+};
+
+use crate::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockResult};
+
+/// Declares one or more chains of [`LockClass`]es.
+///
+/// Each chain is written as names separated by `<`, each of which becomes a
+/// `static` of the given visibility. Several chains can be declared at once,
+/// separated by `;`. See the [`order`](crate::order) module for details.
+#[macro_export]
+macro_rules! define_lock_order {
+    ($($vis:vis $first:ident $(< $rest:ident)*);+ $(;)?) => {
+        $(
+            $vis static $first: $crate::order::LockClass =
+                $crate::order::LockClass::__first(stringify!($first));
+            $crate::__define_lock_order_rest!($vis $first, 1; $($rest)*);
+        )+
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __define_lock_order_rest {
+    ($vis:vis $first:ident, $rank:expr; $next:ident $($rest:ident)*) => {
+        $vis static $next: $crate::order::LockClass =
+            $crate::order::LockClass::__after(stringify!($next), &$first, $rank);
+        $crate::__define_lock_order_rest!($vis $first, $rank + 1; $($rest)*);
+    };
+    ($vis:vis $first:ident, $rank:expr;) => {};
+}
+
+/// A class of locks within a declared lock order.
+///
+/// Created with [`define_lock_order!`](crate::define_lock_order).
+pub struct LockClass {
+    name: &'static str,
+    // The first class of the chain, or `None` for the first class itself.
+    first: Option<&'static LockClass>,
+    rank: usize,
+}
+
+impl LockClass {
+    #[doc(hidden)]
+    pub const fn __first(name: &'static str) -> LockClass {
+        LockClass {
+            name,
+            first: None,
+            rank: 0,
+        }
+    }
+
+    #[doc(hidden)]
+    pub const fn __after(name: &'static str, first: &'static LockClass, rank: usize) -> LockClass {
+        LockClass {
+            name,
+            first: Some(first),
+            rank,
+        }
+    }
+
+    /// Returns the name of the class.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns `true` if the class was declared before `other` in the same
+    /// chain.
+    pub fn precedes(&self, other: &LockClass) -> bool {
+        self.chain() == other.chain() && self.rank < other.rank
+    }
+
+    fn chain(&self) -> *const LockClass {
+        self.first.unwrap_or(self)
+    }
+}
+
+impl fmt::Debug for LockClass {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("LockClass").field(&self.name).finish()
+    }
+}
+
+#[cfg(debug_assertions)]
+mod checks {
+    use std::{cell::RefCell, panic::Location};
+
+    use super::LockClass;
+
+    type Acquired = (&'static LockClass, &'static Location<'static>);
+
+    thread_local! {
+        static HELD: RefCell<Vec<Acquired>> = const { RefCell::new(Vec::new()) };
+    }
+
+    pub(super) fn check(class: &'static LockClass, location: &'static Location<'static>) {
+        HELD.with(|held| {
+            let held = held.borrow();
+            if let Some((held_class, held_location)) = held
+                .iter()
+                .find(|(held_class, _)| class.precedes(held_class))
+            {
+                panic!(
+                    "lock order violation: `{}` acquired at {} while holding `{}` acquired at {}, \
+                     but `{}` is declared to come before `{}`",
+                    class.name,
+                    location,
+                    held_class.name,
+                    held_location,
+                    class.name,
+                    held_class.name,
+                );
+            }
+        });
+    }
+
+    pub(super) fn push(class: &'static LockClass, location: &'static Location<'static>) {
+        HELD.with(|held| held.borrow_mut().push((class, location)));
+    }
+
+    pub(super) fn pop(class: &'static LockClass) {
+        let _ = HELD.try_with(|held| {
+            let mut held = held.borrow_mut();
+            if let Some(idx) = held
+                .iter()
+                .rposition(|(held_class, _)| std::ptr::eq(*held_class, class))
+            {
+                held.remove(idx);
+            }
+        });
+    }
+}
+
+/// Removes the lock from the thread's held locks when dropped.
+struct Held {
+    #[cfg(debug_assertions)]
+    class: &'static LockClass,
+}
+
+impl Held {
+    #[inline]
+    fn new(class: &'static LockClass, location: &'static Location<'static>) -> Held {
+        #[cfg(debug_assertions)]
+        {
+            checks::push(class, location);
+            Held { class }
+        }
+        #[cfg(not(debug_assertions))]
+        {
+            let _ = (class, location);
+            Held {}
+        }
+    }
+}
+
+#[cfg(debug_assertions)]
+impl Drop for Held {
+    fn drop(&mut self) {
+        checks::pop(self.class);
+    }
+}
+
+#[inline]
+fn check(class: &'static LockClass, location: &'static Location<'static>) {
+    #[cfg(debug_assertions)]
+    checks::check(class, location);
+    #[cfg(not(debug_assertions))]
+    let _ = (class, location);
+}
+
+/// A `Mutex` belonging to a [`LockClass`].
+pub struct OrderedMutex<T: ?Sized> {
+    class: &'static LockClass,
+    mutex: Mutex<T>,
+}
+
+impl<T> OrderedMutex<T> {
+    /// Creates a new mutex of the given class in an unlocked state.
+    #[inline]
+    pub const fn new(class: &'static LockClass, t: T) -> OrderedMutex<T> {
+        OrderedMutex {
+            class,
+            mutex: Mutex::new(t),
+        }
+    }
+
+    /// Consumes the mutex, returning the underlying data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.mutex.into_inner()
+    }
+}
+
+impl<T: ?Sized> OrderedMutex<T> {
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so.
+    ///
+    /// # Panics
+    ///
+    /// With debug assertions enabled, panics if the current thread holds a
+    /// lock of a class declared after this mutex's class.
+    #[inline]
+    #[track_caller]
+    pub fn lock(&self) -> OrderedMutexGuard<'_, T> {
+        let location = Location::caller();
+        check(self.class, location);
+        let guard = self.mutex.lock();
+        OrderedMutexGuard {
+            guard,
+            _held: Held::new(self.class, location),
+        }
+    }
+
+    /// Attempts to acquire the mutex without blocking.
+    ///
+    /// Since this cannot deadlock, the lock order is not checked, but the
+    /// acquired lock is taken into account by later checks.
+    #[inline]
+    #[track_caller]
+    pub fn try_lock(&self) -> TryLockResult<OrderedMutexGuard<'_, T>> {
+        let location = Location::caller();
+        let guard = self.mutex.try_lock()?;
+        Ok(OrderedMutexGuard {
+            guard,
+            _held: Held::new(self.class, location),
+        })
+    }
+
+    /// Returns the class of the mutex.
+    #[inline]
+    pub fn class(&self) -> &'static LockClass {
+        self.class
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// No locking is necessary since the borrow guarantees exclusive access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.mutex.get_mut()
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for OrderedMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("OrderedMutex")
+            .field("class", &self.class)
+            .field("mutex", &&self.mutex)
+            .finish()
+    }
+}
+
+/// A guard for an [`OrderedMutex`].
+#[must_use]
+pub struct OrderedMutexGuard<'a, T: ?Sized + 'a> {
+    guard: MutexGuard<'a, T>,
+    _held: Held,
+}
+
+impl<T: ?Sized> Deref for OrderedMutexGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized> DerefMut for OrderedMutexGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for OrderedMutexGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.guard, fmt)
+    }
+}
+
+/// An `RwLock` belonging to a [`LockClass`].
+///
+/// Shared and exclusive acquisitions are checked alike.
+pub struct OrderedRwLock<T: ?Sized> {
+    class: &'static LockClass,
+    rwlock: RwLock<T>,
+}
+
+impl<T> OrderedRwLock<T> {
+    /// Creates a new lock of the given class in an unlocked state.
+    #[inline]
+    pub const fn new(class: &'static LockClass, t: T) -> OrderedRwLock<T> {
+        OrderedRwLock {
+            class,
+            rwlock: RwLock::new(t),
+        }
+    }
+
+    /// Consumes the lock, returning the underlying data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.rwlock.into_inner()
+    }
+}
+
+impl<T: ?Sized> OrderedRwLock<T> {
+    /// Locks the lock with shared read access, blocking the current thread
+    /// until it is able to do so.
+    ///
+    /// # Panics
+    ///
+    /// With debug assertions enabled, panics if the current thread holds a
+    /// lock of a class declared after this lock's class.
+    #[inline]
+    #[track_caller]
+    pub fn read(&self) -> OrderedRwLockReadGuard<'_, T> {
+        let location = Location::caller();
+        check(self.class, location);
+        let guard = self.rwlock.read();
+        OrderedRwLockReadGuard {
+            guard,
+            _held: Held::new(self.class, location),
+        }
+    }
+
+    /// Attempts to lock the lock with shared read access without blocking.
+    ///
+    /// Since this cannot deadlock, the lock order is not checked, but the
+    /// acquired lock is taken into account by later checks.
+    #[inline]
+    #[track_caller]
+    pub fn try_read(&self) -> TryLockResult<OrderedRwLockReadGuard<'_, T>> {
+        let location = Location::caller();
+        let guard = self.rwlock.try_read()?;
+        Ok(OrderedRwLockReadGuard {
+            guard,
+            _held: Held::new(self.class, location),
+        })
+    }
+
+    /// Locks the lock with exclusive write access, blocking the current thread
+    /// until it is able to do so.
+    ///
+    /// # Panics
+    ///
+    /// With debug assertions enabled, panics if the current thread holds a
+    /// lock of a class declared after this lock's class.
+    #[inline]
+    #[track_caller]
+    pub fn write(&self) -> OrderedRwLockWriteGuard<'_, T> {
+        let location = Location::caller();
+        check(self.class, location);
+        let guard = self.rwlock.write();
+        OrderedRwLockWriteGuard {
+            guard,
+            _held: Held::new(self.class, location),
+        }
+    }
+
+    /// Attempts to lock the lock with exclusive write access without blocking.
+    ///
+    /// Since this cannot deadlock, the lock order is not checked, but the
+    /// acquired lock is taken into account by later checks.
+    #[inline]
+    #[track_caller]
+    pub fn try_write(&self) -> TryLockResult<OrderedRwLockWriteGuard<'_, T>> {
+        let location = Location::caller();
+        let guard = self.rwlock.try_write()?;
+        Ok(OrderedRwLockWriteGuard {
+            guard,
+            _held: Held::new(self.class, location),
+        })
+    }
+
+    /// Returns the class of the lock.
+    #[inline]
+    pub fn class(&self) -> &'static LockClass {
+        self.class
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// No locking is necessary since the borrow guarantees exclusive access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.rwlock.get_mut()
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for OrderedRwLock<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("OrderedRwLock")
+            .field("class", &self.class)
+            .field("rwlock", &&self.rwlock)
+            .finish()
+    }
+}
+
+/// A shared guard for an [`OrderedRwLock`].
+#[must_use]
+pub struct OrderedRwLockReadGuard<'a, T: ?Sized + 'a> {
+    guard: RwLockReadGuard<'a, T>,
+    _held: Held,
+}
+
+impl<T: ?Sized> Deref for OrderedRwLockReadGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for OrderedRwLockReadGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.guard, fmt)
+    }
+}
+
+/// An exclusive guard for an [`OrderedRwLock`].
+#[must_use]
+pub struct OrderedRwLockWriteGuard<'a, T: ?Sized + 'a> {
+    guard: RwLockWriteGuard<'a, T>,
+    _held: Held,
+}
+
+impl<T: ?Sized> Deref for OrderedRwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized> DerefMut for OrderedRwLockWriteGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for OrderedRwLockWriteGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.guard, fmt)
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    fmt, mem,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+use crate::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockResult};
+
+impl<T: ?Sized + 'static> Mutex<T> {
+    /// Like [`lock`](Mutex::lock), but returns a guard which keeps the mutex
+    /// alive through `self` instead of borrowing it.
+    ///
+    /// The guard has no lifetime, so it can be stored in a long-lived struct.
+    #[inline]
+    pub fn lock_owned(self: &Arc<Self>) -> OwnedMutexGuard<T> {
+        // SAFETY: the guard is stored alongside a clone of the `Arc`.
+        unsafe { OwnedMutexGuard::new(self.clone(), |mutex| mutex.lock()) }
+    }
+
+    /// Like [`try_lock`](Mutex::try_lock), but returns a guard which keeps the
+    /// mutex alive through `self` instead of borrowing it.
+    #[inline]
+    pub fn try_lock_owned(self: &Arc<Self>) -> TryLockResult<OwnedMutexGuard<T>> {
+        let guard = self.try_lock()?;
+        // SAFETY: as in `lock_owned`.
+        Ok(unsafe { OwnedMutexGuard::new(self.clone(), |_| guard) })
+    }
+}
+
+impl<T: ?Sized + 'static> RwLock<T> {
+    /// Like [`read`](RwLock::read), but returns a guard which keeps the lock
+    /// alive through `self` instead of borrowing it.
+    ///
+    /// The guard has no lifetime, so it can be stored in a long-lived struct.
+    #[inline]
+    pub fn read_owned(self: &Arc<Self>) -> OwnedRwLockReadGuard<T> {
+        // SAFETY: the guard is stored alongside a clone of the `Arc`.
+        unsafe { OwnedRwLockReadGuard::new(self.clone(), |rwlock| rwlock.read()) }
+    }
+
+    /// Like [`try_read`](RwLock::try_read), but returns a guard which keeps
+    /// the lock alive through `self` instead of borrowing it.
+    #[inline]
+    pub fn try_read_owned(self: &Arc<Self>) -> TryLockResult<OwnedRwLockReadGuard<T>> {
+        let guard = self.try_read()?;
+        // SAFETY: as in `read_owned`.
+        Ok(unsafe { OwnedRwLockReadGuard::new(self.clone(), |_| guard) })
+    }
+
+    /// Like [`write`](RwLock::write), but returns a guard which keeps the lock
+    /// alive through `self` instead of borrowing it.
+    ///
+    /// The guard has no lifetime, so it can be stored in a long-lived struct.
+    #[inline]
+    pub fn write_owned(self: &Arc<Self>) -> OwnedRwLockWriteGuard<T> {
+        // SAFETY: as in `read_owned`.
+        unsafe { OwnedRwLockWriteGuard::new(self.clone(), |rwlock| rwlock.write()) }
+    }
+
+    /// Like [`try_write`](RwLock::try_write), but returns a guard which keeps
+    /// the lock alive through `self` instead of borrowing it.
+    #[inline]
+    pub fn try_write_owned(self: &Arc<Self>) -> TryLockResult<OwnedRwLockWriteGuard<T>> {
+        let guard = self.try_write()?;
+        // SAFETY: as in `read_owned`.
+        Ok(unsafe { OwnedRwLockWriteGuard::new(self.clone(), |_| guard) })
+    }
+}
+
+macro_rules! owned_guard {
+    ($(#[$attr:meta])* $name:ident, $guard:ident, $lock:ident, $getter:ident) => {
+        $(#[$attr])*
+        #[must_use]
+        pub struct $name<T: ?Sized + 'static> {
+            // Declared first so that it is dropped, releasing the lock, before
+            // the `Arc` which keeps the lock alive.
+            guard: $guard<'static, T>,
+            lock: Arc<$lock<T>>,
+        }
+
+        impl<T: ?Sized + 'static> $name<T> {
+            // SAFETY: the guard returned by `f` must borrow the lock in `lock`,
+            // since it is extended to `'static` and only kept alive by it.
+            #[inline]
+            unsafe fn new<'a>(
+                lock: Arc<$lock<T>>,
+                f: impl FnOnce(&'a $lock<T>) -> $guard<'a, T>,
+            ) -> $name<T> {
+                let guard = f(&*(Arc::as_ptr(&lock)));
+                $name {
+                    guard: mem::transmute::<$guard<'a, T>, $guard<'static, T>>(guard),
+                    lock,
+                }
+            }
+
+            /// Returns the lock this guard holds.
+            #[inline]
+            pub fn $getter(&self) -> &Arc<$lock<T>> {
+                &self.lock
+            }
+        }
+
+        impl<T: ?Sized + 'static> Deref for $name<T> {
+            type Target = T;
+
+            #[inline]
+            fn deref(&self) -> &T {
+                &self.guard
+            }
+        }
+
+        impl<T: ?Sized + fmt::Debug + 'static> fmt::Debug for $name<T> {
+            fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt.debug_tuple(stringify!($name)).field(&&*self.guard).finish()
+            }
+        }
+    };
+}
+
+owned_guard!(
+    /// Like [`MutexGuard`], but keeps the mutex alive through an `Arc` instead
+    /// of borrowing it. Returned by [`Mutex::lock_owned`].
+    ///
+    /// Like other guards, it must be dropped on the thread which acquired the
+    /// lock, so it cannot be sent to another thread.
+    OwnedMutexGuard,
+    MutexGuard,
+    Mutex,
+    mutex
+);
+
+owned_guard!(
+    /// Like [`RwLockReadGuard`], but keeps the lock alive through an `Arc`
+    /// instead of borrowing it. Returned by [`RwLock::read_owned`].
+    ///
+    /// Like other guards, it must be dropped on the thread which acquired the
+    /// lock, so it cannot be sent to another thread.
+    OwnedRwLockReadGuard,
+    RwLockReadGuard,
+    RwLock,
+    rwlock
+);
+
+owned_guard!(
+    /// Like [`RwLockWriteGuard`], but keeps the lock alive through an `Arc`
+    /// instead of borrowing it. Returned by [`RwLock::write_owned`].
+    ///
+    /// Like other guards, it must be dropped on the thread which acquired the
+    /// lock, so it cannot be sent to another thread.
+    OwnedRwLockWriteGuard,
+    RwLockWriteGuard,
+    RwLock,
+    rwlock
+);
+
+impl<T: ?Sized + 'static> DerefMut for OwnedMutexGuard<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T: ?Sized + 'static> DerefMut for OwnedRwLockWriteGuard<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
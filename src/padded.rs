@@ -0,0 +1,99 @@
+use core::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+/// Aligns and pads a value to the length of a cache line.
+///
+/// Locks which sit next to each other in memory, in an array or in adjacent
+/// fields of a struct, usually share a cache line, so every acquisition of one
+/// of them slows down the threads using the others, even though the locks are
+/// unrelated. Wrapping each of them in a `CachePadded` gives it a cache line of
+/// its own. [`PaddedMutex`](crate::PaddedMutex) and
+/// [`PaddedRwLock`](crate::PaddedRwLock) name the common cases.
+///
+/// The alignment is 128 bytes on targets such as `x86_64` and `aarch64`, which
+/// prefetch cache lines in pairs, 64 bytes on most others, and as little as 32
+/// bytes on some embedded targets. This makes the value at least that large.
+#[cfg_attr(
+    any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "powerpc64",
+    ),
+    repr(align(128))
+)]
+#[cfg_attr(
+    any(
+        target_arch = "arm",
+        target_arch = "mips",
+        target_arch = "mips64",
+        target_arch = "riscv32",
+        target_arch = "sparc",
+        target_arch = "hexagon",
+    ),
+    repr(align(32))
+)]
+#[cfg_attr(target_arch = "s390x", repr(align(256)))]
+#[cfg_attr(
+    not(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        target_arch = "powerpc64",
+        target_arch = "arm",
+        target_arch = "mips",
+        target_arch = "mips64",
+        target_arch = "riscv32",
+        target_arch = "sparc",
+        target_arch = "hexagon",
+        target_arch = "s390x",
+    )),
+    repr(align(64))
+)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CachePadded<T> {
+    value: T,
+}
+
+impl<T> CachePadded<T> {
+    /// Pads a value.
+    #[inline]
+    pub const fn new(value: T) -> CachePadded<T> {
+        CachePadded { value }
+    }
+
+    /// Returns the value, without the padding.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for CachePadded<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T> From<T> for CachePadded<T> {
+    #[inline]
+    fn from(value: T) -> CachePadded<T> {
+        CachePadded::new(value)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CachePadded<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.value, fmt)
+    }
+}
@@ -0,0 +1,382 @@
+//! A minimal parking lot.
+//!
+//! Threads park on a key, which is the address of some object they are waiting
+//! on, and are unparked by other threads using the same key. Waiters live in a
+//! fixed table of queues indexed by key, so the primitives built on top of this
+//! module need no storage of their own for them.
+//!
+//! Every primitive must park on the address of a field it owns exclusively, so
+//! that two objects never share a key.
+//!
+//! The queues are plain Rust, and sleeping is left to `thread::park` and
+//! `thread::park_timeout`, so there is no platform-specific code here, and
+//! none in `Condvar`'s requeueing and counted notifications which are built on
+//! it. There is no separate Windows backend, such as one waiting with
+//! `WaitOnAddress` directly.
+
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{
+        self,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, Thread},
+    time::Instant,
+};
+
+use crate::clock;
+
+/// The token handed to unparked threads when the unparker has nothing to tell
+/// them.
+pub(crate) const DEFAULT_TOKEN: usize = 0;
+
+/// The outcome of a call to `park`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ParkResult {
+    /// The thread was unparked with the given token.
+    Unparked(usize),
+    /// The `validate` callback returned `false`.
+    Invalid,
+    /// The deadline passed before the thread was unparked.
+    TimedOut,
+}
+
+/// The outcome of a call to one of the unpark functions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct UnparkResult {
+    /// The number of threads that were unparked.
+    pub(crate) unparked: usize,
+    /// The number of threads that were moved to another key.
+    pub(crate) requeued: usize,
+    /// Whether threads are still parked on the key afterwards.
+    pub(crate) have_more: bool,
+}
+
+struct ThreadData {
+    thread: Thread,
+    // The key the thread is parked on. Only modified with the key's bucket locked.
+    key: AtomicUsize,
+    // Cleared, with the bucket locked, by whichever thread unparks this one.
+    parked: AtomicBool,
+    token: AtomicUsize,
+}
+
+impl ThreadData {
+    fn new() -> ThreadData {
+        ThreadData {
+            thread: thread::current(),
+            key: AtomicUsize::new(0),
+            parked: AtomicBool::new(false),
+            token: AtomicUsize::new(DEFAULT_TOKEN),
+        }
+    }
+
+    fn unpark_token(&self, token: usize) {
+        self.token.store(token, Ordering::Relaxed);
+        self.parked.store(false, Ordering::Release);
+    }
+}
+
+fn with_thread_data<R>(f: impl FnOnce(&Arc<ThreadData>) -> R) -> R {
+    thread_local! {
+        static THREAD_DATA: Arc<ThreadData> = Arc::new(ThreadData::new());
+    }
+
+    let mut f = Some(f);
+    match THREAD_DATA.try_with(|data| f.take().unwrap()(data)) {
+        Ok(r) => r,
+        // The thread is being torn down; fall back to one-off data.
+        Err(_) => f.take().unwrap()(&Arc::new(ThreadData::new())),
+    }
+}
+
+struct Bucket {
+    queue: sync::Mutex<Vec<Arc<ThreadData>>>,
+    // A copy of the queue's length, readable without locking it.
+    len: AtomicUsize,
+}
+
+impl Bucket {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: Bucket = Bucket {
+        queue: sync::Mutex::new(Vec::new()),
+        len: AtomicUsize::new(0),
+    };
+}
+
+struct Queue {
+    threads: sync::MutexGuard<'static, Vec<Arc<ThreadData>>>,
+    len: &'static AtomicUsize,
+}
+
+impl Deref for Queue {
+    type Target = Vec<Arc<ThreadData>>;
+
+    fn deref(&self) -> &Vec<Arc<ThreadData>> {
+        &self.threads
+    }
+}
+
+impl DerefMut for Queue {
+    fn deref_mut(&mut self) -> &mut Vec<Arc<ThreadData>> {
+        &mut self.threads
+    }
+}
+
+impl Queue {
+    fn publish_len(&self) {
+        self.len.store(self.threads.len(), Ordering::Relaxed);
+    }
+}
+
+impl Drop for Queue {
+    fn drop(&mut self) {
+        self.publish_len();
+    }
+}
+
+const BUCKET_BITS: u32 = 8;
+
+static BUCKETS: [Bucket; 1 << BUCKET_BITS] = [Bucket::EMPTY; 1 << BUCKET_BITS];
+
+fn bucket_index(key: usize) -> usize {
+    // Fibonacci hashing; truncating the multiplier on 32-bit targets is harmless.
+    key.wrapping_mul(0x9E37_79B9_7F4A_7C15_u64 as usize) >> (usize::BITS - BUCKET_BITS)
+}
+
+fn lock_index(idx: usize) -> Queue {
+    let bucket = &BUCKETS[idx];
+    Queue {
+        threads: bucket.queue.lock().unwrap_or_else(|e| e.into_inner()),
+        len: &bucket.len,
+    }
+}
+
+fn lock_bucket(key: usize) -> Queue {
+    lock_index(bucket_index(key))
+}
+
+// Locks the bucket of the key a thread is parked on, which may change until it
+// is locked.
+fn lock_bucket_of(data: &ThreadData) -> (usize, Queue) {
+    loop {
+        let key = data.key.load(Ordering::Relaxed);
+        let queue = lock_bucket(key);
+        if data.key.load(Ordering::Relaxed) == key {
+            return (key, queue);
+        }
+    }
+}
+
+/// Returns `true` if threads may be parked on `key`.
+///
+/// False positives are possible when other keys share the key's bucket.
+#[inline]
+pub(crate) fn may_have_parked(key: usize) -> bool {
+    BUCKETS[bucket_index(key)].len.load(Ordering::Relaxed) != 0
+}
+
+fn has_key(queue: &[Arc<ThreadData>], key: usize) -> bool {
+    queue
+        .iter()
+        .any(|data| data.key.load(Ordering::Relaxed) == key)
+}
+
+/// Parks the current thread on `key` until it is unparked or `deadline` passes.
+///
+/// `validate` is called with the key's bucket locked and aborts the park if it
+/// returns `false`. `before_sleep` is called once the thread is queued, without
+/// any locks held. `timed_out` is called with the bucket locked if the deadline
+/// passes, along with the key the thread was parked on and whether it was the
+/// last thread parked there.
+pub(crate) fn park(
+    key: usize,
+    validate: impl FnOnce() -> bool,
+    before_sleep: impl FnOnce(),
+    timed_out: impl FnOnce(usize, bool),
+    deadline: Option<Instant>,
+) -> ParkResult {
+    with_thread_data(|data| {
+        {
+            let mut queue = lock_bucket(key);
+            if !validate() {
+                return ParkResult::Invalid;
+            }
+            data.key.store(key, Ordering::Relaxed);
+            data.parked.store(true, Ordering::Relaxed);
+            queue.push(data.clone());
+        }
+
+        before_sleep();
+
+        loop {
+            if !data.parked.load(Ordering::Acquire) {
+                return ParkResult::Unparked(data.token.load(Ordering::Relaxed));
+            }
+            // Nothing could unpark the thread, so it leaves the queue as if it
+            // had timed out before panicking.
+            if crate::NO_THREADS {
+                break;
+            }
+            match deadline {
+                Some(deadline) => {
+                    let expired = clock::with_current(|clock| {
+                        if clock.now() >= deadline {
+                            return true;
+                        }
+                        clock.park_until(deadline);
+                        false
+                    });
+                    if expired {
+                        break;
+                    }
+                }
+                None => thread::park(),
+            }
+        }
+
+        let (key, mut queue) = lock_bucket_of(data);
+        // We may have been unparked after the deadline but before locking the bucket.
+        if !data.parked.load(Ordering::Acquire) {
+            return ParkResult::Unparked(data.token.load(Ordering::Relaxed));
+        }
+        let idx = queue
+            .iter()
+            .position(|other| Arc::ptr_eq(other, data))
+            .unwrap();
+        queue.remove(idx);
+        timed_out(key, !has_key(&queue, key));
+        if crate::NO_THREADS {
+            drop(queue);
+            crate::deadlock();
+        }
+        ParkResult::TimedOut
+    })
+}
+
+/// Unparks the thread that has been parked on `key` the longest.
+///
+/// `callback` is called with the bucket locked, even if no thread was unparked,
+/// and returns the token to hand to the unparked thread.
+pub(crate) fn unpark_one(key: usize, callback: impl FnOnce(UnparkResult) -> usize) -> UnparkResult {
+    let mut queue = lock_bucket(key);
+    let mut result = UnparkResult::default();
+    let woken = queue
+        .iter()
+        .position(|data| data.key.load(Ordering::Relaxed) == key)
+        .map(|idx| queue.remove(idx));
+    if woken.is_some() {
+        result.unparked = 1;
+        result.have_more = has_key(&queue, key);
+    }
+
+    let token = callback(result);
+    if let Some(data) = woken {
+        data.unpark_token(token);
+        drop(queue);
+        data.thread.unpark();
+    }
+    result
+}
+
+/// Unparks every thread parked on `key`.
+///
+/// `callback` is called with the bucket locked, even if no thread was unparked,
+/// and returns the token to hand to the unparked threads.
+pub(crate) fn unpark_all(key: usize, callback: impl FnOnce(UnparkResult) -> usize) -> UnparkResult {
+    let mut queue = lock_bucket(key);
+    let mut woken = vec![];
+    queue.retain(|data| {
+        if data.key.load(Ordering::Relaxed) == key {
+            woken.push(data.clone());
+            false
+        } else {
+            true
+        }
+    });
+
+    let result = UnparkResult {
+        unparked: woken.len(),
+        ..UnparkResult::default()
+    };
+    let token = callback(result);
+    for data in &woken {
+        data.unpark_token(token);
+    }
+    drop(queue);
+    for data in &woken {
+        data.thread.unpark();
+    }
+    result
+}
+
+/// Unparks the thread that has been parked on `from` the longest and moves
+/// every other thread parked there to `to`.
+///
+/// `validate` is called with both buckets locked; if it returns `false`, every
+/// thread is unparked instead. `callback` is then called, even if no thread was
+/// unparked, and returns the token to hand to the unparked threads.
+pub(crate) fn unpark_requeue(
+    from: usize,
+    to: usize,
+    validate: impl FnOnce() -> bool,
+    callback: impl FnOnce(UnparkResult) -> usize,
+) -> UnparkResult {
+    let (from_idx, to_idx) = (bucket_index(from), bucket_index(to));
+    // Lock in index order so concurrent requeues cannot deadlock.
+    let (mut from_queue, mut to_queue) = if from_idx == to_idx {
+        (lock_index(from_idx), None)
+    } else if from_idx < to_idx {
+        let from_queue = lock_index(from_idx);
+        (from_queue, Some(lock_index(to_idx)))
+    } else {
+        let to_queue = lock_index(to_idx);
+        (lock_index(from_idx), Some(to_queue))
+    };
+
+    let requeue = validate();
+    let mut result = UnparkResult::default();
+    let mut woken = vec![];
+    let mut moved = vec![];
+    from_queue.retain(|data| {
+        if data.key.load(Ordering::Relaxed) != from {
+            return true;
+        }
+        if !requeue || woken.is_empty() {
+            woken.push(data.clone());
+            return false;
+        }
+        data.key.store(to, Ordering::Relaxed);
+        result.requeued += 1;
+        match to_queue {
+            Some(_) => {
+                moved.push(data.clone());
+                false
+            }
+            None => true,
+        }
+    });
+    // The new length must be visible to the unparked thread, which may go on to
+    // check it.
+    match &mut to_queue {
+        Some(to_queue) => {
+            to_queue.append(&mut moved);
+            to_queue.publish_len();
+        }
+        None => from_queue.publish_len(),
+    }
+    result.unparked = woken.len();
+    result.have_more = result.requeued != 0;
+
+    let token = callback(result);
+    for data in &woken {
+        data.unpark_token(token);
+    }
+    drop(from_queue);
+    drop(to_queue);
+    for data in &woken {
+        data.thread.unpark();
+    }
+    result
+}
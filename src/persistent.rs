@@ -0,0 +1,216 @@
+//! A mutex whose value is persisted to a file.
+//!
+//! A [`PersistentMutex`] loads its value from a JSON file when it is opened,
+//! and writes it back whenever a guard through which the value was mutably
+//! accessed is dropped. Each write goes to a temporary file next to the target,
+//! which is then renamed over it, so the file always holds either the old or
+//! the new value in full.
+//!
+//! Errors from writes triggered by dropping a guard cannot be returned, so they
+//! are kept until retrieved with [`PersistentMutex::take_error`]. Use
+//! [`PersistentMutexGuard::commit`] to persist a change and handle the error
+//! directly.
+
+use std::{
+    ffi::OsString,
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter},
+    ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Mutex, MutexGuard};
+
+/// When a [`PersistentMutex`] writes its value to disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlushPolicy {
+    /// Write the value whenever a guard that modified it is dropped, and make
+    /// sure it has reached the disk before the guard's drop returns.
+    #[default]
+    Sync,
+    /// Write the value whenever a guard that modified it is dropped, leaving
+    /// it to the operating system to decide when the data reaches the disk.
+    NoSync,
+    /// Only write the value when [`PersistentMutex::flush`] or
+    /// [`PersistentMutexGuard::commit`] is called.
+    Manual,
+}
+
+/// A `Mutex` whose value is persisted to a file.
+pub struct PersistentMutex<T> {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    policy: FlushPolicy,
+    data: Mutex<T>,
+    error: Mutex<Option<io::Error>>,
+}
+
+impl<T: Serialize + DeserializeOwned> PersistentMutex<T> {
+    /// Opens a persistent mutex backed by the file at `path`.
+    ///
+    /// If the file does not exist, the mutex starts out with the value
+    /// returned by `init`, and the file is created on the first write.
+    pub fn open(
+        path: impl Into<PathBuf>,
+        init: impl FnOnce() -> T,
+    ) -> io::Result<PersistentMutex<T>> {
+        let path = path.into();
+        let mut tmp_name = path.file_name().map_or_else(OsString::new, OsString::from);
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+
+        let value = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(io::Error::from)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => init(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(PersistentMutex {
+            path,
+            tmp_path,
+            policy: FlushPolicy::default(),
+            data: Mutex::new(value),
+            error: Mutex::new(None),
+        })
+    }
+
+    /// Sets the flush policy.
+    ///
+    /// The default policy is [`FlushPolicy::Sync`].
+    pub fn with_policy(mut self, policy: FlushPolicy) -> PersistentMutex<T> {
+        self.policy = policy;
+        self
+    }
+
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so.
+    #[inline]
+    pub fn lock(&self) -> PersistentMutexGuard<'_, T> {
+        PersistentMutexGuard {
+            mutex: self,
+            guard: self.data.lock(),
+            dirty: false,
+        }
+    }
+
+    /// Writes the current value to the file.
+    pub fn flush(&self) -> io::Result<()> {
+        self.persist(&self.data.lock())
+    }
+
+    /// Returns the error of the most recent failed write triggered by
+    /// dropping a guard, if there was one since the last call.
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.error.lock().take()
+    }
+
+    /// Returns the path of the backing file.
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Consumes the mutex, returning the underlying data without writing it.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    fn persist(&self, value: &T) -> io::Result<()> {
+        let file = File::create(&self.tmp_path)?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, value).map_err(io::Error::from)?;
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        if self.policy == FlushPolicy::Sync {
+            file.sync_all()?;
+        }
+        drop(file);
+        fs::rename(&self.tmp_path, &self.path)?;
+        if self.policy == FlushPolicy::Sync {
+            sync_parent(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+// Makes a rename within the directory durable.
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent(_: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+impl<T: fmt::Debug> fmt::Debug for PersistentMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("PersistentMutex")
+            .field("path", &self.path)
+            .field("policy", &self.policy)
+            .field("data", &self.data)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A guard for a [`PersistentMutex`].
+///
+/// If the value was mutably accessed through the guard, it is written to the
+/// file when the guard is dropped, unless the flush policy is
+/// [`FlushPolicy::Manual`].
+#[must_use]
+pub struct PersistentMutexGuard<'a, T: Serialize + DeserializeOwned> {
+    mutex: &'a PersistentMutex<T>,
+    guard: MutexGuard<'a, T>,
+    dirty: bool,
+}
+
+impl<T: Serialize + DeserializeOwned> PersistentMutexGuard<'_, T> {
+    /// Writes the value to the file and releases the lock, regardless of the
+    /// flush policy.
+    pub fn commit(mut self) -> io::Result<()> {
+        self.dirty = false;
+        self.mutex.persist(&self.guard)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Deref for PersistentMutexGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> DerefMut for PersistentMutexGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        self.dirty = true;
+        &mut self.guard
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Drop for PersistentMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        if !self.dirty || self.mutex.policy == FlushPolicy::Manual {
+            return;
+        }
+        if let Err(e) = self.mutex.persist(&self.guard) {
+            *self.mutex.error.lock() = Some(e);
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + fmt::Debug> fmt::Debug for PersistentMutexGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.guard, fmt)
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+};
+
+use crate::{Mutex, MutexGuard, TryLockResult};
+
+/// A mutex whose value is structurally pinned.
+///
+/// Once the mutex is pinned, its value never moves again, so it can be
+/// self-referential or linked into an intrusive list. The guards returned by
+/// [`lock`](PinnedMutex::lock) only hand out `Pin<&mut T>`, unless `T` is
+/// `Unpin`. This is a separate type rather than a method of
+/// [`Mutex`](crate::Mutex) because `Mutex::lock` hands out `&mut T` through a
+/// shared reference, which would let the value be moved out of a pinned mutex.
+///
+/// It is otherwise a `Mutex`, and is seen by the instrumentation like one.
+#[derive(Default)]
+pub struct PinnedMutex<T: ?Sized> {
+    inner: Mutex<T>,
+}
+
+impl<T> PinnedMutex<T> {
+    /// Creates a new mutex in an unlocked state.
+    #[inline]
+    pub const fn new(t: T) -> PinnedMutex<T> {
+        PinnedMutex {
+            inner: Mutex::new(t),
+        }
+    }
+
+    /// Creates a new mutex pinned in a `Box`.
+    #[inline]
+    pub fn boxed(t: T) -> Pin<Box<PinnedMutex<T>>> {
+        Box::pin(PinnedMutex::new(t))
+    }
+
+    /// Consumes the mutex, returning the underlying data.
+    ///
+    /// A mutex which was pinned can only be consumed if `T` is `Unpin`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: ?Sized> PinnedMutex<T> {
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so.
+    #[inline]
+    pub fn lock(self: Pin<&Self>) -> PinnedMutexGuard<'_, T> {
+        PinnedMutexGuard {
+            guard: self.get_ref().inner.lock(),
+        }
+    }
+
+    /// Attempts to acquire the mutex without blocking.
+    #[inline]
+    pub fn try_lock(self: Pin<&Self>) -> TryLockResult<PinnedMutexGuard<'_, T>> {
+        let guard = self.get_ref().inner.try_lock()?;
+        Ok(PinnedMutexGuard { guard })
+    }
+
+    /// Returns a pinned mutable reference to the data, without locking.
+    #[inline]
+    pub fn get_mut(self: Pin<&mut Self>) -> Pin<&mut T> {
+        // SAFETY: the value is pinned along with the mutex, and never moved
+        // out of it through a shared reference.
+        unsafe { self.map_unchecked_mut(|mutex| mutex.inner.get_mut()) }
+    }
+
+    /// Returns `true` if the mutex is currently held.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.inner.is_locked()
+    }
+}
+
+impl<T> From<T> for PinnedMutex<T> {
+    #[inline]
+    fn from(t: T) -> PinnedMutex<T> {
+        PinnedMutex::new(t)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for PinnedMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(fmt)
+    }
+}
+
+/// A guard for a [`PinnedMutex`].
+///
+/// It dereferences to `&T`, and to `&mut T` only if `T` is `Unpin`.
+/// [`as_mut`](PinnedMutexGuard::as_mut) gives `Pin<&mut T>` otherwise.
+#[must_use]
+pub struct PinnedMutexGuard<'a, T: ?Sized + 'a> {
+    guard: MutexGuard<'a, T>,
+}
+
+impl<T: ?Sized> PinnedMutexGuard<'_, T> {
+    /// Returns a pinned mutable reference to the data.
+    #[inline]
+    pub fn as_mut(&mut self) -> Pin<&mut T> {
+        // SAFETY: the mutex is pinned, and so is its value, which is only
+        // reachable through a pinned reference while a `PinnedMutexGuard`
+        // holds it.
+        unsafe { Pin::new_unchecked(&mut *self.guard) }
+    }
+}
+
+impl<T: ?Sized> Deref for PinnedMutexGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized + Unpin> DerefMut for PinnedMutexGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for PinnedMutexGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
@@ -0,0 +1,263 @@
+//! Policies for a mutex acquired after a panic while it was held.
+//!
+//! Nothing is ever poisoned: a lock only remembers that a guard was dropped
+//! while its thread unwound from a panic, so that later holders can find out
+//! that the data may have been left half-updated. By default a
+//! [`Mutex`](crate::Mutex) then carries on as if nothing happened. A mutex
+//! created with [`Mutex::with_policy`](crate::Mutex::with_policy) instead lets
+//! its [`PoisonPolicy`] react each time it is acquired in that state, until
+//! [`clear_poison`](crate::Mutex::clear_poison) is called.
+//!
+//! To keep such recoveries from going unnoticed without changing how any lock
+//! behaves, a hook set with [`set_hook`] is called for every mutex acquired in
+//! that state, and every `RwLock` acquired for writing in that state, before
+//! the policy of the mutex.
+//!
+//! The guards built on top of `MutexGuard` elsewhere in the crate, such as
+//! [`MappedMutexGuard`](crate::MappedMutexGuard) and
+//! [`OwnedMutexGuard`](crate::OwnedMutexGuard), are only available for the
+//! default [`Ignore`] policy.
+
+use std::{
+    fmt, process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        RwLock,
+    },
+    thread,
+};
+
+/// How a mutex reacts when it is acquired after a panic while it was held.
+pub trait PoisonPolicy {
+    /// Called by every acquisition of a mutex which a panicking thread held
+    /// since it was created or last cleared, with the name it was given with
+    /// `new_named`, if any.
+    ///
+    /// The mutex is held while this runs. If it panics, the new guard is
+    /// dropped and the mutex released as the panic unwinds.
+    fn recovered(&self, name: Option<&'static str>);
+
+    // Fixes up the value of a mutex acquired after a panic while it was held,
+    // before `recovered` is called, returning `true` if the mutex should no
+    // longer be marked.
+    //
+    // SAFETY: `data` must point to the value of the mutex this is the policy
+    // of, and be valid for writes.
+    #[doc(hidden)]
+    #[inline]
+    unsafe fn repair(&self, _data: *mut ()) -> bool {
+        false
+    }
+}
+
+/// Carries on as if nothing happened. This is the default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Ignore;
+
+impl PoisonPolicy for Ignore {
+    #[inline]
+    fn recovered(&self, _: Option<&'static str>) {}
+}
+
+/// Writes a warning to standard error, then carries on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Log;
+
+impl PoisonPolicy for Log {
+    #[cold]
+    fn recovered(&self, name: Option<&'static str>) {
+        eprintln!("antidote: {}", Recovered(name));
+    }
+}
+
+/// Writes an error to standard error and aborts the process.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Abort;
+
+impl PoisonPolicy for Abort {
+    #[cold]
+    fn recovered(&self, name: Option<&'static str>) {
+        eprintln!("antidote: {}; aborting", Recovered(name));
+        process::abort();
+    }
+}
+
+/// Panics, much like unwrapping the `PoisonError` of the standard library's
+/// mutex.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PanicOnRecover;
+
+impl PoisonPolicy for PanicOnRecover {
+    #[cold]
+    fn recovered(&self, name: Option<&'static str>) {
+        panic!("{}", Recovered(name));
+    }
+}
+
+/// Calls a function, with the name of the mutex if it has one.
+///
+/// This is the policy of a mutex created by
+/// [`Mutex::on_poison_recovered`](crate::Mutex::on_poison_recovered), for
+/// recoveries which should be counted or logged per lock.
+#[derive(Clone, Copy, Debug)]
+pub struct Callback(pub fn(Option<&'static str>));
+
+impl PoisonPolicy for Callback {
+    #[cold]
+    fn recovered(&self, name: Option<&'static str>) {
+        (self.0)(name);
+    }
+}
+
+/// Calls a function on the value of the mutex, then clears the mark, so that it
+/// runs once for each panic.
+///
+/// This is the policy of a mutex created by
+/// [`Mutex::with_repair`](crate::Mutex::with_repair), and cannot be created
+/// otherwise.
+pub struct Repair<T> {
+    repair: fn(&mut T),
+}
+
+impl<T> PoisonPolicy for Repair<T> {
+    #[inline]
+    fn recovered(&self, _: Option<&'static str>) {}
+
+    #[cold]
+    unsafe fn repair(&self, data: *mut ()) -> bool {
+        // SAFETY: a `Repair<T>` is only ever the policy of a `Mutex<T, _>`,
+        // or of one unsized from it, whose value is still a `T`.
+        (self.repair)(unsafe { &mut *(data as *mut T) });
+        true
+    }
+}
+
+impl<T> Repair<T> {
+    #[inline]
+    pub(crate) const fn new(repair: fn(&mut T)) -> Repair<T> {
+        Repair { repair }
+    }
+}
+
+impl<T> fmt::Debug for Repair<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Repair").finish_non_exhaustive()
+    }
+}
+
+type Hook = fn(Option<&'static str>);
+
+static HOOK: RwLock<Option<Hook>> = RwLock::new(None);
+
+/// Sets the function called for every lock acquired after a panic while it was
+/// held, or removes it with `None`.
+///
+/// The hook is called with the name of the lock, if it has one, while the lock
+/// is held and before the lock's [`PoisonPolicy`] reacts, whatever that policy
+/// is. It applies to every [`Mutex`](crate::Mutex), and to every
+/// [`RwLock`](crate::RwLock) acquired for writing. If the hook panics, the new
+/// guard is dropped and the lock released as the panic unwinds.
+pub fn set_hook(hook: Option<fn(Option<&'static str>)>) {
+    *HOOK.write().unwrap_or_else(|e| e.into_inner()) = hook;
+}
+
+// Calls the hook, if any, for a lock acquired after a panic while it was held.
+#[cold]
+pub(crate) fn report(name: Option<&'static str>) {
+    let hook = *HOOK.read().unwrap_or_else(|e| e.into_inner());
+    if let Some(hook) = hook {
+        hook(name);
+    }
+}
+
+struct Recovered(Option<&'static str>);
+
+impl fmt::Display for Recovered {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(name) => write!(fmt, "mutex '{}'", name)?,
+            None => fmt.write_str("mutex")?,
+        }
+        fmt.write_str(" acquired after a panic while it was held")
+    }
+}
+
+/// Whether a guard of the lock was dropped by a panicking thread.
+pub(crate) struct Flag {
+    poisoned: AtomicBool,
+}
+
+impl Flag {
+    #[inline]
+    pub(crate) const fn new() -> Flag {
+        Flag {
+            poisoned: AtomicBool::new(false),
+        }
+    }
+
+    /// Starts watching for a panic while a guard is alive.
+    #[inline]
+    pub(crate) fn guard(&self) -> Guard {
+        Guard {
+            panicking: thread::panicking(),
+            recovered: self.get(),
+        }
+    }
+
+    /// Records a panic which started while `guard` was alive.
+    #[inline]
+    pub(crate) fn done(&self, guard: &Guard) {
+        if guard.panicked() {
+            self.poisoned.store(true, Ordering::Relaxed);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get(&self) -> bool {
+        self.poisoned.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub(crate) fn set(&self) {
+        self.poisoned.store(true, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn clear(&self) {
+        self.poisoned.store(false, Ordering::Relaxed);
+    }
+}
+
+impl Default for Flag {
+    #[inline]
+    fn default() -> Flag {
+        Flag::new()
+    }
+}
+
+/// The state of a lock's `Flag` when a guard acquired it.
+pub(crate) struct Guard {
+    // A guard acquired while unwinding does not record the same panic again.
+    panicking: bool,
+    recovered: bool,
+}
+
+impl Guard {
+    /// Returns `true` if the lock was marked when the guard acquired it.
+    #[inline]
+    pub(crate) fn recovered(&self) -> bool {
+        self.recovered
+    }
+
+    /// Returns `true` if a panic started while the guard was alive.
+    #[inline]
+    pub(crate) fn panicked(&self) -> bool {
+        !self.panicking && thread::panicking()
+    }
+
+    /// Keeps the current panic from being recorded when the guard is done.
+    #[inline]
+    pub(crate) fn disarm(&mut self) {
+        self.panicking = true;
+    }
+}
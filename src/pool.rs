@@ -0,0 +1,203 @@
+use std::{
+    fmt,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    time::{Duration, Instant},
+};
+
+use crate::{clock, Condvar, Mutex};
+
+/// A pool of interchangeable items, such as connections or buffers, which
+/// threads borrow one at a time.
+///
+/// [`get`](LockedPool::get) blocks until an item is free and returns a
+/// [`PoolItem`] handing out access to it, which returns the item to the pool
+/// once it is dropped. Items are handed out most recently returned first, so
+/// a pool larger than its load keeps reusing the same, warmest items.
+///
+/// The pool does not create items on its own: it starts with the items given
+/// to it, and more can be added with [`put`](LockedPool::put).
+pub struct LockedPool<T> {
+    items: Mutex<Vec<T>>,
+    returned: Condvar,
+}
+
+impl<T> LockedPool<T> {
+    /// Creates a new pool without any items.
+    #[inline]
+    pub const fn new() -> LockedPool<T> {
+        LockedPool {
+            items: Mutex::new(Vec::new()),
+            returned: Condvar::new(),
+        }
+    }
+
+    /// Adds an item to the pool, waking a thread waiting for one.
+    pub fn put(&self, item: T) {
+        self.items.lock().push(item);
+        self.returned.notify_one();
+    }
+
+    /// Borrows an item, blocking the current thread until one is free.
+    ///
+    /// If the pool is empty and no item is borrowed, this blocks until an item
+    /// is added with [`put`](LockedPool::put).
+    pub fn get(&self) -> PoolItem<'_, T> {
+        match self.get_until_internal(None) {
+            Some(item) => item,
+            None => unreachable!("wait without a deadline timed out"),
+        }
+    }
+
+    /// Borrows an item, blocking the current thread for at most `timeout`, and
+    /// returns `None` if none became free in time.
+    pub fn get_timeout(&self, timeout: Duration) -> Option<PoolItem<'_, T>> {
+        // A deadline too far in the future to represent is treated as no
+        // deadline at all.
+        self.get_until_internal(clock::now().checked_add(timeout))
+    }
+
+    fn get_until_internal(&self, deadline: Option<Instant>) -> Option<PoolItem<'_, T>> {
+        let mut items = self.items.lock();
+        loop {
+            if let Some(item) = items.pop() {
+                return Some(PoolItem::new(self, item));
+            }
+            match deadline {
+                Some(deadline) => {
+                    let (new, result) = self.returned.wait_until(items, deadline);
+                    items = new;
+                    if result.timed_out() {
+                        return items.pop().map(|item| PoolItem::new(self, item));
+                    }
+                }
+                None => items = self.returned.wait(items),
+            }
+        }
+    }
+
+    /// Attempts to borrow an item without blocking, and returns `None` if none
+    /// is free.
+    pub fn try_get(&self) -> Option<PoolItem<'_, T>> {
+        let item = self.items.lock().pop()?;
+        Some(PoolItem::new(self, item))
+    }
+
+    /// Returns the number of items which are currently free.
+    #[inline]
+    pub fn available(&self) -> usize {
+        self.items.lock().len()
+    }
+
+    /// Consumes the pool, returning the items which are free.
+    ///
+    /// Since borrowed items borrow the pool, every item is free by then.
+    #[inline]
+    pub fn into_inner(self) -> Vec<T> {
+        self.items.into_inner()
+    }
+}
+
+impl<T> Default for LockedPool<T> {
+    #[inline]
+    fn default() -> LockedPool<T> {
+        LockedPool::new()
+    }
+}
+
+impl<T> From<Vec<T>> for LockedPool<T> {
+    #[inline]
+    fn from(items: Vec<T>) -> LockedPool<T> {
+        LockedPool {
+            items: Mutex::new(items),
+            returned: Condvar::new(),
+        }
+    }
+}
+
+impl<T> FromIterator<T> for LockedPool<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> LockedPool<T> {
+        LockedPool::from(iter.into_iter().collect::<Vec<T>>())
+    }
+}
+
+impl<T> fmt::Debug for LockedPool<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("LockedPool")
+            .field("available", &self.available())
+            .finish_non_exhaustive()
+    }
+}
+
+/// An item borrowed from a [`LockedPool`].
+///
+/// The item is returned to the pool when this is dropped.
+#[must_use]
+pub struct PoolItem<'a, T> {
+    pool: &'a LockedPool<T>,
+    item: ManuallyDrop<T>,
+}
+
+impl<'a, T> PoolItem<'a, T> {
+    #[inline]
+    fn new(pool: &'a LockedPool<T>, item: T) -> PoolItem<'a, T> {
+        PoolItem {
+            pool,
+            item: ManuallyDrop::new(item),
+        }
+    }
+
+    /// Takes the item out of the pool for good, such as a connection found to
+    /// be broken, and returns it.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`.
+    #[inline]
+    pub fn detach(s: Self) -> T {
+        let mut s = ManuallyDrop::new(s);
+        // SAFETY: the handle is never used or dropped again, so the item is
+        // moved out of it only once.
+        unsafe { ManuallyDrop::take(&mut s.item) }
+    }
+
+    /// Returns the pool the item was borrowed from.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`.
+    #[inline]
+    pub fn pool(s: &Self) -> &'a LockedPool<T> {
+        s.pool
+    }
+}
+
+impl<T> Deref for PoolItem<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.item
+    }
+}
+
+impl<T> DerefMut for PoolItem<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.item
+    }
+}
+
+impl<T> Drop for PoolItem<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: the item is moved out only here, as the handle is dropped.
+        let item = unsafe { ManuallyDrop::take(&mut self.item) };
+        self.pool.put(item);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PoolItem<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
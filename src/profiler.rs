@@ -0,0 +1,255 @@
+//! A sampling profiler for lock state.
+//!
+//! Locks registered with [`register_mutex`] or [`register_rwlock`] are
+//! inspected periodically by a background thread started with
+//! [`Profiler::start`]. Each [`Sample`] records, for every registered lock,
+//! which threads held it and whether other threads appeared to be waiting for
+//! it. Sampling costs nothing on the locks' own fast paths beyond the
+//! bookkeeping of the `tracking` feature, so it is cheap enough to leave
+//! running in production.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::Arc,
+    thread::{self, JoinHandle, Thread},
+    time::{Duration, Instant},
+};
+
+use crate::{held, Condvar, Mutex, RwLock};
+
+static REGISTRY: Mutex<Vec<(&'static str, &'static (dyn Probe + Sync))>> = Mutex::new(Vec::new());
+
+trait Probe {
+    fn addr(&self) -> usize;
+
+    fn contended(&self) -> bool;
+}
+
+impl<T: ?Sized> Probe for Mutex<T> {
+    fn addr(&self) -> usize {
+        Mutex::addr(self)
+    }
+
+    fn contended(&self) -> bool {
+        self.is_contended()
+    }
+}
+
+impl<T: ?Sized> Probe for RwLock<T> {
+    fn addr(&self) -> usize {
+        RwLock::addr(self)
+    }
+
+    fn contended(&self) -> bool {
+        self.writer_waiting()
+    }
+}
+
+/// Registers a `Mutex` to be included in every sample under the given name.
+pub fn register_mutex<T: Send>(name: &'static str, mutex: &'static Mutex<T>) {
+    REGISTRY.lock().push((name, mutex));
+}
+
+/// Registers an `RwLock` to be included in every sample under the given name.
+///
+/// An `RwLock` is reported as contended when a writer appears to be waiting.
+pub fn register_rwlock<T: Send + Sync>(name: &'static str, rwlock: &'static RwLock<T>) {
+    REGISTRY.lock().push((name, rwlock));
+}
+
+/// The state of every registered lock at one point in time.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    elapsed: Duration,
+    locks: Vec<LockState>,
+}
+
+impl Sample {
+    /// Returns the time at which the sample was taken, relative to the start of
+    /// the profiler.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the state of each registered lock, in registration order.
+    pub fn locks(&self) -> &[LockState] {
+        &self.locks
+    }
+}
+
+/// The state of a registered lock within a [`Sample`].
+#[derive(Debug, Clone)]
+pub struct LockState {
+    name: &'static str,
+    holders: Vec<Holder>,
+    contended: bool,
+}
+
+impl LockState {
+    /// Returns the name the lock was registered with.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns `true` if any thread held the lock.
+    pub fn is_held(&self) -> bool {
+        !self.holders.is_empty()
+    }
+
+    /// Returns the threads which held the lock.
+    pub fn holders(&self) -> &[Holder] {
+        &self.holders
+    }
+
+    /// Returns `true` if other threads appeared to be waiting for the lock.
+    pub fn is_contended(&self) -> bool {
+        self.contended
+    }
+}
+
+/// A thread holding a lock within a [`Sample`].
+#[derive(Debug, Clone)]
+pub struct Holder {
+    thread: Thread,
+    shared: bool,
+}
+
+impl Holder {
+    /// Returns the thread holding the lock.
+    pub fn thread(&self) -> &Thread {
+        &self.thread
+    }
+
+    /// Returns `true` if the thread held the lock for shared access only.
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+}
+
+struct Shared {
+    samples: Mutex<VecDeque<Sample>>,
+    capacity: usize,
+    stopped: Mutex<bool>,
+    stop: Condvar,
+}
+
+/// A running sampling profiler.
+///
+/// The sampling thread is stopped when the profiler is dropped.
+pub struct Profiler {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Profiler {
+    /// Starts a thread sampling every registered lock once per `interval`.
+    ///
+    /// At most `capacity` samples are kept; once that many have been taken
+    /// without being collected, the oldest are discarded.
+    pub fn start(interval: Duration, capacity: usize) -> Profiler {
+        let shared = Arc::new(Shared {
+            samples: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            stopped: Mutex::new(false),
+            stop: Condvar::new(),
+        });
+        let thread = thread::Builder::new()
+            .name("antidote-profiler".to_string())
+            .spawn({
+                let shared = shared.clone();
+                move || shared.run(interval)
+            })
+            .expect("failed to spawn profiler thread");
+        Profiler {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// Removes and returns the samples taken so far, oldest first.
+    pub fn take_samples(&self) -> Vec<Sample> {
+        self.shared.samples.lock().drain(..).collect()
+    }
+
+    /// Stops the sampling thread, returning the samples not yet collected.
+    pub fn stop(mut self) -> Vec<Sample> {
+        self.shutdown();
+        self.take_samples()
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            *self.shared.stopped.lock() = true;
+            self.shared.stop.notify_one();
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+impl fmt::Debug for Profiler {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Profiler")
+            .field("capacity", &self.shared.capacity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Shared {
+    fn run(&self, interval: Duration) {
+        let start = Instant::now();
+        let mut next = start;
+        let mut stopped = self.stopped.lock();
+        loop {
+            let now = Instant::now();
+            if now < next {
+                stopped = self.stop.wait_timeout(stopped, next - now).0;
+                if !*stopped {
+                    continue;
+                }
+            }
+            if *stopped {
+                return;
+            }
+
+            let sample = Sample {
+                elapsed: now - start,
+                locks: sample_locks(),
+            };
+            let mut samples = self.samples.lock();
+            if samples.len() == self.capacity {
+                samples.pop_front();
+            }
+            if self.capacity != 0 {
+                samples.push_back(sample);
+            }
+            drop(samples);
+            // Skip the samples that were missed if sampling fell behind.
+            next = (next + interval).max(now);
+        }
+    }
+}
+
+fn sample_locks() -> Vec<LockState> {
+    let registry = REGISTRY.lock().clone();
+    registry
+        .into_iter()
+        .map(|(name, lock)| LockState {
+            name,
+            holders: held::holders(lock.addr())
+                .into_iter()
+                .map(|(thread, access)| Holder {
+                    thread,
+                    shared: access == held::Access::Shared,
+                })
+                .collect(),
+            contended: lock.contended(),
+        })
+        .collect()
+}
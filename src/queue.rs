@@ -0,0 +1,209 @@
+//! A blocking queue for passing items between threads.
+//!
+//! [`BlockingQueue`] is a first-in, first-out queue shared by any number of
+//! producers and consumers, built on the crate's [`Mutex`] and [`Condvar`]:
+//! consumers block while it is empty and, if it was given a capacity,
+//! producers block while it is full, which keeps fast producers from running
+//! arbitrarily far ahead of slow consumers.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{clock, Condvar, Mutex, MutexGuard};
+
+/// A first-in, first-out queue which blocks consumers while it is empty, and
+/// producers while it is full.
+///
+/// Every method takes `&self`, so the queue is shared between threads with an
+/// `Arc`. Timed operations read the clock used by the crate's other timed
+/// operations, so they follow a mock clock installed with the `test-util`
+/// feature.
+pub struct BlockingQueue<T> {
+    items: Mutex<VecDeque<T>>,
+    capacity: usize,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+impl<T> BlockingQueue<T> {
+    /// Creates a new empty queue holding at most `capacity` items.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    #[inline]
+    pub fn new(capacity: usize) -> BlockingQueue<T> {
+        assert!(capacity > 0, "a BlockingQueue needs room for an item");
+        BlockingQueue {
+            items: Mutex::new(VecDeque::new()),
+            capacity,
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+        }
+    }
+
+    /// Creates a new empty queue without a capacity, whose producers never
+    /// block.
+    #[inline]
+    pub fn unbounded() -> BlockingQueue<T> {
+        BlockingQueue::new(usize::MAX)
+    }
+
+    /// Appends `item`, blocking the current thread while the queue is full.
+    pub fn push(&self, item: T) {
+        if self.push_until_internal(item, None).is_err() {
+            unreachable!("push without a deadline timed out");
+        }
+    }
+
+    /// Appends `item` if the queue is not full, without blocking, and hands it
+    /// back otherwise.
+    pub fn try_push(&self, item: T) -> Result<(), T> {
+        let items = self.items.lock();
+        if items.len() >= self.capacity {
+            return Err(item);
+        }
+        self.put(items, item);
+        Ok(())
+    }
+
+    /// Like [`push`](BlockingQueue::push), but gives up and hands `item` back
+    /// if the queue is still full after `timeout`.
+    pub fn push_timeout(&self, item: T, timeout: Duration) -> Result<(), T> {
+        // A deadline too far in the future to represent is treated as no
+        // deadline at all.
+        self.push_until_internal(item, clock::now().checked_add(timeout))
+    }
+
+    /// Like [`push`](BlockingQueue::push), but gives up and hands `item` back
+    /// if the queue is still full once `deadline` has passed.
+    pub fn push_until(&self, item: T, deadline: Instant) -> Result<(), T> {
+        self.push_until_internal(item, Some(deadline))
+    }
+
+    fn push_until_internal(&self, item: T, deadline: Option<Instant>) -> Result<(), T> {
+        let items = self.items.lock();
+        let (items, result) = self
+            .not_full
+            .wait_while_until_internal(items, deadline, |items| items.len() >= self.capacity);
+        if result.timed_out() {
+            let room = items.len() < self.capacity;
+            drop(items);
+            if room {
+                // This thread may have been woken for the free slot, so hand
+                // that wakeup on.
+                self.not_full.notify_one();
+            }
+            return Err(item);
+        }
+        self.put(items, item);
+        Ok(())
+    }
+
+    fn put(&self, mut items: MutexGuard<'_, VecDeque<T>>, item: T) {
+        items.push_back(item);
+        drop(items);
+        self.not_empty.notify_one();
+    }
+
+    /// Removes the item at the front of the queue, blocking the current thread
+    /// while the queue is empty.
+    pub fn pop(&self) -> T {
+        match self.pop_until_internal(None) {
+            Some(item) => item,
+            None => unreachable!("pop without a deadline timed out"),
+        }
+    }
+
+    /// Removes the item at the front of the queue if there is one, without
+    /// blocking.
+    pub fn try_pop(&self) -> Option<T> {
+        let items = self.items.lock();
+        if items.is_empty() {
+            return None;
+        }
+        Some(self.take(items))
+    }
+
+    /// Like [`pop`](BlockingQueue::pop), but gives up and returns `None` if the
+    /// queue is still empty after `timeout`.
+    pub fn pop_timeout(&self, timeout: Duration) -> Option<T> {
+        self.pop_until_internal(clock::now().checked_add(timeout))
+    }
+
+    /// Like [`pop`](BlockingQueue::pop), but gives up and returns `None` if the
+    /// queue is still empty once `deadline` has passed.
+    pub fn pop_until(&self, deadline: Instant) -> Option<T> {
+        self.pop_until_internal(Some(deadline))
+    }
+
+    fn pop_until_internal(&self, deadline: Option<Instant>) -> Option<T> {
+        let items = self.items.lock();
+        let (items, result) = self
+            .not_empty
+            .wait_while_until_internal(items, deadline, |items| items.is_empty());
+        if result.timed_out() {
+            let more = !items.is_empty();
+            drop(items);
+            if more {
+                // This thread may have been woken for the new item, so hand
+                // that wakeup on.
+                self.not_empty.notify_one();
+            }
+            return None;
+        }
+        Some(self.take(items))
+    }
+
+    fn take(&self, mut items: MutexGuard<'_, VecDeque<T>>) -> T {
+        let item = items.pop_front().unwrap();
+        drop(items);
+        self.not_full.notify_one();
+        item
+    }
+
+    /// Returns the number of items in the queue.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.items.lock().len()
+    }
+
+    /// Returns `true` if the queue holds no items.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.items.lock().is_empty()
+    }
+
+    /// Returns `true` if a producer would block.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.items.lock().len() >= self.capacity
+    }
+
+    /// Returns the most items the queue holds at once, which is `usize::MAX`
+    /// for an unbounded queue.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl<T> Default for BlockingQueue<T> {
+    /// Creates an unbounded queue.
+    #[inline]
+    fn default() -> BlockingQueue<T> {
+        BlockingQueue::unbounded()
+    }
+}
+
+impl<T> fmt::Debug for BlockingQueue<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("BlockingQueue")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
@@ -0,0 +1,1492 @@
+use std::{
+    cell::UnsafeCell,
+    fmt, hint,
+    marker::PhantomData,
+    mem,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    backend, clock, config, held,
+    parking::{self, ParkResult, DEFAULT_TOKEN},
+    CancelToken, LockOperation, TryLockError, TryLockResult,
+};
+
+const LOCKED: u8 = 1;
+const PARKED: u8 = 2;
+
+/// A mutex which does not contain the data it protects.
+///
+/// This is useful for serializing access to something which cannot be moved
+/// into a `Mutex`, such as a hardware register, a handle owned by foreign code
+/// or a region of a file. The lock can be held through a [`RawMutexGuard`], or
+/// acquired and released by hand with [`lock`](RawMutex::lock) and
+/// [`unlock`](RawMutex::unlock) when its acquisition and release do not follow
+/// a lexical scope, such as around a callback-based foreign API.
+///
+/// Unlike [`Mutex`](crate::Mutex), the lock may be released by a different
+/// thread than the one which acquired it. It is not seen by the `tracking`,
+/// `histogram` or `opentelemetry` instrumentation.
+pub struct RawMutex {
+    state: AtomicU8,
+}
+
+impl RawMutex {
+    /// Creates a new mutex in an unlocked state.
+    #[inline]
+    pub const fn new() -> RawMutex {
+        RawMutex {
+            state: AtomicU8::new(0),
+        }
+    }
+
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so.
+    ///
+    /// The mutex stays locked until [`unlock`](RawMutex::unlock) is called.
+    /// Locking it again on the thread which holds it deadlocks.
+    #[inline]
+    pub fn lock(&self) {
+        if self
+            .state
+            .compare_exchange_weak(0, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            self.lock_contended();
+        }
+    }
+
+    #[cold]
+    fn lock_contended(&self) {
+        let mut spins = config().spin_iterations();
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & LOCKED == 0 {
+                match self.state.compare_exchange_weak(
+                    state,
+                    state | LOCKED,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => return,
+                    Err(actual) => state = actual,
+                }
+                continue;
+            }
+
+            if state & PARKED == 0 {
+                if spins > 0 {
+                    spins -= 1;
+                    hint::spin_loop();
+                    state = self.state.load(Ordering::Relaxed);
+                    continue;
+                }
+                if let Err(actual) = self.state.compare_exchange_weak(
+                    state,
+                    state | PARKED,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    state = actual;
+                    continue;
+                }
+            }
+
+            parking::park(
+                self.key(),
+                || self.state.load(Ordering::Relaxed) == LOCKED | PARKED,
+                || {},
+                |_, _| {},
+                None,
+            );
+            state = self.state.load(Ordering::Relaxed);
+        }
+    }
+
+    /// Attempts to acquire the mutex without blocking, returning `true` if it
+    /// was acquired.
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & LOCKED != 0 {
+                return false;
+            }
+            match self.state.compare_exchange_weak(
+                state,
+                state | LOCKED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    /// Releases the mutex.
+    ///
+    /// # Safety
+    ///
+    /// The mutex must be locked, and the caller must be entitled to release it:
+    /// it acquired the lock itself, or took over from whoever did. In
+    /// particular, it must not be released this way while a guard for it is
+    /// alive.
+    #[inline]
+    pub unsafe fn unlock(&self) {
+        if self
+            .state
+            .compare_exchange(LOCKED, 0, Ordering::Release, Ordering::Relaxed)
+            .is_err()
+        {
+            self.unlock_contended();
+        }
+    }
+
+    #[cold]
+    fn unlock_contended(&self) {
+        parking::unpark_one(self.key(), |result| {
+            let state = if result.have_more { PARKED } else { 0 };
+            self.state.store(state, Ordering::Release);
+            DEFAULT_TOKEN
+        });
+    }
+
+    /// Returns `true` if the mutex is currently locked.
+    ///
+    /// The answer may be stale as soon as it is returned.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & LOCKED != 0
+    }
+
+    /// Acquires the mutex, returning a guard which releases it when dropped.
+    #[inline]
+    pub fn guard(&self) -> RawMutexGuard<'_> {
+        self.lock();
+        RawMutexGuard { mutex: self }
+    }
+
+    /// Attempts to acquire the mutex without blocking, returning a guard which
+    /// releases it when dropped.
+    #[inline]
+    pub fn try_guard(&self) -> TryLockResult<RawMutexGuard<'_>> {
+        if self.try_lock() {
+            Ok(RawMutexGuard { mutex: self })
+        } else {
+            Err(TryLockError::new(LockOperation::Lock))
+        }
+    }
+
+    #[inline]
+    fn key(&self) -> usize {
+        &self.state as *const AtomicU8 as usize
+    }
+}
+
+impl Default for RawMutex {
+    #[inline]
+    fn default() -> RawMutex {
+        RawMutex::new()
+    }
+}
+
+impl fmt::Debug for RawMutex {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("RawMutex")
+            .field("locked", &self.is_locked())
+            .finish()
+    }
+}
+
+/// A guard holding a [`RawMutex`] locked, releasing it when dropped.
+#[must_use]
+pub struct RawMutexGuard<'a> {
+    mutex: &'a RawMutex,
+}
+
+impl<'a> RawMutexGuard<'a> {
+    /// Returns the mutex this guard holds.
+    #[inline]
+    pub fn mutex(&self) -> &'a RawMutex {
+        self.mutex
+    }
+}
+
+impl Drop for RawMutexGuard<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: the guard holds the lock.
+        unsafe { self.mutex.unlock() };
+    }
+}
+
+impl fmt::Debug for RawMutexGuard<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("RawMutexGuard").finish_non_exhaustive()
+    }
+}
+
+/// A raw mutex which a [`Mutex`](crate::Mutex) can be built on in place of
+/// the built-in one, such as one using priority inheritance.
+///
+/// A mutex built on another raw mutex with
+/// [`Mutex::with_raw`](crate::Mutex::with_raw) is instrumented and offers the
+/// same API as any other, including timed acquisitions and waiting on a
+/// [`Condvar`](crate::Condvar), as those only rely on
+/// [`try_lock`](MutexBackend::try_lock). What relies on the built-in mutex
+/// itself, such as leases, is left out. Such a mutex can be cloned if the raw
+/// mutex can, and its clone should start unlocked.
+///
+/// # Safety
+///
+/// The mutex must exclude any other holder from the moment a method acquiring
+/// it returns, or returns `true`, until [`unlock`](MutexBackend::unlock) is
+/// called. It must support being released by another thread than the one
+/// which acquired it, as guards can be sent to other threads with the
+/// `send-guard` feature, which any crate in the build may enable.
+pub unsafe trait MutexBackend {
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so.
+    fn lock(&self);
+
+    /// Attempts to acquire the mutex without blocking, returning `true` if it
+    /// was acquired.
+    fn try_lock(&self) -> bool;
+
+    /// Releases the mutex.
+    ///
+    /// # Safety
+    ///
+    /// The mutex must be locked, and the caller must be entitled to release
+    /// it.
+    unsafe fn unlock(&self);
+
+    /// Returns `true` if the mutex is currently locked.
+    ///
+    /// The default implementation acquires and releases the mutex right away
+    /// if it is free.
+    #[inline]
+    fn is_locked(&self) -> bool {
+        if self.try_lock() {
+            // SAFETY: the mutex was just acquired.
+            unsafe { self.unlock() };
+            false
+        } else {
+            true
+        }
+    }
+}
+
+// SAFETY: the methods forward to those of `RawMutex`, which excludes as
+// required.
+unsafe impl MutexBackend for RawMutex {
+    #[inline]
+    fn lock(&self) {
+        RawMutex::lock(self);
+    }
+
+    #[inline]
+    fn try_lock(&self) -> bool {
+        RawMutex::try_lock(self)
+    }
+
+    #[inline]
+    unsafe fn unlock(&self) {
+        RawMutex::unlock(self);
+    }
+
+    #[inline]
+    fn is_locked(&self) -> bool {
+        RawMutex::is_locked(self)
+    }
+}
+
+/// The mutex a [`Mutex`](crate::Mutex) is built on by default: the standard
+/// library's, `parking_lot`'s with the `parking_lot` feature, or a futex with
+/// the `futex` feature.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BuiltinMutex(());
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for super::BuiltinMutex {}
+
+    impl<R: super::MutexBackend> Sealed for R {}
+}
+
+/// What a [`Mutex`](crate::Mutex) can be built on: the [`BuiltinMutex`] or
+/// any [`MutexBackend`].
+///
+/// This trait is sealed, and its members are an implementation detail.
+pub trait MutexStorage: sealed::Sealed + Sized {
+    // The mutex holding a value.
+    #[doc(hidden)]
+    type Mutex<T: ?Sized>: ?Sized;
+
+    // A guard holding the mutex, which releases it when dropped.
+    #[doc(hidden)]
+    type Guard<'a, T: ?Sized + 'a>: DerefMut<Target = T>
+    where
+        Self: 'a;
+
+    #[doc(hidden)]
+    fn lock<T: ?Sized>(mutex: &Self::Mutex<T>) -> Self::Guard<'_, T>;
+
+    #[doc(hidden)]
+    fn try_lock<T: ?Sized>(mutex: &Self::Mutex<T>) -> Option<Self::Guard<'_, T>>;
+
+    // SAFETY: no thread may have panicked while holding the mutex.
+    #[doc(hidden)]
+    unsafe fn try_lock_unpoisoned<T: ?Sized>(mutex: &Self::Mutex<T>) -> Option<Self::Guard<'_, T>>;
+
+    #[doc(hidden)]
+    fn into_inner<T>(mutex: Self::Mutex<T>) -> T;
+
+    #[doc(hidden)]
+    fn get_mut<T: ?Sized>(mutex: &mut Self::Mutex<T>) -> &mut T;
+
+    #[doc(hidden)]
+    fn is_locked<T: ?Sized>(mutex: &Self::Mutex<T>) -> bool;
+}
+
+impl MutexStorage for BuiltinMutex {
+    type Mutex<T: ?Sized> = backend::Mutex<T>;
+    type Guard<'a, T: ?Sized + 'a> = backend::MutexGuard<'a, T>;
+
+    #[inline]
+    fn lock<T: ?Sized>(mutex: &Self::Mutex<T>) -> Self::Guard<'_, T> {
+        backend::lock(mutex)
+    }
+
+    #[inline]
+    fn try_lock<T: ?Sized>(mutex: &Self::Mutex<T>) -> Option<Self::Guard<'_, T>> {
+        backend::try_lock(mutex)
+    }
+
+    #[inline]
+    unsafe fn try_lock_unpoisoned<T: ?Sized>(mutex: &Self::Mutex<T>) -> Option<Self::Guard<'_, T>> {
+        // SAFETY: guaranteed by the caller.
+        unsafe { backend::try_lock_unpoisoned(mutex) }
+    }
+
+    #[inline]
+    fn into_inner<T>(mutex: Self::Mutex<T>) -> T {
+        backend::into_inner(mutex)
+    }
+
+    #[inline]
+    fn get_mut<T: ?Sized>(mutex: &mut Self::Mutex<T>) -> &mut T {
+        backend::get_mut(mutex)
+    }
+
+    #[inline]
+    fn is_locked<T: ?Sized>(mutex: &Self::Mutex<T>) -> bool {
+        backend::is_locked(mutex)
+    }
+}
+
+impl<R: MutexBackend> MutexStorage for R {
+    type Mutex<T: ?Sized> = BackendMutex<R, T>;
+    type Guard<'a, T: ?Sized + 'a>
+        = BackendMutexGuard<'a, R, T>
+    where
+        R: 'a;
+
+    #[inline]
+    fn lock<T: ?Sized>(mutex: &Self::Mutex<T>) -> Self::Guard<'_, T> {
+        mutex.raw.lock();
+        BackendMutexGuard::new(mutex)
+    }
+
+    #[inline]
+    fn try_lock<T: ?Sized>(mutex: &Self::Mutex<T>) -> Option<Self::Guard<'_, T>> {
+        if mutex.raw.try_lock() {
+            Some(BackendMutexGuard::new(mutex))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    unsafe fn try_lock_unpoisoned<T: ?Sized>(mutex: &Self::Mutex<T>) -> Option<Self::Guard<'_, T>> {
+        <R as MutexStorage>::try_lock(mutex)
+    }
+
+    #[inline]
+    fn into_inner<T>(mutex: Self::Mutex<T>) -> T {
+        mutex.data.into_inner()
+    }
+
+    #[inline]
+    fn get_mut<T: ?Sized>(mutex: &mut Self::Mutex<T>) -> &mut T {
+        mutex.data.get_mut()
+    }
+
+    #[inline]
+    fn is_locked<T: ?Sized>(mutex: &Self::Mutex<T>) -> bool {
+        mutex.raw.is_locked()
+    }
+}
+
+// The mutex underneath a `Mutex` built on a `MutexBackend`.
+#[derive(Default)]
+pub struct BackendMutex<R, T: ?Sized> {
+    raw: R,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: the raw mutex hands out access to the value to one thread at a time.
+unsafe impl<R: Send, T: ?Sized + Send> Send for BackendMutex<R, T> {}
+unsafe impl<R: Sync, T: ?Sized + Send> Sync for BackendMutex<R, T> {}
+
+impl<R, T> BackendMutex<R, T> {
+    #[inline]
+    pub(crate) const fn new(raw: R, t: T) -> BackendMutex<R, T> {
+        BackendMutex {
+            raw,
+            data: UnsafeCell::new(t),
+        }
+    }
+}
+
+impl<R, T: ?Sized> BackendMutex<R, T> {
+    #[inline]
+    pub(crate) fn raw(&self) -> &R {
+        &self.raw
+    }
+
+    #[inline]
+    pub(crate) fn data_ptr(&self) -> *mut T {
+        self.data.get()
+    }
+}
+
+// A guard holding a `BackendMutex`.
+pub struct BackendMutexGuard<'a, R: MutexBackend, T: ?Sized> {
+    mutex: &'a BackendMutex<R, T>,
+    // Like the built-in guards, this one is not `Send`, unless the
+    // `send-guard` feature is enabled, as every backend can be released by
+    // any thread.
+    _marker: held::NotSend,
+}
+
+// SAFETY: the guard only hands out shared references through `&self`.
+unsafe impl<R: MutexBackend + Sync, T: ?Sized + Sync> Sync for BackendMutexGuard<'_, R, T> {}
+
+impl<'a, R: MutexBackend, T: ?Sized> BackendMutexGuard<'a, R, T> {
+    #[inline]
+    fn new(mutex: &'a BackendMutex<R, T>) -> BackendMutexGuard<'a, R, T> {
+        BackendMutexGuard {
+            mutex,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<R: MutexBackend, T: ?Sized> Drop for BackendMutexGuard<'_, R, T> {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: the guard holds the mutex.
+        unsafe { self.mutex.raw.unlock() };
+    }
+}
+
+impl<R: MutexBackend, T: ?Sized> Deref for BackendMutexGuard<'_, R, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the mutex.
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<R: MutexBackend, T: ?Sized> DerefMut for BackendMutexGuard<'_, R, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the mutex.
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+const WRITER: usize = 1;
+const WAITING: usize = 2;
+// Set while a thread holds upgradable access, which also counts as a reader.
+const UPGRADABLE: usize = 4;
+// Set along with `WAITING` by threads waiting for exclusive access, or to
+// upgrade.
+const WRITER_WAITING: usize = 8;
+const WAITERS: usize = WAITING | WRITER_WAITING;
+const ONE_READER: usize = 16;
+
+/// Which of readers and writers a [`RawRwLock`] or [`RwLock`](crate::RwLock)
+/// lets go first when both want the lock.
+///
+/// The policy is chosen when the lock is created, and only decides whether a
+/// new shared acquisition may join the readers already holding the lock. It
+/// does not depend on the platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Fairness {
+    /// New readers join the readers holding the lock even if writers are
+    /// waiting, so readers always make progress, but a steady stream of them
+    /// can starve writers.
+    ReaderPriority,
+    /// New readers wait while a writer is waiting, so the wait of a writer is
+    /// bounded by the readers already holding the lock, but a steady stream
+    /// of writers can starve readers.
+    WriterPriority,
+    /// New readers wait while any thread is waiting, so that neither readers
+    /// nor writers can starve the other.
+    #[default]
+    Fair,
+}
+
+/// A reader-writer lock which does not contain the data it protects.
+///
+/// This is the reader-writer counterpart of [`RawMutex`]. By default, once a
+/// thread has to wait for the lock, new shared acquisitions queue up behind
+/// it, so a steady stream of readers cannot starve a writer. Locks created
+/// with [`with_fairness`](RawRwLock::with_fairness) can prefer readers or
+/// writers instead.
+///
+/// Besides shared and exclusive access, one thread at a time can hold
+/// upgradable access, which coexists with readers but excludes writers and
+/// other upgradable holders. It can later be upgraded to exclusive access
+/// without releasing the lock in between, so nothing another writer does can
+/// invalidate what the thread read, which makes check-then-write paths safe.
+///
+/// Like [`RawMutex`], the lock may be released by a different thread than the
+/// one which acquired it, and it is not seen by the `tracking`, `histogram` or
+/// `opentelemetry` instrumentation.
+pub struct RawRwLock {
+    state: AtomicUsize,
+    fairness: Fairness,
+}
+
+impl RawRwLock {
+    /// Creates a new lock in an unlocked state.
+    #[inline]
+    pub const fn new() -> RawRwLock {
+        RawRwLock::with_fairness(Fairness::Fair)
+    }
+
+    /// Creates a new lock in an unlocked state, which arbitrates between
+    /// readers and writers according to `fairness`.
+    #[inline]
+    pub const fn with_fairness(fairness: Fairness) -> RawRwLock {
+        RawRwLock {
+            state: AtomicUsize::new(0),
+            fairness,
+        }
+    }
+
+    /// Returns the policy the lock was created with.
+    #[inline]
+    pub fn fairness(&self) -> Fairness {
+        self.fairness
+    }
+
+    /// Acquires shared access, blocking the current thread until it is able to
+    /// do so.
+    ///
+    /// Access is held until [`unlock_shared`](RawRwLock::unlock_shared) is
+    /// called. Acquiring shared access again on a thread which already holds it
+    /// may deadlock if another thread is waiting for the lock, unless the lock
+    /// prefers readers.
+    #[inline]
+    pub fn lock_shared(&self) {
+        if !self.try_lock_shared() {
+            self.lock_shared_contended();
+        }
+    }
+
+    #[cold]
+    fn lock_shared_contended(&self) {
+        self.wait_until(self.shared_locked(), WAITING, None, None);
+    }
+
+    /// Attempts to acquire shared access without blocking, returning `true` if
+    /// it was acquired.
+    ///
+    /// This fails if the lock is held exclusively or, depending on its
+    /// [`Fairness`], if a thread is waiting for it.
+    #[inline]
+    pub fn try_lock_shared(&self) -> bool {
+        self.try_transition(self.shared_locked())
+    }
+
+    // The transition taking shared access under the lock's policy.
+    #[inline]
+    fn shared_locked(&self) -> fn(usize) -> Option<usize> {
+        match self.fairness {
+            Fairness::ReaderPriority => shared_locked_recursive,
+            Fairness::WriterPriority => shared_locked_before_readers,
+            Fairness::Fair => shared_locked,
+        }
+    }
+
+    /// Acquires shared access even if another thread is waiting for the lock,
+    /// blocking the current thread only while the lock is held exclusively.
+    ///
+    /// This lets a thread which already holds shared access acquire it again
+    /// without deadlocking, at the price of possibly starving writers if
+    /// readers keep overlapping.
+    #[inline]
+    pub fn lock_shared_recursive(&self) {
+        if !self.try_lock_shared_recursive() {
+            self.lock_shared_recursive_contended();
+        }
+    }
+
+    #[cold]
+    fn lock_shared_recursive_contended(&self) {
+        self.wait_until(shared_locked_recursive, WAITING, None, None);
+    }
+
+    /// Attempts to acquire shared access without blocking, even if another
+    /// thread is waiting for the lock, returning `true` if it was acquired.
+    ///
+    /// This only fails if the lock is held exclusively.
+    #[inline]
+    pub fn try_lock_shared_recursive(&self) -> bool {
+        self.try_transition(shared_locked_recursive)
+    }
+
+    /// Attempts to acquire shared access, blocking the current thread for at
+    /// most `timeout`, and returning `true` if it was acquired.
+    #[inline]
+    pub fn try_lock_shared_for(&self, timeout: Duration) -> bool {
+        // A deadline too far in the future to represent is treated as no
+        // deadline at all.
+        self.try_lock_shared_until_internal(clock::now().checked_add(timeout))
+    }
+
+    /// Attempts to acquire shared access, blocking the current thread until at
+    /// most `deadline`, and returning `true` if it was acquired.
+    #[inline]
+    pub fn try_lock_shared_until(&self, deadline: Instant) -> bool {
+        self.try_lock_shared_until_internal(Some(deadline))
+    }
+
+    #[inline]
+    fn try_lock_shared_until_internal(&self, deadline: Option<Instant>) -> bool {
+        self.try_lock_shared() || self.wait_until(self.shared_locked(), WAITING, deadline, None)
+    }
+
+    // Like `lock_shared`, but gives up once `token` is cancelled, returning
+    // `false`.
+    #[inline]
+    pub(crate) fn lock_shared_cancellable(&self, token: &CancelToken) -> bool {
+        self.try_lock_shared() || self.wait_until(self.shared_locked(), WAITING, None, Some(token))
+    }
+
+    /// Releases shared access.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held shared, and the caller must be entitled to release
+    /// that access: it acquired it itself, or took over from whoever did. In
+    /// particular, it must not be released this way while a guard for it is
+    /// alive.
+    #[inline]
+    pub unsafe fn unlock_shared(&self) {
+        let state = self.state.fetch_sub(ONE_READER, Ordering::Release) - ONE_READER;
+        // Wake waiters once only an upgradable holder, if any, is left, as it
+        // may be waiting to upgrade.
+        let upgradable = (state & UPGRADABLE != 0) as usize;
+        if state & WAITING != 0 && state / ONE_READER == upgradable {
+            self.unpark_all();
+        }
+    }
+
+    /// Acquires upgradable access, blocking the current thread until it is
+    /// able to do so.
+    ///
+    /// Access is held until [`unlock_upgradable`](RawRwLock::unlock_upgradable)
+    /// is called, or turned into exclusive access with
+    /// [`upgrade`](RawRwLock::upgrade).
+    #[inline]
+    pub fn lock_upgradable(&self) {
+        if !self.try_lock_upgradable() {
+            self.lock_upgradable_contended();
+        }
+    }
+
+    #[cold]
+    fn lock_upgradable_contended(&self) {
+        self.wait_until(upgradable_locked, WAITING, None, None);
+    }
+
+    /// Attempts to acquire upgradable access without blocking, returning
+    /// `true` if it was acquired.
+    ///
+    /// This fails if the lock is held exclusively or upgradably, or a thread is
+    /// waiting for it.
+    #[inline]
+    pub fn try_lock_upgradable(&self) -> bool {
+        self.try_transition(upgradable_locked)
+    }
+
+    /// Releases upgradable access.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held upgradably, and the caller must be entitled to
+    /// release that access: it acquired it itself, or took over from whoever
+    /// did. In particular, it must not be released this way while a guard for
+    /// it is alive.
+    #[inline]
+    pub unsafe fn unlock_upgradable(&self) {
+        let state = self
+            .state
+            .fetch_sub(ONE_READER | UPGRADABLE, Ordering::Release);
+        if state & WAITING != 0 {
+            self.unpark_all();
+        }
+    }
+
+    /// Turns upgradable access into exclusive access, blocking the current
+    /// thread until the other readers have released the lock.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held upgradably, and the caller must be entitled to
+    /// release that access, as for
+    /// [`unlock_upgradable`](RawRwLock::unlock_upgradable).
+    #[inline]
+    pub unsafe fn upgrade(&self) {
+        if !self.try_upgrade() {
+            self.wait_until(upgraded, WAITERS, None, None);
+        }
+    }
+
+    /// Attempts to turn upgradable access into exclusive access without
+    /// blocking, returning `true` if it did.
+    ///
+    /// This fails if any other reader holds the lock. Upgradable access is
+    /// kept in that case.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held upgradably, and the caller must be entitled to
+    /// release that access, as for
+    /// [`unlock_upgradable`](RawRwLock::unlock_upgradable).
+    #[inline]
+    pub unsafe fn try_upgrade(&self) -> bool {
+        self.try_transition(upgraded)
+    }
+
+    /// Attempts to turn upgradable access into exclusive access, blocking the
+    /// current thread for at most `timeout` until the other readers have
+    /// released the lock, and returning `true` if it did.
+    ///
+    /// Upgradable access is kept if this times out.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held upgradably, and the caller must be entitled to
+    /// release that access, as for
+    /// [`unlock_upgradable`](RawRwLock::unlock_upgradable).
+    pub unsafe fn try_upgrade_for(&self, timeout: Duration) -> bool {
+        if self.try_upgrade() {
+            return true;
+        }
+        // A deadline too far in the future to represent is treated as no
+        // deadline at all.
+        self.wait_until(upgraded, WAITERS, clock::now().checked_add(timeout), None)
+    }
+
+    /// Turns upgradable access into shared access, letting another thread
+    /// acquire upgradable access.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held upgradably, and the caller must be entitled to
+    /// release that access, as for
+    /// [`unlock_upgradable`](RawRwLock::unlock_upgradable).
+    #[inline]
+    pub unsafe fn downgrade_upgradable(&self) {
+        let state = self.state.fetch_sub(UPGRADABLE, Ordering::Release);
+        if state & WAITING != 0 {
+            self.unpark_all();
+        }
+    }
+
+    /// Turns exclusive access into shared access, letting other readers in
+    /// without releasing the lock in between.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held exclusively, and the caller must be entitled to
+    /// release that access, as for
+    /// [`unlock_exclusive`](RawRwLock::unlock_exclusive).
+    #[inline]
+    pub unsafe fn downgrade(&self) {
+        let state = self.state.fetch_add(ONE_READER - WRITER, Ordering::Release);
+        if state & WAITING != 0 {
+            self.unpark_all();
+        }
+    }
+
+    /// Turns exclusive access into upgradable access, letting readers in again
+    /// without giving other writers a chance to acquire the lock.
+    ///
+    /// The thread keeps the right to upgrade again later, without queueing up
+    /// behind other writers, which suits maintenance done in several steps.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held exclusively, and the caller must be entitled to
+    /// release that access, as for
+    /// [`unlock_exclusive`](RawRwLock::unlock_exclusive).
+    #[inline]
+    pub unsafe fn downgrade_to_upgradable(&self) {
+        let state = self
+            .state
+            .fetch_add(ONE_READER + UPGRADABLE - WRITER, Ordering::Release);
+        if state & WAITING != 0 {
+            self.unpark_all();
+        }
+    }
+
+    /// Acquires exclusive access, blocking the current thread until it is able
+    /// to do so.
+    ///
+    /// Access is held until [`unlock_exclusive`](RawRwLock::unlock_exclusive)
+    /// is called.
+    #[inline]
+    pub fn lock_exclusive(&self) {
+        if self
+            .state
+            .compare_exchange_weak(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            self.lock_exclusive_contended();
+        }
+    }
+
+    #[cold]
+    fn lock_exclusive_contended(&self) {
+        self.wait_until(exclusive_locked, WAITERS, None, None);
+    }
+
+    /// Attempts to acquire exclusive access without blocking, returning `true`
+    /// if it was acquired.
+    #[inline]
+    pub fn try_lock_exclusive(&self) -> bool {
+        self.try_transition(exclusive_locked)
+    }
+
+    /// Attempts to acquire exclusive access, blocking the current thread for
+    /// at most `timeout`, and returning `true` if it was acquired.
+    #[inline]
+    pub fn try_lock_exclusive_for(&self, timeout: Duration) -> bool {
+        // A deadline too far in the future to represent is treated as no
+        // deadline at all.
+        self.try_lock_exclusive_until_internal(clock::now().checked_add(timeout))
+    }
+
+    /// Attempts to acquire exclusive access, blocking the current thread until
+    /// at most `deadline`, and returning `true` if it was acquired.
+    #[inline]
+    pub fn try_lock_exclusive_until(&self, deadline: Instant) -> bool {
+        self.try_lock_exclusive_until_internal(Some(deadline))
+    }
+
+    #[inline]
+    fn try_lock_exclusive_until_internal(&self, deadline: Option<Instant>) -> bool {
+        self.try_lock_exclusive() || self.wait_until(exclusive_locked, WAITERS, deadline, None)
+    }
+
+    // Like `lock_exclusive`, but gives up once `token` is cancelled, returning
+    // `false`.
+    #[inline]
+    pub(crate) fn lock_exclusive_cancellable(&self, token: &CancelToken) -> bool {
+        self.try_lock_exclusive() || self.wait_until(exclusive_locked, WAITERS, None, Some(token))
+    }
+
+    /// Releases exclusive access.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be held exclusively, and the caller must be entitled to
+    /// release it: it acquired it itself, or took over from whoever did. In
+    /// particular, it must not be released this way while a guard for it is
+    /// alive.
+    #[inline]
+    pub unsafe fn unlock_exclusive(&self) {
+        if self
+            .state
+            .compare_exchange(WRITER, 0, Ordering::Release, Ordering::Relaxed)
+            .is_err()
+        {
+            self.state.fetch_and(!WRITER, Ordering::Release);
+            self.unpark_all();
+        }
+    }
+
+    /// Returns `true` if the lock is currently held, shared or exclusively.
+    ///
+    /// The answer may be stale as soon as it is returned.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & !WAITERS != 0
+    }
+
+    // Returns `true` if threads appear to be blocked waiting for the lock.
+    #[inline]
+    pub(crate) fn has_waiters(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WAITING != 0
+    }
+
+    /// Returns `true` if the lock is currently held upgradably.
+    ///
+    /// The answer may be stale as soon as it is returned.
+    #[inline]
+    pub fn is_locked_upgradable(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & UPGRADABLE != 0
+    }
+
+    /// Returns `true` if the lock is currently held exclusively.
+    ///
+    /// The answer may be stale as soon as it is returned.
+    #[inline]
+    pub fn is_locked_exclusive(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WRITER != 0
+    }
+
+    /// Returns the number of threads currently holding shared access,
+    /// including the holder of upgradable access, if any.
+    ///
+    /// The answer may be stale as soon as it is returned.
+    #[inline]
+    pub fn reader_count(&self) -> usize {
+        self.state.load(Ordering::Relaxed) / ONE_READER
+    }
+
+    /// Acquires shared access, returning a guard which releases it when
+    /// dropped.
+    #[inline]
+    pub fn shared_guard(&self) -> RawRwLockReadGuard<'_> {
+        self.lock_shared();
+        RawRwLockReadGuard { rwlock: self }
+    }
+
+    /// Attempts to acquire shared access without blocking, returning a guard
+    /// which releases it when dropped.
+    #[inline]
+    pub fn try_shared_guard(&self) -> TryLockResult<RawRwLockReadGuard<'_>> {
+        if self.try_lock_shared() {
+            Ok(RawRwLockReadGuard { rwlock: self })
+        } else {
+            Err(TryLockError::new(LockOperation::Read))
+        }
+    }
+
+    /// Acquires upgradable access, returning a guard which releases it when
+    /// dropped.
+    #[inline]
+    pub fn upgradable_guard(&self) -> RawRwLockUpgradableGuard<'_> {
+        self.lock_upgradable();
+        RawRwLockUpgradableGuard { rwlock: self }
+    }
+
+    /// Attempts to acquire upgradable access without blocking, returning a
+    /// guard which releases it when dropped.
+    #[inline]
+    pub fn try_upgradable_guard(&self) -> TryLockResult<RawRwLockUpgradableGuard<'_>> {
+        if self.try_lock_upgradable() {
+            Ok(RawRwLockUpgradableGuard { rwlock: self })
+        } else {
+            Err(TryLockError::new(LockOperation::UpgradableRead))
+        }
+    }
+
+    /// Acquires exclusive access, returning a guard which releases it when
+    /// dropped.
+    #[inline]
+    pub fn exclusive_guard(&self) -> RawRwLockWriteGuard<'_> {
+        self.lock_exclusive();
+        RawRwLockWriteGuard { rwlock: self }
+    }
+
+    /// Attempts to acquire exclusive access without blocking, returning a
+    /// guard which releases it when dropped.
+    #[inline]
+    pub fn try_exclusive_guard(&self) -> TryLockResult<RawRwLockWriteGuard<'_>> {
+        if self.try_lock_exclusive() {
+            Ok(RawRwLockWriteGuard { rwlock: self })
+        } else {
+            Err(TryLockError::new(LockOperation::Write))
+        }
+    }
+
+    // Applies `transition` to the state until it succeeds or declines.
+    #[inline]
+    fn try_transition(&self, transition: fn(usize) -> Option<usize>) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            let new = match transition(state) {
+                Some(new) => new,
+                None => return false,
+            };
+            match self
+                .state
+                .compare_exchange_weak(state, new, Ordering::Acquire, Ordering::Relaxed)
+            {
+                Ok(_) => return true,
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    // Spins and parks until `transition` succeeds, or `deadline` passes or
+    // `cancel` is cancelled in which case `false` is returned. `waiting` are
+    // the bits set before parking.
+    fn wait_until(
+        &self,
+        transition: fn(usize) -> Option<usize>,
+        waiting: usize,
+        deadline: Option<Instant>,
+        cancel: Option<&CancelToken>,
+    ) -> bool {
+        let cancel = cancel.map(|token| token.register(self.key()));
+        let cancelled = || {
+            cancel
+                .as_ref()
+                .map_or(false, |cancel| cancel.is_cancelled())
+        };
+        let mut spins = config().spin_iterations();
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if let Some(new) = transition(state) {
+                match self.state.compare_exchange_weak(
+                    state,
+                    new,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => return true,
+                    Err(actual) => state = actual,
+                }
+                continue;
+            }
+
+            if state & waiting != waiting {
+                if spins > 0 {
+                    spins -= 1;
+                    hint::spin_loop();
+                    state = self.state.load(Ordering::Relaxed);
+                    continue;
+                }
+                if let Err(actual) = self.state.compare_exchange_weak(
+                    state,
+                    state | waiting,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    state = actual;
+                    continue;
+                }
+            }
+
+            // Whoever releases the lock next clears `WAITING` with the bucket
+            // locked before waking everyone, so checking it here cannot miss
+            // that wakeup.
+            if cancelled() {
+                return self.give_up(waiting);
+            }
+            let result = parking::park(
+                self.key(),
+                || self.state.load(Ordering::Relaxed) & WAITING != 0 && !cancelled(),
+                || {},
+                |_, last| {
+                    if last {
+                        self.state.fetch_and(!WAITERS, Ordering::Relaxed);
+                    }
+                },
+                deadline,
+            );
+            if result == ParkResult::TimedOut {
+                return self.give_up(waiting);
+            }
+            state = self.state.load(Ordering::Relaxed);
+        }
+    }
+
+    // Called by a thread which stops waiting before it acquired the lock,
+    // returning `false`. A writer leaves `WRITER_WAITING` set for the threads
+    // still queued, which would keep readers out until the lock is next
+    // released, so it wakes them all to set the bits anew for themselves.
+    #[cold]
+    fn give_up(&self, waiting: usize) -> bool {
+        if waiting & WRITER_WAITING != 0 {
+            self.unpark_all();
+        }
+        false
+    }
+
+    // Woken threads race for the lock again, and those which lose set
+    // `WAITING` anew before going back to sleep.
+    #[cold]
+    fn unpark_all(&self) {
+        parking::unpark_all(self.key(), |_| {
+            self.state.fetch_and(!WAITERS, Ordering::Relaxed);
+            DEFAULT_TOKEN
+        });
+    }
+
+    #[inline]
+    fn key(&self) -> usize {
+        &self.state as *const AtomicUsize as usize
+    }
+}
+
+fn readers_incremented(state: usize) -> usize {
+    state
+        .checked_add(ONE_READER)
+        .expect("RawRwLock reader count overflowed")
+}
+
+fn shared_locked(state: usize) -> Option<usize> {
+    if state & (WRITER | WAITING) == 0 {
+        Some(readers_incremented(state))
+    } else {
+        None
+    }
+}
+
+// Only waiting writers hold readers back.
+fn shared_locked_before_readers(state: usize) -> Option<usize> {
+    if state & (WRITER | WRITER_WAITING) == 0 {
+        Some(readers_incremented(state))
+    } else {
+        None
+    }
+}
+
+// Ignores `WAITING`, so that a thread already holding shared access cannot
+// end up waiting behind a writer which is waiting for it.
+fn shared_locked_recursive(state: usize) -> Option<usize> {
+    if state & WRITER == 0 {
+        Some(readers_incremented(state))
+    } else {
+        None
+    }
+}
+
+fn upgradable_locked(state: usize) -> Option<usize> {
+    if state & (WRITER | WAITING | UPGRADABLE) == 0 {
+        Some(readers_incremented(state) | UPGRADABLE)
+    } else {
+        None
+    }
+}
+
+fn exclusive_locked(state: usize) -> Option<usize> {
+    if state & !WAITERS == 0 {
+        Some(state | WRITER)
+    } else {
+        None
+    }
+}
+
+// Only the upgrading thread itself may still be reading.
+fn upgraded(state: usize) -> Option<usize> {
+    if state & !WAITERS == ONE_READER | UPGRADABLE {
+        Some(state & WAITERS | WRITER)
+    } else {
+        None
+    }
+}
+
+impl Default for RawRwLock {
+    #[inline]
+    fn default() -> RawRwLock {
+        RawRwLock::new()
+    }
+}
+
+impl Clone for RawRwLock {
+    /// Creates a new lock in an unlocked state with the same fairness, whether
+    /// or not this one is held.
+    #[inline]
+    fn clone(&self) -> RawRwLock {
+        RawRwLock::with_fairness(self.fairness)
+    }
+}
+
+impl fmt::Debug for RawRwLock {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.load(Ordering::Relaxed);
+        fmt.debug_struct("RawRwLock")
+            .field("readers", &(state / ONE_READER))
+            .field("upgradable", &(state & UPGRADABLE != 0))
+            .field("writer", &(state & WRITER != 0))
+            .field("fairness", &self.fairness)
+            .finish()
+    }
+}
+
+/// A raw reader-writer lock which an [`RwLock`](crate::RwLock) can be built on
+/// in place of [`RawRwLock`], such as one using priority inheritance.
+///
+/// A lock built on another raw lock with
+/// [`RwLock::with_raw`](crate::RwLock::with_raw) is instrumented like any
+/// other, but only offers the core of the API: shared and exclusive access,
+/// blocking or not, and what is built on it alone, such as
+/// [`with_write`](crate::RwLock::with_write),
+/// [`replace`](crate::RwLock::replace)
+/// or [`read_map`](crate::RwLock::read_map). The rest, such as upgradable
+/// access and timed acquisitions, relies on [`RawRwLock`] itself. Such a lock
+/// can be cloned if the raw lock can, and its clone should start unlocked.
+///
+/// # Safety
+///
+/// Exclusive access must exclude any other access, and shared access must
+/// exclude exclusive access, from the moment a method acquiring it returns, or
+/// returns `true`, until the matching unlock method is called. The lock must
+/// support being released by another thread than the one which acquired it,
+/// as guards can be sent to other threads with the `send-guard` feature,
+/// which any crate in the build may enable.
+pub unsafe trait RwLockBackend {
+    /// Acquires shared access, blocking the current thread until it is able to
+    /// do so.
+    fn lock_shared(&self);
+
+    /// Attempts to acquire shared access without blocking, returning `true` if
+    /// it was acquired.
+    fn try_lock_shared(&self) -> bool;
+
+    /// Releases shared access.
+    ///
+    /// # Safety
+    ///
+    /// Shared access must be held, and the caller must be entitled to release
+    /// it.
+    unsafe fn unlock_shared(&self);
+
+    /// Acquires exclusive access, blocking the current thread until it is able
+    /// to do so.
+    fn lock_exclusive(&self);
+
+    /// Attempts to acquire exclusive access without blocking, returning `true`
+    /// if it was acquired.
+    fn try_lock_exclusive(&self) -> bool;
+
+    /// Releases exclusive access.
+    ///
+    /// # Safety
+    ///
+    /// Exclusive access must be held, and the caller must be entitled to
+    /// release it.
+    unsafe fn unlock_exclusive(&self);
+}
+
+// SAFETY: the methods forward to those of `RawRwLock`, which exclude as
+// required.
+unsafe impl RwLockBackend for RawRwLock {
+    #[inline]
+    fn lock_shared(&self) {
+        RawRwLock::lock_shared(self);
+    }
+
+    #[inline]
+    fn try_lock_shared(&self) -> bool {
+        RawRwLock::try_lock_shared(self)
+    }
+
+    #[inline]
+    unsafe fn unlock_shared(&self) {
+        RawRwLock::unlock_shared(self);
+    }
+
+    #[inline]
+    fn lock_exclusive(&self) {
+        RawRwLock::lock_exclusive(self);
+    }
+
+    #[inline]
+    fn try_lock_exclusive(&self) -> bool {
+        RawRwLock::try_lock_exclusive(self)
+    }
+
+    #[inline]
+    unsafe fn unlock_exclusive(&self) {
+        RawRwLock::unlock_exclusive(self);
+    }
+}
+
+/// A guard holding shared access to a [`RawRwLock`], releasing it when
+/// dropped.
+#[must_use]
+pub struct RawRwLockReadGuard<'a> {
+    rwlock: &'a RawRwLock,
+}
+
+impl<'a> RawRwLockReadGuard<'a> {
+    /// Returns the lock this guard holds.
+    #[inline]
+    pub fn rwlock(&self) -> &'a RawRwLock {
+        self.rwlock
+    }
+}
+
+impl Drop for RawRwLockReadGuard<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: the guard holds shared access.
+        unsafe { self.rwlock.unlock_shared() };
+    }
+}
+
+impl fmt::Debug for RawRwLockReadGuard<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("RawRwLockReadGuard")
+            .finish_non_exhaustive()
+    }
+}
+
+/// A guard holding upgradable access to a [`RawRwLock`], releasing it when
+/// dropped.
+#[must_use]
+pub struct RawRwLockUpgradableGuard<'a> {
+    rwlock: &'a RawRwLock,
+}
+
+impl<'a> RawRwLockUpgradableGuard<'a> {
+    /// Returns the lock this guard holds.
+    #[inline]
+    pub fn rwlock(&self) -> &'a RawRwLock {
+        self.rwlock
+    }
+
+    /// Turns the upgradable access into exclusive access, blocking the current
+    /// thread until the other readers have released the lock.
+    #[inline]
+    pub fn upgrade(self) -> RawRwLockWriteGuard<'a> {
+        let rwlock = self.rwlock;
+        mem::forget(self);
+        // SAFETY: the guard held upgradable access, and was forgotten.
+        unsafe { rwlock.upgrade() };
+        RawRwLockWriteGuard { rwlock }
+    }
+
+    /// Attempts to turn the upgradable access into exclusive access without
+    /// blocking, returning the guard back if other readers hold the lock.
+    #[inline]
+    pub fn try_upgrade(self) -> Result<RawRwLockWriteGuard<'a>, RawRwLockUpgradableGuard<'a>> {
+        // SAFETY: the guard holds upgradable access, and is forgotten if it is
+        // turned into exclusive access.
+        if unsafe { self.rwlock.try_upgrade() } {
+            let rwlock = self.rwlock;
+            mem::forget(self);
+            Ok(RawRwLockWriteGuard { rwlock })
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Attempts to turn the upgradable access into exclusive access, blocking
+    /// the current thread for at most `timeout` until the other readers have
+    /// released the lock, and returning the guard back if it times out.
+    #[inline]
+    pub fn try_upgrade_for(
+        self,
+        timeout: Duration,
+    ) -> Result<RawRwLockWriteGuard<'a>, RawRwLockUpgradableGuard<'a>> {
+        // SAFETY: as in `try_upgrade`.
+        if unsafe { self.rwlock.try_upgrade_for(timeout) } {
+            let rwlock = self.rwlock;
+            mem::forget(self);
+            Ok(RawRwLockWriteGuard { rwlock })
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Turns the upgradable access into shared access, letting another thread
+    /// acquire upgradable access.
+    #[inline]
+    pub fn downgrade(self) -> RawRwLockReadGuard<'a> {
+        let rwlock = self.rwlock;
+        mem::forget(self);
+        // SAFETY: the guard held upgradable access, and was forgotten.
+        unsafe { rwlock.downgrade_upgradable() };
+        RawRwLockReadGuard { rwlock }
+    }
+}
+
+impl Drop for RawRwLockUpgradableGuard<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: the guard holds upgradable access.
+        unsafe { self.rwlock.unlock_upgradable() };
+    }
+}
+
+impl fmt::Debug for RawRwLockUpgradableGuard<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("RawRwLockUpgradableGuard")
+            .finish_non_exhaustive()
+    }
+}
+
+/// A guard holding exclusive access to a [`RawRwLock`], releasing it when
+/// dropped.
+#[must_use]
+pub struct RawRwLockWriteGuard<'a> {
+    rwlock: &'a RawRwLock,
+}
+
+impl<'a> RawRwLockWriteGuard<'a> {
+    /// Returns the lock this guard holds.
+    #[inline]
+    pub fn rwlock(&self) -> &'a RawRwLock {
+        self.rwlock
+    }
+
+    /// Turns the exclusive access into shared access, letting other readers in
+    /// without releasing the lock in between.
+    #[inline]
+    pub fn downgrade(self) -> RawRwLockReadGuard<'a> {
+        let rwlock = self.rwlock;
+        mem::forget(self);
+        // SAFETY: the guard held exclusive access, and was forgotten.
+        unsafe { rwlock.downgrade() };
+        RawRwLockReadGuard { rwlock }
+    }
+
+    /// Turns the exclusive access into upgradable access, letting readers in
+    /// again without giving other writers a chance to acquire the lock.
+    ///
+    /// Unlike [`downgrade`](RawRwLockWriteGuard::downgrade), the returned guard
+    /// can be upgraded again later without queueing up behind other writers.
+    #[inline]
+    pub fn downgrade_to_upgradable(self) -> RawRwLockUpgradableGuard<'a> {
+        let rwlock = self.rwlock;
+        mem::forget(self);
+        // SAFETY: the guard held exclusive access, and was forgotten.
+        unsafe { rwlock.downgrade_to_upgradable() };
+        RawRwLockUpgradableGuard { rwlock }
+    }
+}
+
+impl Drop for RawRwLockWriteGuard<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: the guard holds exclusive access.
+        unsafe { self.rwlock.unlock_exclusive() };
+    }
+}
+
+impl fmt::Debug for RawRwLockWriteGuard<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("RawRwLockWriteGuard")
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicPtr, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use crate::Mutex;
+
+/// A shared value which readers take snapshots of without waiting, and which
+/// writers replace as a whole.
+///
+/// [`load`](ReadMostly::load) never blocks nor contends with other readers: it
+/// only touches a couple of atomic counters to clone the current `Arc`, which
+/// stays valid however long the reader keeps it. Writers are serialized by a
+/// mutex of the cell, so that [`update`](ReadMostly::update) can build the new
+/// value from the current one without losing a concurrent update. Publishing a
+/// value waits for the loads already in progress to finish cloning the value it
+/// replaces, which takes no longer than a few instructions per load.
+pub struct ReadMostly<T> {
+    // Owns one strong reference to the current value.
+    value: AtomicPtr<T>,
+    // The number of loads in progress in each of the two reader slots, and the
+    // epoch whose parity selects the slot new loads enter.
+    readers: [AtomicUsize; 2],
+    epoch: AtomicUsize,
+    writer: Mutex<()>,
+}
+
+// SAFETY: the cell hands out `Arc<T>`s to other threads.
+unsafe impl<T: Send + Sync> Send for ReadMostly<T> {}
+unsafe impl<T: Send + Sync> Sync for ReadMostly<T> {}
+
+impl<T> ReadMostly<T> {
+    /// Creates a new cell holding `value`.
+    #[inline]
+    pub fn new(value: T) -> ReadMostly<T> {
+        ReadMostly::from_arc(Arc::new(value))
+    }
+
+    /// Creates a new cell holding the value `value` points to.
+    pub fn from_arc(value: Arc<T>) -> ReadMostly<T> {
+        ReadMostly {
+            value: AtomicPtr::new(Arc::into_raw(value) as *mut T),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            epoch: AtomicUsize::new(0),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Returns a snapshot of the current value.
+    #[inline]
+    pub fn load(&self) -> Arc<T> {
+        let slot = &self.readers[self.epoch.load(Ordering::SeqCst) & 1];
+        slot.fetch_add(1, Ordering::SeqCst);
+        let ptr = self.value.load(Ordering::SeqCst);
+        // SAFETY: a store only releases the value it replaced once both slots
+        // have drained, and this load is counted in one of them.
+        let value = unsafe {
+            Arc::increment_strong_count(ptr);
+            Arc::from_raw(ptr)
+        };
+        slot.fetch_sub(1, Ordering::SeqCst);
+        value
+    }
+
+    /// Replaces the value.
+    #[inline]
+    pub fn store(&self, value: T) {
+        self.swap(value);
+    }
+
+    /// Replaces the value, returning the one it replaced.
+    pub fn swap(&self, value: T) -> Arc<T> {
+        self.swap_arc(Arc::new(value))
+    }
+
+    /// Replaces the value with the one `value` points to, returning the one it
+    /// replaced.
+    pub fn swap_arc(&self, value: Arc<T>) -> Arc<T> {
+        let _writer = self.writer.lock();
+        self.publish(value)
+    }
+
+    /// Replaces the value with what `f` builds from it, returning the one it
+    /// replaced.
+    ///
+    /// Other writers wait while `f` runs, so none of them can replace the
+    /// value in the meantime, while readers keep loading the current value
+    /// until the new one is published.
+    pub fn update(&self, f: impl FnOnce(&T) -> T) -> Arc<T> {
+        let _writer = self.writer.lock();
+        // Stores are serialized, so the current value stays put while `f`
+        // runs.
+        let current = self.load();
+        self.publish(Arc::new(f(&current)))
+    }
+
+    // Publishes `value`, and returns the reference to the old value the cell
+    // owned once no load can still be about to clone it. The caller holds the
+    // writer lock.
+    fn publish(&self, value: Arc<T>) -> Arc<T> {
+        let new = Arc::into_raw(value) as *mut T;
+        let old = self.value.swap(new, Ordering::SeqCst);
+        // Loads entering a slot after the swap see the new value, so flipping
+        // the epoch twice and draining the slot left behind each time waits
+        // out every load which may have seen the old one, while new loads are
+        // directed to the other slot and cannot hold the wait up.
+        for _ in 0..2 {
+            let epoch = self.epoch.fetch_add(1, Ordering::SeqCst);
+            let slot = &self.readers[epoch & 1];
+            while slot.load(Ordering::SeqCst) != 0 {
+                thread::yield_now();
+            }
+        }
+        // SAFETY: the cell owned a strong reference to the old value.
+        unsafe { Arc::from_raw(old) }
+    }
+
+    /// Consumes the cell, returning the current value.
+    #[inline]
+    pub fn into_arc(self) -> Arc<T> {
+        self.load()
+    }
+}
+
+impl<T> Drop for ReadMostly<T> {
+    fn drop(&mut self) {
+        // SAFETY: the cell owns a strong reference to the current value.
+        drop(unsafe { Arc::from_raw(*self.value.get_mut()) });
+    }
+}
+
+impl<T: Default> Default for ReadMostly<T> {
+    #[inline]
+    fn default() -> ReadMostly<T> {
+        ReadMostly::new(T::default())
+    }
+}
+
+impl<T> From<T> for ReadMostly<T> {
+    #[inline]
+    fn from(t: T) -> ReadMostly<T> {
+        ReadMostly::new(t)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ReadMostly<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("ReadMostly")
+            .field("value", &self.load())
+            .finish()
+    }
+}
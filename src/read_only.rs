@@ -0,0 +1,116 @@
+use std::{fmt, sync::Arc};
+
+use crate::{RwLock, RwLockReadGuard, TryLockResult};
+
+impl<T: ?Sized> RwLock<T> {
+    /// Returns a handle which can only acquire shared access to the lock.
+    ///
+    /// Code given the handle instead of the lock itself cannot take the write
+    /// lock, which is checked at compile time.
+    #[inline]
+    pub fn read_handle(&self) -> ReadOnly<'_, T> {
+        ReadOnly { lock: self }
+    }
+
+    /// Like [`read_handle`](RwLock::read_handle), but returns a handle which
+    /// keeps the lock alive through `self` instead of borrowing it.
+    #[inline]
+    pub fn read_handle_owned(self: &Arc<Self>) -> ReadHandle<T> {
+        ReadHandle { lock: self.clone() }
+    }
+}
+
+/// A handle to an [`RwLock`] which can only acquire shared access.
+///
+/// It is created with [`RwLock::read_handle`], and is as cheap to copy as a
+/// reference.
+pub struct ReadOnly<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+impl<'a, T: ?Sized> ReadOnly<'a, T> {
+    /// Like [`RwLock::read`].
+    #[inline]
+    pub fn read(&self) -> RwLockReadGuard<'a, T> {
+        self.lock.read()
+    }
+
+    /// Like [`RwLock::try_read`].
+    #[inline]
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'a, T>> {
+        self.lock.try_read()
+    }
+}
+
+impl<T: ?Sized> Clone for ReadOnly<'_, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for ReadOnly<'_, T> {}
+
+impl<'a, T: ?Sized> From<&'a RwLock<T>> for ReadOnly<'a, T> {
+    #[inline]
+    fn from(lock: &'a RwLock<T>) -> ReadOnly<'a, T> {
+        lock.read_handle()
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ReadOnly<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.lock, fmt)
+    }
+}
+
+/// A handle to an [`RwLock`] in an `Arc` which can only acquire shared access.
+///
+/// It is created with [`RwLock::read_handle_owned`]. Unlike [`ReadOnly`], it
+/// has no lifetime, so it can be stored in a long-lived struct or sent to
+/// another thread.
+pub struct ReadHandle<T: ?Sized> {
+    lock: Arc<RwLock<T>>,
+}
+
+impl<T: ?Sized> ReadHandle<T> {
+    /// Like [`RwLock::read`].
+    #[inline]
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.lock.read()
+    }
+
+    /// Like [`RwLock::try_read`].
+    #[inline]
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        self.lock.try_read()
+    }
+
+    /// Returns a borrowed handle to the same lock.
+    #[inline]
+    pub fn as_read_only(&self) -> ReadOnly<'_, T> {
+        self.lock.read_handle()
+    }
+}
+
+impl<T: ?Sized> Clone for ReadHandle<T> {
+    #[inline]
+    fn clone(&self) -> ReadHandle<T> {
+        ReadHandle {
+            lock: self.lock.clone(),
+        }
+    }
+}
+
+impl<T: ?Sized> From<Arc<RwLock<T>>> for ReadHandle<T> {
+    #[inline]
+    fn from(lock: Arc<RwLock<T>>) -> ReadHandle<T> {
+        ReadHandle { lock }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ReadHandle<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.lock, fmt)
+    }
+}
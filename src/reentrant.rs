@@ -0,0 +1,357 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    marker::PhantomData,
+    ops::Deref,
+    panic::{RefUnwindSafe, UnwindSafe},
+    thread::{self, ThreadId},
+};
+
+use crate::{Condvar, LockOperation, Mutex, TryLockError, TryLockResult};
+
+/// A lock which can be acquired several times by the thread holding it.
+///
+/// Since nested acquisitions hand out guards to the same value, a guard only
+/// grants access to a `RefCell<T>`. Borrowing it mutably while another borrow
+/// is alive panics, which only happens if the value is genuinely aliased, as
+/// opposed to merely locked again further up the stack.
+///
+/// If the value never needs to be mutated, or only through interior
+/// mutability, [`ReentrantMutex`] hands out `&T` directly.
+pub struct ReentrantLock<T: ?Sized> {
+    raw: RawReentrant,
+    data: RefCell<T>,
+}
+
+// The ownership state shared by `ReentrantLock` and `ReentrantMutex`.
+struct RawReentrant {
+    state: Mutex<State>,
+    unlocked: Condvar,
+}
+
+struct State {
+    owner: Option<ThreadId>,
+    count: usize,
+}
+
+// SAFETY: the `RefCell` is only accessed through guards, which are confined to
+// the single thread holding the lock.
+unsafe impl<T: ?Sized + Send> Sync for ReentrantLock<T> {}
+
+impl<T: ?Sized + UnwindSafe> UnwindSafe for ReentrantLock<T> {}
+impl<T: ?Sized + RefUnwindSafe> RefUnwindSafe for ReentrantLock<T> {}
+
+impl<T> ReentrantLock<T> {
+    /// Creates a new lock in an unlocked state.
+    #[inline]
+    pub const fn new(t: T) -> ReentrantLock<T> {
+        ReentrantLock {
+            raw: RawReentrant::new(),
+            data: RefCell::new(t),
+        }
+    }
+
+    /// Consumes the lock, returning the underlying data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl RawReentrant {
+    #[inline]
+    const fn new() -> RawReentrant {
+        RawReentrant {
+            state: Mutex::new(State {
+                owner: None,
+                count: 0,
+            }),
+            unlocked: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) {
+        let me = thread::current().id();
+        let mut state = self.state.lock();
+        if state.owner != Some(me) {
+            while state.owner.is_some() {
+                state = self.unlocked.wait(state);
+            }
+            state.owner = Some(me);
+        }
+        state.count += 1;
+    }
+
+    fn try_lock(&self) -> bool {
+        let me = thread::current().id();
+        let mut state = self.state.lock();
+        match state.owner {
+            Some(owner) if owner != me => return false,
+            _ => state.owner = Some(me),
+        }
+        state.count += 1;
+        true
+    }
+
+    fn unlock(&self) {
+        let mut state = self.state.lock();
+        state.count -= 1;
+        if state.count == 0 {
+            state.owner = None;
+            drop(state);
+            self.unlocked.notify_one();
+        }
+    }
+}
+
+impl<T: ?Sized> ReentrantLock<T> {
+    /// Acquires the lock, blocking the current thread until it is able to do
+    /// so.
+    ///
+    /// If the current thread already holds the lock, this returns immediately.
+    pub fn lock(&self) -> ReentrantLockGuard<'_, T> {
+        self.raw.lock();
+        ReentrantLockGuard::new(self)
+    }
+
+    /// Attempts to acquire the lock without blocking.
+    ///
+    /// If the current thread already holds the lock, this succeeds.
+    pub fn try_lock(&self) -> TryLockResult<ReentrantLockGuard<'_, T>> {
+        if self.raw.try_lock() {
+            Ok(ReentrantLockGuard::new(self))
+        } else {
+            Err(TryLockError::new(LockOperation::Lock))
+        }
+    }
+
+    /// Calls `f` with shared access to the value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently mutably borrowed by this thread.
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.lock().borrow())
+    }
+
+    /// Calls `f` with exclusive access to the value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed by this thread.
+    pub fn with_mut<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.lock().borrow_mut())
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// No locking is necessary since the borrow guarantees exclusive access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default> Default for ReentrantLock<T> {
+    #[inline]
+    fn default() -> ReentrantLock<T> {
+        ReentrantLock::new(T::default())
+    }
+}
+
+impl<T> From<T> for ReentrantLock<T> {
+    #[inline]
+    fn from(t: T) -> ReentrantLock<T> {
+        ReentrantLock::new(t)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ReentrantLock<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_struct("ReentrantLock");
+        match self.try_lock() {
+            Ok(guard) => match guard.try_borrow() {
+                Ok(data) => d.field("data", &&*data),
+                Err(_) => d.field("data", &format_args!("<borrowed>")),
+            },
+            Err(_) => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+/// A guard for a [`ReentrantLock`].
+///
+/// The lock is released once every guard held by the thread has been dropped.
+#[must_use]
+pub struct ReentrantLockGuard<'a, T: ?Sized + 'a> {
+    lock: &'a ReentrantLock<T>,
+    // The lock is owned by a thread, so its guards must stay on that thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a, T: ?Sized> ReentrantLockGuard<'a, T> {
+    fn new(lock: &'a ReentrantLock<T>) -> ReentrantLockGuard<'a, T> {
+        ReentrantLockGuard {
+            lock,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for ReentrantLockGuard<'_, T> {
+    type Target = RefCell<T>;
+
+    #[inline]
+    fn deref(&self) -> &RefCell<T> {
+        &self.lock.data
+    }
+}
+
+impl<T: ?Sized> Drop for ReentrantLockGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.raw.unlock();
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ReentrantLockGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.lock.data, fmt)
+    }
+}
+
+/// A mutex which can be acquired several times by the thread holding it,
+/// handing out shared references to the value.
+///
+/// Like `std::sync::ReentrantLock`, and unlike [`ReentrantLock`], a guard
+/// dereferences to `&T`, since nested guards on the same thread alias the
+/// value. Mutation goes through interior mutability, typically a `Cell` or
+/// `RefCell` inside the mutex. As only the owning thread ever accesses the
+/// value, `T` need not be `Sync` for the mutex to be shared between threads.
+pub struct ReentrantMutex<T: ?Sized> {
+    raw: RawReentrant,
+    data: T,
+}
+
+// SAFETY: the value is only accessed through guards, which are confined to the
+// single thread holding the mutex.
+unsafe impl<T: ?Sized + Send> Sync for ReentrantMutex<T> {}
+
+impl<T: ?Sized + UnwindSafe> UnwindSafe for ReentrantMutex<T> {}
+impl<T: ?Sized + RefUnwindSafe> RefUnwindSafe for ReentrantMutex<T> {}
+
+impl<T> ReentrantMutex<T> {
+    /// Creates a new mutex in an unlocked state.
+    #[inline]
+    pub const fn new(t: T) -> ReentrantMutex<T> {
+        ReentrantMutex {
+            raw: RawReentrant::new(),
+            data: t,
+        }
+    }
+
+    /// Consumes the mutex, returning the underlying data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data
+    }
+}
+
+impl<T: ?Sized> ReentrantMutex<T> {
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so.
+    ///
+    /// If the current thread already holds the mutex, this returns
+    /// immediately.
+    pub fn lock(&self) -> ReentrantMutexGuard<'_, T> {
+        self.raw.lock();
+        ReentrantMutexGuard::new(self)
+    }
+
+    /// Attempts to acquire the mutex without blocking.
+    ///
+    /// If the current thread already holds the mutex, this succeeds.
+    pub fn try_lock(&self) -> TryLockResult<ReentrantMutexGuard<'_, T>> {
+        if self.raw.try_lock() {
+            Ok(ReentrantMutexGuard::new(self))
+        } else {
+            Err(TryLockError::new(LockOperation::Lock))
+        }
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// No locking is necessary since the borrow guarantees exclusive access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+}
+
+impl<T: Default> Default for ReentrantMutex<T> {
+    #[inline]
+    fn default() -> ReentrantMutex<T> {
+        ReentrantMutex::new(T::default())
+    }
+}
+
+impl<T> From<T> for ReentrantMutex<T> {
+    #[inline]
+    fn from(t: T) -> ReentrantMutex<T> {
+        ReentrantMutex::new(t)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ReentrantMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_struct("ReentrantMutex");
+        match self.try_lock() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(_) => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+/// A guard for a [`ReentrantMutex`].
+///
+/// The mutex is released once every guard held by the thread has been
+/// dropped.
+#[must_use]
+pub struct ReentrantMutexGuard<'a, T: ?Sized + 'a> {
+    lock: &'a ReentrantMutex<T>,
+    // See `ReentrantLockGuard`.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a, T: ?Sized> ReentrantMutexGuard<'a, T> {
+    fn new(lock: &'a ReentrantMutex<T>) -> ReentrantMutexGuard<'a, T> {
+        ReentrantMutexGuard {
+            lock,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for ReentrantMutexGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.lock.data
+    }
+}
+
+impl<T: ?Sized> Drop for ReentrantMutexGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.raw.unlock();
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ReentrantMutexGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.lock.data, fmt)
+    }
+}
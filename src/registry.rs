@@ -0,0 +1,210 @@
+//! A registry of locks whose state can be dumped on demand.
+//!
+//! Locks registered with [`register_mutex`] or [`register_rwlock`] are listed
+//! by [`dump`], along with the type of the value they protect, the threads
+//! holding them and the threads blocked acquiring them, as recorded by the
+//! bookkeeping of the `tracking` feature. The [`Dump`] it returns displays as
+//! a report meant for a log, so that a wedged process can be asked which
+//! locks it is stuck on without attaching a debugger. A dump is cheap but
+//! allocates and takes locks of its own, so a signal should be turned into a
+//! call to it from an ordinary thread rather than from the signal handler.
+//!
+//! Registration is for the lifetime of the process, which is why only locks in
+//! statics can be registered. Nothing is recorded for locks which are not.
+
+use std::{any, fmt, sync, thread::Thread};
+
+use crate::{debug, held, Mutex, RwLock};
+
+static REGISTRY: sync::Mutex<Vec<Entry>> = sync::Mutex::new(Vec::new());
+
+#[derive(Clone, Copy)]
+struct Entry {
+    name: &'static str,
+    kind: LockKind,
+    type_name: &'static str,
+    addr: usize,
+}
+
+fn register(name: &'static str, kind: LockKind, type_name: &'static str, addr: usize) {
+    REGISTRY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Entry {
+            name,
+            kind,
+            type_name,
+            addr,
+        });
+}
+
+/// Registers a `Mutex` to be included in every dump under the given name.
+pub fn register_mutex<T: ?Sized + Send>(name: &'static str, mutex: &'static Mutex<T>) {
+    register(name, LockKind::Mutex, any::type_name::<T>(), mutex.addr());
+}
+
+/// Registers an `RwLock` to be included in every dump under the given name.
+pub fn register_rwlock<T: ?Sized + Send + Sync>(name: &'static str, rwlock: &'static RwLock<T>) {
+    register(name, LockKind::RwLock, any::type_name::<T>(), rwlock.addr());
+}
+
+/// Returns the current state of every registered lock.
+///
+/// The locks are inspected one at a time while other threads keep using them,
+/// so the dump is not an atomic snapshot of all of them together.
+pub fn dump() -> Dump {
+    let registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let locks = registry
+        .into_iter()
+        .map(|entry| LockDump {
+            name: entry.name,
+            kind: entry.kind,
+            type_name: entry.type_name,
+            addr: entry.addr,
+            holders: held::holders(entry.addr)
+                .into_iter()
+                .map(|(thread, access)| Holder {
+                    thread,
+                    shared: access == held::Access::Shared,
+                })
+                .collect(),
+            waiters: held::waiters(entry.addr),
+        })
+        .collect();
+    Dump { locks }
+}
+
+/// The kind of a registered lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockKind {
+    /// A [`Mutex`].
+    Mutex,
+    /// An [`RwLock`].
+    RwLock,
+}
+
+/// The state of every registered lock, returned by [`dump`].
+///
+/// Its `Display` implementation lists the locks one per line, followed by
+/// their holders and the threads they block.
+#[derive(Debug, Clone)]
+pub struct Dump {
+    locks: Vec<LockDump>,
+}
+
+impl Dump {
+    /// Returns the state of each registered lock, in registration order.
+    pub fn locks(&self) -> &[LockDump] {
+        &self.locks
+    }
+}
+
+impl fmt::Display for Dump {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let held = self.locks.iter().filter(|lock| lock.is_held()).count();
+        writeln!(
+            fmt,
+            "antidote: {} registered lock{}, {} held",
+            self.locks.len(),
+            if self.locks.len() == 1 { "" } else { "s" },
+            held,
+        )?;
+        for lock in &self.locks {
+            let kind = match lock.kind {
+                LockKind::Mutex => "mutex",
+                LockKind::RwLock => "rwlock",
+            };
+            write!(
+                fmt,
+                "  {} '{}' at {:#x} ({}): ",
+                kind, lock.name, lock.addr, lock.type_name,
+            )?;
+            if lock.holders.is_empty() {
+                fmt.write_str("free")?;
+            } else {
+                for (i, holder) in lock.holders.iter().enumerate() {
+                    let access = if holder.shared { "shared" } else { "exclusive" };
+                    let sep = if i == 0 { "held by " } else { ", " };
+                    write!(fmt, "{}{} ({})", sep, debug::name(&holder.thread), access)?;
+                }
+            }
+            if !lock.waiters.is_empty() {
+                let waiters = lock.waiters.iter().map(debug::name).collect::<Vec<_>>();
+                write!(fmt, ", blocking {}", waiters.join(", "))?;
+            }
+            writeln!(fmt)?;
+        }
+        Ok(())
+    }
+}
+
+/// The state of a registered lock within a [`Dump`].
+#[derive(Debug, Clone)]
+pub struct LockDump {
+    name: &'static str,
+    kind: LockKind,
+    type_name: &'static str,
+    addr: usize,
+    holders: Vec<Holder>,
+    waiters: Vec<Thread>,
+}
+
+impl LockDump {
+    /// Returns the name the lock was registered with.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns whether the lock is a `Mutex` or an `RwLock`.
+    pub fn kind(&self) -> LockKind {
+        self.kind
+    }
+
+    /// Returns the name of the type of the value the lock protects, as given by
+    /// `std::any::type_name`.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Returns the address of the lock.
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+
+    /// Returns `true` if any thread held the lock.
+    pub fn is_held(&self) -> bool {
+        !self.holders.is_empty()
+    }
+
+    /// Returns the threads which held the lock.
+    ///
+    /// A thread holding the lock through several guards at once is listed
+    /// once.
+    pub fn holders(&self) -> &[Holder] {
+        &self.holders
+    }
+
+    /// Returns the threads which were blocked acquiring the lock.
+    pub fn waiters(&self) -> &[Thread] {
+        &self.waiters
+    }
+}
+
+/// A thread holding a lock within a [`Dump`].
+#[derive(Debug, Clone)]
+pub struct Holder {
+    thread: Thread,
+    shared: bool,
+}
+
+impl Holder {
+    /// Returns the thread holding the lock.
+    pub fn thread(&self) -> &Thread {
+        &self.thread
+    }
+
+    /// Returns `true` if the thread held the lock for shared access only.
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+}
@@ -0,0 +1,349 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{clock, Closed, Condvar, Mutex, MutexGuard};
+
+/// A counting semaphore.
+///
+/// A semaphore holds a number of permits. Acquiring permits blocks until
+/// enough of them are available, and they are returned to the semaphore when
+/// the [`SemaphorePermit`] holding them is dropped.
+///
+/// Permits can be acquired several at a time, which models weighted resources
+/// such as memory budgets directly.
+///
+/// A semaphore created with [`new`](Semaphore::new) is unfair: whichever
+/// waiter notices first that enough permits are available takes them, which
+/// maximizes throughput but can starve some threads under bursty load, in
+/// particular those acquiring many permits at once. One created with
+/// [`new_fair`](Semaphore::new_fair) instead hands out permits in the order
+/// they were asked for.
+///
+/// A semaphore can be [closed](Semaphore::close) to shut down the threads
+/// using it: every thread waiting for permits is then woken with a [`Closed`]
+/// error, and so is every later attempt to acquire permits.
+pub struct Semaphore {
+    state: Mutex<State>,
+    released: Condvar,
+}
+
+struct State {
+    permits: usize,
+    fair: bool,
+    closed: bool,
+    // The tickets of the threads waiting for permits, in arrival order. Only
+    // used by fair semaphores.
+    queue: Vec<u64>,
+    next_ticket: u64,
+}
+
+impl Semaphore {
+    /// Creates a new unfair semaphore with the given number of permits.
+    #[inline]
+    pub const fn new(permits: usize) -> Semaphore {
+        Semaphore::with_fairness(permits, false)
+    }
+
+    /// Creates a new fair semaphore with the given number of permits.
+    ///
+    /// Permits are handed out in first-in, first-out order: a thread asking
+    /// for permits never overtakes one which asked before it, even if there
+    /// are enough permits for it but not for the earlier thread. This also
+    /// applies to [`try_acquire`](Semaphore::try_acquire), which fails while
+    /// other threads are waiting.
+    #[inline]
+    pub const fn new_fair(permits: usize) -> Semaphore {
+        Semaphore::with_fairness(permits, true)
+    }
+
+    const fn with_fairness(permits: usize, fair: bool) -> Semaphore {
+        Semaphore {
+            state: Mutex::new(State {
+                permits,
+                fair,
+                closed: false,
+                queue: Vec::new(),
+                next_ticket: 0,
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Returns `true` if the semaphore was created with
+    /// [`new_fair`](Semaphore::new_fair).
+    #[inline]
+    pub fn is_fair(&self) -> bool {
+        self.state.lock().fair
+    }
+
+    /// Acquires a permit, blocking the current thread until one is available.
+    ///
+    /// Returns an error if the semaphore is closed, before or while waiting.
+    #[inline]
+    pub fn acquire(&self) -> Result<SemaphorePermit<'_>, Closed> {
+        self.acquire_many(1)
+    }
+
+    /// Acquires a permit, blocking the current thread for at most `timeout`.
+    ///
+    /// Returns [`TryAcquireError::NoPermits`] if no permit became available in
+    /// time, and [`TryAcquireError::Closed`] if the semaphore is closed, before
+    /// or while waiting.
+    #[inline]
+    pub fn acquire_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.acquire_many_timeout(1, timeout)
+    }
+
+    /// Attempts to acquire a permit without blocking.
+    #[inline]
+    pub fn try_acquire(&self) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.try_acquire_many(1)
+    }
+
+    /// Acquires `n` permits at once, blocking the current thread until that
+    /// many are available.
+    ///
+    /// Returns an error if the semaphore is closed, before or while waiting.
+    /// If fewer than `n` permits will ever be available, this blocks until the
+    /// semaphore is closed.
+    pub fn acquire_many(&self, n: usize) -> Result<SemaphorePermit<'_>, Closed> {
+        // Without a deadline, only closing the semaphore makes this fail.
+        self.acquire_many_until_internal(n, None)
+            .map_err(|_| Closed(()))
+    }
+
+    /// Acquires `n` permits at once, blocking the current thread for at most
+    /// `timeout`.
+    ///
+    /// Fails like [`acquire_timeout`](Semaphore::acquire_timeout). A thread
+    /// waiting on a fair semaphore which gives up lets the threads behind it
+    /// go ahead.
+    pub fn acquire_many_timeout(
+        &self,
+        n: usize,
+        timeout: Duration,
+    ) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        // A deadline too far in the future to represent is treated as no
+        // deadline at all.
+        self.acquire_many_until_internal(n, clock::now().checked_add(timeout))
+    }
+
+    fn acquire_many_until_internal(
+        &self,
+        n: usize,
+        deadline: Option<Instant>,
+    ) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        let mut state = self.state.lock();
+        if !state.fair {
+            while state.permits < n && !state.closed {
+                let (new, timed_out) = self.wait(state, deadline);
+                state = new;
+                if timed_out && state.permits < n && !state.closed {
+                    return Err(TryAcquireError::NoPermits);
+                }
+            }
+            if state.closed {
+                return Err(TryAcquireError::Closed);
+            }
+            state.permits -= n;
+            return Ok(SemaphorePermit { sem: self, n });
+        }
+
+        if state.closed {
+            return Err(TryAcquireError::Closed);
+        }
+        if state.queue.is_empty() && state.permits >= n {
+            state.permits -= n;
+            return Ok(SemaphorePermit { sem: self, n });
+        }
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.queue.push(ticket);
+        while (state.queue[0] != ticket || state.permits < n) && !state.closed {
+            let (new, timed_out) = self.wait(state, deadline);
+            state = new;
+            if timed_out && (state.queue[0] != ticket || state.permits < n) && !state.closed {
+                let first = state.queue[0] == ticket;
+                state.queue.retain(|&t| t != ticket);
+                if first && !state.queue.is_empty() && state.permits > 0 {
+                    // The next thread in line may be satisfied by what is left.
+                    drop(state);
+                    self.released.notify_all();
+                }
+                return Err(TryAcquireError::NoPermits);
+            }
+        }
+        if state.closed {
+            state.queue.retain(|&t| t != ticket);
+            return Err(TryAcquireError::Closed);
+        }
+        state.queue.remove(0);
+        state.permits -= n;
+        if !state.queue.is_empty() && state.permits > 0 {
+            // The next thread in line may be satisfied by what is left.
+            drop(state);
+            self.released.notify_all();
+        }
+        Ok(SemaphorePermit { sem: self, n })
+    }
+
+    // Waits for permits to be released, returning whether `deadline` passed.
+    fn wait<'a>(
+        &self,
+        state: MutexGuard<'a, State>,
+        deadline: Option<Instant>,
+    ) -> (MutexGuard<'a, State>, bool) {
+        match deadline {
+            Some(deadline) => {
+                let (state, result) = self.released.wait_until(state, deadline);
+                (state, result.timed_out())
+            }
+            None => (self.released.wait(state), false),
+        }
+    }
+
+    /// Attempts to acquire `n` permits at once without blocking.
+    pub fn try_acquire_many(&self, n: usize) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        let mut state = self.state.lock();
+        if state.closed {
+            return Err(TryAcquireError::Closed);
+        }
+        if state.permits < n || (state.fair && !state.queue.is_empty()) {
+            return Err(TryAcquireError::NoPermits);
+        }
+        state.permits -= n;
+        Ok(SemaphorePermit { sem: self, n })
+    }
+
+    /// Closes the semaphore, waking every thread waiting for permits.
+    ///
+    /// Those threads, and every later attempt to acquire permits, fail with a
+    /// [`Closed`] error. Permits already acquired stay valid and are returned
+    /// to the semaphore as usual. Closing a semaphore twice has no effect.
+    pub fn close(&self) {
+        let mut state = self.state.lock();
+        if state.closed {
+            return;
+        }
+        state.closed = true;
+        drop(state);
+        self.released.notify_all();
+    }
+
+    /// Returns `true` if the semaphore has been closed.
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().closed
+    }
+
+    /// Adds `n` permits to the semaphore.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of available permits would overflow.
+    #[doc(alias = "release")]
+    pub fn add_permits(&self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        let mut state = self.state.lock();
+        state.permits = state
+            .permits
+            .checked_add(n)
+            .expect("semaphore permit count overflowed");
+        drop(state);
+        // Waiters may need differing numbers of permits, so any of them could
+        // now be able to proceed.
+        self.released.notify_all();
+    }
+
+    /// Removes up to `n` of the currently available permits from the
+    /// semaphore, returning the number removed.
+    pub fn forget_permits(&self, n: usize) -> usize {
+        let mut state = self.state.lock();
+        let n = n.min(state.permits);
+        state.permits -= n;
+        n
+    }
+
+    /// Returns the number of permits currently available.
+    #[inline]
+    pub fn available_permits(&self) -> usize {
+        self.state.lock().permits
+    }
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock();
+        fmt.debug_struct("Semaphore")
+            .field("permits", &state.permits)
+            .field("fair", &state.fair)
+            .field("closed", &state.closed)
+            .finish()
+    }
+}
+
+/// Permits acquired from a [`Semaphore`].
+///
+/// The permits are returned to the semaphore when this is dropped.
+#[must_use]
+pub struct SemaphorePermit<'a> {
+    sem: &'a Semaphore,
+    n: usize,
+}
+
+impl SemaphorePermit<'_> {
+    /// Returns the number of permits held.
+    #[inline]
+    pub fn num_permits(&self) -> usize {
+        self.n
+    }
+
+    /// Drops the permits without returning them to the semaphore, permanently
+    /// reducing the number of permits it holds.
+    #[doc(alias = "forget_permit")]
+    #[inline]
+    pub fn forget(mut self) {
+        self.n = 0;
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        self.sem.add_permits(self.n);
+    }
+}
+
+impl fmt::Debug for SemaphorePermit<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("SemaphorePermit")
+            .field("permits", &self.n)
+            .finish()
+    }
+}
+
+/// The error returned by [`Semaphore::try_acquire`],
+/// [`Semaphore::acquire_timeout`] and their variants acquiring several permits.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum TryAcquireError {
+    /// The semaphore has been closed.
+    Closed,
+    /// Not enough permits are available, or did not become available in time.
+    NoPermits,
+}
+
+impl fmt::Display for TryAcquireError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TryAcquireError::Closed => fmt.write_str("semaphore was closed"),
+            TryAcquireError::NoPermits => fmt.write_str("not enough permits are available"),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    mem::MaybeUninit,
+    ptr,
+    sync::atomic::{self, AtomicUsize, Ordering},
+};
+
+use crate::spin::Backoff;
+
+/// A sequence lock, for small `Copy` values read much more often than they are
+/// written.
+///
+/// Readers never write to the lock: they copy the value out and check that no
+/// write started or finished meanwhile, retrying if one did. Reading therefore
+/// costs a couple of loads when there is no writer, and any number of readers
+/// can read at once without taking cache lines away from each other, which is
+/// where an [`RwLock`](crate::RwLock) read lock becomes expensive. Writers are
+/// exclusive, spin while they wait for each other, and are never held up by
+/// readers, so readers retry for as long as writes keep coming.
+///
+/// Values are copied out rather than borrowed, so `T` should be small, such as
+/// a few counters or timestamps.
+pub struct SeqLock<T: Copy> {
+    // Odd while a write is in progress, and bumped by two by every write.
+    seq: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: the value is only ever copied out to readers, and written by one
+// writer at a time.
+unsafe impl<T: Copy + Send> Sync for SeqLock<T> {}
+
+impl<T: Copy> SeqLock<T> {
+    /// Creates a new sequence lock holding `t`.
+    #[inline]
+    pub const fn new(t: T) -> SeqLock<T> {
+        SeqLock {
+            seq: AtomicUsize::new(0),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Consumes the lock, returning the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    /// Returns a copy of the value.
+    ///
+    /// This retries while a write is in progress.
+    #[inline]
+    pub fn read(&self) -> T {
+        let mut backoff = Backoff::new();
+        loop {
+            if let Some(t) = self.try_read() {
+                return t;
+            }
+            backoff.spin();
+        }
+    }
+
+    /// Returns a copy of the value, or `None` if a write was in progress.
+    ///
+    /// Unlike [`read`](SeqLock::read), this never waits.
+    #[inline]
+    pub fn try_read(&self) -> Option<T> {
+        let seq = self.seq.load(Ordering::Acquire);
+        if seq & 1 != 0 {
+            return None;
+        }
+        // SAFETY: a writer may be writing the value concurrently, so the copy
+        // is kept uninitialized until the sequence number shows it was not
+        // torn.
+        let t = unsafe { ptr::read_volatile(self.data.get() as *const MaybeUninit<T>) };
+        atomic::fence(Ordering::Acquire);
+        if self.seq.load(Ordering::Relaxed) != seq {
+            return None;
+        }
+        // SAFETY: no write overlapped the copy.
+        Some(unsafe { t.assume_init() })
+    }
+
+    /// Replaces the value.
+    #[inline]
+    pub fn write(&self, t: T) {
+        self.update(|value| *value = t);
+    }
+
+    /// Calls `f` with a mutable reference to the value, waiting for other
+    /// writers first, and returns its result.
+    ///
+    /// Readers retry until `f` returns, so it should be short. If `f` panics,
+    /// readers see whatever it had written by then.
+    pub fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut backoff = Backoff::new();
+        let seq = loop {
+            let seq = self.seq.load(Ordering::Relaxed);
+            if seq & 1 == 0
+                && self
+                    .seq
+                    .compare_exchange_weak(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                break seq;
+            }
+            backoff.spin();
+        };
+        // Keeps the writes to the value from being seen before the sequence
+        // number saying a write is in progress.
+        atomic::fence(Ordering::Release);
+
+        let _end = EndWrite {
+            seq: &self.seq,
+            next: seq.wrapping_add(2),
+        };
+        // SAFETY: the odd sequence number excludes other writers, and readers
+        // only keep what they copy if no write overlapped it.
+        f(unsafe { &mut *self.data.get() })
+    }
+
+    /// Returns a mutable reference to the value.
+    ///
+    /// No locking is necessary since the borrow guarantees exclusive access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+// Ends a write when it is dropped, even if the writer panicked, so readers do
+// not retry forever.
+struct EndWrite<'a> {
+    seq: &'a AtomicUsize,
+    next: usize,
+}
+
+impl Drop for EndWrite<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        self.seq.store(self.next, Ordering::Release);
+    }
+}
+
+impl<T: Copy> From<T> for SeqLock<T> {
+    #[inline]
+    fn from(t: T) -> SeqLock<T> {
+        SeqLock::new(t)
+    }
+}
+
+impl<T: Copy + Default> Default for SeqLock<T> {
+    #[inline]
+    fn default() -> SeqLock<T> {
+        SeqLock::new(T::default())
+    }
+}
+
+impl<T: Copy + fmt::Debug> fmt::Debug for SeqLock<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("SeqLock")
+            .field("data", &self.read())
+            .finish()
+    }
+}
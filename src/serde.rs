@@ -0,0 +1,31 @@
+use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Mutex, RwLock};
+
+impl<T: ?Sized + Serialize> Serialize for Mutex<T> {
+    /// Serializes the value while holding the lock.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.lock().serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Mutex<T> {
+    /// Deserializes a value into a new, unlocked mutex.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Mutex<T>, D::Error> {
+        T::deserialize(deserializer).map(Mutex::new)
+    }
+}
+
+impl<T: ?Sized + Serialize> Serialize for RwLock<T> {
+    /// Serializes the value while holding shared access.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.read().serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for RwLock<T> {
+    /// Deserializes a value into a new, unlocked lock.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<RwLock<T>, D::Error> {
+        T::deserialize(deserializer).map(RwLock::new)
+    }
+}
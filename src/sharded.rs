@@ -0,0 +1,434 @@
+//! Locks split into a fixed number of shards.
+//!
+//! [`Striped`] spreads unrelated keys over several mutexes, which
+//! [`ShardedMutexMap`] builds a concurrent hash map on, and [`ShardedRwLock`]
+//! spreads the readers of a single value over several locks.
+//! The number of shards is a const parameter, so each instance is a single
+//! inline array whose size is chosen at compile time: small targets can use a
+//! handful of shards, while heavily contended servers can use many more. The
+//! default is [`DEFAULT_SHARDS`].
+
+use std::{
+    array,
+    borrow::Borrow,
+    cell::UnsafeCell,
+    collections::{hash_map::RandomState, HashMap},
+    fmt,
+    hash::{BuildHasher, Hash, Hasher},
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    slice::MutexSliceExt, CachePadded, LockOperation, MappedMutexGuard, Mutex, MutexGuard,
+    RawRwLock, TryLockError, TryLockResult,
+};
+
+/// The number of shards used when none is specified.
+pub const DEFAULT_SHARDS: usize = 16;
+
+struct NonZero<const N: usize>;
+
+impl<const N: usize> NonZero<N> {
+    const CHECK: () = assert!(N != 0, "a sharded lock needs at least one shard");
+}
+
+/// A set of `N` mutexes selected by hashing a key.
+///
+/// Lock striping lets unrelated keys be locked concurrently without allocating
+/// a mutex per key: each key always maps to the same shard, so every access to
+/// it is serialized, while keys mapping to other shards are not blocked.
+pub struct Striped<T, const N: usize = DEFAULT_SHARDS> {
+    shards: [Mutex<T>; N],
+    hasher: RandomState,
+}
+
+impl<T, const N: usize> Striped<T, N> {
+    /// Creates a new `Striped`, calling `f` with each shard's index to create
+    /// its value.
+    pub fn from_fn(mut f: impl FnMut(usize) -> T) -> Striped<T, N> {
+        #[allow(clippy::let_unit_value)]
+        let () = NonZero::<N>::CHECK;
+        Striped {
+            shards: array::from_fn(|i| Mutex::new(f(i))),
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// Creates a new `Striped` with every shard set to `T::default()`.
+    pub fn new() -> Striped<T, N>
+    where
+        T: Default,
+    {
+        Striped::from_fn(|_| T::default())
+    }
+
+    /// Returns the index of the shard `key` maps to.
+    #[inline]
+    pub fn shard_index<K: Hash + ?Sized>(&self, key: &K) -> usize {
+        let mut hasher = self.hasher.build_hasher();
+        key.hash(&mut hasher);
+        (hasher.finish() % N as u64) as usize
+    }
+
+    /// Locks the shard `key` maps to.
+    #[inline]
+    pub fn lock<K: Hash + ?Sized>(&self, key: &K) -> MutexGuard<'_, T> {
+        self.shards[self.shard_index(key)].lock()
+    }
+
+    /// Returns the shard with the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not less than `N`.
+    #[inline]
+    pub fn shard(&self, index: usize) -> &Mutex<T> {
+        &self.shards[index]
+    }
+
+    /// Returns every shard.
+    ///
+    /// The extension traits of the [`slice`](crate::slice) module can be used
+    /// to process them.
+    #[inline]
+    pub fn shards(&self) -> &[Mutex<T>; N] {
+        &self.shards
+    }
+
+    /// Locks every shard in index order.
+    #[inline]
+    pub fn lock_all(&self) -> Vec<MutexGuard<'_, T>> {
+        self.shards.lock_all()
+    }
+
+    /// Consumes the `Striped`, returning the value of each shard.
+    pub fn into_inner(self) -> [T; N] {
+        self.shards.map(Mutex::into_inner)
+    }
+}
+
+impl<T: Default, const N: usize> Default for Striped<T, N> {
+    #[inline]
+    fn default() -> Striped<T, N> {
+        Striped::new()
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for Striped<T, N> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Striped")
+            .field("shards", &self.shards)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A hash map split into `N` shards, each guarded by its own mutex.
+///
+/// Each key belongs to the shard picked by [`Striped`], so operations on keys
+/// in different shards run concurrently, while those on a single key are
+/// serialized. Every method locks at most one shard at a time, so no call can
+/// deadlock with another, but nothing is atomic across shards: in particular,
+/// [`for_each_shard`](ShardedMutexMap::for_each_shard) sees each shard as it
+/// is when it gets to it, not a snapshot of the whole map.
+pub struct ShardedMutexMap<K, V, const N: usize = DEFAULT_SHARDS> {
+    // Each shard's table hashes with its own keys, independent of the one
+    // picking the shard, so the keys of a shard are not all alike to it.
+    shards: Striped<HashMap<K, V>, N>,
+}
+
+impl<K: Hash + Eq, V, const N: usize> ShardedMutexMap<K, V, N> {
+    /// Creates an empty map.
+    #[inline]
+    pub fn new() -> ShardedMutexMap<K, V, N> {
+        ShardedMutexMap {
+            shards: Striped::new(),
+        }
+    }
+
+    /// Locks the shard of `key` and returns a guard for its value, or `None`
+    /// if the map does not contain it.
+    ///
+    /// The whole shard stays locked for as long as the guard is alive.
+    pub fn get_locked<Q>(&self, key: &Q) -> Option<MappedMutexGuard<'_, HashMap<K, V>, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        MutexGuard::try_map(self.shards.lock(key), |shard| shard.get_mut(key)).ok()
+    }
+
+    /// Inserts a value, returning the one previously stored for `key`, if any.
+    #[inline]
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.shards.lock(&key).insert(key, value)
+    }
+
+    /// Removes the value stored for `key` and returns it, if any.
+    #[inline]
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shards.lock(key).remove(key)
+    }
+
+    /// Calls `f` with each shard in turn, locking one shard at a time.
+    ///
+    /// Other threads can modify the shards `f` has not reached yet, or has
+    /// already been called with, while it runs.
+    pub fn for_each_shard(&self, mut f: impl FnMut(&mut HashMap<K, V>)) {
+        for shard in self.shards.shards() {
+            f(&mut shard.lock());
+        }
+    }
+
+    /// Returns the number of entries, adding up the shards one at a time.
+    pub fn len(&self) -> usize {
+        let mut len = 0;
+        self.for_each_shard(|shard| len += shard.len());
+        len
+    }
+
+    /// Returns `true` if no shard has any entries, checking them one at a
+    /// time.
+    pub fn is_empty(&self) -> bool {
+        self.shards
+            .shards()
+            .iter()
+            .all(|shard| shard.lock().is_empty())
+    }
+
+    /// Consumes the map, returning the entries of each shard.
+    #[inline]
+    pub fn into_shards(self) -> [HashMap<K, V>; N] {
+        self.shards.into_inner()
+    }
+}
+
+impl<K: Hash + Eq, V, const N: usize> Default for ShardedMutexMap<K, V, N> {
+    #[inline]
+    fn default() -> ShardedMutexMap<K, V, N> {
+        ShardedMutexMap::new()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, const N: usize> fmt::Debug for ShardedMutexMap<K, V, N> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("ShardedMutexMap")
+            .field("shards", &self.shards.shards())
+            .finish_non_exhaustive()
+    }
+}
+
+// Keeps each shard on a cache line of its own, so readers of different shards
+// never write to the same line.
+type Shard = CachePadded<RawRwLock>;
+
+thread_local! {
+    // Spreads threads over the shards in the order they first read.
+    static THREAD_INDEX: usize = {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    };
+}
+
+/// A reader-writer lock whose readers are spread over `N` shards.
+///
+/// Each thread reads through one shard, picked when it first reads, so readers
+/// on different threads mostly update different cache lines instead of
+/// contending on a single reader count. In exchange, a writer locks every
+/// shard in turn, which makes writing much more expensive than with
+/// [`RwLock`](crate::RwLock). It suits data which is read far more often than
+/// it is written, such as configuration.
+///
+/// The shards are [`RawRwLock`]s, so the lock is not seen by the `tracking`,
+/// `histogram` or `opentelemetry` instrumentation, and each instance takes up
+/// `N` cache lines.
+pub struct ShardedRwLock<T: ?Sized, const N: usize = DEFAULT_SHARDS> {
+    shards: [Shard; N],
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: the lock hands out `&mut T` to one thread at a time, or `&T` to any
+// number of threads.
+unsafe impl<T: ?Sized + Send + Sync, const N: usize> Sync for ShardedRwLock<T, N> {}
+
+impl<T, const N: usize> ShardedRwLock<T, N> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const UNLOCKED: Shard = CachePadded::new(RawRwLock::new());
+
+    /// Creates a new lock in an unlocked state.
+    #[inline]
+    pub const fn new(t: T) -> ShardedRwLock<T, N> {
+        #[allow(clippy::let_unit_value)]
+        let () = NonZero::<N>::CHECK;
+        ShardedRwLock {
+            shards: [Self::UNLOCKED; N],
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Consumes the lock, returning the underlying data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized, const N: usize> ShardedRwLock<T, N> {
+    #[inline]
+    fn shard(&self) -> &RawRwLock {
+        let index = THREAD_INDEX.try_with(|&index| index).unwrap_or(0);
+        &self.shards[index % N]
+    }
+
+    /// Acquires shared access through the current thread's shard, blocking
+    /// the current thread until it is able to do so.
+    #[inline]
+    pub fn read(&self) -> ShardedRwLockReadGuard<'_, T, N> {
+        let shard = self.shard();
+        shard.lock_shared();
+        ShardedRwLockReadGuard { lock: self, shard }
+    }
+
+    /// Attempts to acquire shared access without blocking.
+    #[inline]
+    pub fn try_read(&self) -> TryLockResult<ShardedRwLockReadGuard<'_, T, N>> {
+        let shard = self.shard();
+        if shard.try_lock_shared() {
+            Ok(ShardedRwLockReadGuard { lock: self, shard })
+        } else {
+            Err(TryLockError::new(LockOperation::Read))
+        }
+    }
+
+    /// Acquires exclusive access, locking every shard in index order, and
+    /// blocking the current thread until it is able to do so.
+    pub fn write(&self) -> ShardedRwLockWriteGuard<'_, T, N> {
+        for shard in &self.shards {
+            shard.lock_exclusive();
+        }
+        ShardedRwLockWriteGuard { lock: self }
+    }
+
+    /// Attempts to acquire exclusive access without blocking.
+    pub fn try_write(&self) -> TryLockResult<ShardedRwLockWriteGuard<'_, T, N>> {
+        for (locked, shard) in self.shards.iter().enumerate() {
+            if !shard.try_lock_exclusive() {
+                for shard in &self.shards[..locked] {
+                    // SAFETY: the shard was locked exclusively by this loop.
+                    unsafe { shard.unlock_exclusive() };
+                }
+                return Err(TryLockError::new(LockOperation::Write));
+            }
+        }
+        Ok(ShardedRwLockWriteGuard { lock: self })
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default, const N: usize> Default for ShardedRwLock<T, N> {
+    #[inline]
+    fn default() -> ShardedRwLock<T, N> {
+        ShardedRwLock::new(T::default())
+    }
+}
+
+impl<T, const N: usize> From<T> for ShardedRwLock<T, N> {
+    #[inline]
+    fn from(t: T) -> ShardedRwLock<T, N> {
+        ShardedRwLock::new(t)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, const N: usize> fmt::Debug for ShardedRwLock<T, N> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_struct("ShardedRwLock");
+        match self.try_read() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(_) => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+/// A guard granting shared access to the value of a [`ShardedRwLock`].
+#[must_use]
+pub struct ShardedRwLockReadGuard<'a, T: ?Sized + 'a, const N: usize = DEFAULT_SHARDS> {
+    lock: &'a ShardedRwLock<T, N>,
+    shard: &'a RawRwLock,
+}
+
+impl<T: ?Sized, const N: usize> Drop for ShardedRwLockReadGuard<'_, T, N> {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: the guard holds shared access through the shard.
+        unsafe { self.shard.unlock_shared() };
+    }
+}
+
+impl<T: ?Sized, const N: usize> Deref for ShardedRwLockReadGuard<'_, T, N> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds shared access.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, const N: usize> fmt::Debug for ShardedRwLockReadGuard<'_, T, N> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("ShardedRwLockReadGuard")
+            .field(&&**self)
+            .finish()
+    }
+}
+
+/// A guard granting exclusive access to the value of a [`ShardedRwLock`].
+#[must_use]
+pub struct ShardedRwLockWriteGuard<'a, T: ?Sized + 'a, const N: usize = DEFAULT_SHARDS> {
+    lock: &'a ShardedRwLock<T, N>,
+}
+
+impl<T: ?Sized, const N: usize> Drop for ShardedRwLockWriteGuard<'_, T, N> {
+    fn drop(&mut self) {
+        for shard in self.lock.shards.iter().rev() {
+            // SAFETY: the guard holds every shard exclusively.
+            unsafe { shard.unlock_exclusive() };
+        }
+    }
+}
+
+impl<T: ?Sized, const N: usize> Deref for ShardedRwLockWriteGuard<'_, T, N> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds exclusive access.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized, const N: usize> DerefMut for ShardedRwLockWriteGuard<'_, T, N> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds exclusive access.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug, const N: usize> fmt::Debug for ShardedRwLockWriteGuard<'_, T, N> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("ShardedRwLockWriteGuard")
+            .field(&&**self)
+            .finish()
+    }
+}
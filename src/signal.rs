@@ -0,0 +1,246 @@
+//! Acquisitions which a Unix signal interrupts.
+//!
+//! [`Mutex::lock_interruptible`], [`RwLock::read_interruptible`] and
+//! [`RwLock::write_interruptible`] block like their plain counterparts, but
+//! give up with [`Interrupted`] if the process receives the given signal while
+//! they wait, so that Ctrl-C can get a command-line tool out of a wedged lock
+//! with `libc::SIGINT`.
+//!
+//! While at least one thread waits for a signal this way, the crate handles
+//! that signal itself: the previous disposition of the signal, be it the
+//! default action, being ignored or another handler, is suspended until the
+//! last such wait ends, and is then restored. A signal received while no
+//! thread waits for it is handled as it would be without this module. The
+//! handler only writes to a pipe, which a background thread started on first
+//! use reads to wake the waiters.
+
+use std::{
+    collections::HashMap,
+    error, fmt, io, mem,
+    os::raw::c_int,
+    ptr,
+    sync::{
+        self,
+        atomic::{AtomicI32, Ordering},
+    },
+    thread,
+};
+
+use crate::{
+    poison::PoisonPolicy, raw::MutexStorage, CancelToken, Mutex, MutexGuard, RwLock,
+    RwLockReadGuard, RwLockWriteGuard,
+};
+
+static WAITS: sync::Mutex<Option<HashMap<c_int, Wait>>> = sync::Mutex::new(None);
+
+static START: sync::Once = sync::Once::new();
+
+// The write end of the pipe the handler writes signal numbers to.
+static PIPE: AtomicI32 = AtomicI32::new(-1);
+
+// The interruptible waits in progress for one signal.
+struct Wait {
+    waiters: usize,
+    // Cancelled when the signal is received, and then replaced for the waits
+    // which start afterwards.
+    token: CancelToken,
+    // The disposition to restore once the last waiter is done.
+    previous: libc::sigaction,
+}
+
+/// The error returned by an acquisition interrupted by a signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interrupted {
+    signal: c_int,
+}
+
+impl Interrupted {
+    /// Returns the number of the signal which interrupted the acquisition.
+    #[inline]
+    pub fn signal(&self) -> c_int {
+        self.signal
+    }
+}
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "lock call failed because signal {} was received",
+            self.signal
+        )
+    }
+}
+
+impl error::Error for Interrupted {}
+
+impl<T: ?Sized, P: PoisonPolicy, R: MutexStorage> Mutex<T, P, R> {
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so or the process receives `signal`.
+    ///
+    /// A mutex which is free is acquired without touching the disposition of
+    /// the signal. See the [`signal`](crate::signal) module for how the signal
+    /// is handled while the thread waits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a handler cannot be installed for `signal`, as for `SIGKILL`
+    /// and `SIGSTOP`.
+    pub fn lock_interruptible(
+        &self,
+        signal: c_int,
+    ) -> Result<MutexGuard<'_, T, P, R>, Interrupted> {
+        if let Ok(guard) = self.try_lock() {
+            return Ok(guard);
+        }
+        interruptible(signal, |token| self.lock_cancellable(token).ok())
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Acquires shared access, blocking the current thread until it is able to
+    /// do so or the process receives `signal`.
+    ///
+    /// This follows the same rules as [`Mutex::lock_interruptible`].
+    pub fn read_interruptible(&self, signal: c_int) -> Result<RwLockReadGuard<'_, T>, Interrupted> {
+        if let Ok(guard) = self.try_read() {
+            return Ok(guard);
+        }
+        interruptible(signal, |token| self.read_cancellable(token).ok())
+    }
+
+    /// Acquires exclusive access, blocking the current thread until it is able
+    /// to do so or the process receives `signal`.
+    ///
+    /// This follows the same rules as [`Mutex::lock_interruptible`].
+    pub fn write_interruptible(
+        &self,
+        signal: c_int,
+    ) -> Result<RwLockWriteGuard<'_, T>, Interrupted> {
+        if let Ok(guard) = self.try_write() {
+            return Ok(guard);
+        }
+        interruptible(signal, |token| self.write_cancellable(token).ok())
+    }
+}
+
+fn interruptible<G>(
+    signal: c_int,
+    acquire: impl FnOnce(&CancelToken) -> Option<G>,
+) -> Result<G, Interrupted> {
+    START.call_once(start);
+    let token = begin(signal);
+    let guard = acquire(&token);
+    end(signal);
+    guard.ok_or(Interrupted { signal })
+}
+
+fn waits() -> sync::MutexGuard<'static, Option<HashMap<c_int, Wait>>> {
+    WAITS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Registers a waiter for `signal`, installing the handler for the first one.
+fn begin(signal: c_int) -> CancelToken {
+    let mut waits = waits();
+    let waits = waits.get_or_insert_with(HashMap::new);
+    if let Some(wait) = waits.get_mut(&signal) {
+        wait.waiters += 1;
+        return wait.token.clone();
+    }
+    // SAFETY: the handler only calls `write`, which is async-signal-safe, and
+    // both actions are fully initialized.
+    let previous = unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handle as extern "C" fn(c_int) as libc::sighandler_t;
+        libc::sigemptyset(&mut action.sa_mask);
+        action.sa_flags = libc::SA_RESTART;
+        let mut previous = mem::zeroed();
+        if libc::sigaction(signal, &action, &mut previous) != 0 {
+            panic!(
+                "cannot interrupt locks on signal {}: {}",
+                signal,
+                io::Error::last_os_error(),
+            );
+        }
+        previous
+    };
+    let token = CancelToken::new();
+    waits.insert(
+        signal,
+        Wait {
+            waiters: 1,
+            token: token.clone(),
+            previous,
+        },
+    );
+    token
+}
+
+// Unregisters a waiter for `signal`, restoring the previous disposition after
+// the last one.
+fn end(signal: c_int) {
+    let mut waits = waits();
+    let waits = waits.as_mut().unwrap();
+    let wait = waits.get_mut(&signal).unwrap();
+    wait.waiters -= 1;
+    if wait.waiters == 0 {
+        let wait = waits.remove(&signal).unwrap();
+        // SAFETY: `previous` is what `sigaction` returned.
+        unsafe { libc::sigaction(signal, &wait.previous, ptr::null_mut()) };
+    }
+}
+
+// Creates the pipe and starts the thread which wakes the waiters for the
+// signals written to it.
+fn start() {
+    let mut fds = [0; 2];
+    // SAFETY: `fds` has room for both ends.
+    let ret = unsafe { libc::pipe(fds.as_mut_ptr()) };
+    assert_eq!(ret, 0, "pipe failed: {}", io::Error::last_os_error());
+    for fd in fds {
+        // SAFETY: `fd` was just opened.
+        unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    }
+    // A full pipe drops signals rather than blocking the handler, which is
+    // harmless as the waiters have plenty to wake them already.
+    // SAFETY: as above.
+    unsafe {
+        let flags = libc::fcntl(fds[1], libc::F_GETFL);
+        libc::fcntl(fds[1], libc::F_SETFL, flags | libc::O_NONBLOCK);
+    }
+    PIPE.store(fds[1], Ordering::Release);
+    let read = fds[0];
+    thread::Builder::new()
+        .name("antidote-signal".to_string())
+        .spawn(move || loop {
+            let mut signal = 0u8;
+            // SAFETY: `signal` is a valid buffer of one byte.
+            let n = unsafe { libc::read(read, &mut signal as *mut u8 as *mut libc::c_void, 1) };
+            if n == 1 {
+                interrupt(c_int::from(signal));
+            } else if n == 0 || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                return;
+            }
+        })
+        .expect("failed to spawn the signal thread");
+}
+
+// Wakes the waiters for `signal`. Those which start waiting afterwards wait
+// for the next one.
+fn interrupt(signal: c_int) {
+    let mut waits = waits();
+    if let Some(wait) = waits.as_mut().and_then(|waits| waits.get_mut(&signal)) {
+        let token = mem::take(&mut wait.token);
+        drop(waits);
+        token.cancel();
+    }
+}
+
+extern "C" fn handle(signal: c_int) {
+    let fd = PIPE.load(Ordering::Acquire);
+    let byte = signal as u8;
+    // SAFETY: `write` is async-signal-safe, and `byte` is a valid buffer of
+    // one byte. It only fails, and so only clobbers `errno` for the code the
+    // signal interrupted, if the pipe is full.
+    unsafe { libc::write(fd, &byte as *const u8 as *const libc::c_void, 1) };
+}
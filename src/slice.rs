@@ -0,0 +1,247 @@
+//! Extension traits for slices of locks.
+//!
+//! Sharded state is often stored as a slice or `Vec` of locks. These traits
+//! iterate over such a slice, locking each element in turn, or lock every
+//! element at once.
+//!
+//! Locks are always acquired in index order. As long as every piece of code
+//! that holds several elements of the same slice at once acquires them through
+//! these traits (or otherwise in ascending index order), doing so cannot
+//! deadlock.
+//!
+//! With the `rayon` feature, [`ParMutexSliceExt`] and [`ParRwLockSliceExt`]
+//! process every element in parallel instead, each under its own guard. No
+//! thread holds more than one of the guards at a time.
+
+use std::{iter::FusedIterator, slice};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Extension methods for slices of `Mutex`es.
+pub trait MutexSliceExt<T> {
+    /// Returns an iterator which locks each mutex in turn.
+    ///
+    /// Each mutex is locked when the iterator reaches it, and stays locked for
+    /// as long as the yielded guard is alive.
+    fn iter_locked(&self) -> IterLocked<'_, T>;
+
+    /// Locks every mutex in index order.
+    fn lock_all(&self) -> Vec<MutexGuard<'_, T>>;
+}
+
+impl<T> MutexSliceExt<T> for [Mutex<T>] {
+    #[inline]
+    fn iter_locked(&self) -> IterLocked<'_, T> {
+        IterLocked(self.iter())
+    }
+
+    #[inline]
+    fn lock_all(&self) -> Vec<MutexGuard<'_, T>> {
+        self.iter_locked().collect()
+    }
+}
+
+/// Extension methods for slices of `RwLock`s.
+pub trait RwLockSliceExt<T> {
+    /// Returns an iterator which read-locks each lock in turn.
+    ///
+    /// Each lock is acquired when the iterator reaches it, and stays locked for
+    /// as long as the yielded guard is alive.
+    fn iter_read(&self) -> IterRead<'_, T>;
+
+    /// Returns an iterator which write-locks each lock in turn.
+    ///
+    /// Each lock is acquired when the iterator reaches it, and stays locked for
+    /// as long as the yielded guard is alive.
+    fn iter_write(&self) -> IterWrite<'_, T>;
+
+    /// Read-locks every lock in index order.
+    fn read_all(&self) -> Vec<RwLockReadGuard<'_, T>>;
+
+    /// Write-locks every lock in index order.
+    fn write_all(&self) -> Vec<RwLockWriteGuard<'_, T>>;
+}
+
+impl<T> RwLockSliceExt<T> for [RwLock<T>] {
+    #[inline]
+    fn iter_read(&self) -> IterRead<'_, T> {
+        IterRead(self.iter())
+    }
+
+    #[inline]
+    fn iter_write(&self) -> IterWrite<'_, T> {
+        IterWrite(self.iter())
+    }
+
+    #[inline]
+    fn read_all(&self) -> Vec<RwLockReadGuard<'_, T>> {
+        self.iter_read().collect()
+    }
+
+    #[inline]
+    fn write_all(&self) -> Vec<RwLockWriteGuard<'_, T>> {
+        self.iter_write().collect()
+    }
+}
+
+macro_rules! lock_iter {
+    ($(#[$attr:meta])* $name:ident, $lock:ident, $guard:ident, $method:ident) => {
+        $(#[$attr])*
+        #[derive(Debug)]
+        pub struct $name<'a, T>(slice::Iter<'a, $lock<T>>);
+
+        impl<'a, T> Iterator for $name<'a, T> {
+            type Item = $guard<'a, T>;
+
+            #[inline]
+            fn next(&mut self) -> Option<$guard<'a, T>> {
+                self.0.next().map(|lock| lock.$method())
+            }
+
+            #[inline]
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.0.size_hint()
+            }
+        }
+
+        impl<T> ExactSizeIterator for $name<'_, T> {}
+
+        impl<T> FusedIterator for $name<'_, T> {}
+    };
+}
+
+lock_iter!(
+    /// An iterator which locks each `Mutex` of a slice in turn.
+    ///
+    /// Created by [`MutexSliceExt::iter_locked`].
+    IterLocked,
+    Mutex,
+    MutexGuard,
+    lock
+);
+
+lock_iter!(
+    /// An iterator which read-locks each `RwLock` of a slice in turn.
+    ///
+    /// Created by [`RwLockSliceExt::iter_read`].
+    IterRead,
+    RwLock,
+    RwLockReadGuard,
+    read
+);
+
+lock_iter!(
+    /// An iterator which write-locks each `RwLock` of a slice in turn.
+    ///
+    /// Created by [`RwLockSliceExt::iter_write`].
+    IterWrite,
+    RwLock,
+    RwLockWriteGuard,
+    write
+);
+
+/// Parallel extension methods for slices of `Mutex`es.
+///
+/// Each mutex is locked by the worker thread processing it, for the duration of
+/// one call to the closure.
+#[cfg(feature = "rayon")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+pub trait ParMutexSliceExt<T> {
+    /// Calls `f` on the contents of every mutex in parallel.
+    fn par_for_each_locked<F>(&self, f: F)
+    where
+        F: Fn(&mut T) + Sync + Send;
+
+    /// Calls `f` on the contents of every mutex in parallel, collecting the
+    /// results in index order.
+    fn par_map_locked<F, R>(&self, f: F) -> Vec<R>
+    where
+        F: Fn(&mut T) -> R + Sync + Send,
+        R: Send;
+}
+
+#[cfg(feature = "rayon")]
+impl<T: Send> ParMutexSliceExt<T> for [Mutex<T>] {
+    fn par_for_each_locked<F>(&self, f: F)
+    where
+        F: Fn(&mut T) + Sync + Send,
+    {
+        self.par_iter().for_each(|lock| f(&mut lock.lock()))
+    }
+
+    fn par_map_locked<F, R>(&self, f: F) -> Vec<R>
+    where
+        F: Fn(&mut T) -> R + Sync + Send,
+        R: Send,
+    {
+        self.par_iter().map(|lock| f(&mut lock.lock())).collect()
+    }
+}
+
+/// Parallel extension methods for slices of `RwLock`s.
+///
+/// Each lock is acquired by the worker thread processing it, for the duration
+/// of one call to the closure.
+#[cfg(feature = "rayon")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+pub trait ParRwLockSliceExt<T> {
+    /// Calls `f` on the contents of every lock in parallel, under a read lock.
+    fn par_for_each_read<F>(&self, f: F)
+    where
+        F: Fn(&T) + Sync + Send;
+
+    /// Calls `f` on the contents of every lock in parallel, under a write lock.
+    fn par_for_each_write<F>(&self, f: F)
+    where
+        F: Fn(&mut T) + Sync + Send;
+
+    /// Calls `f` on the contents of every lock in parallel under a read lock,
+    /// collecting the results in index order.
+    fn par_map_read<F, R>(&self, f: F) -> Vec<R>
+    where
+        F: Fn(&T) -> R + Sync + Send,
+        R: Send;
+
+    /// Calls `f` on the contents of every lock in parallel under a write lock,
+    /// collecting the results in index order.
+    fn par_map_write<F, R>(&self, f: F) -> Vec<R>
+    where
+        F: Fn(&mut T) -> R + Sync + Send,
+        R: Send;
+}
+
+#[cfg(feature = "rayon")]
+impl<T: Send + Sync> ParRwLockSliceExt<T> for [RwLock<T>] {
+    fn par_for_each_read<F>(&self, f: F)
+    where
+        F: Fn(&T) + Sync + Send,
+    {
+        self.par_iter().for_each(|lock| f(&lock.read()))
+    }
+
+    fn par_for_each_write<F>(&self, f: F)
+    where
+        F: Fn(&mut T) + Sync + Send,
+    {
+        self.par_iter().for_each(|lock| f(&mut lock.write()))
+    }
+
+    fn par_map_read<F, R>(&self, f: F) -> Vec<R>
+    where
+        F: Fn(&T) -> R + Sync + Send,
+        R: Send,
+    {
+        self.par_iter().map(|lock| f(&lock.read())).collect()
+    }
+
+    fn par_map_write<F, R>(&self, f: F) -> Vec<R>
+    where
+        F: Fn(&mut T) -> R + Sync + Send,
+        R: Send,
+    {
+        self.par_iter().map(|lock| f(&mut lock.write())).collect()
+    }
+}
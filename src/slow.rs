@@ -0,0 +1,178 @@
+//! Warnings about slow acquisitions and long holds.
+//!
+//! With the `slow-warnings` feature enabled, a `Mutex` or `RwLock` can be given
+//! thresholds with [`Mutex::set_slow_thresholds`] and
+//! [`RwLock::set_slow_thresholds`]. From then on, an acquisition which waited
+//! for the lock longer than the wait threshold, and a guard which held it
+//! longer than the hold threshold, emit a warning with the address of the lock,
+//! its name if it has one, the time it took and the name of the thread, and
+//! with the `holder-location` feature where the lock was acquired by the
+//! holder the acquisition waited for, or by the guard which held it. With
+//! the `tracing` feature enabled the warning is instead a `WARN` event, `slow
+//! lock acquisition` or `lock held too long`, carrying the fields described in
+//! the [`tracing`](mod@crate::tracing) module, the time it took in `took_ns`
+//! and the threshold in `threshold_ns`. Otherwise it is written to standard
+//! error.
+//!
+//! Holds are only measured when the guard is dropped, so a guard which is
+//! never dropped is never reported. The [`lease`](crate::lease) module covers
+//! that case.
+//!
+//! Locks have no thresholds until they are set. Checking them makes every
+//! acquisition and release somewhat more expensive, and every lock 16 bytes
+//! larger.
+
+#[cfg(not(feature = "tracing"))]
+use std::thread;
+use std::{
+    panic::Location,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{
+    poison::PoisonPolicy,
+    raw::{MutexStorage, RwLockBackend},
+    Mutex, RwLock,
+};
+
+// A threshold which is not set.
+const NONE: u64 = u64::MAX;
+
+/// The thresholds kept by each lock.
+pub(crate) struct Thresholds {
+    wait_ns: AtomicU64,
+    hold_ns: AtomicU64,
+}
+
+impl Thresholds {
+    pub(crate) const fn new() -> Thresholds {
+        Thresholds {
+            wait_ns: AtomicU64::new(NONE),
+            hold_ns: AtomicU64::new(NONE),
+        }
+    }
+
+    fn set(&self, wait: Option<Duration>, hold: Option<Duration>) {
+        self.wait_ns
+            .store(wait.map_or(NONE, nanos), Ordering::Relaxed);
+        self.hold_ns
+            .store(hold.map_or(NONE, nanos), Ordering::Relaxed);
+    }
+
+    /// Warns if an acquisition of the lock at `addr` waited for longer than
+    /// the wait threshold, for the holder which acquired it at `holder`.
+    #[inline]
+    pub(crate) fn waited(
+        &self,
+        addr: usize,
+        name: Option<&'static str>,
+        waited: Duration,
+        holder: Option<&'static Location<'static>>,
+    ) {
+        let threshold = self.wait_ns.load(Ordering::Relaxed);
+        if threshold != NONE && nanos(waited) > threshold {
+            warn(Slow::Wait, addr, name, waited, threshold, holder);
+        }
+    }
+
+    /// Warns if the lock at `addr` was held for longer than the hold
+    /// threshold, by the guard which acquired it at `holder`.
+    #[inline]
+    pub(crate) fn held(
+        &self,
+        addr: usize,
+        name: Option<&'static str>,
+        held: Duration,
+        holder: Option<&'static Location<'static>>,
+    ) {
+        let threshold = self.hold_ns.load(Ordering::Relaxed);
+        if threshold != NONE && nanos(held) > threshold {
+            warn(Slow::Hold, addr, name, held, threshold, holder);
+        }
+    }
+}
+
+impl Default for Thresholds {
+    #[inline]
+    fn default() -> Thresholds {
+        Thresholds::new()
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    // `NONE` itself stands for no threshold, and is hundreds of years anyway.
+    u64::try_from(duration.as_nanos()).map_or(NONE - 1, |nanos| nanos.min(NONE - 1))
+}
+
+#[derive(Clone, Copy)]
+enum Slow {
+    Wait,
+    Hold,
+}
+
+#[cold]
+fn warn(
+    slow: Slow,
+    addr: usize,
+    name: Option<&'static str>,
+    took: Duration,
+    threshold: u64,
+    holder: Option<&'static Location<'static>>,
+) {
+    let threshold = Duration::from_nanos(threshold);
+    #[cfg(feature = "tracing")]
+    match slow {
+        Slow::Wait => crate::tracing::slow_wait(addr, name, took, threshold, holder),
+        Slow::Hold => crate::tracing::slow_hold(addr, name, took, threshold, holder),
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let what = match slow {
+            Slow::Wait => "waited for",
+            Slow::Hold => "held",
+        };
+        let current = thread::current();
+        let thread = current.name().unwrap_or("<unnamed>");
+        let holder = match (slow, holder) {
+            (Slow::Wait, Some(holder)) => format!(", held by a guard acquired at {}", holder),
+            (Slow::Hold, Some(holder)) => format!(", by a guard acquired at {}", holder),
+            (_, None) => String::new(),
+        };
+        match name {
+            Some(name) => eprintln!(
+                "antidote: lock '{}' at {:#x} {} {:?} by thread '{}', over its {:?} threshold{}",
+                name, addr, what, took, thread, threshold, holder,
+            ),
+            None => eprintln!(
+                "antidote: lock at {:#x} {} {:?} by thread '{}', over its {:?} threshold{}",
+                addr, what, took, thread, threshold, holder,
+            ),
+        }
+    }
+}
+
+impl<T: ?Sized, P: PoisonPolicy, R: MutexStorage> Mutex<T, P, R> {
+    /// Sets the thresholds past which waiting for the mutex, and holding it,
+    /// emit a warning, or removes them with `None`.
+    ///
+    /// See the [`slow`](crate::slow) module.
+    #[cfg_attr(docsrs, doc(cfg(feature = "slow-warnings")))]
+    #[inline]
+    pub fn set_slow_thresholds(&self, wait: Option<Duration>, hold: Option<Duration>) {
+        self.instrumentation.slow.set(wait, hold);
+    }
+}
+
+impl<T: ?Sized, R: RwLockBackend> RwLock<T, R> {
+    /// Sets the thresholds past which waiting for the lock, and holding it,
+    /// emit a warning, or removes them with `None`.
+    ///
+    /// Shared and exclusive access are measured alike. See the
+    /// [`slow`](crate::slow) module.
+    #[cfg_attr(docsrs, doc(cfg(feature = "slow-warnings")))]
+    #[inline]
+    pub fn set_slow_thresholds(&self, wait: Option<Duration>, hold: Option<Duration>) {
+        self.instrumentation.slow.set(wait, hold);
+    }
+}
@@ -0,0 +1,248 @@
+//! The spinning mutex, which is also the `no_std` mutex.
+//!
+//! Without the standard library there is no way to put a thread to sleep, so
+//! the `no_std` locks spin until they are acquired, backing off exponentially
+//! to limit the traffic on the lock's cache line. They offer the subset of the
+//! API of the standard locks which does not depend on the standard library.
+
+use core::{
+    cell::UnsafeCell,
+    fmt, hint,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    panic::{RefUnwindSafe, UnwindSafe},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+#[cfg(feature = "std")]
+use crate::{Lock, Unlock};
+use crate::{LockOperation, TryLockError, TryLockResult};
+
+// The longest a waiter spins between two attempts, as a power of two.
+const MAX_BACKOFF_SHIFT: u32 = 10;
+
+// Spins for exponentially longer on each call. On a target without threads,
+// the wait could never end, so it panics instead.
+pub(crate) struct Backoff(u32);
+
+impl Backoff {
+    #[inline]
+    pub(crate) fn new() -> Backoff {
+        Backoff(0)
+    }
+
+    #[inline]
+    pub(crate) fn spin(&mut self) {
+        if crate::NO_THREADS {
+            crate::deadlock();
+        }
+        for _ in 0..1u32 << self.0 {
+            hint::spin_loop();
+        }
+        if self.0 < MAX_BACKOFF_SHIFT {
+            self.0 += 1;
+        }
+    }
+}
+
+/// A mutex which spins while it waits instead of putting the thread to sleep.
+///
+/// A thread waiting for the mutex retries with exponential backoff until it is
+/// released. This only pays off for critical sections of a few loads and
+/// stores, or where a thread must not sleep, and wastes CPU time whenever the
+/// holder is descheduled. It has the same basic API as
+/// [`Mutex`](crate::Mutex), so code can switch between the two.
+///
+/// In `no_std` builds, this is the crate's `Mutex`.
+#[derive(Default)]
+pub struct SpinMutex<T: ?Sized> {
+    locked: AtomicBool,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: the mutex hands out `&mut T` to one thread at a time.
+unsafe impl<T: ?Sized + Send> Send for SpinMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for SpinMutex<T> {}
+
+impl<T: ?Sized> UnwindSafe for SpinMutex<T> {}
+impl<T: ?Sized> RefUnwindSafe for SpinMutex<T> {}
+
+impl<T> SpinMutex<T> {
+    /// Like `std::sync::Mutex::new`.
+    #[inline]
+    pub const fn new(t: T) -> SpinMutex<T> {
+        SpinMutex {
+            locked: AtomicBool::new(false),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Like `std::sync::Mutex::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T> From<T> for SpinMutex<T> {
+    /// Like `std::sync::Mutex::from`.
+    #[inline]
+    fn from(t: T) -> SpinMutex<T> {
+        SpinMutex::new(t)
+    }
+}
+
+impl<T: ?Sized> SpinMutex<T> {
+    /// Like `std::sync::Mutex::lock`.
+    #[inline]
+    pub fn lock(&self) -> SpinMutexGuard<'_, T> {
+        if !self.try_acquire() {
+            self.lock_contended();
+        }
+        SpinMutexGuard::new(self)
+    }
+
+    #[cold]
+    fn lock_contended(&self) {
+        let mut backoff = Backoff::new();
+        loop {
+            // Wait for the lock to look free before writing to it again.
+            while self.locked.load(Ordering::Relaxed) {
+                backoff.spin();
+            }
+            if self.try_acquire() {
+                return;
+            }
+        }
+    }
+
+    /// Like `std::sync::Mutex::try_lock`.
+    #[inline]
+    pub fn try_lock(&self) -> TryLockResult<SpinMutexGuard<'_, T>> {
+        if self.try_acquire() {
+            Ok(SpinMutexGuard::new(self))
+        } else {
+            Err(TryLockError::new(LockOperation::Lock))
+        }
+    }
+
+    #[inline]
+    fn try_acquire(&self) -> bool {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// Like `std::sync::Mutex::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for SpinMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        // Named after what it is exported as.
+        let mut d = fmt.debug_struct(if cfg!(feature = "std") {
+            "SpinMutex"
+        } else {
+            "Mutex"
+        });
+        match self.try_lock() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(_) => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+#[must_use]
+/// A guard for a [`SpinMutex`].
+///
+/// In `no_std` builds, this is the crate's `MutexGuard`.
+pub struct SpinMutexGuard<'a, T: ?Sized + 'a> {
+    lock: &'a SpinMutex<T>,
+    // Like the standard library's guards, this one is not `Send`.
+    _not_send: PhantomData<*const ()>,
+}
+
+// SAFETY: the guard only hands out `&T` through shared references to it.
+unsafe impl<T: ?Sized + Sync> Sync for SpinMutexGuard<'_, T> {}
+
+impl<'a, T: ?Sized> SpinMutexGuard<'a, T> {
+    #[inline]
+    fn new(lock: &'a SpinMutex<T>) -> SpinMutexGuard<'a, T> {
+        SpinMutexGuard {
+            lock,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for SpinMutexGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized> Deref for SpinMutexGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds the lock.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for SpinMutexGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds the lock.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for SpinMutexGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let name = if cfg!(feature = "std") {
+            "SpinMutexGuard"
+        } else {
+            "MutexGuard"
+        };
+        fmt.debug_tuple(name).field(&&**self).finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: ?Sized> Lock<T> for SpinMutex<T> {
+    type Guard<'a>
+        = SpinMutexGuard<'a, T>
+    where
+        T: 'a;
+
+    #[inline]
+    fn lock(&self) -> SpinMutexGuard<'_, T> {
+        self.lock()
+    }
+
+    #[inline]
+    fn try_lock(&self) -> TryLockResult<SpinMutexGuard<'_, T>> {
+        self.try_lock()
+    }
+
+    #[inline]
+    fn get_mut(&mut self) -> &mut T {
+        self.get_mut()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: ?Sized> Unlock for SpinMutexGuard<'_, T> {
+    #[inline]
+    fn unlocked<U>(&mut self, f: impl FnOnce() -> U) -> U {
+        let lock = self.lock;
+        crate::unlocked(self, || lock.lock(), f)
+    }
+}
@@ -0,0 +1,275 @@
+//! The `no_std` reader-writer lock.
+//!
+//! Like [`SpinMutex`](crate::SpinMutex), it spins until it is acquired,
+//! backing off exponentially.
+
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    panic::{RefUnwindSafe, UnwindSafe},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{spin::Backoff, LockOperation, TryLockError, TryLockResult};
+
+const WRITER: usize = 1;
+// Set while a writer spins, which holds off new readers so that a steady
+// stream of them cannot starve it.
+const WRITER_WAITING: usize = 2;
+const ONE_READER: usize = 4;
+
+/// Like `std::sync::RwLock` except that it does not poison itself.
+///
+/// In `no_std` builds, this is a spinlock: a thread waiting for the lock spins
+/// until it can acquire it. A spinning writer holds off new readers.
+#[derive(Default)]
+pub struct RwLock<T: ?Sized> {
+    state: AtomicUsize,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: the lock hands out `&mut T` to one thread at a time, or `&T` to any
+// number of threads.
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+impl<T: ?Sized> UnwindSafe for RwLock<T> {}
+impl<T: ?Sized> RefUnwindSafe for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    /// Like `std::sync::RwLock::new`.
+    #[inline]
+    pub const fn new(t: T) -> RwLock<T> {
+        RwLock {
+            state: AtomicUsize::new(0),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Like `std::sync::RwLock::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T> From<T> for RwLock<T> {
+    /// Like `std::sync::RwLock::from`.
+    #[inline]
+    fn from(t: T) -> RwLock<T> {
+        RwLock::new(t)
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Like `std::sync::RwLock::read`.
+    #[inline]
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        if !self.try_acquire_shared() {
+            self.read_contended();
+        }
+        RwLockReadGuard::new(self)
+    }
+
+    #[cold]
+    fn read_contended(&self) {
+        let mut backoff = Backoff::new();
+        while !self.try_acquire_shared() {
+            backoff.spin();
+        }
+    }
+
+    /// Like `std::sync::RwLock::try_read`.
+    #[inline]
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        if self.try_acquire_shared() {
+            Ok(RwLockReadGuard::new(self))
+        } else {
+            Err(TryLockError::new(LockOperation::Read))
+        }
+    }
+
+    #[inline]
+    fn try_acquire_shared(&self) -> bool {
+        let state = self.state.load(Ordering::Relaxed);
+        state & (WRITER | WRITER_WAITING) == 0
+            && self
+                .state
+                .compare_exchange_weak(
+                    state,
+                    state + ONE_READER,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+    }
+
+    /// Like `std::sync::RwLock::write`.
+    #[inline]
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        if !self.try_acquire_exclusive() {
+            self.write_contended();
+        }
+        RwLockWriteGuard::new(self)
+    }
+
+    #[cold]
+    fn write_contended(&self) {
+        let mut backoff = Backoff::new();
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & !WRITER_WAITING == 0 {
+                // Acquiring the lock clears the flag. Other spinning writers
+                // set it again.
+                match self.state.compare_exchange_weak(
+                    state,
+                    WRITER,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => return,
+                    Err(actual) => state = actual,
+                }
+                continue;
+            }
+            if state & WRITER_WAITING == 0 {
+                self.state.fetch_or(WRITER_WAITING, Ordering::Relaxed);
+            }
+            backoff.spin();
+            state = self.state.load(Ordering::Relaxed);
+        }
+    }
+
+    /// Like `std::sync::RwLock::try_write`.
+    #[inline]
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        if self.try_acquire_exclusive() {
+            Ok(RwLockWriteGuard::new(self))
+        } else {
+            Err(TryLockError::new(LockOperation::Write))
+        }
+    }
+
+    #[inline]
+    fn try_acquire_exclusive(&self) -> bool {
+        self.state
+            .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// Like `std::sync::RwLock::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_struct("RwLock");
+        match self.try_read() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(_) => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+#[must_use]
+/// Like `std::sync::RwLockReadGuard`.
+pub struct RwLockReadGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+    // See `SpinMutexGuard`.
+    _not_send: PhantomData<*const ()>,
+}
+
+// SAFETY: the guard only hands out `&T`.
+unsafe impl<T: ?Sized + Sync> Sync for RwLockReadGuard<'_, T> {}
+
+impl<'a, T: ?Sized> RwLockReadGuard<'a, T> {
+    #[inline]
+    fn new(lock: &'a RwLock<T>) -> RwLockReadGuard<'a, T> {
+        RwLockReadGuard {
+            lock,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(ONE_READER, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds shared access.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("RwLockReadGuard").field(&&**self).finish()
+    }
+}
+
+#[must_use]
+/// Like `std::sync::RwLockWriteGuard`.
+pub struct RwLockWriteGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+    // See `SpinMutexGuard`.
+    _not_send: PhantomData<*const ()>,
+}
+
+// SAFETY: the guard only hands out `&T` through shared references to it.
+unsafe impl<T: ?Sized + Sync> Sync for RwLockWriteGuard<'_, T> {}
+
+impl<'a, T: ?Sized> RwLockWriteGuard<'a, T> {
+    #[inline]
+    fn new(lock: &'a RwLock<T>) -> RwLockWriteGuard<'a, T> {
+        RwLockWriteGuard {
+            lock,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        // A spinning writer may have set `WRITER_WAITING` meanwhile.
+        self.lock.state.fetch_and(!WRITER, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard holds exclusive access.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard holds exclusive access.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("RwLockWriteGuard").field(&&**self).finish()
+    }
+}
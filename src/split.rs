@@ -0,0 +1,435 @@
+use std::{
+    fmt,
+    iter::FusedIterator,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    rc::Rc,
+};
+
+use crate::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+macro_rules! split_guard {
+    ($(#[$attr:meta])* $name:ident, $guard:ident) => {
+        $(#[$attr])*
+        #[must_use]
+        pub struct $name<'a, T: ?Sized + 'a, U: ?Sized + 'a> {
+            // Keeps the lock held until every part is dropped. `data` points
+            // into the value it guards, and is disjoint from the other parts.
+            _guard: Rc<$guard<'a, T>>,
+            data: NonNull<U>,
+            _marker: PhantomData<&'a mut U>,
+        }
+
+        // SAFETY: the guard only hands out references to `U`, and the `Rc` is
+        // never cloned through a shared reference.
+        unsafe impl<T: ?Sized, U: ?Sized + Sync> Sync for $name<'_, T, U> {}
+
+        impl<'a, T: ?Sized> $guard<'a, T> {
+            /// Makes two guards granting access to disjoint parts of the
+            /// locked value, selected by `f`.
+            ///
+            /// The lock stays held until both guards are dropped. This is an
+            /// associated function so that it does not shadow a method of `T`.
+            pub fn map_split<U: ?Sized, V: ?Sized>(
+                mut orig: $guard<'a, T>,
+                f: impl FnOnce(&mut T) -> (&mut U, &mut V),
+            ) -> ($name<'a, T, U>, $name<'a, T, V>) {
+                let (u, v) = f(&mut *orig);
+                let (u, v) = (NonNull::from(u), NonNull::from(v));
+                let guard = Rc::new(orig);
+                ($name::new(guard.clone(), u), $name::new(guard, v))
+            }
+
+            /// Makes two guards granting access to the elements of the locked
+            /// slice before `mid` and from `mid` on.
+            ///
+            /// This is [`map_split`](Self::map_split) with
+            /// `<[E]>::split_at_mut`, for any value which is a slice, such as a
+            /// `Vec` or an array. It is an associated function so that it does
+            /// not shadow `<[E]>::split_at`.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `mid` is larger than the length of the slice.
+            #[inline]
+            pub fn split_at<E>(
+                orig: $guard<'a, T>,
+                mid: usize,
+            ) -> ($name<'a, T, [E]>, $name<'a, T, [E]>)
+            where
+                T: AsMut<[E]>,
+            {
+                $guard::map_split(orig, |t| t.as_mut().split_at_mut(mid))
+            }
+        }
+
+        impl<'a, T: ?Sized, U: ?Sized> $name<'a, T, U> {
+            #[inline]
+            fn new(guard: Rc<$guard<'a, T>>, data: NonNull<U>) -> $name<'a, T, U> {
+                $name {
+                    _guard: guard,
+                    data,
+                    _marker: PhantomData,
+                }
+            }
+
+            /// Makes a guard granting access to a part of the part this guard
+            /// grants access to, selected by `f`.
+            #[inline]
+            pub fn map<V: ?Sized>(
+                mut orig: $name<'a, T, U>,
+                f: impl FnOnce(&mut U) -> &mut V,
+            ) -> $name<'a, T, V> {
+                let data = NonNull::from(f(&mut *orig));
+                $name::new(orig._guard, data)
+            }
+
+            /// Splits the part this guard grants access to into two further
+            /// parts, selected by `f`.
+            #[inline]
+            pub fn map_split<V: ?Sized, W: ?Sized>(
+                mut orig: $name<'a, T, U>,
+                f: impl FnOnce(&mut U) -> (&mut V, &mut W),
+            ) -> ($name<'a, T, V>, $name<'a, T, W>) {
+                let (v, w) = f(&mut *orig);
+                let (v, w) = (NonNull::from(v), NonNull::from(w));
+                ($name::new(orig._guard.clone(), v), $name::new(orig._guard, w))
+            }
+        }
+
+        impl<T: ?Sized, U: ?Sized> Deref for $name<'_, T, U> {
+            type Target = U;
+
+            #[inline]
+            fn deref(&self) -> &U {
+                // SAFETY: `data` was derived from the guard, which is still
+                // alive, and no other part overlaps it.
+                unsafe { self.data.as_ref() }
+            }
+        }
+
+        impl<T: ?Sized, U: ?Sized> DerefMut for $name<'_, T, U> {
+            #[inline]
+            fn deref_mut(&mut self) -> &mut U {
+                // SAFETY: as in `deref`.
+                unsafe { self.data.as_mut() }
+            }
+        }
+
+        impl<T: ?Sized, U: ?Sized + fmt::Debug> fmt::Debug for $name<'_, T, U> {
+            fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Debug::fmt(&**self, fmt)
+            }
+        }
+    };
+}
+
+split_guard!(
+    /// A guard granting access to one of several disjoint parts of the value
+    /// protected by a [`Mutex`](crate::Mutex), returned by
+    /// [`MutexGuard::map_split`] and [`MutexGuard::split_at`].
+    ///
+    /// The mutex stays locked until every part is dropped. A part cannot be
+    /// sent to another thread, but a reference to what it guards can, for
+    /// example to a scoped thread.
+    SplitMutexGuard,
+    MutexGuard
+);
+
+split_guard!(
+    /// A guard granting access to one of several disjoint parts of the value
+    /// protected by an [`RwLock`](crate::RwLock), returned by
+    /// [`RwLockWriteGuard::map_split`] and [`RwLockWriteGuard::split_at`].
+    ///
+    /// The lock stays held until every part is dropped. A part cannot be sent
+    /// to another thread, but a reference to what it guards can, for example
+    /// to a scoped thread.
+    SplitRwLockWriteGuard,
+    RwLockWriteGuard
+);
+
+/// A guard granting shared access to one of several parts of the value
+/// protected by an [`RwLock`](crate::RwLock), returned by
+/// [`RwLockReadGuard::map_split`], [`RwLockReadGuard::split_at`] and
+/// [`RwLock::iter_read`].
+///
+/// The lock stays held for reading until every part is dropped. A part cannot
+/// be sent to another thread, but a reference to what it guards can, for
+/// example to a scoped thread.
+#[must_use]
+pub struct SplitRwLockReadGuard<'a, T: ?Sized + 'a, U: ?Sized + 'a> {
+    _guard: Rc<RwLockReadGuard<'a, T>>,
+    data: NonNull<U>,
+    _marker: PhantomData<&'a U>,
+}
+
+// SAFETY: the guard only hands out shared references to `U`, and the `Rc` is
+// never cloned through a shared reference.
+unsafe impl<T: ?Sized, U: ?Sized + Sync> Sync for SplitRwLockReadGuard<'_, T, U> {}
+
+impl<'a, T: ?Sized> RwLockReadGuard<'a, T> {
+    /// Makes two guards granting access to parts of the locked value,
+    /// selected by `f`.
+    ///
+    /// The lock stays held until both guards are dropped. This is an
+    /// associated function so that it does not shadow a method of `T`.
+    pub fn map_split<U: ?Sized, V: ?Sized>(
+        orig: RwLockReadGuard<'a, T>,
+        f: impl FnOnce(&T) -> (&U, &V),
+    ) -> (
+        SplitRwLockReadGuard<'a, T, U>,
+        SplitRwLockReadGuard<'a, T, V>,
+    ) {
+        let (u, v) = f(&*orig);
+        let (u, v) = (NonNull::from(u), NonNull::from(v));
+        let guard = Rc::new(orig);
+        (
+            SplitRwLockReadGuard::new(guard.clone(), u),
+            SplitRwLockReadGuard::new(guard, v),
+        )
+    }
+
+    /// Makes two guards granting access to the elements of the locked slice
+    /// before `mid` and from `mid` on.
+    ///
+    /// This is [`map_split`](Self::map_split) with `<[E]>::split_at`. It is an
+    /// associated function so that it does not shadow `<[E]>::split_at`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid` is larger than the length of the slice.
+    #[inline]
+    pub fn split_at<E>(
+        orig: RwLockReadGuard<'a, T>,
+        mid: usize,
+    ) -> (
+        SplitRwLockReadGuard<'a, T, [E]>,
+        SplitRwLockReadGuard<'a, T, [E]>,
+    )
+    where
+        T: AsRef<[E]>,
+    {
+        RwLockReadGuard::map_split(orig, |t| t.as_ref().split_at(mid))
+    }
+}
+
+impl<'a, T: ?Sized, U: ?Sized> SplitRwLockReadGuard<'a, T, U> {
+    #[inline]
+    fn new(guard: Rc<RwLockReadGuard<'a, T>>, data: NonNull<U>) -> SplitRwLockReadGuard<'a, T, U> {
+        SplitRwLockReadGuard {
+            _guard: guard,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Makes a guard granting access to a part of the part this guard grants
+    /// access to, selected by `f`.
+    #[inline]
+    pub fn map<V: ?Sized>(
+        orig: SplitRwLockReadGuard<'a, T, U>,
+        f: impl FnOnce(&U) -> &V,
+    ) -> SplitRwLockReadGuard<'a, T, V> {
+        let data = NonNull::from(f(&*orig));
+        SplitRwLockReadGuard::new(orig._guard, data)
+    }
+
+    /// Splits the part this guard grants access to into two further parts,
+    /// selected by `f`.
+    #[inline]
+    pub fn map_split<V: ?Sized, W: ?Sized>(
+        orig: SplitRwLockReadGuard<'a, T, U>,
+        f: impl FnOnce(&U) -> (&V, &W),
+    ) -> (
+        SplitRwLockReadGuard<'a, T, V>,
+        SplitRwLockReadGuard<'a, T, W>,
+    ) {
+        let (v, w) = f(&*orig);
+        let (v, w) = (NonNull::from(v), NonNull::from(w));
+        (
+            SplitRwLockReadGuard::new(orig._guard.clone(), v),
+            SplitRwLockReadGuard::new(orig._guard, w),
+        )
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> Deref for SplitRwLockReadGuard<'_, T, U> {
+    type Target = U;
+
+    #[inline]
+    fn deref(&self) -> &U {
+        // SAFETY: `data` was derived from the guard, which is still alive.
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized + fmt::Debug> fmt::Debug for SplitRwLockReadGuard<'_, T, U> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Acquires the mutex and returns an iterator over the elements of the
+    /// locked collection.
+    ///
+    /// Each element is yielded in a guard of its own, which keeps the mutex
+    /// locked along with the iterator, so that the references it hands out
+    /// cannot outlive the lock. The mutex is unlocked once the iterator and
+    /// every element it yielded are dropped.
+    // The mutable borrow is of the locked value, which the iterator holds.
+    #[allow(clippy::mut_from_ref)]
+    pub fn iter_locked<'a, E: ?Sized + 'a>(
+        &'a self,
+    ) -> IterLocked<'a, T, <&'a mut T as IntoIterator>::IntoIter>
+    where
+        &'a mut T: IntoIterator<Item = &'a mut E>,
+    {
+        let mut guard = self.lock();
+        let data: *mut T = &mut *guard;
+        let guard = Rc::new(guard);
+        // SAFETY: the value is locked for as long as `guard` is alive, and is
+        // only reached through the iterator and the elements it yields, which
+        // all keep `guard` alive.
+        let iter = unsafe { &mut *data }.into_iter();
+        IterLocked { iter, guard }
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Locks the lock for reading and returns an iterator over the elements
+    /// of the locked collection.
+    ///
+    /// Each element is yielded in a guard of its own, which keeps the lock
+    /// held along with the iterator, so that the references it hands out
+    /// cannot outlive the lock. The lock is released once the iterator and
+    /// every element it yielded are dropped.
+    pub fn iter_read<'a, E: ?Sized + 'a>(
+        &'a self,
+    ) -> IterRead<'a, T, <&'a T as IntoIterator>::IntoIter>
+    where
+        &'a T: IntoIterator<Item = &'a E>,
+    {
+        let guard = self.read();
+        let data: *const T = &*guard;
+        let guard = Rc::new(guard);
+        // SAFETY: as in `Mutex::iter_locked`.
+        let iter = unsafe { &*data }.into_iter();
+        IterRead { iter, guard }
+    }
+}
+
+/// An iterator over the elements of a collection in a [`Mutex`], returned by
+/// [`Mutex::iter_locked`].
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct IterLocked<'a, T: ?Sized + 'a, I> {
+    // Declared first so it is dropped before the guard.
+    iter: I,
+    guard: Rc<MutexGuard<'a, T>>,
+}
+
+impl<'a, T: ?Sized, E: ?Sized + 'a, I> Iterator for IterLocked<'a, T, I>
+where
+    I: Iterator<Item = &'a mut E>,
+{
+    type Item = SplitMutexGuard<'a, T, E>;
+
+    #[inline]
+    fn next(&mut self) -> Option<SplitMutexGuard<'a, T, E>> {
+        let e = self.iter.next()?;
+        Some(SplitMutexGuard::new(self.guard.clone(), NonNull::from(e)))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T: ?Sized, E: ?Sized + 'a, I> DoubleEndedIterator for IterLocked<'a, T, I>
+where
+    I: DoubleEndedIterator<Item = &'a mut E>,
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<SplitMutexGuard<'a, T, E>> {
+        let e = self.iter.next_back()?;
+        Some(SplitMutexGuard::new(self.guard.clone(), NonNull::from(e)))
+    }
+}
+
+impl<'a, T: ?Sized, E: ?Sized + 'a, I> ExactSizeIterator for IterLocked<'a, T, I> where
+    I: ExactSizeIterator<Item = &'a mut E>
+{
+}
+
+impl<'a, T: ?Sized, E: ?Sized + 'a, I> FusedIterator for IterLocked<'a, T, I> where
+    I: FusedIterator<Item = &'a mut E>
+{
+}
+
+impl<T: ?Sized, I> fmt::Debug for IterLocked<'_, T, I> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("IterLocked").finish_non_exhaustive()
+    }
+}
+
+/// An iterator over the elements of a collection in an [`RwLock`], returned
+/// by [`RwLock::iter_read`].
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct IterRead<'a, T: ?Sized + 'a, I> {
+    // Declared first so it is dropped before the guard.
+    iter: I,
+    guard: Rc<RwLockReadGuard<'a, T>>,
+}
+
+impl<'a, T: ?Sized, E: ?Sized + 'a, I> Iterator for IterRead<'a, T, I>
+where
+    I: Iterator<Item = &'a E>,
+{
+    type Item = SplitRwLockReadGuard<'a, T, E>;
+
+    #[inline]
+    fn next(&mut self) -> Option<SplitRwLockReadGuard<'a, T, E>> {
+        let e = self.iter.next()?;
+        Some(SplitRwLockReadGuard::new(
+            self.guard.clone(),
+            NonNull::from(e),
+        ))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T: ?Sized, E: ?Sized + 'a, I> DoubleEndedIterator for IterRead<'a, T, I>
+where
+    I: DoubleEndedIterator<Item = &'a E>,
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<SplitRwLockReadGuard<'a, T, E>> {
+        let e = self.iter.next_back()?;
+        Some(SplitRwLockReadGuard::new(
+            self.guard.clone(),
+            NonNull::from(e),
+        ))
+    }
+}
+
+impl<'a, T: ?Sized, E: ?Sized + 'a, I> ExactSizeIterator for IterRead<'a, T, I> where
+    I: ExactSizeIterator<Item = &'a E>
+{
+}
+
+impl<'a, T: ?Sized, E: ?Sized + 'a, I> FusedIterator for IterRead<'a, T, I> where
+    I: FusedIterator<Item = &'a E>
+{
+}
+
+impl<T: ?Sized, I> fmt::Debug for IterRead<'_, T, I> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("IterRead").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,48 @@
+use stable_deref_trait::StableDeref;
+
+use crate::{
+    poison::PoisonPolicy,
+    raw::{MutexStorage, RwLockBackend},
+    MappedMutexGuard, MappedRwLockReadGuard, MappedRwLockWriteGuard, MutexGuard, OwnedMutexGuard,
+    OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLockReadGuard, RwLockUpgradableReadGuard,
+    RwLockWriteGuard, SplitMutexGuard, SplitRwLockReadGuard, SplitRwLockWriteGuard,
+};
+
+// Every guard dereferences to the value inside its lock, or to part of it,
+// which does not move while the guard is alive, wherever the guard itself is
+// moved to.
+
+unsafe impl<T: ?Sized, P: PoisonPolicy, R: MutexStorage> StableDeref for MutexGuard<'_, T, P, R> {}
+
+unsafe impl<T: ?Sized, R: RwLockBackend> StableDeref for RwLockReadGuard<'_, T, R> {}
+
+unsafe impl<T: ?Sized> StableDeref for RwLockUpgradableReadGuard<'_, T> {}
+
+unsafe impl<T: ?Sized, R: RwLockBackend> StableDeref for RwLockWriteGuard<'_, T, R> {}
+
+unsafe impl<T: ?Sized, U: ?Sized, P: PoisonPolicy, R: MutexStorage> StableDeref
+    for MappedMutexGuard<'_, T, U, P, R>
+{
+}
+
+unsafe impl<T: ?Sized, U: ?Sized, R: RwLockBackend> StableDeref
+    for MappedRwLockReadGuard<'_, T, U, R>
+{
+}
+
+unsafe impl<T: ?Sized, U: ?Sized, R: RwLockBackend> StableDeref
+    for MappedRwLockWriteGuard<'_, T, U, R>
+{
+}
+
+unsafe impl<T: ?Sized, U: ?Sized> StableDeref for SplitMutexGuard<'_, T, U> {}
+
+unsafe impl<T: ?Sized, U: ?Sized> StableDeref for SplitRwLockReadGuard<'_, T, U> {}
+
+unsafe impl<T: ?Sized, U: ?Sized> StableDeref for SplitRwLockWriteGuard<'_, T, U> {}
+
+unsafe impl<T: ?Sized + 'static> StableDeref for OwnedMutexGuard<T> {}
+
+unsafe impl<T: ?Sized + 'static> StableDeref for OwnedRwLockReadGuard<T> {}
+
+unsafe impl<T: ?Sized + 'static> StableDeref for OwnedRwLockWriteGuard<T> {}
@@ -0,0 +1,169 @@
+//! Per-lock contention statistics.
+//!
+//! With the `stats` feature enabled, every `Mutex` and `RwLock` keeps a few
+//! atomic counters of its own: how often it was acquired, how often an
+//! acquisition had to block, how long those acquisitions waited in total and
+//! the longest the lock was then held. They are read with
+//! [`Mutex::stats`] and [`RwLock::stats`]. Shared and exclusive acquisitions of
+//! an `RwLock` are counted together, and failed `try_` acquisitions are not
+//! counted at all.
+//!
+//! Recording adds a few uncontended atomic operations to every acquisition and
+//! release, and makes each lock at least 32 bytes larger.
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{
+    poison::PoisonPolicy,
+    raw::{MutexStorage, RwLockBackend},
+    Mutex, RwLock,
+};
+
+/// The counters kept by each lock.
+pub(crate) struct Counters {
+    acquisitions: AtomicU64,
+    contended: AtomicU64,
+    wait_ns: AtomicU64,
+    max_hold_ns: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) const fn new() -> Counters {
+        Counters {
+            acquisitions: AtomicU64::new(0),
+            contended: AtomicU64::new(0),
+            wait_ns: AtomicU64::new(0),
+            max_hold_ns: AtomicU64::new(0),
+        }
+    }
+
+    /// Records an acquisition, which blocked for `waited` if it is `Some`.
+    #[inline]
+    pub(crate) fn acquired(&self, waited: Option<Duration>) {
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        if let Some(waited) = waited {
+            self.contended.fetch_add(1, Ordering::Relaxed);
+            self.wait_ns.fetch_add(nanos(waited), Ordering::Relaxed);
+        }
+    }
+
+    /// Records a release after the lock was held for `held`.
+    #[inline]
+    pub(crate) fn released(&self, held: Duration) {
+        let held = nanos(held);
+        // Most holds are shorter than the longest, so avoid writing when
+        // nothing changes.
+        if held > self.max_hold_ns.load(Ordering::Relaxed) {
+            self.max_hold_ns.fetch_max(held, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self) -> LockStats {
+        LockStats {
+            acquisitions: self.acquisitions.load(Ordering::Relaxed),
+            contended: self.contended.load(Ordering::Relaxed),
+            total_wait: Duration::from_nanos(self.wait_ns.load(Ordering::Relaxed)),
+            max_hold: Duration::from_nanos(self.max_hold_ns.load(Ordering::Relaxed)),
+        }
+    }
+
+    fn reset(&self) {
+        self.acquisitions.store(0, Ordering::Relaxed);
+        self.contended.store(0, Ordering::Relaxed);
+        self.wait_ns.store(0, Ordering::Relaxed);
+        self.max_hold_ns.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Default for Counters {
+    #[inline]
+    fn default() -> Counters {
+        Counters::new()
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos().try_into().unwrap_or(u64::MAX)
+}
+
+/// A snapshot of the statistics of a lock.
+///
+/// The counters are read one at a time while other threads may keep using the
+/// lock, so they are not necessarily consistent with each other.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct LockStats {
+    acquisitions: u64,
+    contended: u64,
+    total_wait: Duration,
+    max_hold: Duration,
+}
+
+impl LockStats {
+    /// Returns the number of times the lock was acquired.
+    pub fn acquisitions(&self) -> u64 {
+        self.acquisitions
+    }
+
+    /// Returns the number of acquisitions which had to block.
+    pub fn contended(&self) -> u64 {
+        self.contended
+    }
+
+    /// Returns the total time acquisitions spent blocked on the lock.
+    pub fn total_wait(&self) -> Duration {
+        self.total_wait
+    }
+
+    /// Returns the longest time the lock was held, by a guard which has since
+    /// been dropped.
+    pub fn max_hold(&self) -> Duration {
+        self.max_hold
+    }
+}
+
+impl fmt::Debug for LockStats {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("LockStats")
+            .field("acquisitions", &self.acquisitions)
+            .field("contended", &self.contended)
+            .field("total_wait", &self.total_wait)
+            .field("max_hold", &self.max_hold)
+            .finish()
+    }
+}
+
+impl<T: ?Sized, P: PoisonPolicy, R: MutexStorage> Mutex<T, P, R> {
+    /// Returns the statistics recorded for the mutex so far.
+    #[cfg_attr(docsrs, doc(cfg(feature = "stats")))]
+    #[inline]
+    pub fn stats(&self) -> LockStats {
+        self.instrumentation.stats.snapshot()
+    }
+
+    /// Resets the statistics of the mutex to zero.
+    #[cfg_attr(docsrs, doc(cfg(feature = "stats")))]
+    #[inline]
+    pub fn reset_stats(&self) {
+        self.instrumentation.stats.reset();
+    }
+}
+
+impl<T: ?Sized, R: RwLockBackend> RwLock<T, R> {
+    /// Returns the statistics recorded for the lock so far.
+    #[cfg_attr(docsrs, doc(cfg(feature = "stats")))]
+    #[inline]
+    pub fn stats(&self) -> LockStats {
+        self.instrumentation.stats.snapshot()
+    }
+
+    /// Resets the statistics of the lock to zero.
+    #[cfg_attr(docsrs, doc(cfg(feature = "stats")))]
+    #[inline]
+    pub fn reset_stats(&self) {
+        self.instrumentation.stats.reset();
+    }
+}
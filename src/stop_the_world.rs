@@ -0,0 +1,176 @@
+//! Pausing a set of worker threads at safe points.
+//!
+//! Worker threads register with a [`StopTheWorld`] and periodically call
+//! [`Worker::checkpoint`] at points where they hold no locks and the shared
+//! state they touch is consistent. A coordinator calls
+//! [`StopTheWorld::pause_all`], which blocks until every registered worker is
+//! parked in a checkpoint, and keeps them there until the returned [`Paused`]
+//! guard is dropped or [`Paused::resume`] is called.
+//!
+//! A worker's checkpoint only blocks while a pause is requested; otherwise it
+//! costs a single atomic load.
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{Condvar, Mutex};
+
+/// A coordinator for pausing every registered worker thread at once.
+#[derive(Default)]
+pub struct StopTheWorld {
+    // Only modified with `state` locked, but read without it by checkpoints.
+    pausing: AtomicBool,
+    state: Mutex<State>,
+    // Signalled when workers arrive at a checkpoint or deregister.
+    arrived: Condvar,
+    // Signalled when a pause ends.
+    resumed: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    workers: usize,
+    parked: usize,
+    // Incremented on every resume.
+    generation: u64,
+}
+
+impl StopTheWorld {
+    /// Creates a new `StopTheWorld` with no registered workers.
+    pub const fn new() -> StopTheWorld {
+        StopTheWorld {
+            pausing: AtomicBool::new(false),
+            state: Mutex::new(State {
+                workers: 0,
+                parked: 0,
+                generation: 0,
+            }),
+            arrived: Condvar::new(),
+            resumed: Condvar::new(),
+        }
+    }
+
+    /// Registers the current thread as a worker.
+    ///
+    /// If the world is paused, this blocks until it resumes. The worker is
+    /// deregistered when the returned handle is dropped.
+    pub fn register(&self) -> Worker<'_> {
+        let mut state = self.state.lock();
+        while self.pausing.load(Ordering::Relaxed) {
+            state = self.resumed.wait(state);
+        }
+        state.workers += 1;
+        Worker { stw: self }
+    }
+
+    /// Blocks until every registered worker is parked in a checkpoint, and
+    /// keeps them parked until the returned guard is dropped.
+    ///
+    /// If another pause is in progress, this first waits for it to end. The
+    /// calling thread must not itself be a registered worker of this
+    /// `StopTheWorld`, or it will wait for itself forever.
+    pub fn pause_all(&self) -> Paused<'_> {
+        let mut state = self.state.lock();
+        while self.pausing.load(Ordering::Relaxed) {
+            state = self.resumed.wait(state);
+        }
+        self.pausing.store(true, Ordering::Relaxed);
+        while state.parked < state.workers {
+            state = self.arrived.wait(state);
+        }
+        Paused { stw: self }
+    }
+
+    /// Pauses every registered worker, calls `f`, then resumes them.
+    pub fn with_paused<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _paused = self.pause_all();
+        f()
+    }
+
+    fn resume(&self) {
+        let mut state = self.state.lock();
+        state.parked = 0;
+        state.generation += 1;
+        self.pausing.store(false, Ordering::Relaxed);
+        self.resumed.notify_all();
+    }
+}
+
+impl fmt::Debug for StopTheWorld {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("StopTheWorld")
+            .field("pausing", &self.pausing.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+/// A worker thread's registration with a [`StopTheWorld`].
+///
+/// Created by [`StopTheWorld::register`].
+#[derive(Debug)]
+pub struct Worker<'a> {
+    stw: &'a StopTheWorld,
+}
+
+impl Worker<'_> {
+    /// Parks the current thread for as long as a pause is in progress.
+    ///
+    /// This must be called regularly, and only at points where the worker
+    /// holds no locks the coordinator may need.
+    #[inline]
+    pub fn checkpoint(&self) {
+        if self.stw.pausing.load(Ordering::Relaxed) {
+            self.park();
+        }
+    }
+
+    #[cold]
+    fn park(&self) {
+        let stw = self.stw;
+        let mut state = stw.state.lock();
+        if !stw.pausing.load(Ordering::Relaxed) {
+            return;
+        }
+        let generation = state.generation;
+        state.parked += 1;
+        stw.arrived.notify_all();
+        while state.generation == generation {
+            state = stw.resumed.wait(state);
+        }
+    }
+}
+
+impl Drop for Worker<'_> {
+    fn drop(&mut self) {
+        let mut state = self.stw.state.lock();
+        state.workers -= 1;
+        if self.stw.pausing.load(Ordering::Relaxed) {
+            self.stw.arrived.notify_all();
+        }
+    }
+}
+
+/// A guard which keeps every worker of a [`StopTheWorld`] parked.
+///
+/// Created by [`StopTheWorld::pause_all`].
+#[derive(Debug)]
+#[must_use]
+pub struct Paused<'a> {
+    stw: &'a StopTheWorld,
+}
+
+impl Paused<'_> {
+    /// Resumes the workers.
+    ///
+    /// This is equivalent to dropping the guard.
+    #[inline]
+    pub fn resume(self) {}
+}
+
+impl Drop for Paused<'_> {
+    fn drop(&mut self) {
+        self.stw.resume();
+    }
+}
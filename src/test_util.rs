@@ -0,0 +1,1060 @@
+//! Utilities for testing code built on this crate.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    fmt,
+    fs::File,
+    hash::{Hash, Hasher},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::{
+        self,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::Clock, Condvar, Lock, LockOperation, Mutex, MutexGuard, TryLockError, TryLockResult,
+    WaitTimeoutResult,
+};
+
+thread_local! {
+    static INSTALLED: RefCell<Option<MockClock>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn installed_clock() -> Option<MockClock> {
+    INSTALLED
+        .try_with(|installed| installed.borrow().clone())
+        .ok()
+        .flatten()
+}
+
+/// A manually advanced clock for timed operations.
+///
+/// Once installed on a thread, every timed operation of this crate performed by
+/// that thread,
+/// such as [`Condvar::wait_timeout`](crate::Condvar::wait_timeout), measures
+/// time with this clock instead of the system clock. Time only moves when
+/// [`MockClock::advance`] is called, so tests of timeout behavior run instantly
+/// and deterministically.
+///
+/// A clock is installed per thread; clones share the same time and can be
+/// installed on several threads. The [`lease`](crate::lease) watchdog always
+/// uses the system clock.
+#[derive(Debug, Clone)]
+pub struct MockClock(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    base: Instant,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    elapsed: Duration,
+    sleepers: Vec<Thread>,
+}
+
+impl MockClock {
+    /// Creates a new clock, starting at the current system time.
+    pub fn new() -> MockClock {
+        MockClock(Arc::new(Inner {
+            base: Instant::now(),
+            state: Mutex::new(State {
+                elapsed: Duration::ZERO,
+                sleepers: vec![],
+            }),
+        }))
+    }
+
+    /// Installs the clock on the current thread until the returned guard is
+    /// dropped.
+    pub fn install(&self) -> MockClockGuard {
+        let previous = INSTALLED.with(|installed| installed.replace(Some(self.clone())));
+        MockClockGuard { previous }
+    }
+
+    /// Returns the clock's current time.
+    pub fn now(&self) -> Instant {
+        self.0.base + self.0.state.lock().elapsed
+    }
+
+    /// Moves the clock forward, waking any threads whose timed operations may
+    /// have expired.
+    pub fn advance(&self, dur: Duration) {
+        let sleepers = {
+            let mut state = self.0.state.lock();
+            state.elapsed += dur;
+            std::mem::take(&mut state.sleepers)
+        };
+        for thread in sleepers {
+            thread.unpark();
+        }
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> MockClock {
+        MockClock::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        MockClock::now(self)
+    }
+
+    fn park_until(&self, deadline: Instant) {
+        {
+            // Registering under the same lock `advance` takes means no advance can be
+            // missed.
+            let mut state = self.0.state.lock();
+            if self.0.base + state.elapsed >= deadline {
+                return;
+            }
+            state.sleepers.push(thread::current());
+        }
+        thread::park();
+    }
+}
+
+/// Restores the previously installed clock when dropped.
+///
+/// Created by [`MockClock::install`].
+#[must_use]
+#[derive(Debug)]
+pub struct MockClockGuard {
+    previous: Option<MockClock>,
+}
+
+impl Drop for MockClockGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        let _ = INSTALLED.try_with(|installed| *installed.borrow_mut() = previous);
+    }
+}
+
+static CHAOS_ACTIVE: AtomicBool = AtomicBool::new(false);
+static CHAOS_GENERATION: AtomicU64 = AtomicU64::new(0);
+// The crate's own locks would inject delays into themselves.
+static CHAOS: sync::RwLock<Option<ActiveChaos>> = sync::RwLock::new(None);
+
+thread_local! {
+    // The generation of the chaos configuration the stream was seeded for, and
+    // the stream's state.
+    static CHAOS_STREAM: Cell<(u64, u64)> = const { Cell::new((0, 0)) };
+}
+
+struct ActiveChaos {
+    chaos: Chaos,
+    generation: u64,
+}
+
+/// Randomized delays at lock boundaries, to widen race windows.
+///
+/// While installed, every attempt to acquire a `Mutex` or `RwLock` may be
+/// preceded, and every release followed, by a yield or a short sleep of the
+/// calling thread. Interleavings which would otherwise be vanishingly rare
+/// then come up routinely, so concurrency bugs surface in tests rather than in
+/// production.
+///
+/// The delays are drawn from the seed, with each thread drawing from its own
+/// stream derived from the seed and the thread's name. A run performing the
+/// same sequence of lock operations on each thread therefore gets the same
+/// delays; threads should be named for this to hold across runs, since
+/// unnamed threads are told apart by their `ThreadId`.
+#[derive(Debug, Clone)]
+pub struct Chaos {
+    seed: u64,
+    probability: f64,
+    max_delay: Duration,
+}
+
+impl Chaos {
+    /// Creates a new chaos configuration with the given seed.
+    ///
+    /// By default, one lock boundary in ten is delayed, by up to a
+    /// millisecond.
+    pub fn new(seed: u64) -> Chaos {
+        Chaos {
+            seed,
+            probability: 0.1,
+            max_delay: Duration::from_millis(1),
+        }
+    }
+
+    /// Sets the probability that a lock boundary is delayed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `probability` is not between 0 and 1.
+    pub fn probability(mut self, probability: f64) -> Chaos {
+        assert!(
+            (0.0..=1.0).contains(&probability),
+            "probability must be between 0 and 1"
+        );
+        self.probability = probability;
+        self
+    }
+
+    /// Sets the longest a delay may sleep for.
+    ///
+    /// Half of the delays only yield instead of sleeping.
+    pub fn max_delay(mut self, max_delay: Duration) -> Chaos {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Returns the seed.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Injects delays on every thread until the returned guard is dropped.
+    ///
+    /// Only one configuration is active at a time; installing one replaces the
+    /// one installed before.
+    pub fn install(&self) -> ChaosGuard {
+        let generation = CHAOS_GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+        *CHAOS.write().unwrap_or_else(|e| e.into_inner()) = Some(ActiveChaos {
+            chaos: self.clone(),
+            generation,
+        });
+        CHAOS_ACTIVE.store(true, Ordering::Release);
+        ChaosGuard { generation }
+    }
+}
+
+/// Stops injecting delays when dropped.
+///
+/// Created by [`Chaos::install`].
+#[must_use]
+#[derive(Debug)]
+pub struct ChaosGuard {
+    generation: u64,
+}
+
+impl Drop for ChaosGuard {
+    fn drop(&mut self) {
+        let mut chaos = CHAOS.write().unwrap_or_else(|e| e.into_inner());
+        if chaos.as_ref().map(|active| active.generation) == Some(self.generation) {
+            CHAOS_ACTIVE.store(false, Ordering::Relaxed);
+            *chaos = None;
+        }
+    }
+}
+
+#[inline]
+pub(crate) fn before_acquire() {
+    if CHAOS_ACTIVE.load(Ordering::Relaxed) {
+        inject_chaos();
+    }
+    if TRACE_ACTIVE.load(Ordering::Relaxed) {
+        wait_for_turn();
+    }
+}
+
+#[inline]
+pub(crate) fn acquired(addr: usize) {
+    if TRACE_ACTIVE.load(Ordering::Relaxed) {
+        trace_event(EventKind::Acquire, addr);
+    }
+}
+
+#[inline]
+pub(crate) fn acquire_failed(addr: usize) {
+    if TRACE_ACTIVE.load(Ordering::Relaxed) {
+        trace_event(EventKind::Fail, addr);
+    }
+}
+
+#[inline]
+pub(crate) fn released(addr: usize) {
+    if TRACE_ACTIVE.load(Ordering::Relaxed) {
+        wait_for_turn();
+        trace_event(EventKind::Release, addr);
+    }
+    if CHAOS_ACTIVE.load(Ordering::Relaxed) {
+        inject_chaos();
+    }
+}
+
+#[cold]
+fn inject_chaos() {
+    let delay = {
+        let chaos = CHAOS.read().unwrap_or_else(|e| e.into_inner());
+        let active = match &*chaos {
+            Some(active) => active,
+            None => return,
+        };
+        let draw = match CHAOS_STREAM.try_with(|stream| {
+            let (generation, mut state) = stream.get();
+            if generation != active.generation {
+                state = stream_seed(active.chaos.seed);
+            }
+            let draw = (splitmix64(&mut state), splitmix64(&mut state));
+            stream.set((active.generation, state));
+            draw
+        }) {
+            Ok(draw) => draw,
+            Err(_) => return,
+        };
+
+        // Map the draw onto [0, 1) using its top 53 bits.
+        if (draw.0 >> 11) as f64 / (1u64 << 53) as f64 >= active.chaos.probability {
+            return;
+        }
+        if draw.1 & 1 == 0 {
+            None
+        } else {
+            let max = active.chaos.max_delay.as_nanos().min(u64::MAX as u128) as u64;
+            Some(Duration::from_nanos((draw.1 >> 1) % max.saturating_add(1)))
+        }
+    };
+
+    match delay {
+        None => thread::yield_now(),
+        Some(delay) => thread::sleep(delay),
+    }
+}
+
+fn stream_seed(seed: u64) -> u64 {
+    // `DefaultHasher::new` always uses the same keys, so streams are stable
+    // across runs.
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    let thread = thread::current();
+    match thread.name() {
+        Some(name) => name.hash(&mut hasher),
+        None => thread.id().hash(&mut hasher),
+    }
+    hasher.finish()
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+static TRACE_ACTIVE: AtomicBool = AtomicBool::new(false);
+static TRACE: sync::Mutex<Option<Session>> = sync::Mutex::new(None);
+static TRACE_TURN: sync::Condvar = sync::Condvar::new();
+
+// How long a replaying thread waits for its turn before the replay is
+// considered to have diverged.
+const REPLAY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EventKind {
+    Acquire,
+    Release,
+    Fail,
+}
+
+impl EventKind {
+    fn tag(self) -> char {
+        match self {
+            EventKind::Acquire => 'a',
+            EventKind::Release => 'r',
+            EventKind::Fail => 'f',
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            EventKind::Acquire => "acquire",
+            EventKind::Release => "release",
+            EventKind::Fail => "fail to acquire",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Event {
+    kind: EventKind,
+    thread: u32,
+    lock: u32,
+}
+
+/// A recorded sequence of lock acquisitions and releases.
+///
+/// A trace is recorded with a [`TraceRecorder`], can be saved to and loaded
+/// from a file, and is reproduced with [`Trace::replay`].
+///
+/// Locks are identified by the order in which they were first used, and
+/// threads by their names. Only named threads take part in recording and
+/// replaying, and their names must be unique. Every named thread in the
+/// process is traced, so tests using traces should not run in parallel with
+/// other tests taking locks on named threads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    threads: Vec<String>,
+    events: Vec<Event>,
+}
+
+impl Trace {
+    /// Returns the number of recorded events.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns `true` if no events were recorded.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Returns the names of the threads taking part in the trace.
+    pub fn threads(&self) -> impl Iterator<Item = &str> {
+        self.threads.iter().map(|name| &**name)
+    }
+
+    /// Writes the trace in its text format.
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = BufWriter::new(writer);
+        writeln!(writer, "antidote-trace 1")?;
+        for name in &self.threads {
+            if name.contains('\n') {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "thread name contains a newline",
+                ));
+            }
+            writeln!(writer, "t {}", name)?;
+        }
+        for event in &self.events {
+            writeln!(
+                writer,
+                "{} {} {}",
+                event.kind.tag(),
+                event.thread,
+                event.lock
+            )?;
+        }
+        writer.flush()
+    }
+
+    /// Reads a trace written by [`Trace::write_to`].
+    pub fn read_from<R: Read>(reader: R) -> io::Result<Trace> {
+        fn invalid(msg: &str) -> io::Error {
+            io::Error::new(io::ErrorKind::InvalidData, msg)
+        }
+
+        let mut lines = BufReader::new(reader).lines();
+        match lines.next().transpose()? {
+            Some(header) if header == "antidote-trace 1" => {}
+            _ => return Err(invalid("not an antidote trace")),
+        }
+        let mut trace = Trace {
+            threads: vec![],
+            events: vec![],
+        };
+        for line in lines {
+            let line = line?;
+            if let Some(name) = line.strip_prefix("t ") {
+                trace.threads.push(name.to_string());
+                continue;
+            }
+            let mut fields = line.split(' ');
+            let kind = match fields.next() {
+                Some("a") => EventKind::Acquire,
+                Some("r") => EventKind::Release,
+                Some("f") => EventKind::Fail,
+                _ => return Err(invalid("invalid trace event")),
+            };
+            let mut number = || -> io::Result<u32> {
+                fields
+                    .next()
+                    .and_then(|field| field.parse().ok())
+                    .ok_or_else(|| invalid("invalid trace event"))
+            };
+            let event = Event {
+                kind,
+                thread: number()?,
+                lock: number()?,
+            };
+            if event.thread as usize >= trace.threads.len() {
+                return Err(invalid("trace event refers to an unknown thread"));
+            }
+            trace.events.push(event);
+        }
+        Ok(trace)
+    }
+
+    /// Saves the trace to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.write_to(File::create(path)?)
+    }
+
+    /// Loads a trace from a file.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Trace> {
+        Trace::read_from(File::open(path)?)
+    }
+
+    /// Starts replaying the trace.
+    ///
+    /// Until the returned guard is dropped, each lock operation of a thread
+    /// taking part in the trace blocks until every operation recorded before
+    /// it has happened, so the threads acquire and release locks in exactly
+    /// the recorded order. If the threads do something other than what was
+    /// recorded, replay stops enforcing the order and
+    /// [`Replay::finish`] reports the divergence.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a trace is already being recorded or replayed.
+    pub fn replay(&self) -> Replay {
+        start_session(Session {
+            replay: true,
+            threads: self.threads.clone(),
+            events: self.events.clone(),
+            locks: HashMap::new(),
+            cursor: 0,
+            diverged: None,
+        });
+        Replay { _private: () }
+    }
+}
+
+/// Records a [`Trace`] of the lock operations of every named thread.
+#[derive(Debug)]
+pub struct TraceRecorder {
+    _private: (),
+}
+
+impl TraceRecorder {
+    /// Starts recording.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a trace is already being recorded or replayed.
+    pub fn start() -> TraceRecorder {
+        start_session(Session {
+            replay: false,
+            threads: vec![],
+            events: vec![],
+            locks: HashMap::new(),
+            cursor: 0,
+            diverged: None,
+        });
+        TraceRecorder { _private: () }
+    }
+
+    /// Stops recording, returning the trace.
+    pub fn finish(self) -> Trace {
+        let session = end_session();
+        Trace {
+            threads: session.threads,
+            events: session.events,
+        }
+    }
+}
+
+impl Drop for TraceRecorder {
+    fn drop(&mut self) {
+        if TRACE_ACTIVE.load(Ordering::Relaxed) {
+            end_session();
+        }
+    }
+}
+
+/// A trace being replayed.
+///
+/// Created by [`Trace::replay`]. Replay stops when the guard is dropped.
+#[derive(Debug)]
+pub struct Replay {
+    _private: (),
+}
+
+impl Replay {
+    /// Stops replaying, checking that the trace was reproduced in full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the threads diverged from the trace, or if not every recorded
+    /// event happened.
+    #[track_caller]
+    pub fn finish(self) {
+        let session = end_session();
+        if let Some(divergence) = session.diverged {
+            panic!("replay diverged from the trace: {}", divergence);
+        }
+        assert!(
+            session.cursor == session.events.len(),
+            "replay ended after {} of {} recorded events",
+            session.cursor,
+            session.events.len(),
+        );
+    }
+}
+
+impl Drop for Replay {
+    fn drop(&mut self) {
+        if TRACE_ACTIVE.load(Ordering::Relaxed) {
+            end_session();
+        }
+    }
+}
+
+struct Session {
+    replay: bool,
+    threads: Vec<String>,
+    events: Vec<Event>,
+    // Lock addresses of this run, mapped to their identifiers in the trace.
+    locks: HashMap<usize, u32>,
+    cursor: usize,
+    diverged: Option<String>,
+}
+
+impl Session {
+    fn thread(&self, name: &str) -> Option<u32> {
+        self.threads
+            .iter()
+            .position(|thread| thread == name)
+            .map(|idx| idx as u32)
+    }
+
+    fn lock(&mut self, addr: usize) -> u32 {
+        let next = self.locks.len() as u32;
+        *self.locks.entry(addr).or_insert(next)
+    }
+
+    fn enforcing(&self) -> bool {
+        self.replay && self.diverged.is_none()
+    }
+
+    fn describe(&self, event: &Event) -> String {
+        format!(
+            "thread `{}` to {} lock {}",
+            self.threads[event.thread as usize],
+            event.kind.describe(),
+            event.lock
+        )
+    }
+}
+
+fn trace_session() -> sync::MutexGuard<'static, Option<Session>> {
+    TRACE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn start_session(session: Session) {
+    let mut current = trace_session();
+    assert!(
+        current.is_none(),
+        "a trace is already being recorded or replayed"
+    );
+    *current = Some(session);
+    TRACE_ACTIVE.store(true, Ordering::Release);
+}
+
+fn end_session() -> Session {
+    let mut current = trace_session();
+    TRACE_ACTIVE.store(false, Ordering::Relaxed);
+    let session = current
+        .take()
+        .expect("no trace is being recorded or replayed");
+    drop(current);
+    // Release any thread still waiting for its turn.
+    TRACE_TURN.notify_all();
+    session
+}
+
+#[cold]
+fn wait_for_turn() {
+    let thread = thread::current();
+    let name = match thread.name() {
+        Some(name) => name,
+        None => return,
+    };
+    let mut current = trace_session();
+    let deadline = Instant::now() + REPLAY_TIMEOUT;
+    loop {
+        let session = match &mut *current {
+            Some(session) if session.enforcing() => session,
+            _ => return,
+        };
+        let me = match session.thread(name) {
+            Some(me) => me,
+            None => return,
+        };
+        let expected = match session.events.get(session.cursor) {
+            Some(expected) => *expected,
+            None => {
+                session.diverged = Some(format!(
+                    "thread `{}` used a lock after the last recorded event",
+                    name
+                ));
+                return;
+            }
+        };
+        if expected.thread == me {
+            return;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            session.diverged = Some(format!(
+                "thread `{}` timed out waiting for {}",
+                name,
+                session.describe(&expected)
+            ));
+            drop(current);
+            TRACE_TURN.notify_all();
+            return;
+        }
+        current = TRACE_TURN
+            .wait_timeout(current, deadline - now)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+    }
+}
+
+#[cold]
+fn trace_event(kind: EventKind, addr: usize) {
+    let thread = thread::current();
+    let name = match thread.name() {
+        Some(name) => name,
+        None => return,
+    };
+    let mut current = trace_session();
+    let session = match &mut *current {
+        Some(session) => session,
+        None => return,
+    };
+
+    if !session.replay {
+        let thread = session.thread(name).unwrap_or_else(|| {
+            session.threads.push(name.to_string());
+            session.threads.len() as u32 - 1
+        });
+        let lock = session.lock(addr);
+        session.events.push(Event { kind, thread, lock });
+        return;
+    }
+
+    if !session.enforcing() {
+        return;
+    }
+    let thread = match session.thread(name) {
+        Some(thread) => thread,
+        None => return,
+    };
+    let lock = session.lock(addr);
+    let actual = Event { kind, thread, lock };
+    match session.events.get(session.cursor) {
+        Some(expected) if *expected == actual => session.cursor += 1,
+        Some(expected) => {
+            session.diverged = Some(format!(
+                "expected {}, but it tried to {} lock {}",
+                session.describe(expected),
+                kind.describe(),
+                lock
+            ));
+        }
+        None => {
+            session.diverged = Some(format!(
+                "thread `{}` used a lock after the last recorded event",
+                name
+            ));
+        }
+    }
+    drop(current);
+    TRACE_TURN.notify_all();
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for event in &self.events {
+            writeln!(
+                fmt,
+                "{} {} lock {}",
+                self.threads[event.thread as usize],
+                event.kind.describe(),
+                event.lock
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Faults for [`FaultyMutex`] and [`FaultyCondvar`] to inject, scripted by a
+/// test.
+///
+/// Each kind of fault is queued on its own, and injected into the next
+/// operation it applies to on any lock or condition variable sharing the
+/// script, whichever thread performs it. Clones share the same script, so a
+/// test keeps one to add faults while the code under test runs.
+///
+/// Delays are spent on the current thread's clock: they advance a
+/// [`MockClock`] installed on the thread, and sleep otherwise. With a mock
+/// clock, a test of timeout and retry paths therefore runs instantly and
+/// takes the same path every time.
+#[derive(Debug, Clone, Default)]
+pub struct Faults(Arc<Mutex<Script>>);
+
+#[derive(Debug, Default)]
+struct Script {
+    failed_tries: usize,
+    delays: VecDeque<Duration>,
+    wakeups: usize,
+}
+
+impl Faults {
+    /// Creates a new script without any faults.
+    pub fn new() -> Faults {
+        Faults::default()
+    }
+
+    /// Makes the next `n` attempts to acquire a lock without blocking, or
+    /// within a timeout, fail.
+    ///
+    /// A timed attempt fails once its whole timeout has been spent.
+    pub fn fail_tries(&self, n: usize) -> &Faults {
+        self.0.lock().failed_tries += n;
+        self
+    }
+
+    /// Delays the next blocking or timed acquisition of a lock by `delay`, as
+    /// if another thread held the lock for that long.
+    ///
+    /// A timed acquisition whose timeout is no longer than the delay fails once
+    /// its whole timeout has been spent. Attempts which do not block are not
+    /// delayed.
+    pub fn delay_acquisition(&self, delay: Duration) -> &Faults {
+        self.0.lock().delays.push_back(delay);
+        self
+    }
+
+    /// Makes the next `n` waits on a condition variable wake up spuriously,
+    /// right after releasing the lock.
+    pub fn spurious_wakeups(&self, n: usize) -> &Faults {
+        self.0.lock().wakeups += n;
+        self
+    }
+
+    /// Returns `true` if every fault scripted so far has been injected.
+    pub fn is_exhausted(&self) -> bool {
+        let script = self.0.lock();
+        script.failed_tries == 0 && script.delays.is_empty() && script.wakeups == 0
+    }
+
+    /// Discards the faults which have not been injected yet.
+    pub fn clear(&self) {
+        *self.0.lock() = Script::default();
+    }
+
+    fn take_failed_try(&self) -> bool {
+        take_one(&mut self.0.lock().failed_tries)
+    }
+
+    fn take_delay(&self) -> Option<Duration> {
+        self.0.lock().delays.pop_front()
+    }
+
+    fn take_wakeup(&self) -> bool {
+        take_one(&mut self.0.lock().wakeups)
+    }
+}
+
+fn take_one(count: &mut usize) -> bool {
+    match count.checked_sub(1) {
+        Some(rest) => {
+            *count = rest;
+            true
+        }
+        None => false,
+    }
+}
+
+// Lets `dur` pass on the current thread's clock.
+fn spend(dur: Duration) {
+    match installed_clock() {
+        Some(clock) => clock.advance(dur),
+        None => thread::sleep(dur),
+    }
+}
+
+/// A [`Mutex`] whose acquisitions fail or are delayed as scripted by
+/// [`Faults`].
+///
+/// It hands out the guards of the `Mutex` underneath, so a [`FaultyCondvar`],
+/// or a plain [`Condvar`], waits on them as usual. Code written against the
+/// [`Lock`] trait takes it in place of a `Mutex`.
+pub struct FaultyMutex<T: ?Sized> {
+    faults: Faults,
+    inner: Mutex<T>,
+}
+
+impl<T> FaultyMutex<T> {
+    /// Creates a new unlocked mutex injecting the faults of `faults`.
+    pub fn new(t: T, faults: &Faults) -> FaultyMutex<T> {
+        FaultyMutex {
+            faults: faults.clone(),
+            inner: Mutex::new(t),
+        }
+    }
+
+    /// Consumes the mutex, returning the value.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: ?Sized> FaultyMutex<T> {
+    /// Like [`Mutex::lock`], after the scripted delay if there is one.
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        if let Some(delay) = self.faults.take_delay() {
+            spend(delay);
+        }
+        self.inner.lock()
+    }
+
+    /// Like [`Mutex::try_lock`], unless a failure is scripted.
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        if self.faults.take_failed_try() {
+            return Err(TryLockError::new(LockOperation::Lock));
+        }
+        self.inner.try_lock()
+    }
+
+    /// Like [`Mutex::try_lock_for`], after the scripted delay if there is one,
+    /// unless a failure is scripted.
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn try_lock_for(&self, timeout: Duration) -> Option<MutexGuard<'_, T>> {
+        if self.faults.take_failed_try() {
+            spend(timeout);
+            return None;
+        }
+        match self.faults.take_delay() {
+            Some(delay) if delay >= timeout => {
+                spend(timeout);
+                None
+            }
+            Some(delay) => {
+                spend(delay);
+                self.inner.try_lock_for(timeout - delay)
+            }
+            None => self.inner.try_lock_for(timeout),
+        }
+    }
+
+    /// Returns the script of the faults the mutex injects.
+    pub fn faults(&self) -> &Faults {
+        &self.faults
+    }
+
+    /// Like [`Mutex::get_mut`].
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+}
+
+impl<T: ?Sized> Lock<T> for FaultyMutex<T> {
+    type Guard<'a>
+        = MutexGuard<'a, T>
+    where
+        T: 'a;
+
+    fn lock(&self) -> MutexGuard<'_, T> {
+        self.lock()
+    }
+
+    fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        self.try_lock()
+    }
+
+    fn get_mut(&mut self) -> &mut T {
+        self.get_mut()
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for FaultyMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("FaultyMutex")
+            .field("inner", &&self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A [`Condvar`] whose waits wake up spuriously as scripted by [`Faults`].
+///
+/// A scripted wakeup releases the mutex and acquires it again before the wait
+/// returns, as a real spurious wakeup would, so other threads may change the
+/// value in between.
+#[derive(Debug)]
+pub struct FaultyCondvar {
+    faults: Faults,
+    inner: Condvar,
+}
+
+impl FaultyCondvar {
+    /// Creates a new condition variable injecting the faults of `faults`.
+    pub fn new(faults: &Faults) -> FaultyCondvar {
+        FaultyCondvar {
+            faults: faults.clone(),
+            inner: Condvar::new(),
+        }
+    }
+
+    /// Like [`Condvar::wait`], unless a spurious wakeup is scripted.
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn wait<'a, T>(&self, mut guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        if self.faults.take_wakeup() {
+            MutexGuard::unlocked(&mut guard, || {});
+            return guard;
+        }
+        self.inner.wait(guard)
+    }
+
+    /// Like [`Condvar::wait_timeout`], unless a spurious wakeup is scripted,
+    /// which does not time out.
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn wait_timeout<'a, T>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        dur: Duration,
+    ) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+        if self.faults.take_wakeup() {
+            MutexGuard::unlocked(&mut guard, || {});
+            return (guard, WaitTimeoutResult(false));
+        }
+        self.inner.wait_timeout(guard, dur)
+    }
+
+    /// Like [`Condvar::wait_while`], checking `condition` again after every
+    /// scripted wakeup.
+    #[cfg_attr(feature = "holder-location", track_caller)]
+    pub fn wait_while<'a, T>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: impl FnMut(&mut T) -> bool,
+    ) -> MutexGuard<'a, T> {
+        while condition(&mut guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    /// Like [`Condvar::notify_one`].
+    pub fn notify_one(&self) -> bool {
+        self.inner.notify_one()
+    }
+
+    /// Like [`Condvar::notify_all`].
+    pub fn notify_all(&self) {
+        self.inner.notify_all();
+    }
+
+    /// Returns the script of the faults the condition variable injects.
+    pub fn faults(&self) -> &Faults {
+        &self.faults
+    }
+}
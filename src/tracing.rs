@@ -0,0 +1,199 @@
+//! Reporting of lock behavior through `tracing`.
+//!
+//! With the `tracing` feature enabled, `Mutex` and `RwLock` acquisitions and
+//! releases are emitted as events with the `antidote` target:
+//!
+//! * `DEBUG` `lock contended` when an acquisition is about to block.
+//! * `DEBUG` `lock acquired` once an acquisition which blocked succeeds, with
+//!   the time it waited in `wait_ns`.
+//! * `DEBUG` `lock acquisition timed out` when a timed acquisition gives up,
+//!   with the time it waited in `wait_ns`.
+//! * `TRACE` `lock acquired` when an acquisition succeeds without blocking.
+//! * `TRACE` `lock released` when a guard is dropped, with the time the lock
+//!   was held in `hold_ns`.
+//! * `WARN` `slow lock acquisition` and `lock held too long`, with the
+//!   `slow-warnings` feature enabled, when a lock's thresholds are exceeded,
+//!   with the `holder-location` feature enabled where the holder acquired the
+//!   lock in `lock.holder`.
+//!
+//! Every event carries the address of the lock in `lock.addr` and the id of
+//! the current thread in `thread.id`. The acquisition events also carry the
+//! kind of access, `exclusive` or `shared`, in `lock.access`. Locks registered
+//! with [`register_mutex`] or [`register_rwlock`] are additionally identified
+//! by `lock.name` and `lock.kind`. With the `names` feature enabled, locks
+//! created with `new_named` carry their name in `lock.name` as well. Nothing is
+//! computed for events which the current subscriber does not enable, so
+//! filtering out the `antidote` target leaves only the cost of measuring the
+//! durations.
+
+use std::{sync, thread, time::Duration};
+
+use ::tracing::Level;
+
+use crate::{held::Access, Mutex, RwLock};
+
+static REGISTRY: sync::RwLock<Vec<(&'static str, &'static str, usize)>> =
+    sync::RwLock::new(Vec::new());
+
+fn register(name: &'static str, kind: &'static str, addr: usize) {
+    REGISTRY
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((name, kind, addr));
+}
+
+/// Registers a `Mutex` to be reported under the given name.
+pub fn register_mutex<T: ?Sized>(name: &'static str, mutex: &'static Mutex<T>) {
+    register(name, "mutex", mutex.addr());
+}
+
+/// Registers an `RwLock` to be reported under the given name.
+pub fn register_rwlock<T: ?Sized>(name: &'static str, rwlock: &'static RwLock<T>) {
+    register(name, "rwlock", rwlock.addr());
+}
+
+// Returns the name and kind of the lock at `addr`, if it was registered.
+fn lookup(addr: usize) -> Option<(&'static str, &'static str)> {
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    registry
+        .iter()
+        .find(|&&(_, _, registered)| registered == addr)
+        .map(|&(name, kind, _)| (name, kind))
+}
+
+fn access(access: Access) -> &'static str {
+    match access {
+        Access::Exclusive => "exclusive",
+        Access::Shared => "shared",
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos().try_into().unwrap_or(u64::MAX)
+}
+
+// Emits an event about the lock at `addr`, looking up its registration only if
+// the event is enabled.
+macro_rules! lock_event {
+    ($level:expr, $addr:expr, $name:expr, $($field:tt)*) => {
+        if ::tracing::enabled!(target: "antidote", $level) {
+            let addr = $addr;
+            let thread = thread::current().id();
+            match (lookup(addr), $name) {
+                (Some((name, kind)), _) => ::tracing::event!(
+                    target: "antidote",
+                    $level,
+                    lock.addr = format_args!("{:#x}", addr),
+                    lock.name = name,
+                    lock.kind = kind,
+                    thread.id = ?thread,
+                    $($field)*
+                ),
+                (None, Some(name)) => ::tracing::event!(
+                    target: "antidote",
+                    $level,
+                    lock.addr = format_args!("{:#x}", addr),
+                    lock.name = name,
+                    thread.id = ?thread,
+                    $($field)*
+                ),
+                (None, None) => ::tracing::event!(
+                    target: "antidote",
+                    $level,
+                    lock.addr = format_args!("{:#x}", addr),
+                    thread.id = ?thread,
+                    $($field)*
+                ),
+            }
+        }
+    };
+}
+
+pub(crate) fn contended(addr: usize, name: Option<&'static str>) {
+    lock_event!(Level::DEBUG, addr, name, "lock contended");
+}
+
+pub(crate) fn gave_up(addr: usize, name: Option<&'static str>, waited: Option<Duration>) {
+    let waited = waited.map_or(0, nanos);
+    lock_event!(
+        Level::DEBUG,
+        addr,
+        name,
+        wait_ns = waited,
+        "lock acquisition timed out"
+    );
+}
+
+pub(crate) fn acquired(
+    addr: usize,
+    name: Option<&'static str>,
+    access: Access,
+    waited: Option<Duration>,
+) {
+    let access = self::access(access);
+    match waited {
+        Some(waited) => lock_event!(
+            Level::DEBUG,
+            addr,
+            name,
+            lock.access = access,
+            wait_ns = nanos(waited),
+            "lock acquired"
+        ),
+        None => lock_event!(
+            Level::TRACE,
+            addr,
+            name,
+            lock.access = access,
+            "lock acquired"
+        ),
+    }
+}
+
+pub(crate) fn released(addr: usize, name: Option<&'static str>, held: Duration) {
+    lock_event!(
+        Level::TRACE,
+        addr,
+        name,
+        hold_ns = nanos(held),
+        "lock released"
+    );
+}
+
+#[cfg(feature = "slow-warnings")]
+pub(crate) fn slow_wait(
+    addr: usize,
+    name: Option<&'static str>,
+    waited: Duration,
+    threshold: Duration,
+    holder: Option<&'static std::panic::Location<'static>>,
+) {
+    lock_event!(
+        Level::WARN,
+        addr,
+        name,
+        took_ns = nanos(waited),
+        threshold_ns = nanos(threshold),
+        lock.holder = holder.map(::tracing::field::display),
+        "slow lock acquisition"
+    );
+}
+
+#[cfg(feature = "slow-warnings")]
+pub(crate) fn slow_hold(
+    addr: usize,
+    name: Option<&'static str>,
+    held: Duration,
+    threshold: Duration,
+    holder: Option<&'static std::panic::Location<'static>>,
+) {
+    lock_event!(
+        Level::WARN,
+        addr,
+        name,
+        took_ns = nanos(held),
+        threshold_ns = nanos(threshold),
+        lock.holder = holder.map(::tracing::field::display),
+        "lock held too long"
+    );
+}
@@ -0,0 +1,156 @@
+use core::{
+    cell::{Ref, RefCell, RefMut},
+    ops::{Deref, DerefMut},
+};
+
+use crate::{
+    LockOperation, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError,
+    TryLockResult,
+};
+
+/// A lock granting exclusive access to a value of type `T`.
+///
+/// This allows code to be written generically over the kind of lock protecting
+/// a value, leaving the choice of a concrete lock type to the application.
+///
+/// `RefCell` implements this trait and [`SharedLock`] as well, so that
+/// single-threaded code and tests can use the same generic code without any
+/// locking. Acquiring it while it is already borrowed panics instead of
+/// blocking, as a single thread would never get the lock.
+pub trait Lock<T: ?Sized> {
+    /// The guard granting exclusive access to the value.
+    type Guard<'a>: DerefMut<Target = T>
+    where
+        Self: 'a;
+
+    /// Acquires the lock, blocking the current thread until it is able to do
+    /// so.
+    fn lock(&self) -> Self::Guard<'_>;
+
+    /// Attempts to acquire the lock without blocking.
+    fn try_lock(&self) -> TryLockResult<Self::Guard<'_>>;
+
+    /// Returns a mutable reference to the value.
+    ///
+    /// No locking is necessary since the borrow guarantees exclusive access.
+    fn get_mut(&mut self) -> &mut T;
+}
+
+/// A lock which can additionally grant shared access to a value of type `T`.
+pub trait SharedLock<T: ?Sized>: Lock<T> {
+    /// The guard granting shared access to the value.
+    type SharedGuard<'a>: Deref<Target = T>
+    where
+        Self: 'a;
+
+    /// Acquires shared access, blocking the current thread until it is able to
+    /// do so.
+    fn lock_shared(&self) -> Self::SharedGuard<'_>;
+
+    /// Attempts to acquire shared access without blocking.
+    fn try_lock_shared(&self) -> TryLockResult<Self::SharedGuard<'_>>;
+}
+
+impl<T: ?Sized> Lock<T> for Mutex<T> {
+    type Guard<'a>
+        = MutexGuard<'a, T>
+    where
+        T: 'a;
+
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, T> {
+        self.lock()
+    }
+
+    #[inline]
+    fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        self.try_lock()
+    }
+
+    #[inline]
+    fn get_mut(&mut self) -> &mut T {
+        self.get_mut()
+    }
+}
+
+impl<T: ?Sized> Lock<T> for RwLock<T> {
+    type Guard<'a>
+        = RwLockWriteGuard<'a, T>
+    where
+        T: 'a;
+
+    #[inline]
+    fn lock(&self) -> RwLockWriteGuard<'_, T> {
+        self.write()
+    }
+
+    #[inline]
+    fn try_lock(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        self.try_write()
+    }
+
+    #[inline]
+    fn get_mut(&mut self) -> &mut T {
+        self.get_mut()
+    }
+}
+
+impl<T: ?Sized> SharedLock<T> for RwLock<T> {
+    type SharedGuard<'a>
+        = RwLockReadGuard<'a, T>
+    where
+        T: 'a;
+
+    #[inline]
+    fn lock_shared(&self) -> RwLockReadGuard<'_, T> {
+        self.read()
+    }
+
+    #[inline]
+    fn try_lock_shared(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        self.try_read()
+    }
+}
+
+impl<T: ?Sized> Lock<T> for RefCell<T> {
+    type Guard<'a>
+        = RefMut<'a, T>
+    where
+        T: 'a;
+
+    #[inline]
+    #[track_caller]
+    fn lock(&self) -> RefMut<'_, T> {
+        self.borrow_mut()
+    }
+
+    #[inline]
+    fn try_lock(&self) -> TryLockResult<RefMut<'_, T>> {
+        self.try_borrow_mut()
+            .map_err(|_| TryLockError::new(LockOperation::Lock))
+    }
+
+    #[inline]
+    fn get_mut(&mut self) -> &mut T {
+        self.get_mut()
+    }
+}
+
+impl<T: ?Sized> SharedLock<T> for RefCell<T> {
+    type SharedGuard<'a>
+        = Ref<'a, T>
+    where
+        T: 'a;
+
+    #[inline]
+    #[track_caller]
+    fn lock_shared(&self) -> Ref<'_, T> {
+        self.borrow()
+    }
+
+    #[inline]
+    fn try_lock_shared(&self) -> TryLockResult<Ref<'_, T>> {
+        self.try_borrow()
+            .map_err(|_| TryLockError::new(LockOperation::Read))
+    }
+}
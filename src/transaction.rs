@@ -0,0 +1,108 @@
+use std::{
+    fmt,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    ptr,
+};
+
+use crate::{
+    poison::{Ignore, PoisonPolicy},
+    Mutex, MutexGuard,
+};
+
+impl<T: Clone, P: PoisonPolicy> Mutex<T, P> {
+    /// Acquires the mutex and takes a snapshot of the value, which is put back
+    /// if the thread panics before the guard is dropped.
+    ///
+    /// Other threads then only ever see the value as it was before or after a
+    /// critical section, never half-updated, and the mutex is not reported as
+    /// [poisoned](Mutex::was_poisoned) by a panic rolled back this way. This
+    /// costs a clone of the value per acquisition. The half-updated value is
+    /// dropped while the thread unwinds, so a panic in its `Drop` aborts the
+    /// process.
+    #[inline]
+    pub fn lock_transactional(&self) -> TransactionalMutexGuard<'_, T, P> {
+        let guard = self.lock();
+        let snapshot = (*guard).clone();
+        TransactionalMutexGuard {
+            guard,
+            snapshot: Some(snapshot),
+        }
+    }
+}
+
+/// A `MutexGuard` which puts the value it started with back if its thread
+/// panics.
+///
+/// Created by [`Mutex::lock_transactional`].
+#[must_use]
+pub struct TransactionalMutexGuard<'a, T: 'a, P: PoisonPolicy = Ignore> {
+    guard: MutexGuard<'a, T, P>,
+    // `None` once committed.
+    snapshot: Option<T>,
+}
+
+impl<'a, T, P: PoisonPolicy> TransactionalMutexGuard<'a, T, P> {
+    /// Keeps the changes made so far, and returns a plain guard which no
+    /// longer puts anything back.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`.
+    #[inline]
+    pub fn commit(mut s: Self) -> MutexGuard<'a, T, P> {
+        s.snapshot = None;
+        let s = ManuallyDrop::new(s);
+        // SAFETY: `s` is never used or dropped again, so the guard is moved out
+        // of it only once, and there is no snapshot left to leak.
+        unsafe { ptr::read(&s.guard) }
+    }
+}
+
+impl<T: Clone, P: PoisonPolicy> TransactionalMutexGuard<'_, T, P> {
+    /// Puts the value the guard started with back, without releasing the
+    /// mutex.
+    ///
+    /// This is an associated function so that it does not shadow a method of
+    /// `T`.
+    #[inline]
+    pub fn rollback(s: &mut Self) {
+        if let Some(snapshot) = &s.snapshot {
+            (*s.guard).clone_from(snapshot);
+        }
+    }
+}
+
+impl<T, P: PoisonPolicy> Drop for TransactionalMutexGuard<'_, T, P> {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(snapshot) = self.snapshot.take() {
+            if self.guard.poison.panicked() {
+                *self.guard = snapshot;
+                // The value is back as it was, so there is nothing to report.
+                self.guard.poison.disarm();
+            }
+        }
+    }
+}
+
+impl<T, P: PoisonPolicy> Deref for TransactionalMutexGuard<'_, T, P> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T, P: PoisonPolicy> DerefMut for TransactionalMutexGuard<'_, T, P> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T: fmt::Debug, P: PoisonPolicy> fmt::Debug for TransactionalMutexGuard<'_, T, P> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.guard, fmt)
+    }
+}
@@ -0,0 +1,184 @@
+//! A mutex backed by Darwin's `os_unfair_lock`.
+//!
+//! `os_unfair_lock` is a single 32-bit word, smaller and faster to acquire than
+//! the pthread mutex underlying the standard library's `Mutex` on these
+//! platforms, and lets the kernel donate the priority of a blocked thread to
+//! the lock's owner. As its name says, it makes no fairness guarantees: a
+//! thread releasing the lock may reacquire it immediately even if others are
+//! waiting.
+//!
+//! [`UnfairMutex`] implements [`Lock`], so it can be swapped in for [`Mutex`]
+//! wherever a lock is chosen through [`Guarded`]. It cannot be used with
+//! [`Condvar`].
+//!
+//! [`Mutex`]: crate::Mutex
+//! [`Guarded`]: crate::Guarded
+//! [`Condvar`]: crate::Condvar
+
+use std::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    panic::{RefUnwindSafe, UnwindSafe},
+};
+
+use crate::{Lock, LockOperation, TryLockError, TryLockResult};
+
+/// A mutual exclusion primitive backed by `os_unfair_lock`.
+pub struct UnfairMutex<T: ?Sized> {
+    lock: UnsafeCell<libc::os_unfair_lock>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for UnfairMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for UnfairMutex<T> {}
+
+impl<T: ?Sized> UnwindSafe for UnfairMutex<T> {}
+impl<T: ?Sized> RefUnwindSafe for UnfairMutex<T> {}
+
+impl<T> UnfairMutex<T> {
+    /// Creates a new mutex in an unlocked state.
+    #[inline]
+    pub const fn new(t: T) -> UnfairMutex<T> {
+        UnfairMutex {
+            lock: UnsafeCell::new(libc::OS_UNFAIR_LOCK_INIT),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Consumes the mutex, returning the underlying data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> UnfairMutex<T> {
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so.
+    ///
+    /// Locking the mutex again on a thread which already holds it aborts the
+    /// process.
+    #[inline]
+    pub fn lock(&self) -> UnfairMutexGuard<'_, T> {
+        unsafe { libc::os_unfair_lock_lock(self.lock.get()) };
+        UnfairMutexGuard::new(self)
+    }
+
+    /// Attempts to acquire the mutex without blocking.
+    #[inline]
+    pub fn try_lock(&self) -> TryLockResult<UnfairMutexGuard<'_, T>> {
+        if unsafe { libc::os_unfair_lock_trylock(self.lock.get()) } {
+            Ok(UnfairMutexGuard::new(self))
+        } else {
+            Err(TryLockError::new(LockOperation::Lock))
+        }
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// No locking is necessary since the borrow guarantees exclusive access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T> From<T> for UnfairMutex<T> {
+    #[inline]
+    fn from(t: T) -> UnfairMutex<T> {
+        UnfairMutex::new(t)
+    }
+}
+
+impl<T: Default> Default for UnfairMutex<T> {
+    #[inline]
+    fn default() -> UnfairMutex<T> {
+        UnfairMutex::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for UnfairMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = fmt.debug_struct("UnfairMutex");
+        match self.try_lock() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(_) => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+impl<T: ?Sized> Lock<T> for UnfairMutex<T> {
+    type Guard<'a>
+        = UnfairMutexGuard<'a, T>
+    where
+        T: 'a;
+
+    #[inline]
+    fn lock(&self) -> UnfairMutexGuard<'_, T> {
+        self.lock()
+    }
+
+    #[inline]
+    fn try_lock(&self) -> TryLockResult<UnfairMutexGuard<'_, T>> {
+        self.try_lock()
+    }
+
+    #[inline]
+    fn get_mut(&mut self) -> &mut T {
+        self.get_mut()
+    }
+}
+
+/// A guard for an [`UnfairMutex`].
+///
+/// `os_unfair_lock` must be released by the thread that acquired it, so unlike
+/// `MutexGuard`, this guard can never be sent to another thread.
+#[must_use]
+pub struct UnfairMutexGuard<'a, T: ?Sized + 'a> {
+    mutex: &'a UnfairMutex<T>,
+    _not_send: PhantomData<*const ()>,
+}
+
+unsafe impl<T: ?Sized + Sync> Sync for UnfairMutexGuard<'_, T> {}
+
+impl<'a, T: ?Sized> UnfairMutexGuard<'a, T> {
+    #[inline]
+    fn new(mutex: &'a UnfairMutex<T>) -> UnfairMutexGuard<'a, T> {
+        UnfairMutexGuard {
+            mutex,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for UnfairMutexGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { libc::os_unfair_lock_unlock(self.mutex.lock.get()) };
+    }
+}
+
+impl<T: ?Sized> Deref for UnfairMutexGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for UnfairMutexGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for UnfairMutexGuard<'_, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, fmt)
+    }
+}
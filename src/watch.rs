@@ -0,0 +1,163 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{clock, CondvarAny, RwLock, RwLockReadGuard};
+
+/// A value which threads can read, and wait for until it satisfies a
+/// condition.
+///
+/// Writers replace or modify the value with [`set`](Watch::set) and
+/// [`update`](Watch::update), which wake every thread blocked in
+/// [`wait`](Watch::wait) or [`wait_for`](Watch::wait_for) to check its
+/// condition against the new value. Readers hold shared access to the value
+/// while they look at it, so they never block each other. This suits values
+/// which many threads watch and seldom change, such as a configuration
+/// reloaded at runtime or a flag telling threads to shut down.
+pub struct Watch<T> {
+    value: RwLock<T>,
+    // The number of changes so far, only bumped with the write lock held.
+    version: AtomicU64,
+    changed: CondvarAny,
+}
+
+impl<T> Watch<T> {
+    /// Creates a new watch holding `value`.
+    #[inline]
+    pub const fn new(value: T) -> Watch<T> {
+        Watch {
+            value: RwLock::new(value),
+            version: AtomicU64::new(0),
+            changed: CondvarAny::new(),
+        }
+    }
+
+    /// Returns a copy of the current value.
+    #[inline]
+    pub fn get(&self) -> T
+    where
+        T: Clone,
+    {
+        self.value.read().clone()
+    }
+
+    /// Acquires shared access to the current value, which no writer can
+    /// change until the guard is dropped.
+    #[inline]
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.value.read()
+    }
+
+    /// Replaces the value, waking every thread waiting for it to change, and
+    /// returns the old one.
+    pub fn set(&self, value: T) -> T {
+        self.update(|old| std::mem::replace(old, value))
+    }
+
+    /// Modifies the value with `f`, waking every thread waiting for it to
+    /// change, and returns what `f` returns.
+    ///
+    /// The waiting threads are woken even if `f` leaves the value as it was.
+    pub fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut value = self.value.write();
+        let result = f(&mut value);
+        self.version.fetch_add(1, Ordering::Relaxed);
+        drop(value);
+        self.changed.notify_all();
+        result
+    }
+
+    /// Returns the number of times the value has been changed.
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Relaxed)
+    }
+
+    /// Blocks the current thread until the value satisfies `condition`, and
+    /// returns shared access to it.
+    ///
+    /// The condition is checked against the current value first, so this
+    /// returns right away if it is satisfied already, and then against every
+    /// new value, so it may miss a value which is replaced again before this
+    /// thread gets to check it.
+    pub fn wait(&self, condition: impl FnMut(&T) -> bool) -> RwLockReadGuard<'_, T> {
+        match self.wait_until_internal(condition, None) {
+            Some(value) => value,
+            None => unreachable!("wait without a deadline timed out"),
+        }
+    }
+
+    /// Like [`wait`](Watch::wait), but gives up and returns `None` if the value
+    /// does not satisfy `condition` within `timeout`.
+    pub fn wait_for(
+        &self,
+        condition: impl FnMut(&T) -> bool,
+        timeout: Duration,
+    ) -> Option<RwLockReadGuard<'_, T>> {
+        // A deadline too far in the future to represent is treated as no
+        // deadline at all.
+        self.wait_until_internal(condition, clock::now().checked_add(timeout))
+    }
+
+    /// Like [`wait`](Watch::wait), but gives up and returns `None` if the value
+    /// does not satisfy `condition` once `deadline` has passed.
+    pub fn wait_until(
+        &self,
+        condition: impl FnMut(&T) -> bool,
+        deadline: Instant,
+    ) -> Option<RwLockReadGuard<'_, T>> {
+        self.wait_until_internal(condition, Some(deadline))
+    }
+
+    fn wait_until_internal(
+        &self,
+        mut condition: impl FnMut(&T) -> bool,
+        deadline: Option<Instant>,
+    ) -> Option<RwLockReadGuard<'_, T>> {
+        let mut value = self.value.read();
+        loop {
+            if condition(&value) {
+                return Some(value);
+            }
+            if self
+                .changed
+                .wait_until_internal(&mut value, deadline)
+                .timed_out()
+            {
+                // The value may have changed right before the deadline.
+                return if condition(&value) { Some(value) } else { None };
+            }
+        }
+    }
+
+    /// Consumes the watch, returning the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Default> Default for Watch<T> {
+    #[inline]
+    fn default() -> Watch<T> {
+        Watch::new(T::default())
+    }
+}
+
+impl<T> From<T> for Watch<T> {
+    #[inline]
+    fn from(t: T) -> Watch<T> {
+        Watch::new(t)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Watch<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Watch")
+            .field("value", &*self.value.read())
+            .field("version", &self.version())
+            .finish()
+    }
+}
@@ -0,0 +1,15 @@
+use std::{
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+// Joins `handle`, failing the test instead of hanging if the thread stays
+// blocked, as it does if a wakeup is lost.
+pub fn join_within<T>(handle: JoinHandle<T>) -> T {
+    let deadline = Instant::now() + Duration::from_secs(60);
+    while !handle.is_finished() {
+        assert!(Instant::now() < deadline, "a thread was never woken");
+        thread::sleep(Duration::from_millis(1));
+    }
+    handle.join().unwrap()
+}
@@ -0,0 +1,291 @@
+#![cfg(feature = "std")]
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use antidote::{Condvar, Mutex};
+
+mod common;
+
+use common::join_within;
+
+type Pair = Arc<(Mutex<()>, Condvar)>;
+
+// Spawns a thread waiting once on the condition variable, with an optional
+// timeout, and returning whether it timed out.
+fn spawn_waiter(pair: &Pair, timeout: Option<Duration>) -> JoinHandle<bool> {
+    let pair = pair.clone();
+    thread::spawn(move || {
+        let (mutex, condvar) = &*pair;
+        let guard = mutex.lock();
+        match timeout {
+            Some(timeout) => condvar.wait_timeout(guard, timeout).1.timed_out(),
+            None => {
+                drop(condvar.wait(guard));
+                false
+            }
+        }
+    })
+}
+
+// Blocks until `n` threads are waiting on `condvar`.
+fn wait_for_waiters(condvar: &Condvar, n: usize) {
+    while condvar.waiters() != n {
+        thread::yield_now();
+    }
+}
+
+#[test]
+fn notify_one_reports_whether_a_thread_was_woken() {
+    let pair = Pair::default();
+    let condvar = &pair.1;
+    assert!(!condvar.notify_one());
+
+    let waiter = spawn_waiter(&pair, None);
+    wait_for_waiters(condvar, 1);
+    assert!(condvar.notify_one());
+    assert!(!join_within(waiter));
+    assert_eq!(condvar.waiters(), 0);
+    assert!(!condvar.notify_one());
+}
+
+#[test]
+fn counted_notifications() {
+    let pair = Pair::default();
+    let condvar = &pair.1;
+    assert_eq!(condvar.notify_one_counted(), 0);
+    assert_eq!(condvar.notify_all_counted(), 0);
+
+    let waiters: Vec<_> = (0..4).map(|_| spawn_waiter(&pair, None)).collect();
+    wait_for_waiters(condvar, 4);
+    assert_eq!(condvar.notify_one_counted(), 1);
+    assert_eq!(condvar.waiters(), 3);
+    assert_eq!(condvar.notify_all_counted(), 3);
+    assert_eq!(condvar.waiters(), 0);
+    for waiter in waiters {
+        assert!(!join_within(waiter));
+    }
+    assert_eq!(condvar.notify_all_counted(), 0);
+}
+
+#[test]
+fn waiters_after_timeouts() {
+    let pair = Pair::default();
+    let condvar = &pair.1;
+    let blocked = spawn_waiter(&pair, None);
+    let timed: Vec<_> = (0..3)
+        .map(|_| spawn_waiter(&pair, Some(Duration::from_millis(20))))
+        .collect();
+
+    for waiter in timed {
+        assert!(join_within(waiter));
+    }
+    // Only the waiter without a timeout is left, and it is the one woken.
+    wait_for_waiters(condvar, 1);
+    assert_eq!(condvar.notify_all_counted(), 1);
+    assert!(!join_within(blocked));
+    assert_eq!(condvar.waiters(), 0);
+}
+
+#[test]
+fn notified_before_timing_out() {
+    let pair = Pair::default();
+    let condvar = &pair.1;
+    let waiter = spawn_waiter(&pair, Some(Duration::from_secs(3600)));
+    wait_for_waiters(condvar, 1);
+    assert!(condvar.notify_one());
+    assert!(!join_within(waiter));
+    assert_eq!(condvar.waiters(), 0);
+}
+
+#[test]
+fn two_mutexes() {
+    let condvar = Arc::new(Condvar::new());
+    let mutexes = [Arc::new(Mutex::new(0)), Arc::new(Mutex::new(0))];
+    let spawn = |mutex: &Arc<Mutex<i32>>| {
+        let mutex = mutex.clone();
+        let condvar = condvar.clone();
+        thread::spawn(move || {
+            let mut guard = mutex.lock();
+            while *guard == 0 {
+                guard = condvar.wait(guard);
+            }
+            *guard -= 1;
+        })
+    };
+
+    let waiters: Vec<_> = mutexes.iter().chain(&mutexes).map(&spawn).collect();
+    wait_for_waiters(&condvar, 4);
+    for mutex in &mutexes {
+        *mutex.lock() = 2;
+    }
+    // Waiters using different mutexes cannot be requeued onto one of them, so
+    // they are all woken.
+    assert_eq!(condvar.notify_all_requeue(&*mutexes[0]), 4);
+    for waiter in waiters {
+        join_within(waiter);
+    }
+    assert_eq!(condvar.waiters(), 0);
+    for mutex in &mutexes {
+        assert_eq!(*mutex.lock(), 0);
+    }
+
+    // Once they are gone, the condition variable can be used with either one.
+    for mutex in &mutexes {
+        let waiter = spawn(mutex);
+        wait_for_waiters(&condvar, 1);
+        *mutex.lock() = 1;
+        assert_eq!(condvar.notify_all_requeue(&**mutex), 1);
+        join_within(waiter);
+        assert_eq!(condvar.waiters(), 0);
+    }
+}
+
+#[test]
+fn two_mutexes_notified_one_at_a_time() {
+    let condvar = Arc::new(Condvar::new());
+    let first = Arc::new(Mutex::new(()));
+    let second = Arc::new(Mutex::new(()));
+    let spawn = |mutex: &Arc<Mutex<()>>| {
+        let mutex = mutex.clone();
+        let condvar = condvar.clone();
+        thread::spawn(move || drop(condvar.wait(mutex.lock())))
+    };
+
+    let waiters = [spawn(&first), spawn(&second), spawn(&first)];
+    wait_for_waiters(&condvar, 3);
+    for left in (0..3).rev() {
+        assert!(condvar.notify_one());
+        assert_eq!(condvar.waiters(), left);
+    }
+    assert!(!condvar.notify_one());
+    for waiter in waiters {
+        join_within(waiter);
+    }
+}
+
+// Spawns `n` threads each waiting for `mutex` to hold `true`, with or without
+// a timeout, and counting themselves in the second field once it does.
+fn spawn_requeued(
+    pair: &Arc<(Mutex<(bool, usize)>, Condvar)>,
+    n: usize,
+    timeout: Option<Duration>,
+) -> Vec<JoinHandle<()>> {
+    (0..n)
+        .map(|_| {
+            let pair = pair.clone();
+            thread::spawn(move || {
+                let (mutex, condvar) = &*pair;
+                let mut guard = match timeout {
+                    Some(timeout) => {
+                        let (guard, result) =
+                            condvar.wait_timeout_while(mutex.lock(), timeout, |state| !state.0);
+                        assert!(!result.timed_out());
+                        guard
+                    }
+                    None => condvar.wait_while(mutex.lock(), |state| !state.0),
+                };
+                guard.1 += 1;
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn requeue_with_mutex_held() {
+    for timeout in [None, Some(Duration::from_secs(3600))] {
+        let pair = Arc::new((Mutex::new((false, 0)), Condvar::new()));
+        let (mutex, condvar) = &*pair;
+        let waiters = spawn_requeued(&pair, 8, timeout);
+        wait_for_waiters(condvar, 8);
+
+        let mut guard = mutex.lock();
+        guard.0 = true;
+        assert_eq!(condvar.notify_all_requeue(mutex), 8);
+        assert_eq!(condvar.waiters(), 0);
+        // The woken waiter and the requeued ones all wait for the guard, and
+        // are then handed the mutex one after the other.
+        assert_eq!(guard.1, 0);
+        drop(guard);
+        for waiter in waiters {
+            join_within(waiter);
+        }
+        assert_eq!(mutex.lock().1, 8);
+    }
+}
+
+#[test]
+fn requeue_with_mutex_not_held() {
+    for timeout in [None, Some(Duration::from_secs(3600))] {
+        let pair = Arc::new((Mutex::new((false, 0)), Condvar::new()));
+        let (mutex, condvar) = &*pair;
+        let waiters = spawn_requeued(&pair, 8, timeout);
+        wait_for_waiters(condvar, 8);
+
+        mutex.lock().0 = true;
+        assert_eq!(condvar.notify_all_requeue(mutex), 8);
+        for waiter in waiters {
+            join_within(waiter);
+        }
+        assert_eq!(mutex.lock().1, 8);
+    }
+}
+
+#[test]
+fn requeue_rounds_with_contended_mutex() {
+    const ROUNDS: usize = 500;
+    let pair = Arc::new((Mutex::new(0), Condvar::new()));
+    let stop = Arc::new(AtomicBool::new(false));
+
+    let waiters: Vec<_> = (0..6)
+        .map(|_| {
+            let pair = pair.clone();
+            thread::spawn(move || {
+                let (mutex, condvar) = &*pair;
+                for round in 1..=ROUNDS {
+                    drop(condvar.wait_while(mutex.lock(), |current| *current < round));
+                }
+            })
+        })
+        .collect();
+    // Threads taking the mutex without waiting compete with the requeued
+    // waiters for it.
+    let lockers: Vec<_> = (0..2)
+        .map(|_| {
+            let pair = pair.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    drop(pair.0.lock());
+                }
+            })
+        })
+        .collect();
+
+    let (mutex, condvar) = &*pair;
+    for round in 1..=ROUNDS {
+        let mut guard = mutex.lock();
+        *guard = round;
+        if round % 2 == 0 {
+            drop(guard);
+            condvar.notify_all_requeue(mutex);
+        } else {
+            condvar.notify_all_requeue(mutex);
+            drop(guard);
+        }
+    }
+    for waiter in waiters {
+        join_within(waiter);
+    }
+    stop.store(true, Ordering::Relaxed);
+    for locker in lockers {
+        join_within(locker);
+    }
+    assert_eq!(condvar.waiters(), 0);
+}
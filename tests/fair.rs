@@ -0,0 +1,144 @@
+#![cfg(feature = "std")]
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use antidote::{FairMutex, Mutex};
+
+mod common;
+
+use common::join_within;
+
+// Long enough for a thread blocked on the mutex to stop spinning and park.
+const PARK: Duration = Duration::from_millis(50);
+
+// The tests relying on threads having parked are run one at a time, so that
+// the threads of the others cannot hold them up.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+// Spawns a thread running `f`, and returns once it is likely to be parked in
+// it.
+fn spawn_parked<T, F>(f: F) -> JoinHandle<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let started = Arc::new(AtomicBool::new(false));
+    let handle = {
+        let started = started.clone();
+        thread::spawn(move || {
+            started.store(true, Ordering::SeqCst);
+            f()
+        })
+    };
+    while !started.load(Ordering::SeqCst) {
+        thread::yield_now();
+    }
+    thread::sleep(PARK);
+    handle
+}
+
+#[test]
+fn mutual_exclusion() {
+    let _serial = SERIAL.lock();
+    const THREADS: usize = 8;
+    const ITERATIONS: usize = 10_000;
+    let shared = Arc::new((FairMutex::new(0), AtomicBool::new(false)));
+
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let (mutex, inside) = &*shared;
+                for _ in 0..ITERATIONS {
+                    let mut guard = mutex.lock();
+                    assert!(!inside.swap(true, Ordering::SeqCst), "mutex held twice");
+                    *guard += 1;
+                    inside.store(false, Ordering::SeqCst);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        join_within(handle);
+    }
+    assert_eq!(*shared.0.lock(), THREADS * ITERATIONS);
+    assert!(!shared.0.is_locked());
+}
+
+#[test]
+fn granted_in_arrival_order() {
+    let _serial = SERIAL.lock();
+    let mutex = Arc::new(FairMutex::new(vec![]));
+    let guard = mutex.lock();
+    // Each waiter parks before the next one arrives.
+    let waiters: Vec<_> = (0..5)
+        .map(|i| {
+            let mutex = mutex.clone();
+            spawn_parked(move || mutex.lock().push(i))
+        })
+        .collect();
+
+    drop(guard);
+    // The lock was handed to the first waiter, so it is not free to take even
+    // though its new holder may not have run yet.
+    assert!(mutex.try_lock().is_err());
+    // Locking again queues up behind the other waiters.
+    mutex.lock().push(5);
+    for waiter in waiters {
+        join_within(waiter);
+    }
+    assert_eq!(*mutex.lock(), [0, 1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn releasing_thread_cannot_barge() {
+    let _serial = SERIAL.lock();
+    let mutex = Arc::new(FairMutex::new(()));
+    let acquired = Arc::new(AtomicUsize::new(0));
+    let guard = mutex.lock();
+    let waiter = {
+        let mutex = mutex.clone();
+        let acquired = acquired.clone();
+        spawn_parked(move || {
+            let _guard = mutex.lock();
+            acquired.fetch_add(1, Ordering::SeqCst);
+        })
+    };
+
+    // However fast this thread asks again, the waiter gets the lock first.
+    drop(guard);
+    let guard = mutex.lock();
+    assert_eq!(acquired.load(Ordering::SeqCst), 1);
+    drop(guard);
+    join_within(waiter);
+}
+
+#[test]
+fn hog_does_not_starve_others() {
+    let mutex = Arc::new(FairMutex::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let hog = {
+        let mutex = mutex.clone();
+        let stop = stop.clone();
+        thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                let _guard = mutex.lock();
+                thread::sleep(Duration::from_micros(100));
+            }
+        })
+    };
+
+    for _ in 0..100 {
+        *mutex.lock() += 1;
+    }
+    stop.store(true, Ordering::Relaxed);
+    join_within(hog);
+    assert_eq!(*mutex.lock(), 100);
+}
@@ -0,0 +1,121 @@
+//! Smoke tests for the mutex of the `futex` feature, whose waiters sleep on
+//! the lock word with the waits of each platform.
+#![cfg(feature = "futex")]
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use antidote::{Condvar, Mutex, MutexGuard};
+
+mod common;
+
+use common::join_within;
+
+#[test]
+fn contended_lock_unlock() {
+    const THREADS: usize = 8;
+    const ITERATIONS: usize = 20_000;
+    let shared = Arc::new((Mutex::new(0), AtomicBool::new(false)));
+
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let (mutex, inside) = &*shared;
+                for _ in 0..ITERATIONS {
+                    let mut guard = mutex.lock();
+                    assert!(!inside.swap(true, Ordering::SeqCst), "mutex held twice");
+                    *guard += 1;
+                    inside.store(false, Ordering::SeqCst);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        join_within(handle);
+    }
+    assert_eq!(*shared.0.lock(), THREADS * ITERATIONS);
+    assert!(!shared.0.is_locked());
+}
+
+#[test]
+fn sleeping_waiters_are_woken() {
+    let mutex = Arc::new(Mutex::new(0));
+    let guard = mutex.lock();
+    let waiters: Vec<_> = (0..4)
+        .map(|_| {
+            let mutex = mutex.clone();
+            thread::spawn(move || *mutex.lock() += 1)
+        })
+        .collect();
+    // Long enough for the waiters to stop spinning and go to sleep.
+    thread::sleep(Duration::from_millis(50));
+    assert!(mutex.is_locked());
+    drop(guard);
+    for waiter in waiters {
+        join_within(waiter);
+    }
+    assert_eq!(*mutex.lock(), 4);
+}
+
+#[test]
+fn leaked_guard_is_force_unlocked() {
+    let mutex = Arc::new(Mutex::new(0));
+    *MutexGuard::leak(mutex.lock()) += 1;
+    let waiter = {
+        let mutex = mutex.clone();
+        thread::spawn(move || *mutex.lock() += 1)
+    };
+    // Long enough for the waiter to go to sleep.
+    thread::sleep(Duration::from_millis(50));
+    assert!(mutex.is_locked());
+    // SAFETY: the mutex was locked by this thread, and its guard leaked.
+    unsafe { mutex.force_unlock() };
+    join_within(waiter);
+    assert_eq!(*mutex.lock(), 2);
+    // SAFETY: no guard is alive, so nothing else accesses the data.
+    assert_eq!(unsafe { *mutex.data_ptr() }, 2);
+}
+
+#[test]
+fn timed_lock_while_contended() {
+    let mutex = Arc::new(Mutex::new(()));
+    let guard = mutex.lock();
+    let waiter = {
+        let mutex = mutex.clone();
+        thread::spawn(move || mutex.try_lock_for(Duration::from_millis(20)).is_none())
+    };
+    assert!(join_within(waiter));
+    drop(guard);
+    assert!(mutex.try_lock_for(Duration::from_millis(20)).is_some());
+}
+
+#[test]
+fn ping_pong() {
+    const ROUNDS: usize = 2_000;
+    let pair = Arc::new((Mutex::new(0), Condvar::new()));
+
+    // Each thread waits for the counter to reach its own parity.
+    let spawn = |parity: usize| {
+        let pair = pair.clone();
+        thread::spawn(move || {
+            let (mutex, condvar) = &*pair;
+            for _ in 0..ROUNDS {
+                let mut guard = condvar.wait_while(mutex.lock(), |count| *count % 2 != parity);
+                *guard += 1;
+                condvar.notify_one();
+            }
+        })
+    };
+    let even = spawn(0);
+    let odd = spawn(1);
+    join_within(even);
+    join_within(odd);
+    assert_eq!(*pair.0.lock(), 2 * ROUNDS);
+}
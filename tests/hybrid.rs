@@ -0,0 +1,98 @@
+#![cfg(feature = "std")]
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use antidote::HybridMutex;
+
+mod common;
+
+use common::join_within;
+
+// Runs `THREADS` threads incrementing a counter under the mutex, holding it
+// for `hold` each time, and checks that no two held it at once.
+fn contend(iterations: usize, hold: Option<Duration>) {
+    const THREADS: usize = 8;
+    let shared = Arc::new((HybridMutex::new(0), AtomicBool::new(false)));
+
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let (mutex, inside) = &*shared;
+                for _ in 0..iterations {
+                    let mut guard = mutex.lock();
+                    assert!(!inside.swap(true, Ordering::SeqCst), "mutex held twice");
+                    *guard += 1;
+                    if let Some(hold) = hold {
+                        thread::sleep(hold);
+                    }
+                    inside.store(false, Ordering::SeqCst);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        join_within(handle);
+    }
+    assert_eq!(*shared.0.lock(), THREADS * iterations);
+    assert!(!shared.0.is_locked());
+}
+
+#[test]
+fn short_critical_sections() {
+    // Where waiters mostly get the mutex while spinning.
+    contend(20_000, None);
+}
+
+#[test]
+fn long_critical_sections() {
+    // Where waiters mostly give up spinning and park.
+    contend(100, Some(Duration::from_micros(200)));
+}
+
+#[test]
+fn alternating_critical_sections() {
+    // The spin estimate moves in both directions.
+    for _ in 0..3 {
+        contend(5_000, None);
+        contend(20, Some(Duration::from_millis(1)));
+    }
+}
+
+#[test]
+fn parked_waiters_are_woken() {
+    let mutex = Arc::new(HybridMutex::new(0));
+    let guard = mutex.lock();
+    let waiters: Vec<_> = (0..4)
+        .map(|_| {
+            let mutex = mutex.clone();
+            thread::spawn(move || *mutex.lock() += 1)
+        })
+        .collect();
+    // Long enough for the waiters to stop spinning and park.
+    thread::sleep(Duration::from_millis(50));
+    assert!(mutex.try_lock().is_err());
+    drop(guard);
+    for waiter in waiters {
+        join_within(waiter);
+    }
+    assert_eq!(*mutex.lock(), 4);
+}
+
+#[test]
+fn guard_released_by_another_thread() {
+    let mutex = HybridMutex::new(0);
+    let guard = mutex.lock();
+    thread::scope(|scope| {
+        scope.spawn(|| *mutex.lock() += 1);
+        scope.spawn(move || drop(guard));
+    });
+    assert_eq!(*mutex.lock(), 1);
+}
@@ -0,0 +1,444 @@
+#![cfg(feature = "std")]
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use antidote::{CancelToken, Fairness, RawRwLock, RwLock};
+
+mod common;
+
+use common::join_within;
+
+const FAIRNESS: [Fairness; 3] = [
+    Fairness::ReaderPriority,
+    Fairness::WriterPriority,
+    Fairness::Fair,
+];
+
+const WRITTEN: usize = usize::MAX;
+
+// Tracks the accesses the lock has granted, failing as soon as two of them
+// conflict.
+#[derive(Default)]
+struct Checker {
+    // The number of readers, or `WRITTEN`.
+    state: AtomicUsize,
+    upgradable: AtomicBool,
+}
+
+impl Checker {
+    fn read(&self) {
+        self.state
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |state| {
+                (state != WRITTEN).then_some(state + 1)
+            })
+            .expect("shared access granted to a writer's lock");
+    }
+
+    fn unread(&self) {
+        self.state.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn write(&self) {
+        self.state
+            .compare_exchange(0, WRITTEN, Ordering::SeqCst, Ordering::SeqCst)
+            .expect("exclusive access granted to a held lock");
+    }
+
+    fn unwrite(&self) {
+        assert_eq!(self.state.swap(0, Ordering::SeqCst), WRITTEN);
+    }
+
+    fn upgradable(&self) {
+        self.read();
+        assert!(
+            !self.upgradable.swap(true, Ordering::SeqCst),
+            "upgradable access granted twice"
+        );
+    }
+
+    fn unupgradable(&self) {
+        assert!(self.upgradable.swap(false, Ordering::SeqCst));
+        self.unread();
+    }
+
+    // From upgradable to exclusive access.
+    fn upgraded(&self) {
+        assert!(self.upgradable.swap(false, Ordering::SeqCst));
+        self.state
+            .compare_exchange(1, WRITTEN, Ordering::SeqCst, Ordering::SeqCst)
+            .expect("upgraded while other readers held the lock");
+    }
+
+    // From exclusive to shared access.
+    fn downgraded(&self) {
+        assert_eq!(self.state.swap(1, Ordering::SeqCst), WRITTEN);
+    }
+
+    // From exclusive to upgradable access.
+    fn downgraded_to_upgradable(&self) {
+        self.downgraded();
+        assert!(!self.upgradable.swap(true, Ordering::SeqCst));
+    }
+}
+
+// Runs every kind of acquisition, transition and timeout on `threads` threads
+// at once.
+fn stress(fairness: Fairness, threads: usize, iterations: usize) {
+    let shared = Arc::new((RawRwLock::with_fairness(fairness), Checker::default()));
+    let timeout = Duration::from_micros(50);
+
+    let handles: Vec<_> = (0..threads)
+        .map(|thread| {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let (lock, checker) = &*shared;
+                for i in 0..iterations {
+                    match (thread * 7 + i) % 9 {
+                        0 | 1 => {
+                            lock.lock_shared();
+                            checker.read();
+                            checker.unread();
+                            unsafe { lock.unlock_shared() };
+                        }
+                        2 => {
+                            lock.lock_exclusive();
+                            checker.write();
+                            checker.unwrite();
+                            unsafe { lock.unlock_exclusive() };
+                        }
+                        3 => {
+                            // Upgrade, then downgrade to shared access.
+                            lock.lock_upgradable();
+                            checker.upgradable();
+                            unsafe { lock.upgrade() };
+                            checker.upgraded();
+                            checker.downgraded();
+                            unsafe { lock.downgrade() };
+                            checker.unread();
+                            unsafe { lock.unlock_shared() };
+                        }
+                        4 => {
+                            // Downgrade to upgradable access and upgrade again.
+                            lock.lock_exclusive();
+                            checker.write();
+                            checker.downgraded_to_upgradable();
+                            unsafe { lock.downgrade_to_upgradable() };
+                            unsafe { lock.upgrade() };
+                            checker.upgraded();
+                            checker.unwrite();
+                            unsafe { lock.unlock_exclusive() };
+                        }
+                        5 => {
+                            if lock.try_lock_exclusive_for(timeout) {
+                                checker.write();
+                                checker.unwrite();
+                                unsafe { lock.unlock_exclusive() };
+                            }
+                        }
+                        6 => {
+                            if lock.try_lock_shared_for(timeout) {
+                                checker.read();
+                                checker.unread();
+                                unsafe { lock.unlock_shared() };
+                            }
+                        }
+                        7 => {
+                            lock.lock_upgradable();
+                            checker.upgradable();
+                            if unsafe { lock.try_upgrade_for(timeout) } {
+                                checker.upgraded();
+                                checker.unwrite();
+                                unsafe { lock.unlock_exclusive() };
+                            } else {
+                                checker.unupgradable();
+                                unsafe { lock.unlock_upgradable() };
+                            }
+                        }
+                        _ => {
+                            if lock.try_lock_upgradable() {
+                                checker.upgradable();
+                                checker.unupgradable();
+                                unsafe { lock.downgrade_upgradable() };
+                                checker.read();
+                                checker.unread();
+                                unsafe { lock.unlock_shared() };
+                            } else if lock.try_lock_shared() {
+                                checker.read();
+                                checker.unread();
+                                unsafe { lock.unlock_shared() };
+                            }
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        join_within(handle);
+    }
+
+    let (lock, _) = &*shared;
+    assert!(!lock.is_locked());
+    assert!(lock.try_lock_exclusive());
+}
+
+#[test]
+fn stress_reader_priority() {
+    stress(Fairness::ReaderPriority, 8, 5000);
+}
+
+#[test]
+fn stress_writer_priority() {
+    stress(Fairness::WriterPriority, 8, 5000);
+}
+
+#[test]
+fn stress_fair() {
+    stress(Fairness::Fair, 8, 5000);
+}
+
+// Blocks until a thread is waiting for exclusive access to `lock`, which
+// holds back new readers under every policy but `ReaderPriority`.
+fn wait_for_writer(lock: &RawRwLock) {
+    if lock.fairness() == Fairness::ReaderPriority {
+        thread::sleep(Duration::from_millis(20));
+        return;
+    }
+    while lock.try_lock_shared() {
+        unsafe { lock.unlock_shared() };
+        thread::yield_now();
+    }
+}
+
+#[test]
+fn readers_behind_writer() {
+    for fairness in FAIRNESS {
+        let lock = Arc::new(RawRwLock::with_fairness(fairness));
+        lock.lock_shared();
+        let writer = {
+            let lock = lock.clone();
+            thread::spawn(move || {
+                lock.lock_exclusive();
+                unsafe { lock.unlock_exclusive() };
+            })
+        };
+        wait_for_writer(&lock);
+
+        // Only readers preferred over writers may join while one waits.
+        let joined = lock.try_lock_shared();
+        assert_eq!(joined, fairness == Fairness::ReaderPriority, "{fairness:?}");
+        if joined {
+            unsafe { lock.unlock_shared() };
+        }
+        // Recursive acquisitions always may.
+        assert!(lock.try_lock_shared_recursive());
+        unsafe { lock.unlock_shared() };
+
+        unsafe { lock.unlock_shared() };
+        join_within(writer);
+        assert!(!lock.is_locked());
+    }
+}
+
+#[test]
+fn readers_behind_timed_out_writer() {
+    for fairness in FAIRNESS {
+        let lock = Arc::new(RawRwLock::with_fairness(fairness));
+        lock.lock_shared();
+        let writer = {
+            let lock = lock.clone();
+            thread::spawn(move || lock.try_lock_exclusive_for(Duration::from_millis(200)))
+        };
+        wait_for_writer(&lock);
+        let reader = {
+            let lock = lock.clone();
+            thread::spawn(move || {
+                lock.lock_shared();
+                unsafe { lock.unlock_shared() };
+            })
+        };
+
+        assert!(!join_within(writer), "{fairness:?}");
+        // The reader gets in while the first one still holds the lock.
+        join_within(reader);
+        assert!(lock.try_lock_shared(), "{fairness:?}");
+        unsafe { lock.unlock_shared() };
+
+        unsafe { lock.unlock_shared() };
+        assert!(!lock.is_locked());
+        assert!(lock.try_lock_exclusive());
+    }
+}
+
+#[test]
+fn readers_behind_cancelled_writer() {
+    for fairness in FAIRNESS {
+        let rwlock = Arc::new(RwLock::with_fairness(fairness, 0));
+        let token = CancelToken::new();
+        let guard = rwlock.read();
+        let writer = {
+            let rwlock = rwlock.clone();
+            let token = token.clone();
+            thread::spawn(move || rwlock.write_cancellable(&token).is_err())
+        };
+        while !rwlock.writer_waiting() {
+            thread::yield_now();
+        }
+        let reader = {
+            let rwlock = rwlock.clone();
+            thread::spawn(move || *rwlock.read())
+        };
+        thread::sleep(Duration::from_millis(20));
+
+        token.cancel();
+        assert!(join_within(writer), "{fairness:?}");
+        assert_eq!(join_within(reader), 0);
+        drop(guard);
+        assert!(rwlock.try_write().is_ok());
+    }
+}
+
+#[test]
+fn writer_behind_timed_out_writer() {
+    for fairness in FAIRNESS {
+        let lock = Arc::new(RawRwLock::with_fairness(fairness));
+        lock.lock_shared();
+        let waiting = {
+            let lock = lock.clone();
+            thread::spawn(move || {
+                lock.lock_exclusive();
+                unsafe { lock.unlock_exclusive() };
+            })
+        };
+        wait_for_writer(&lock);
+        assert!(!lock.try_lock_exclusive_for(Duration::from_millis(20)));
+
+        // The writer still waiting is woken once the reader leaves.
+        unsafe { lock.unlock_shared() };
+        join_within(waiting);
+        assert!(!lock.is_locked());
+    }
+}
+
+#[test]
+fn upgrade_waits_for_readers() {
+    for fairness in FAIRNESS {
+        let lock = Arc::new(RawRwLock::with_fairness(fairness));
+        let upgraded = Arc::new(AtomicBool::new(false));
+        lock.lock_shared();
+        let upgrader = {
+            let lock = lock.clone();
+            let upgraded = upgraded.clone();
+            thread::spawn(move || {
+                lock.lock_upgradable();
+                unsafe { lock.upgrade() };
+                upgraded.store(true, Ordering::SeqCst);
+                unsafe { lock.downgrade_to_upgradable() };
+                // Downgraded, it lets readers in again and keeps others from
+                // acquiring upgradable access.
+                assert!(lock.try_lock_shared_recursive());
+                assert!(!lock.try_lock_upgradable());
+                unsafe { lock.unlock_shared() };
+                unsafe { lock.upgrade() };
+                unsafe { lock.unlock_exclusive() };
+            })
+        };
+        wait_for_writer(&lock);
+        assert!(!upgraded.load(Ordering::SeqCst));
+        assert!(lock.is_locked_upgradable());
+        unsafe { lock.unlock_shared() };
+        join_within(upgrader);
+        assert!(upgraded.load(Ordering::SeqCst));
+        assert!(!lock.is_locked());
+    }
+}
+
+#[test]
+fn downgrade_lets_blocked_readers_in() {
+    for fairness in FAIRNESS {
+        let lock = Arc::new(RawRwLock::with_fairness(fairness));
+        lock.lock_exclusive();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                thread::spawn(move || {
+                    lock.lock_shared();
+                    unsafe { lock.unlock_shared() };
+                })
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(20));
+
+        unsafe { lock.downgrade() };
+        // The readers get in while the downgraded writer still reads.
+        for reader in readers {
+            join_within(reader);
+        }
+        assert!(!lock.try_lock_exclusive());
+        unsafe { lock.unlock_shared() };
+        assert!(!lock.is_locked());
+    }
+}
+
+#[test]
+fn downgrade_to_upgradable_lets_blocked_readers_in() {
+    for fairness in FAIRNESS {
+        let lock = Arc::new(RawRwLock::with_fairness(fairness));
+        lock.lock_exclusive();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let lock = lock.clone();
+                thread::spawn(move || {
+                    lock.lock_shared();
+                    unsafe { lock.unlock_shared() };
+                })
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(20));
+
+        unsafe { lock.downgrade_to_upgradable() };
+        for reader in readers {
+            join_within(reader);
+        }
+        assert!(lock.is_locked_upgradable());
+        assert!(!lock.try_lock_upgradable());
+        assert!(unsafe { lock.try_upgrade() });
+        assert!(lock.is_locked_exclusive());
+        unsafe { lock.unlock_exclusive() };
+        assert!(!lock.is_locked());
+    }
+}
+
+#[test]
+fn timed_out_upgrade_keeps_upgradable_access() {
+    for fairness in FAIRNESS {
+        let lock = Arc::new(RawRwLock::with_fairness(fairness));
+        lock.lock_shared();
+        lock.lock_upgradable();
+        assert!(!unsafe { lock.try_upgrade_for(Duration::from_millis(20)) });
+        assert!(lock.is_locked_upgradable());
+        assert_eq!(lock.reader_count(), 2);
+
+        // A blocked reader is let in once the upgrade has given up.
+        let reader = {
+            let lock = lock.clone();
+            thread::spawn(move || {
+                lock.lock_shared();
+                unsafe { lock.unlock_shared() };
+            })
+        };
+        join_within(reader);
+
+        unsafe { lock.unlock_shared() };
+        assert!(unsafe { lock.try_upgrade() });
+        unsafe { lock.unlock_exclusive() };
+        assert!(!lock.is_locked());
+    }
+}
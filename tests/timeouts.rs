@@ -0,0 +1,337 @@
+use std::{
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use antidote::{test_util::MockClock, Condvar, Mutex, RwLock, RwLockUpgradableReadGuard};
+
+// Far longer than a test may take, so that a wait on the system clock hangs
+// the test instead of passing it.
+const TIMEOUT: Duration = Duration::from_secs(3600);
+
+// Moves `clock` forward until the thread behind `handle` has finished, whether
+// or not it has started waiting yet, and returns what it returned.
+fn advance_until_finished<T>(clock: &MockClock, handle: JoinHandle<T>) -> T {
+    while !handle.is_finished() {
+        clock.advance(TIMEOUT / 10);
+        thread::yield_now();
+    }
+    handle.join().unwrap()
+}
+
+// Runs `f` on a new thread with `clock` installed, returning whether it
+// succeeded and how long it took according to the clock.
+fn spawn_timed<F>(clock: &MockClock, f: F) -> JoinHandle<(bool, Duration)>
+where
+    F: FnOnce() -> bool + Send + 'static,
+{
+    let clock = clock.clone();
+    thread::spawn(move || {
+        let _installed = clock.install();
+        let start = clock.now();
+        let succeeded = f();
+        (succeeded, clock.now() - start)
+    })
+}
+
+#[test]
+fn mutex_try_lock_for_times_out() {
+    let clock = MockClock::new();
+    let mutex = Arc::new(Mutex::new(0));
+    let guard = mutex.lock();
+
+    let worker = spawn_timed(&clock, {
+        let mutex = mutex.clone();
+        move || mutex.try_lock_for(TIMEOUT).is_some()
+    });
+    let (acquired, elapsed) = advance_until_finished(&clock, worker);
+    assert!(!acquired);
+    assert!(elapsed >= TIMEOUT);
+    drop(guard);
+    assert!(!mutex.is_locked());
+}
+
+#[test]
+fn mutex_try_lock_for_acquires_once_released() {
+    let clock = MockClock::new();
+    let mutex = Arc::new(Mutex::new(0));
+    let guard = mutex.lock();
+
+    let worker = spawn_timed(&clock, {
+        let mutex = mutex.clone();
+        move || {
+            mutex
+                .try_lock_for(TIMEOUT)
+                .map(|mut guard| *guard += 1)
+                .is_some()
+        }
+    });
+    drop(guard);
+    let (acquired, elapsed) = worker.join().unwrap();
+    assert!(acquired);
+    assert_eq!(elapsed, Duration::ZERO);
+    assert_eq!(*mutex.lock(), 1);
+}
+
+#[test]
+fn mutex_deadline_in_the_past() {
+    let clock = MockClock::new();
+    let _installed = clock.install();
+    let mutex = Arc::new(Mutex::new(0));
+    let past = clock.now() - Duration::from_secs(1);
+
+    // A free mutex is still acquired.
+    assert!(mutex.try_lock_until(past).is_some());
+    assert!(mutex.try_lock_for(Duration::ZERO).is_some());
+
+    let guard = mutex.lock();
+    let other = {
+        let mutex = mutex.clone();
+        let clock = clock.clone();
+        thread::spawn(move || {
+            let _installed = clock.install();
+            mutex.try_lock_until(past).is_none() && mutex.try_lock_for(Duration::ZERO).is_none()
+        })
+    };
+    assert!(other.join().unwrap());
+    drop(guard);
+    assert_eq!(clock.now(), past + Duration::from_secs(1));
+}
+
+#[test]
+fn condvar_wait_timeout_times_out() {
+    let clock = MockClock::new();
+    let pair = Arc::new((Mutex::new(()), Condvar::new()));
+
+    let worker = spawn_timed(&clock, {
+        let pair = pair.clone();
+        move || {
+            let (mutex, condvar) = &*pair;
+            let (_guard, result) = condvar.wait_timeout(mutex.lock(), TIMEOUT);
+            result.timed_out()
+        }
+    });
+    let (timed_out, elapsed) = advance_until_finished(&clock, worker);
+    assert!(timed_out);
+    assert!(elapsed >= TIMEOUT);
+    assert_eq!(pair.1.waiters(), 0);
+}
+
+#[test]
+fn condvar_wait_timeout_while_notified() {
+    let clock = MockClock::new();
+    let pair = Arc::new((Mutex::new(false), Condvar::new()));
+
+    let worker = spawn_timed(&clock, {
+        let pair = pair.clone();
+        move || {
+            let (mutex, condvar) = &*pair;
+            let (guard, result) =
+                condvar.wait_timeout_while(mutex.lock(), TIMEOUT, |ready| !*ready);
+            *guard && !result.timed_out()
+        }
+    });
+    *pair.0.lock() = true;
+    pair.1.notify_all();
+    let (notified, elapsed) = worker.join().unwrap();
+    assert!(notified);
+    assert_eq!(elapsed, Duration::ZERO);
+}
+
+#[test]
+fn condvar_wait_until_times_out() {
+    let clock = MockClock::new();
+    let pair = Arc::new((Mutex::new(()), Condvar::new()));
+    let deadline = clock.now() + TIMEOUT;
+
+    let worker = {
+        let pair = pair.clone();
+        let clock = clock.clone();
+        thread::spawn(move || {
+            let _installed = clock.install();
+            let (mutex, condvar) = &*pair;
+            let (_guard, result) = condvar.wait_until(mutex.lock(), deadline);
+            (result.timed_out(), clock.now())
+        })
+    };
+    let (timed_out, now) = advance_until_finished(&clock, worker);
+    assert!(timed_out);
+    assert!(now >= deadline);
+}
+
+#[test]
+fn condvar_deadline_in_the_past() {
+    let clock = MockClock::new();
+    let _installed = clock.install();
+    let mutex = Mutex::new(0);
+    let condvar = Condvar::new();
+    let past = clock.now() - Duration::from_secs(1);
+
+    let (guard, result) = condvar.wait_until(mutex.lock(), past);
+    assert!(result.timed_out());
+    let (guard, result) = condvar.wait_timeout(guard, Duration::ZERO);
+    assert!(result.timed_out());
+    let (guard, result) = condvar.wait_while_until(guard, past, |value| *value == 0);
+    assert!(result.timed_out());
+    // The condition is checked before waiting, so a deadline in the past does
+    // not report a timeout if it no longer holds.
+    let (_guard, result) = condvar.wait_while_until(guard, past, |value| *value != 0);
+    assert!(!result.timed_out());
+    assert_eq!(condvar.waiters(), 0);
+}
+
+#[test]
+fn rwlock_try_read_for_times_out_behind_writer() {
+    let clock = MockClock::new();
+    let rwlock = Arc::new(RwLock::new(0));
+    let guard = rwlock.write();
+
+    let worker = spawn_timed(&clock, {
+        let rwlock = rwlock.clone();
+        move || rwlock.try_read_for(TIMEOUT).is_some()
+    });
+    let (acquired, elapsed) = advance_until_finished(&clock, worker);
+    assert!(!acquired);
+    assert!(elapsed >= TIMEOUT);
+    drop(guard);
+    assert!(!rwlock.is_locked());
+}
+
+#[test]
+fn rwlock_try_write_for_times_out_behind_reader() {
+    let clock = MockClock::new();
+    let rwlock = Arc::new(RwLock::new(0));
+    let guard = rwlock.read();
+
+    let worker = spawn_timed(&clock, {
+        let rwlock = rwlock.clone();
+        move || rwlock.try_write_for(TIMEOUT).is_some()
+    });
+    let (acquired, elapsed) = advance_until_finished(&clock, worker);
+    assert!(!acquired);
+    assert!(elapsed >= TIMEOUT);
+
+    // The writer which gave up no longer holds back readers.
+    assert!(rwlock.try_read().is_ok());
+    drop(guard);
+    assert!(rwlock.try_write().is_ok());
+}
+
+#[test]
+fn rwlock_try_write_for_acquires_once_released() {
+    let clock = MockClock::new();
+    let rwlock = Arc::new(RwLock::new(0));
+    let guard = rwlock.read();
+
+    let worker = spawn_timed(&clock, {
+        let rwlock = rwlock.clone();
+        move || {
+            rwlock
+                .try_write_for(TIMEOUT)
+                .map(|mut guard| *guard += 1)
+                .is_some()
+        }
+    });
+    drop(guard);
+    let (acquired, elapsed) = worker.join().unwrap();
+    assert!(acquired);
+    assert_eq!(elapsed, Duration::ZERO);
+    assert_eq!(*rwlock.read(), 1);
+}
+
+#[test]
+fn rwlock_deadline_in_the_past() {
+    let clock = MockClock::new();
+    let _installed = clock.install();
+    let rwlock = Arc::new(RwLock::new(0));
+    let past = clock.now() - Duration::from_secs(1);
+
+    assert!(rwlock.try_write_until(past).is_some());
+    assert!(rwlock.try_read_until(past).is_some());
+
+    let guard = rwlock.write();
+    let other = {
+        let rwlock = rwlock.clone();
+        let clock = clock.clone();
+        thread::spawn(move || {
+            let _installed = clock.install();
+            rwlock.try_read_until(past).is_none()
+                && rwlock.try_read_for(Duration::ZERO).is_none()
+                && rwlock.try_write_until(past).is_none()
+                && rwlock.try_write_for(Duration::ZERO).is_none()
+        })
+    };
+    assert!(other.join().unwrap());
+    drop(guard);
+    assert!(!rwlock.is_locked());
+}
+
+#[test]
+fn try_upgrade_for_times_out_behind_reader() {
+    let clock = MockClock::new();
+    let rwlock = Arc::new(RwLock::new(0));
+    let reader = rwlock.read();
+
+    let worker = spawn_timed(&clock, {
+        let rwlock = rwlock.clone();
+        move || {
+            let upgradable = rwlock.upgradable_read();
+            match RwLockUpgradableReadGuard::try_upgrade_for(upgradable, TIMEOUT) {
+                Ok(_) => true,
+                // The guard given back still holds upgradable access.
+                Err(upgradable) => {
+                    assert!(rwlock.try_upgradable_read().is_err());
+                    drop(upgradable);
+                    false
+                }
+            }
+        }
+    });
+    let (upgraded, elapsed) = advance_until_finished(&clock, worker);
+    assert!(!upgraded);
+    assert!(elapsed >= TIMEOUT);
+
+    // Readers are let in again once the upgrade gave up.
+    assert!(rwlock.try_read().is_ok());
+    drop(reader);
+    assert!(rwlock.try_write().is_ok());
+}
+
+#[test]
+fn try_upgrade_for_upgrades_once_readers_leave() {
+    let clock = MockClock::new();
+    let rwlock = Arc::new(RwLock::new(0));
+    let reader = rwlock.read();
+
+    let worker = spawn_timed(&clock, {
+        let rwlock = rwlock.clone();
+        move || {
+            let upgradable = rwlock.upgradable_read();
+            RwLockUpgradableReadGuard::try_upgrade_for(upgradable, TIMEOUT)
+                .map(|mut guard| *guard += 1)
+                .is_ok()
+        }
+    });
+    drop(reader);
+    let (upgraded, elapsed) = worker.join().unwrap();
+    assert!(upgraded);
+    assert_eq!(elapsed, Duration::ZERO);
+    assert_eq!(*rwlock.read(), 1);
+}
+
+#[test]
+fn try_upgrade_for_zero_timeout() {
+    let clock = MockClock::new();
+    let _installed = clock.install();
+    let rwlock = RwLock::new(0);
+
+    let upgradable = rwlock.upgradable_read();
+    let reader = rwlock.read();
+    let upgradable = match RwLockUpgradableReadGuard::try_upgrade_for(upgradable, Duration::ZERO) {
+        Ok(_) => panic!("upgraded while a reader held the lock"),
+        Err(upgradable) => upgradable,
+    };
+    drop(reader);
+    assert!(RwLockUpgradableReadGuard::try_upgrade_for(upgradable, Duration::ZERO).is_ok());
+}
@@ -0,0 +1,183 @@
+#![cfg(feature = "std")]
+
+use std::{
+    mem,
+    panic::{self, AssertUnwindSafe, RefUnwindSafe, UnwindSafe},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use antidote::{
+    poison::{self, PanicOnRecover, PoisonPolicy},
+    Mutex, RwLock,
+};
+
+fn assert_unwind_safe<T: UnwindSafe + RefUnwindSafe + ?Sized>() {}
+
+#[test]
+fn locks_are_unwind_safe() {
+    assert_unwind_safe::<Mutex<Vec<i32>>>();
+    assert_unwind_safe::<Mutex<Vec<i32>, poison::Log>>();
+    assert_unwind_safe::<Mutex<Vec<i32>, PanicOnRecover>>();
+    assert_unwind_safe::<Mutex<[i32]>>();
+    assert_unwind_safe::<RwLock<Vec<i32>>>();
+    assert_unwind_safe::<RwLock<[i32]>>();
+    assert_unwind_safe::<&Mutex<Vec<i32>>>();
+    assert_unwind_safe::<&RwLock<Vec<i32>>>();
+}
+
+// Panics while holding a guard of `mutex`, without `AssertUnwindSafe`.
+fn panic_holding<P: PoisonPolicy>(mutex: &Mutex<i32, P>) {
+    let result = panic::catch_unwind(|| {
+        let mut guard = mutex.lock();
+        *guard += 1;
+        panic!("panicking while holding the mutex");
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+fn mutex_released_by_unwinding() {
+    let mutex = Mutex::new(0);
+    assert!(!mutex.was_poisoned());
+    panic_holding(&mutex);
+    assert!(!mutex.is_locked());
+    assert!(mutex.was_poisoned());
+    assert_eq!(*mutex.try_lock().unwrap(), 1);
+
+    mutex.clear_poison();
+    assert!(!mutex.was_poisoned());
+}
+
+#[test]
+fn mutex_log_policy() {
+    let mutex = Mutex::with_policy(poison::Log, 0);
+    panic_holding(&mutex);
+    assert!(!mutex.is_locked());
+    assert!(mutex.was_poisoned());
+    assert_eq!(*mutex.lock(), 1);
+    assert!(mutex.was_poisoned());
+}
+
+#[test]
+fn mutex_callback_policy() {
+    static RECOVERED: AtomicUsize = AtomicUsize::new(0);
+
+    let mutex = Mutex::new(0).on_poison_recovered(|_| {
+        RECOVERED.fetch_add(1, Ordering::Relaxed);
+    });
+    panic_holding(&mutex);
+    assert!(!mutex.is_locked());
+    assert!(mutex.was_poisoned());
+    assert_eq!(RECOVERED.load(Ordering::Relaxed), 0);
+    assert_eq!(*mutex.lock(), 1);
+    assert_eq!(RECOVERED.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn mutex_panic_on_recover_policy() {
+    let mutex = Mutex::with_policy(PanicOnRecover, 0);
+    panic_holding(&mutex);
+    assert!(!mutex.is_locked());
+    assert!(mutex.was_poisoned());
+
+    // The policy panics with the new guard, which releases the mutex again.
+    let result = panic::catch_unwind(|| *mutex.lock());
+    assert!(result.is_err());
+    assert!(!mutex.is_locked());
+    assert!(mutex.was_poisoned());
+
+    mutex.clear_poison();
+    assert_eq!(*mutex.lock(), 1);
+}
+
+#[test]
+fn mutex_repair_policy() {
+    let mutex = Mutex::with_repair(0, |value: &mut i32| *value = 0);
+    panic_holding(&mutex);
+    assert!(!mutex.is_locked());
+    assert!(mutex.was_poisoned());
+    assert_eq!(*mutex.lock(), 0);
+    assert!(!mutex.was_poisoned());
+}
+
+#[test]
+fn rwlock_write_released_by_unwinding() {
+    let rwlock = RwLock::new(0);
+    let result = panic::catch_unwind(|| {
+        let mut guard = rwlock.write();
+        *guard += 1;
+        panic!("panicking while holding the lock");
+    });
+    assert!(result.is_err());
+    assert!(!rwlock.is_locked());
+    assert!(rwlock.was_poisoned());
+    assert_eq!(*rwlock.try_write().unwrap(), 1);
+
+    rwlock.clear_poison();
+    assert!(!rwlock.was_poisoned());
+}
+
+#[test]
+fn rwlock_read_released_by_unwinding() {
+    let rwlock = RwLock::new(0);
+    let result = panic::catch_unwind(|| {
+        let _first = rwlock.read();
+        let _second = rwlock.read();
+        panic!("panicking while holding the lock");
+    });
+    assert!(result.is_err());
+    assert!(!rwlock.is_locked());
+    // Like the standard library, panics with shared access do not count.
+    assert!(!rwlock.was_poisoned());
+    assert!(rwlock.try_write().is_ok());
+}
+
+#[test]
+fn mutex_force_reinit_forgets_poison_and_holder() {
+    let mut mutex = Mutex::new(0);
+    panic_holding(&mutex);
+    assert!(mutex.was_poisoned());
+    // Left locked by this thread, as one inherited by a child process would be.
+    mem::forget(mutex.lock());
+
+    mutex.force_reinit();
+    assert!(!mutex.was_poisoned());
+    assert!(!mutex.is_locked());
+    // In debug builds, this panics if the mutex is still recorded as held by
+    // this thread.
+    *mutex.lock() += 1;
+    assert_eq!(*mutex.lock(), 2);
+}
+
+#[test]
+fn rwlock_force_reinit_forgets_poison_and_holder() {
+    let mut rwlock = RwLock::new(0);
+    let result = panic::catch_unwind(|| {
+        let mut guard = rwlock.write();
+        *guard += 1;
+        panic!("panicking while holding the lock");
+    });
+    assert!(result.is_err());
+    assert!(rwlock.was_poisoned());
+    mem::forget(rwlock.write());
+
+    rwlock.force_reinit();
+    assert!(!rwlock.was_poisoned());
+    assert!(!rwlock.is_locked());
+    *rwlock.write() += 1;
+    assert_eq!(*rwlock.read(), 2);
+}
+
+#[test]
+fn guard_moved_into_catch_unwind() {
+    let mutex = Mutex::new(vec![1, 2, 3]);
+    let guard = mutex.lock();
+    let result = panic::catch_unwind(AssertUnwindSafe(move || {
+        let _guard = guard;
+        panic!("panicking while holding the mutex");
+    }));
+    assert!(result.is_err());
+    assert!(!mutex.is_locked());
+    assert!(mutex.was_poisoned());
+    assert_eq!(*mutex.lock(), [1, 2, 3]);
+}